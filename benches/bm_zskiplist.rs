@@ -1,8 +1,11 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 use std::time::Duration;
 
+use curly_giggle::collection::skiplist::zrangespec::ZRangeSpec;
 use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
 
+const SIZES: [usize; 4] = [1_000, 10_000, 100_000, 1_000_000];
+
 fn bm_zskiplist_create(c: &mut Criterion) {
     let mut group = c.benchmark_group("ZSkiplist - create");
     group.measurement_time(Duration::new(10, 0)); // Set measurement time to 10 seconds for more stable results
@@ -16,5 +19,40 @@ fn bm_zskiplist_create(c: &mut Criterion) {
     group.finish();
 }
 
+fn build_list(size: usize) -> ZSkipList<usize> {
+    let mut list = ZSkipList::zsl_create();
+    for i in 0..size {
+        list.zsl_insert(i as f64, i);
+    }
+    list
+}
+
+// The ranges sit near the tail, the worst case for a linear scan. With the
+// levels being used the time should grow with log(size), not size.
+fn bm_zskiplist_range_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ZSkiplist - range lookup");
+
+    for size in SIZES {
+        let list = build_list(size);
+        let range = ZRangeSpec::new((size - 10) as f64, (size - 5) as f64);
+
+        group.bench_with_input(BenchmarkId::new("first_in_range", size), &range, |b, range| {
+            b.iter(|| list.zsl_first_in_range(black_box(range)))
+        });
+        group.bench_with_input(BenchmarkId::new("last_in_range", size), &range, |b, range| {
+            b.iter(|| list.zsl_last_in_range(black_box(range)))
+        });
+
+        let range = ZRangeSpec::new(0.0, size as f64);
+        let n = (size - 10) as i64;
+        group.bench_with_input(BenchmarkId::new("nth_in_range", size), &range, |b, range| {
+            b.iter(|| list.zsl_nth_in_range(black_box(range), black_box(n)))
+        });
+    }
+
+    group.finish();
+}
+
 criterion_group!(create_bench, bm_zskiplist_create);
-criterion_main!(create_bench);
\ No newline at end of file
+criterion_group!(range_bench, bm_zskiplist_range_lookup);
+criterion_main!(create_bench, range_bench);
@@ -0,0 +1,66 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A source of wall-clock time in milliseconds.
+///
+/// Everything that deals with timeouts takes a `Clock` so tests can drive
+/// time by hand instead of sleeping.
+pub trait Clock {
+    fn now_ms(&self) -> u64;
+}
+
+/// The real clock, backed by `SystemTime`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+}
+
+/// A clock that only moves when told to.
+///
+/// Clones share the same time, so a test can keep one handle and give
+/// another one to the structure under test.
+///
+/// # Examples
+///
+/// ```
+/// use curly_giggle::clock::{Clock, ManualClock};
+///
+/// let clock = ManualClock::new(1000);
+/// let handle = clock.clone();
+/// handle.advance(500);
+/// assert_eq!(clock.now_ms(), 1500);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<u64>>,
+}
+
+impl ManualClock {
+    pub fn new(now_ms: u64) -> Self {
+        ManualClock {
+            now: Rc::new(Cell::new(now_ms)),
+        }
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.now.set(now_ms);
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.now.set(self.now.get() + ms);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_ms(&self) -> u64 {
+        self.now.get()
+    }
+}
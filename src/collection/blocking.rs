@@ -0,0 +1,605 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+use crate::clock::Clock;
+use crate::collection::list::LinkedList;
+use crate::collection::zset::{ZAddFlags, ZAddReply, ZSet, ZSetWhere};
+
+pub type ClientId = u64;
+
+/// A client parked on one or more keys, waiting for data.
+pub struct BlockedClient<K, R> {
+    pub keys: Vec<K>,
+    pub request: R,
+    // Absolute deadline in ms, `None` blocks forever
+    pub deadline: Option<u64>,
+    seq: u64,
+}
+
+/// Bookkeeping for clients blocked on keys, shared by every blocking type.
+///
+/// Each key keeps its waiters in FIFO order, and a key pushed to while it has
+/// waiters is queued as ready until the owner serves it (`blockingState` and
+/// `ready_keys` in Redis).
+pub struct BlockingKeys<K, R> {
+    clients: HashMap<ClientId, BlockedClient<K, R>>,
+    waiters: HashMap<K, VecDeque<ClientId>>,
+    ready_keys: Vec<K>,
+    ready_set: HashSet<K>,
+    next_seq: u64,
+}
+
+impl<K, R> Default for BlockingKeys<K, R>
+where
+    K: Eq + Hash + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, R> BlockingKeys<K, R>
+where
+    K: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        BlockingKeys {
+            clients: HashMap::new(),
+            waiters: HashMap::new(),
+            ready_keys: Vec::new(),
+            ready_set: HashSet::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Park `client` on `keys` until one of them is served or `deadline` passes.
+    ///
+    /// A client that is already blocked is unblocked first.
+    pub fn block(&mut self, client: ClientId, keys: Vec<K>, request: R, deadline: Option<u64>) {
+        self.unblock(client);
+        let mut seen = HashSet::new();
+        for key in keys.iter() {
+            // BLPOP k k must only queue the client once
+            if seen.insert(key) {
+                self.waiters.entry(key.clone()).or_default().push_back(client);
+            }
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.clients.insert(client, BlockedClient { keys, request, deadline, seq });
+    }
+
+    /// Remove `client` from every key it waits on, returning its registration.
+    pub fn unblock(&mut self, client: ClientId) -> Option<BlockedClient<K, R>> {
+        let blocked = self.clients.remove(&client)?;
+        for key in blocked.keys.iter() {
+            if let Some(queue) = self.waiters.get_mut(key) {
+                queue.retain(|&c| c != client);
+                if queue.is_empty() {
+                    self.waiters.remove(key);
+                }
+            }
+        }
+        Some(blocked)
+    }
+
+    pub fn is_blocked(&self, client: ClientId) -> bool {
+        self.clients.contains_key(&client)
+    }
+
+    pub fn blocked_clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn request(&self, client: ClientId) -> Option<&R> {
+        self.clients.get(&client).map(|c| &c.request)
+    }
+
+    /// Number of clients waiting on `key`.
+    pub fn waiting_on(&self, key: &K) -> usize {
+        self.waiters.get(key).map_or(0, |q| q.len())
+    }
+
+    /// The client that has been waiting on `key` the longest.
+    pub fn first_waiter(&self, key: &K) -> Option<ClientId> {
+        self.waiters.get(key).and_then(|q| q.front().copied())
+    }
+
+    /// Mark `key` as ready to be served. Keys nobody waits on are ignored.
+    pub fn signal_key_as_ready(&mut self, key: &K) {
+        if self.waiting_on(key) == 0 || self.ready_set.contains(key) {
+            return;
+        }
+        self.ready_set.insert(key.clone());
+        self.ready_keys.push(key.clone());
+    }
+
+    /// Take the ready keys in the order they were signaled.
+    pub fn take_ready_keys(&mut self) -> Vec<K> {
+        self.ready_set.clear();
+        std::mem::take(&mut self.ready_keys)
+    }
+
+    /// Unblock every client whose deadline is at or before `now_ms`.
+    ///
+    /// Clients are returned ordered by deadline, then by the order they blocked.
+    pub fn expire(&mut self, now_ms: u64) -> Vec<ClientId> {
+        let mut expired: Vec<(u64, u64, ClientId)> = self
+            .clients
+            .iter()
+            .filter_map(|(&id, c)| match c.deadline {
+                Some(deadline) if deadline <= now_ms => Some((deadline, c.seq, id)),
+                _ => None,
+            })
+            .collect();
+        expired.sort_unstable();
+        expired
+            .into_iter()
+            .map(|(_, _, id)| {
+                self.unblock(id);
+                id
+            })
+            .collect()
+    }
+}
+
+/// Which end of a list to pop from or push to (`LEFT` / `RIGHT`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListWhere {
+    Head,
+    Tail,
+}
+
+/// What a client blocked on lists will do once served.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ListRequest<K> {
+    Pop(ListWhere),
+    Move {
+        dst: K,
+        wherefrom: ListWhere,
+        whereto: ListWhere,
+    },
+}
+
+/// Outcome of a blocking command.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BlockResult<K, T> {
+    /// Data was available, the command completed without blocking.
+    Served { key: K, value: T },
+    /// The client is now blocked.
+    Blocked,
+}
+
+/// A reply delivered to a client that was blocked.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Served<K, T> {
+    pub client: ClientId,
+    pub key: K,
+    pub value: T,
+}
+
+/// The data side of a blocking type: the values stored under each key, and what
+/// a blocked client gets once a key it waits on has data.
+pub trait BlockingStore<K> {
+    /// What a blocked client will do once served.
+    type Request: Clone;
+    /// What a served client gets.
+    type Value;
+
+    /// Whether a client blocked on `key` can be served.
+    fn has_data(&self, key: &K) -> bool;
+
+    /// Serves `request` from `key`, which has data. Keys receiving data in turn
+    /// are signaled on `blocked`.
+    fn serve(&mut self, key: &K, request: Self::Request, blocked: &mut BlockingKeys<K, Self::Request>) -> Self::Value;
+}
+
+/// Keys of a [`BlockingStore`] along with the clients blocked on them.
+///
+/// Writes only mark keys as ready. As in Redis, blocked clients are served once
+/// the current command is done, by calling
+/// [`handle_clients_blocked_on_keys`](Blocking::handle_clients_blocked_on_keys).
+pub struct Blocking<K, S: BlockingStore<K>, C> {
+    store: S,
+    blocked: BlockingKeys<K, S::Request>,
+    clock: C,
+}
+
+impl<K, S, C> Blocking<K, S, C>
+where
+    K: Eq + Hash + Clone,
+    S: BlockingStore<K> + Default,
+    C: Clock,
+{
+    pub fn new(clock: C) -> Self {
+        Blocking {
+            store: S::default(),
+            blocked: BlockingKeys::new(),
+            clock,
+        }
+    }
+
+    pub fn blocked(&self) -> &BlockingKeys<K, S::Request> {
+        &self.blocked
+    }
+
+    /// Serve clients blocked on keys that received data.
+    ///
+    /// Each ready key serves its waiters in FIFO order while it has data. Serving
+    /// a client can make another key ready in turn, e.g. the destination of
+    /// BLMOVE, so this loops until no key is left ready.
+    pub fn handle_clients_blocked_on_keys(&mut self) -> Vec<Served<K, S::Value>> {
+        let mut served = Vec::new();
+        loop {
+            let ready = self.blocked.take_ready_keys();
+            if ready.is_empty() {
+                break;
+            }
+            for key in ready {
+                while self.store.has_data(&key) {
+                    let client = match self.blocked.first_waiter(&key) {
+                        Some(client) => client,
+                        None => break,
+                    };
+                    let request = self.blocked.unblock(client).unwrap().request;
+                    let value = self.store.serve(&key, request, &mut self.blocked);
+                    served.push(Served { client, key: key.clone(), value });
+                }
+            }
+        }
+        served
+    }
+
+    /// Unblock clients whose timeout elapsed. Each of them gets a null reply.
+    pub fn expire_blocked_clients(&mut self) -> Vec<ClientId> {
+        let now = self.clock.now_ms();
+        self.blocked.expire(now)
+    }
+
+    /// Forget a client, e.g. because its connection was closed.
+    pub fn disconnect(&mut self, client: ClientId) -> bool {
+        self.blocked.unblock(client).is_some()
+    }
+
+    // Park `client` on `keys`, `timeout_ms` of 0 blocks forever
+    fn block(&mut self, client: ClientId, keys: Vec<K>, request: S::Request, timeout_ms: u64) {
+        let deadline = if timeout_ms == 0 {
+            None
+        } else {
+            Some(self.clock.now_ms() + timeout_ms)
+        };
+        self.blocked.block(client, keys, request, deadline);
+    }
+}
+
+/// The lists of [`BlockingLists`].
+pub struct ListStore<K, T> {
+    lists: HashMap<K, LinkedList<T>>,
+}
+
+impl<K, T> Default for ListStore<K, T> {
+    fn default() -> Self {
+        ListStore { lists: HashMap::new() }
+    }
+}
+
+impl<K, T> ListStore<K, T>
+where
+    K: Eq + Hash + Clone,
+    T: Clone,
+{
+    fn push(&mut self, key: K, values: Vec<T>, whereto: ListWhere, blocked: &mut BlockingKeys<K, ListRequest<K>>) -> usize {
+        blocked.signal_key_as_ready(&key);
+        let list = self.lists.entry(key).or_default();
+        for value in values {
+            match whereto {
+                ListWhere::Head => list.push_front(value),
+                ListWhere::Tail => list.push_back(value),
+            }
+        }
+        list.len()
+    }
+
+    fn pop(&mut self, key: &K, wherefrom: ListWhere) -> Option<T> {
+        let list = self.lists.get_mut(key)?;
+        let value = match wherefrom {
+            ListWhere::Head => list.pop_front(),
+            ListWhere::Tail => list.pop_back(),
+        };
+        // Empty lists are removed from the keyspace
+        if list.is_empty() {
+            self.lists.remove(key);
+        }
+        value
+    }
+
+    fn lmove(
+        &mut self,
+        src: &K,
+        dst: K,
+        wherefrom: ListWhere,
+        whereto: ListWhere,
+        blocked: &mut BlockingKeys<K, ListRequest<K>>,
+    ) -> Option<T> {
+        let value = self.pop(src, wherefrom)?;
+        self.push(dst, vec![value.clone()], whereto, blocked);
+        Some(value)
+    }
+}
+
+impl<K, T> BlockingStore<K> for ListStore<K, T>
+where
+    K: Eq + Hash + Clone,
+    T: Clone,
+{
+    type Request = ListRequest<K>;
+    type Value = T;
+
+    fn has_data(&self, key: &K) -> bool {
+        self.lists.contains_key(key)
+    }
+
+    fn serve(&mut self, key: &K, request: ListRequest<K>, blocked: &mut BlockingKeys<K, ListRequest<K>>) -> T {
+        match request {
+            ListRequest::Pop(wherefrom) => self.pop(key, wherefrom).unwrap(),
+            ListRequest::Move { dst, wherefrom, whereto } => self.lmove(key, dst, wherefrom, whereto, blocked).unwrap(),
+        }
+    }
+}
+
+/// Keyed lists with BLPOP / BRPOP / BLMOVE semantics.
+///
+/// # Examples
+///
+/// ```
+/// use curly_giggle::clock::ManualClock;
+/// use curly_giggle::collection::blocking::{BlockingLists, BlockResult, Served};
+///
+/// let mut lists = BlockingLists::new(ManualClock::new(0));
+/// assert_eq!(lists.blpop(1, vec!["jobs"], 0), BlockResult::Blocked);
+///
+/// lists.rpush("jobs", vec![42]);
+/// let served = lists.handle_clients_blocked_on_keys();
+/// assert_eq!(served, vec![Served { client: 1, key: "jobs", value: 42 }]);
+/// ```
+pub type BlockingLists<K, T, C> = Blocking<K, ListStore<K, T>, C>;
+
+impl<K, T, C> Blocking<K, ListStore<K, T>, C>
+where
+    K: Eq + Hash + Clone,
+    T: Clone,
+    C: Clock,
+{
+    pub fn llen(&self, key: &K) -> usize {
+        self.store.lists.get(key).map_or(0, |l| l.len())
+    }
+
+    pub fn exists(&self, key: &K) -> bool {
+        self.store.lists.contains_key(key)
+    }
+
+    pub fn lpush(&mut self, key: K, values: Vec<T>) -> usize {
+        self.store.push(key, values, ListWhere::Head, &mut self.blocked)
+    }
+
+    pub fn rpush(&mut self, key: K, values: Vec<T>) -> usize {
+        self.store.push(key, values, ListWhere::Tail, &mut self.blocked)
+    }
+
+    pub fn lpop(&mut self, key: &K) -> Option<T> {
+        self.store.pop(key, ListWhere::Head)
+    }
+
+    pub fn rpop(&mut self, key: &K) -> Option<T> {
+        self.store.pop(key, ListWhere::Tail)
+    }
+
+    /// Atomically pop from `src` and push to `dst`, returning the moved element.
+    pub fn lmove(&mut self, src: &K, dst: K, wherefrom: ListWhere, whereto: ListWhere) -> Option<T> {
+        self.store.lmove(src, dst, wherefrom, whereto, &mut self.blocked)
+    }
+
+    /// BLPOP: pop from the first non-empty key in `keys`, or block.
+    ///
+    /// `timeout_ms` of 0 blocks forever.
+    pub fn blpop(&mut self, client: ClientId, keys: Vec<K>, timeout_ms: u64) -> BlockResult<K, T> {
+        self.blocking_pop(client, keys, ListWhere::Head, timeout_ms)
+    }
+
+    /// BRPOP: pop from the tail of the first non-empty key in `keys`, or block.
+    pub fn brpop(&mut self, client: ClientId, keys: Vec<K>, timeout_ms: u64) -> BlockResult<K, T> {
+        self.blocking_pop(client, keys, ListWhere::Tail, timeout_ms)
+    }
+
+    /// BLMOVE: like `lmove`, but blocks while `src` is empty.
+    pub fn blmove(
+        &mut self,
+        client: ClientId,
+        src: K,
+        dst: K,
+        wherefrom: ListWhere,
+        whereto: ListWhere,
+        timeout_ms: u64,
+    ) -> BlockResult<K, T> {
+        if let Some(value) = self.lmove(&src, dst.clone(), wherefrom, whereto) {
+            return BlockResult::Served { key: src, value };
+        }
+        self.block(client, vec![src], ListRequest::Move { dst, wherefrom, whereto }, timeout_ms);
+        BlockResult::Blocked
+    }
+
+    fn blocking_pop(&mut self, client: ClientId, keys: Vec<K>, wherefrom: ListWhere, timeout_ms: u64) -> BlockResult<K, T> {
+        // Keys are checked in the order they were given
+        for key in keys.iter() {
+            if let Some(value) = self.store.pop(key, wherefrom) {
+                return BlockResult::Served { key: key.clone(), value };
+            }
+        }
+        self.block(client, keys, ListRequest::Pop(wherefrom), timeout_ms);
+        BlockResult::Blocked
+    }
+}
+
+/// `(score, member)` pairs popped from a sorted set, best first.
+pub type ZPopped<T> = Vec<(f64, T)>;
+
+/// What a client blocked on sorted sets will pop once served (BZPOPMIN, BZPOPMAX
+/// and BZMPOP).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ZSetRequest {
+    pub wherefrom: ZSetWhere,
+    pub count: usize,
+}
+
+/// The sorted sets of [`BlockingZSets`].
+pub struct ZSetStore<K, T> {
+    zsets: HashMap<K, ZSet<T>>,
+}
+
+impl<K, T> Default for ZSetStore<K, T> {
+    fn default() -> Self {
+        ZSetStore { zsets: HashMap::new() }
+    }
+}
+
+impl<K, T> ZSetStore<K, T>
+where
+    K: Eq + Hash + Clone,
+    T: Ord + Hash + Clone + AsRef<[u8]>,
+{
+    fn pop(&mut self, key: &K, wherefrom: ZSetWhere, count: usize) -> ZPopped<T> {
+        let zset = match self.zsets.get_mut(key) {
+            Some(zset) => zset,
+            None => return Vec::new(),
+        };
+        let popped = zset.zset_pop(wherefrom, count);
+        // Empty sets are removed from the keyspace
+        if zset.zcard() == 0 {
+            self.zsets.remove(key);
+        }
+        popped
+    }
+}
+
+impl<K, T> BlockingStore<K> for ZSetStore<K, T>
+where
+    K: Eq + Hash + Clone,
+    T: Ord + Hash + Clone + AsRef<[u8]>,
+{
+    type Request = ZSetRequest;
+    type Value = ZPopped<T>;
+
+    fn has_data(&self, key: &K) -> bool {
+        self.zsets.contains_key(key)
+    }
+
+    fn serve(&mut self, key: &K, request: ZSetRequest, _: &mut BlockingKeys<K, ZSetRequest>) -> ZPopped<T> {
+        self.pop(key, request.wherefrom, request.count)
+    }
+}
+
+/// Keyed sorted sets with BZPOPMIN / BZPOPMAX / BZMPOP semantics.
+///
+/// Works like [`BlockingLists`]: adding to a key only marks it as ready.
+///
+/// # Examples
+///
+/// ```
+/// use curly_giggle::clock::ManualClock;
+/// use curly_giggle::collection::blocking::{BlockingZSets, BlockResult, Served};
+/// use curly_giggle::collection::zset::{ZAddFlags, ZSetWhere};
+///
+/// let mut zsets = BlockingZSets::new(ManualClock::new(0));
+/// assert_eq!(zsets.bzmpop(1, vec!["board"], ZSetWhere::Max, 2, 0), Ok(BlockResult::Blocked));
+///
+/// zsets.zadd("board", ZAddFlags::default(), vec![(1.0, "a"), (3.0, "b"), (2.0, "c")]).unwrap();
+/// let served = zsets.handle_clients_blocked_on_keys();
+/// assert_eq!(served, vec![Served { client: 1, key: "board", value: vec![(3.0, "b"), (2.0, "c")] }]);
+/// ```
+pub type BlockingZSets<K, T, C> = Blocking<K, ZSetStore<K, T>, C>;
+
+impl<K, T, C> Blocking<K, ZSetStore<K, T>, C>
+where
+    K: Eq + Hash + Clone,
+    T: Ord + Hash + Clone + AsRef<[u8]>,
+    C: Clock,
+{
+    pub fn zcard(&self, key: &K) -> usize {
+        self.store.zsets.get(key).map_or(0, |zset| zset.zcard())
+    }
+
+    pub fn exists(&self, key: &K) -> bool {
+        self.store.zsets.contains_key(key)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&ZSet<T>> {
+        self.store.zsets.get(key)
+    }
+
+    /// ZADD on `key`, creating the set if needed.
+    pub fn zadd(&mut self, key: K, flags: ZAddFlags, elements: ZPopped<T>) -> Result<ZAddReply, String> {
+        let zsets = &mut self.store.zsets;
+        let zset = zsets.entry(key.clone()).or_default();
+        let reply = zset.zadd(flags, elements);
+        // Empty sets are removed from the keyspace, e.g. after ZADD XX on a new key
+        if zset.zcard() == 0 {
+            zsets.remove(&key);
+        } else {
+            self.blocked.signal_key_as_ready(&key);
+        }
+        reply
+    }
+
+    /// ZMPOP: pop up to `count` members from the first non-empty key in `keys`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `keys` is empty or `count` is 0.
+    pub fn zmpop(&mut self, keys: &[K], wherefrom: ZSetWhere, count: usize) -> Result<Option<(K, ZPopped<T>)>, String> {
+        Self::check_zmpop_args(keys, count)?;
+        for key in keys.iter() {
+            if self.zcard(key) > 0 {
+                return Ok(Some((key.clone(), self.store.pop(key, wherefrom, count))));
+            }
+        }
+        Ok(None)
+    }
+
+    /// BZMPOP: like `zmpop`, but blocks while all the keys are empty.
+    ///
+    /// `timeout_ms` of 0 blocks forever.
+    pub fn bzmpop(
+        &mut self,
+        client: ClientId,
+        keys: Vec<K>,
+        wherefrom: ZSetWhere,
+        count: usize,
+        timeout_ms: u64,
+    ) -> Result<BlockResult<K, ZPopped<T>>, String> {
+        if let Some((key, value)) = self.zmpop(&keys, wherefrom, count)? {
+            return Ok(BlockResult::Served { key, value });
+        }
+        self.block(client, keys, ZSetRequest { wherefrom, count }, timeout_ms);
+        Ok(BlockResult::Blocked)
+    }
+
+    /// BZPOPMIN: BZMPOP of a single member with the lowest score.
+    pub fn bzpopmin(&mut self, client: ClientId, keys: Vec<K>, timeout_ms: u64) -> Result<BlockResult<K, ZPopped<T>>, String> {
+        self.bzmpop(client, keys, ZSetWhere::Min, 1, timeout_ms)
+    }
+
+    /// BZPOPMAX: BZMPOP of a single member with the highest score.
+    pub fn bzpopmax(&mut self, client: ClientId, keys: Vec<K>, timeout_ms: u64) -> Result<BlockResult<K, ZPopped<T>>, String> {
+        self.bzmpop(client, keys, ZSetWhere::Max, 1, timeout_ms)
+    }
+
+    fn check_zmpop_args(keys: &[K], count: usize) -> Result<(), String> {
+        if keys.is_empty() {
+            return Err("numkeys should be greater than 0".to_string());
+        }
+        if count == 0 {
+            return Err("count should be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
@@ -1,46 +1,334 @@
-use std::collections::HashMap;
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
 
+use rand::{thread_rng, Rng};
+
+// Buckets of a table holding its first key, like `DICT_HT_INITIAL_SIZE`
+const DICT_HT_INITIAL_SIZE: usize = 4;
+
+/// A hash table chaining the entries of each bucket, like the `dict` of Redis.
+/// Unlike a `HashMap`, its buckets can be picked at random, so keys are sampled
+/// without walking the whole table.
+#[derive(Clone)]
 pub struct Dict<K, V> {
-    map: HashMap<K, V>,
+    table: Vec<Vec<(K, V)>>,
+    used: usize,
+    hasher: RandomState,
+}
+
+impl<K, V> Default for Dict<K, V>
+where
+    K: Eq + Hash,
+{
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<K, V> Dict<K, V>
 where
-    K: Eq + std::hash::Hash,
+    K: Eq + Hash,
 {
     pub fn new() -> Self {
         Dict {
-            map: HashMap::new(),
+            table: Vec::new(),
+            used: 0,
+            hasher: RandomState::new(),
         }
     }
 
     pub fn create(&mut self, key: K, value: V) {
-        self.map.insert(key, value);
+        self.insert(key, value);
     }
 
     pub fn add(&mut self, key: K, value: V) {
-        self.map.insert(key, value);
+        self.insert(key, value);
     }
 
     pub fn replace(&mut self, key: K, value: V) {
-        if self.map.contains_key(&key) {
-            self.map.insert(key, value);
+        if let Some(old) = self.fetch_value_mut(&key) {
+            *old = value;
         }
     }
 
-    pub fn fetch_value(&self, key: &K) -> Option<&V> {
-        self.map.get(key)
+    pub fn fetch_value<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let idx = self.bucket_index(key)?;
+        self.table[idx].iter().find(|(k, _)| k.borrow() == key).map(|(_, v)| v)
     }
 
     pub fn get_random_key(&self) -> Option<&K> {
-        self.map.keys().next()
+        self.get_random_key_with_rng(&mut thread_rng())
+    }
+
+    // Picks a random non-empty bucket, then a random entry of its chain, like
+    // `dictGetRandomKey`
+    pub fn get_random_key_with_rng<R: Rng>(&self, rng: &mut R) -> Option<&K> {
+        if self.used == 0 {
+            return None;
+        }
+        loop {
+            let chain = &self.table[rng.gen_range(0..self.table.len())];
+            if !chain.is_empty() {
+                return Some(&chain[rng.gen_range(0..chain.len())].0);
+            }
+        }
+    }
+
+    // Up to `count` keys from consecutive buckets after a random one, like
+    // `dictGetSomeKeys`. Cheaper than as many random keys but less evenly
+    // distributed, and gives up after `count * 10` buckets so fewer may be found.
+    pub fn get_some_keys<R: Rng>(&self, rng: &mut R, count: usize) -> Vec<&K> {
+        if count >= self.used {
+            // Every key, which takes about as long as walking the buckets
+            return self.keys().collect();
+        }
+        let mut keys = Vec::with_capacity(count);
+        let mask = self.table.len() - 1;
+        let mut idx = rng.gen_range(0..self.table.len());
+        let mut empty_len = 0;
+        for _ in 0..count * 10 {
+            let chain = &self.table[idx];
+            if chain.is_empty() {
+                // Jump elsewhere rather than crossing a long run of empty buckets
+                empty_len += 1;
+                if empty_len >= 5 && empty_len > count {
+                    idx = rng.gen_range(0..self.table.len());
+                    empty_len = 0;
+                    continue;
+                }
+            } else {
+                empty_len = 0;
+                for (key, _) in chain {
+                    keys.push(key);
+                    if keys.len() == count {
+                        return keys;
+                    }
+                }
+            }
+            idx = (idx + 1) & mask;
+        }
+        keys
+    }
+
+    pub fn delete<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.unlink(key);
+    }
+
+    // Remove the entry and hand it back instead of dropping it
+    pub fn unlink<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let idx = self.bucket_index(key)?;
+        let chain = &mut self.table[idx];
+        let pos = chain.iter().position(|(k, _)| k.borrow() == key)?;
+        let entry = chain.swap_remove(pos);
+        self.used -= 1;
+        self.shrink_if_needed();
+        Some(entry)
+    }
+
+    pub fn fetch_value_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let idx = self.bucket_index(key)?;
+        self.table[idx].iter_mut().find(|(k, _)| k.borrow() == key).map(|(_, v)| v)
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.fetch_value(key).is_some()
     }
 
-    pub fn delete(&mut self, key: &K) {
-        self.map.remove(key);
+    pub fn size(&self) -> usize {
+        self.used
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.used == 0
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            buckets: self.table.iter(),
+            chain: [].iter(),
+            remaining: self.used,
+        }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    // Bytes of the table, without what keys and values own, like `dictMemUsage`
+    pub fn mem_usage(&self) -> usize {
+        self.used * std::mem::size_of::<(K, V)>() + self.table.len() * std::mem::size_of::<Vec<(K, V)>>()
     }
 
     pub fn release(&mut self) {
-        self.map.clear();
+        self.table = Vec::new();
+        self.used = 0;
+    }
+
+    // Returns false if the key was already present, in which case only the value
+    // is replaced
+    fn insert(&mut self, key: K, value: V) -> bool {
+        if let Some(old) = self.fetch_value_mut(&key) {
+            *old = value;
+            return false;
+        }
+        self.expand_if_needed();
+        let idx = self.index_of(&key);
+        self.table[idx].push((key, value));
+        self.used += 1;
+        true
+    }
+
+    fn index_of<Q: Hash + ?Sized>(&self, key: &Q) -> usize {
+        self.hasher.hash_one(key) as usize & (self.table.len() - 1)
+    }
+
+    fn bucket_index<Q: Hash + ?Sized>(&self, key: &Q) -> Option<usize> {
+        if self.table.is_empty() {
+            None
+        } else {
+            Some(self.index_of(key))
+        }
     }
-}
\ No newline at end of file
+
+    // Doubles the table once it holds as many entries as buckets
+    fn expand_if_needed(&mut self) {
+        if self.table.is_empty() {
+            self.resize(DICT_HT_INITIAL_SIZE);
+        } else if self.used >= self.table.len() {
+            self.resize(self.table.len() * 2);
+        }
+    }
+
+    // Shrinks the table once less than 1/8 of its buckets would be used
+    fn shrink_if_needed(&mut self) {
+        if self.table.len() > DICT_HT_INITIAL_SIZE && self.used * 8 < self.table.len() {
+            self.resize(self.used.next_power_of_two().max(DICT_HT_INITIAL_SIZE));
+        }
+    }
+
+    fn resize(&mut self, size: usize) {
+        let old = std::mem::replace(&mut self.table, (0..size).map(|_| Vec::new()).collect());
+        for (key, value) in old.into_iter().flatten() {
+            let idx = self.index_of(&key);
+            self.table[idx].push((key, value));
+        }
+    }
+}
+
+// Keys without values, for dicts used as sets
+impl<K> Dict<K, ()>
+where
+    K: Eq + Hash,
+{
+    // Returns false if the key was already present
+    pub fn add_key(&mut self, key: K) -> bool {
+        self.insert(key, ())
+    }
+}
+
+/// Iterator over the entries of a `Dict`, in bucket order.
+pub struct Iter<'a, K, V> {
+    buckets: std::slice::Iter<'a, Vec<(K, V)>>,
+    chain: std::slice::Iter<'a, (K, V)>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((key, value)) = self.chain.next() {
+                self.remaining -= 1;
+                return Some((key, value));
+            }
+            self.chain = self.buckets.next()?.iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+/// Iterator over the keys of a `Dict`.
+pub struct Keys<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+
+/// Iterator taking the entries out of a `Dict`.
+pub struct IntoIter<K, V> {
+    buckets: std::vec::IntoIter<Vec<(K, V)>>,
+    chain: std::vec::IntoIter<(K, V)>,
+    remaining: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.chain.next() {
+                self.remaining -= 1;
+                return Some(entry);
+            }
+            self.chain = self.buckets.next()?.into_iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> IntoIterator for Dict<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            buckets: self.table.into_iter(),
+            chain: Vec::new().into_iter(),
+            remaining: self.used,
+        }
+    }
+}
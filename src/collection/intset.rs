@@ -0,0 +1,338 @@
+use rand::{thread_rng, Rng};
+
+// Header: encoding (u32 LE) followed by length (u32 LE)
+const INTSET_HEADER_SIZE: usize = 8;
+
+/// Width of the integers stored in an `IntSet`. The discriminant is the width in
+/// bytes, as written in the header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IntSetEncoding {
+    Int16 = 2,
+    Int32 = 4,
+    Int64 = 8,
+}
+
+impl IntSetEncoding {
+    /// Returns the smallest encoding able to hold `value`, like `_intsetValueEncoding`.
+    pub fn for_value(value: i64) -> Self {
+        if value < i32::MIN as i64 || value > i32::MAX as i64 {
+            IntSetEncoding::Int64
+        } else if value < i16::MIN as i64 || value > i16::MAX as i64 {
+            IntSetEncoding::Int32
+        } else {
+            IntSetEncoding::Int16
+        }
+    }
+
+    /// Returns the width of an element in bytes.
+    pub fn size(self) -> usize {
+        self as usize
+    }
+
+    fn from_size(size: u32) -> Option<Self> {
+        match size {
+            2 => Some(IntSetEncoding::Int16),
+            4 => Some(IntSetEncoding::Int32),
+            8 => Some(IntSetEncoding::Int64),
+            _ => None,
+        }
+    }
+}
+
+/// A sorted set of integers stored in a single byte buffer, like the Redis `intset`.
+///
+/// All the elements share the width of the largest one, and the whole set is upgraded
+/// in place when a value that does not fit arrives. The buffer layout is stable, so it
+/// can be written out with `as_bytes` and read back with `from_bytes`:
+///
+/// ```text
+/// <encoding: u32 LE> <length: u32 LE> <element 0> ... <element length - 1>
+/// ```
+///
+/// where each element is a little-endian integer of `encoding` bytes, in strictly
+/// ascending order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntSet {
+    blob: Vec<u8>,
+}
+
+impl Default for IntSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IntSet {
+    /// Creates an empty intset using the 16-bit encoding.
+    pub fn new() -> Self {
+        let mut blob = Vec::with_capacity(INTSET_HEADER_SIZE);
+        blob.extend_from_slice(&(IntSetEncoding::Int16 as u32).to_le_bytes());
+        blob.extend_from_slice(&0u32.to_le_bytes());
+        IntSet { blob }
+    }
+
+    pub fn encoding(&self) -> IntSetEncoding {
+        IntSetEncoding::from_size(self.header(0)).unwrap()
+    }
+
+    pub fn len(&self) -> usize {
+        self.header(4) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of bytes of the serialized intset, header included.
+    pub fn blob_len(&self) -> usize {
+        self.blob.len()
+    }
+
+    /// Returns the serialized intset.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.blob
+    }
+
+    fn header(&self, offset: usize) -> u32 {
+        u32::from_le_bytes(self.blob[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn set_encoding(&mut self, encoding: IntSetEncoding) {
+        self.blob[0..4].copy_from_slice(&(encoding as u32).to_le_bytes());
+    }
+
+    fn set_len(&mut self, len: usize) {
+        self.blob[4..8].copy_from_slice(&(len as u32).to_le_bytes());
+    }
+
+    fn get_encoded(&self, pos: usize, encoding: IntSetEncoding) -> i64 {
+        let start = INTSET_HEADER_SIZE + pos * encoding.size();
+        let bytes = &self.blob[start..start + encoding.size()];
+        match encoding {
+            IntSetEncoding::Int16 => i16::from_le_bytes(bytes.try_into().unwrap()) as i64,
+            IntSetEncoding::Int32 => i32::from_le_bytes(bytes.try_into().unwrap()) as i64,
+            IntSetEncoding::Int64 => i64::from_le_bytes(bytes.try_into().unwrap()),
+        }
+    }
+
+    fn set(&mut self, pos: usize, value: i64) {
+        let encoding = self.encoding();
+        let start = INTSET_HEADER_SIZE + pos * encoding.size();
+        let bytes = &mut self.blob[start..start + encoding.size()];
+        match encoding {
+            IntSetEncoding::Int16 => bytes.copy_from_slice(&(value as i16).to_le_bytes()),
+            IntSetEncoding::Int32 => bytes.copy_from_slice(&(value as i32).to_le_bytes()),
+            IntSetEncoding::Int64 => bytes.copy_from_slice(&value.to_le_bytes()),
+        }
+    }
+
+    /// Returns the element at `pos` in ascending order.
+    pub fn get(&self, pos: usize) -> Option<i64> {
+        if pos < self.len() {
+            Some(self.get_encoded(pos, self.encoding()))
+        } else {
+            None
+        }
+    }
+
+    pub fn min(&self) -> Option<i64> {
+        self.get(0)
+    }
+
+    pub fn max(&self) -> Option<i64> {
+        self.len().checked_sub(1).and_then(|pos| self.get(pos))
+    }
+
+    /// Binary search for `value`, like `intsetSearch`. Returns `Ok` with its position
+    /// if present, or `Err` with the position where it would be inserted.
+    fn search(&self, value: i64) -> Result<usize, usize> {
+        let len = self.len();
+        if len == 0 {
+            return Err(0);
+        }
+        // Values outside the range can be placed without searching
+        if value > self.max().unwrap() {
+            return Err(len);
+        }
+        if value < self.min().unwrap() {
+            return Err(0);
+        }
+
+        let encoding = self.encoding();
+        let (mut min, mut max) = (0, len);
+        while min < max {
+            let mid = min + (max - min) / 2;
+            let cur = self.get_encoded(mid, encoding);
+            if value > cur {
+                min = mid + 1;
+            } else if value < cur {
+                max = mid;
+            } else {
+                return Ok(mid);
+            }
+        }
+        Err(min)
+    }
+
+    /// Returns whether `value` is in the set.
+    pub fn find(&self, value: i64) -> bool {
+        IntSetEncoding::for_value(value) <= self.encoding() && self.search(value).is_ok()
+    }
+
+    // Moves the elements from `from` to the end of the set to start at `to`, after
+    // the buffer has been resized for the new length.
+    fn move_tail(&mut self, from: usize, to: usize, count: usize) {
+        let size = self.encoding().size();
+        let src = INTSET_HEADER_SIZE + from * size;
+        self.blob.copy_within(src..src + count * size, INTSET_HEADER_SIZE + to * size);
+    }
+
+    // Widens every element to the encoding of `value` and adds it, like
+    // `intsetUpgradeAndAdd`. A value that needs a wider encoding is either below or
+    // above every element, so it goes at one end.
+    fn upgrade_and_add(&mut self, value: i64) {
+        let old = self.encoding();
+        let new = IntSetEncoding::for_value(value);
+        let len = self.len();
+        let prepend = usize::from(value < 0);
+
+        self.blob.resize(INTSET_HEADER_SIZE + (len + 1) * new.size(), 0);
+        self.set_encoding(new);
+        // From the back so no element is overwritten before it is moved
+        for pos in (0..len).rev() {
+            let element = self.get_encoded(pos, old);
+            self.set(pos + prepend, element);
+        }
+        if prepend == 1 {
+            self.set(0, value);
+        } else {
+            self.set(len, value);
+        }
+        self.set_len(len + 1);
+    }
+
+    /// Adds `value`, upgrading the encoding if needed.
+    ///
+    /// Returns `false` if the value was already present.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::intset::{IntSet, IntSetEncoding};
+    ///
+    /// let mut set = IntSet::new();
+    /// assert!(set.add(5));
+    /// assert!(!set.add(5));
+    /// assert_eq!(set.encoding(), IntSetEncoding::Int16);
+    ///
+    /// set.add(1 << 40);
+    /// assert_eq!(set.encoding(), IntSetEncoding::Int64);
+    /// assert_eq!(set.iter().collect::<Vec<_>>(), vec![5, 1 << 40]);
+    /// ```
+    pub fn add(&mut self, value: i64) -> bool {
+        if IntSetEncoding::for_value(value) > self.encoding() {
+            self.upgrade_and_add(value);
+            return true;
+        }
+        match self.search(value) {
+            Ok(_) => false,
+            Err(pos) => {
+                let len = self.len();
+                self.blob.resize(self.blob.len() + self.encoding().size(), 0);
+                if pos < len {
+                    self.move_tail(pos, pos + 1, len - pos);
+                }
+                self.set(pos, value);
+                self.set_len(len + 1);
+                true
+            }
+        }
+    }
+
+    /// Removes `value`. Returns `false` if it was not present.
+    ///
+    /// The encoding is never downgraded.
+    pub fn remove(&mut self, value: i64) -> bool {
+        if IntSetEncoding::for_value(value) > self.encoding() {
+            return false;
+        }
+        match self.search(value) {
+            Ok(pos) => {
+                let len = self.len();
+                if pos < len - 1 {
+                    self.move_tail(pos + 1, pos, len - pos - 1);
+                }
+                self.blob.truncate(self.blob.len() - self.encoding().size());
+                self.set_len(len - 1);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Returns a random element, or `None` if the set is empty.
+    pub fn random(&self) -> Option<i64> {
+        self.random_with_rng(&mut thread_rng())
+    }
+
+    /// Like `random`, drawing from `rng`.
+    pub fn random_with_rng<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<i64> {
+        if self.is_empty() {
+            return None;
+        }
+        self.get(rng.gen_range(0..self.len()))
+    }
+
+    /// Returns the elements in ascending order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = i64> + ExactSizeIterator + '_ {
+        let encoding = self.encoding();
+        (0..self.len()).map(move |pos| self.get_encoded(pos, encoding))
+    }
+
+    /// Checks that `bytes` is a well formed intset, like `intsetValidateIntegrity`,
+    /// and takes it as one. Meant for buffers read from untrusted input, where an
+    /// empty intset is invalid since empty keys are never stored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::intset::IntSet;
+    ///
+    /// let mut set = IntSet::new();
+    /// set.add(-3);
+    /// set.add(70_000);
+    /// let copy = IntSet::from_bytes(set.as_bytes()).unwrap();
+    /// assert_eq!(copy, set);
+    ///
+    /// assert!(IntSet::from_bytes(&[2, 0, 0, 0, 1, 0, 0, 0]).is_err());
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < INTSET_HEADER_SIZE {
+            return Err("intset header is truncated".to_string());
+        }
+        let set = IntSet { blob: bytes.to_vec() };
+        let encoding = IntSetEncoding::from_size(set.header(0))
+            .ok_or_else(|| format!("invalid intset encoding {}", set.header(0)))?;
+        let len = set.header(4) as usize;
+        if len.checked_mul(encoding.size()).map(|size| size + INTSET_HEADER_SIZE) != Some(bytes.len()) {
+            return Err("intset length does not match its size".to_string());
+        }
+        if len == 0 {
+            return Err("intset is empty".to_string());
+        }
+        if set.iter().zip(set.iter().skip(1)).any(|(prev, cur)| prev >= cur) {
+            return Err("intset elements are not strictly ascending".to_string());
+        }
+        Ok(set)
+    }
+}
+
+impl FromIterator<i64> for IntSet {
+    fn from_iter<I: IntoIterator<Item = i64>>(iter: I) -> Self {
+        let mut set = IntSet::new();
+        for value in iter {
+            set.add(value);
+        }
+        set
+    }
+}
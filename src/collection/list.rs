@@ -31,6 +31,10 @@ impl<T> LinkedList<T> {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub fn front(&self) -> Option<&T> {
         // SAFETY: front is always a valid pointer
         unsafe { self.front.as_ref().map(|node| &node.as_ref().elem) }
@@ -63,6 +67,28 @@ impl<T> LinkedList<T> {
         }
     }
 
+    pub fn push_back(&mut self, elem: T) {
+        // SAFETY: it's a linked-list
+        unsafe {
+            let new = NonNull::new_unchecked(Box::into_raw(Box::new(Node {
+                front: None,
+                back: None,
+                elem,
+            })));
+            if let Some(old) = self.back {
+                // Put the new back after the old one
+                (*old.as_ptr()).back = Some(new);
+                (*new.as_ptr()).front = Some(old);
+            } else {
+                // If there's no back, then we're the empty list and need
+                // to set the front too.
+                self.front = Some(new);
+            }
+            self.back = Some(new);
+            self.len += 1;
+        }
+    }
+
     pub fn pop_front(&mut self) -> Option<T> {
         unsafe {
             // Only have to do stuff if there is a front node to pop.
@@ -88,5 +114,81 @@ impl<T> LinkedList<T> {
             })
         }
     }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        unsafe {
+            self.back.map(|node| {
+                let boxed_node = Box::from_raw(node.as_ptr());
+                let result = boxed_node.elem;
+
+                // Make the previous node into the new back.
+                self.back = boxed_node.front;
+                if let Some(new) = self.back {
+                    (*new.as_ptr()).back = None;
+                } else {
+                    // If the back is now null, then this list is now empty!
+                    self.front = None;
+                }
+
+                self.len -= 1;
+                result
+            })
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            front: self.front,
+            len: self.len,
+            _boo: PhantomData,
+        }
+    }
+}
+
+pub struct Iter<'a, T> {
+    front: Link<T>,
+    len: usize,
+    _boo: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // SAFETY: the list outlives the iterator and is not changed meanwhile
+        self.front.map(|node| unsafe {
+            self.len -= 1;
+            self.front = (*node.as_ptr()).back;
+            &(*node.as_ptr()).elem
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> Default for LinkedList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> Clone for LinkedList<T> {
+    fn clone(&self) -> Self {
+        let mut list = Self::new();
+        for elem in self.iter() {
+            list.push_back(elem.clone());
+        }
+        list
+    }
 }
 
+impl<T> Drop for LinkedList<T> {
+    fn drop(&mut self) {
+        // Pop until we have to stop
+        while self.pop_front().is_some() {}
+    }
+}
@@ -0,0 +1,635 @@
+use std::borrow::Cow;
+
+// Header: total bytes (u32 LE) followed by the number of elements (u16 LE)
+const LP_HDR_SIZE: usize = 6;
+// Stored as the number of elements once there are too many to fit the header
+const LP_HDR_NUMELE_UNKNOWN: usize = u16::MAX as usize;
+const LP_EOF: u8 = 0xFF;
+
+const LP_ENCODING_7BIT_UINT: u8 = 0x00;
+const LP_ENCODING_7BIT_UINT_MASK: u8 = 0x80;
+const LP_ENCODING_6BIT_STR: u8 = 0x80;
+const LP_ENCODING_6BIT_STR_MASK: u8 = 0xC0;
+const LP_ENCODING_13BIT_INT: u8 = 0xC0;
+const LP_ENCODING_13BIT_INT_MASK: u8 = 0xE0;
+const LP_ENCODING_12BIT_STR: u8 = 0xE0;
+const LP_ENCODING_12BIT_STR_MASK: u8 = 0xF0;
+const LP_ENCODING_16BIT_INT: u8 = 0xF1;
+const LP_ENCODING_24BIT_INT: u8 = 0xF2;
+const LP_ENCODING_32BIT_INT: u8 = 0xF3;
+const LP_ENCODING_64BIT_INT: u8 = 0xF4;
+const LP_ENCODING_32BIT_STR: u8 = 0xF0;
+
+/// An element of a listpack: strings that look like integers are stored, and read
+/// back, as integers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListpackEntry<'a> {
+    Int(i64),
+    Str(&'a [u8]),
+}
+
+impl<'a> ListpackEntry<'a> {
+    /// Returns the element as a string, formatting integers in decimal.
+    pub fn to_bytes(&self) -> Cow<'a, [u8]> {
+        match *self {
+            ListpackEntry::Int(value) => Cow::Owned(value.to_string().into_bytes()),
+            ListpackEntry::Str(s) => Cow::Borrowed(s),
+        }
+    }
+
+    // The integer a string would be stored as, if any
+    fn as_int(&self) -> Option<i64> {
+        match *self {
+            ListpackEntry::Int(value) => Some(value),
+            ListpackEntry::Str(s) => string_to_int(s),
+        }
+    }
+}
+
+impl From<i64> for ListpackEntry<'_> {
+    fn from(value: i64) -> Self {
+        ListpackEntry::Int(value)
+    }
+}
+
+impl<'a> From<&'a [u8]> for ListpackEntry<'a> {
+    fn from(s: &'a [u8]) -> Self {
+        ListpackEntry::Str(s)
+    }
+}
+
+impl<'a> From<&'a str> for ListpackEntry<'a> {
+    fn from(s: &'a str) -> Self {
+        ListpackEntry::Str(s.as_bytes())
+    }
+}
+
+/// Where to insert relative to an existing element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListpackWhere {
+    Before,
+    After,
+}
+
+/// Parses `s` as a decimal integer the way Redis `string2ll` does: no sign other than a
+/// leading `-`, no leading zeros and no surrounding spaces, so that formatting the
+/// integer gives back `s`.
+pub(crate) fn string_to_int(s: &[u8]) -> Option<i64> {
+    if s.is_empty() || s.len() > 20 {
+        return None;
+    }
+    if s == b"0" {
+        return Some(0);
+    }
+    let digits = s.strip_prefix(b"-").unwrap_or(s);
+    if !matches!(digits.first(), Some(b'1'..=b'9')) || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    std::str::from_utf8(s).ok()?.parse().ok()
+}
+
+// Writes the length of an entry, without the back-length itself, so that it can be
+// read from its last byte backwards: 7 bits per byte, the high bit set on every byte
+// but the first one.
+fn encode_backlen(len: usize, out: &mut Vec<u8>) {
+    let size = backlen_size(len);
+    for i in (0..size).rev() {
+        let bits = ((len >> (7 * i)) & 127) as u8;
+        out.push(if i == size - 1 { bits } else { bits | 128 });
+    }
+}
+
+fn backlen_size(len: usize) -> usize {
+    match len {
+        0..=127 => 1,
+        128..=16382 => 2,
+        16383..=2097150 => 3,
+        2097151..=268435454 => 4,
+        _ => 5,
+    }
+}
+
+// Appends the encoding and the data of `entry`, without the back-length.
+fn encode_entry(entry: &ListpackEntry, out: &mut Vec<u8>) {
+    if let Some(v) = entry.as_int() {
+        if (0..=127).contains(&v) {
+            out.push(v as u8 | LP_ENCODING_7BIT_UINT);
+        } else if (-4096..=4095).contains(&v) {
+            let v = if v < 0 { (1 << 13) + v } else { v } as u16;
+            out.push((v >> 8) as u8 | LP_ENCODING_13BIT_INT);
+            out.push(v as u8);
+        } else if (-32768..=32767).contains(&v) {
+            out.push(LP_ENCODING_16BIT_INT);
+            out.extend_from_slice(&(v as i16).to_le_bytes());
+        } else if (-8388608..=8388607).contains(&v) {
+            out.push(LP_ENCODING_24BIT_INT);
+            out.extend_from_slice(&(v as i32).to_le_bytes()[..3]);
+        } else if (-2147483648..=2147483647).contains(&v) {
+            out.push(LP_ENCODING_32BIT_INT);
+            out.extend_from_slice(&(v as i32).to_le_bytes());
+        } else {
+            out.push(LP_ENCODING_64BIT_INT);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        return;
+    }
+
+    let s = match entry {
+        ListpackEntry::Str(s) => s,
+        ListpackEntry::Int(_) => unreachable!(),
+    };
+    let len = s.len();
+    if len < 64 {
+        out.push(len as u8 | LP_ENCODING_6BIT_STR);
+    } else if len < 4096 {
+        out.push((len >> 8) as u8 | LP_ENCODING_12BIT_STR);
+        out.push(len as u8);
+    } else {
+        out.push(LP_ENCODING_32BIT_STR);
+        out.extend_from_slice(&(len as u32).to_le_bytes());
+    }
+    out.extend_from_slice(s);
+}
+
+/// Returns the size of the encoding and data of the entry starting with `bytes`, or
+/// `None` if the encoding byte is invalid or its length bytes are missing.
+fn encoded_size(bytes: &[u8]) -> Option<usize> {
+    let b = *bytes.first()?;
+    let size = if b & LP_ENCODING_7BIT_UINT_MASK == LP_ENCODING_7BIT_UINT {
+        1
+    } else if b & LP_ENCODING_6BIT_STR_MASK == LP_ENCODING_6BIT_STR {
+        1 + (b & 0x3F) as usize
+    } else if b & LP_ENCODING_13BIT_INT_MASK == LP_ENCODING_13BIT_INT {
+        2
+    } else if b & LP_ENCODING_12BIT_STR_MASK == LP_ENCODING_12BIT_STR {
+        2 + (((b & 0x0F) as usize) << 8 | *bytes.get(1)? as usize)
+    } else {
+        match b {
+            LP_ENCODING_16BIT_INT => 3,
+            LP_ENCODING_24BIT_INT => 4,
+            LP_ENCODING_32BIT_INT => 5,
+            LP_ENCODING_64BIT_INT => 9,
+            LP_ENCODING_32BIT_STR => 5 + u32::from_le_bytes(bytes.get(1..5)?.try_into().unwrap()) as usize,
+            _ => return None,
+        }
+    };
+    Some(size)
+}
+
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+/// A compact list of strings and integers in a single byte buffer, like the Redis
+/// `listpack`.
+///
+/// ```text
+/// <total bytes: u32 LE> <num elements: u16 LE> <entry> ... <entry> <0xFF>
+/// ```
+///
+/// Each entry is an encoding byte, possibly followed by length bytes and data, then a
+/// back-length holding the size of all that so the list can be walked backwards.
+///
+/// Entries are addressed by their byte offset in the buffer, as returned by `first`,
+/// `last`, `next`, `prev` and `seek`. An offset is invalidated by any change to the list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Listpack {
+    buf: Vec<u8>,
+}
+
+impl Default for Listpack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Listpack {
+    /// Creates an empty listpack, like `lpNew`.
+    pub fn new() -> Self {
+        let mut buf = Vec::with_capacity(LP_HDR_SIZE + 1);
+        buf.extend_from_slice(&((LP_HDR_SIZE + 1) as u32).to_le_bytes());
+        buf.extend_from_slice(&0u16.to_le_bytes());
+        buf.push(LP_EOF);
+        Listpack { buf }
+    }
+
+    /// Checks that `bytes` is a well formed listpack and takes it as one. Meant for
+    /// buffers read from untrusted input.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        Self::validate_integrity(bytes, true)?;
+        Ok(Listpack { buf: bytes.to_vec() })
+    }
+
+    /// Validates the listpack in `bytes`, like `lpValidateIntegrity`.
+    ///
+    /// Only the header and the terminator are checked unless `deep` is set, in which
+    /// case every entry is checked to lie within the buffer and to have a matching
+    /// back-length, and the number of entries is checked against the header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::listpack::Listpack;
+    ///
+    /// let mut lp = Listpack::new();
+    /// lp.append("hello");
+    /// assert!(Listpack::validate_integrity(lp.as_bytes(), true).is_ok());
+    ///
+    /// let mut corrupt = lp.as_bytes().to_vec();
+    /// corrupt[6] = 0x8F; // claim a 15 byte string
+    /// assert!(Listpack::validate_integrity(&corrupt, false).is_ok());
+    /// assert!(Listpack::validate_integrity(&corrupt, true).is_err());
+    /// ```
+    pub fn validate_integrity(bytes: &[u8], deep: bool) -> Result<(), String> {
+        if bytes.len() < LP_HDR_SIZE + 1 {
+            return Err("listpack is smaller than its header".to_string());
+        }
+        let total = u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        if total != bytes.len() {
+            return Err("listpack total bytes does not match its size".to_string());
+        }
+        if bytes[total - 1] != LP_EOF {
+            return Err("listpack is not terminated".to_string());
+        }
+        if !deep {
+            return Ok(());
+        }
+
+        let eof = total - 1;
+        let mut p = LP_HDR_SIZE;
+        let mut count = 0;
+        while p < eof {
+            let size = encoded_size(&bytes[p..eof]).ok_or("invalid listpack entry encoding")?;
+            let backlen_end = p
+                .checked_add(size)
+                .and_then(|end| end.checked_add(backlen_size(size)))
+                .filter(|&end| end <= eof)
+                .ok_or("listpack entry overflows the listpack")?;
+            if decode_backlen(bytes, backlen_end - 1, p + size) != Some(size) {
+                return Err("listpack entry back-length does not match".to_string());
+            }
+            p = backlen_end;
+            count += 1;
+        }
+
+        let num_elements = u16::from_le_bytes(bytes[4..6].try_into().unwrap()) as usize;
+        if num_elements != LP_HDR_NUMELE_UNKNOWN && num_elements != count {
+            return Err("listpack number of elements does not match".to_string());
+        }
+        Ok(())
+    }
+
+    /// Returns the serialized listpack.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Returns the total size of the listpack in bytes, like `lpBytes`.
+    pub fn blob_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns the number of elements, like `lpLength`. Walks the whole list once there
+    /// are more than the header can count.
+    pub fn len(&self) -> usize {
+        let num_elements = u16::from_le_bytes(self.buf[4..6].try_into().unwrap()) as usize;
+        if num_elements != LP_HDR_NUMELE_UNKNOWN {
+            return num_elements;
+        }
+        let mut count = 0;
+        let mut p = self.first();
+        while let Some(pos) = p {
+            count += 1;
+            p = self.next(pos);
+        }
+        count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf[LP_HDR_SIZE] == LP_EOF
+    }
+
+    // Updates the total bytes and moves the number of elements by `delta`. Once the
+    // count is unknown it stays so, as in Redis.
+    fn update_header(&mut self, delta: isize) {
+        let total = self.buf.len();
+        assert!(total <= u32::MAX as usize, "listpack is too large");
+        self.buf[0..4].copy_from_slice(&(total as u32).to_le_bytes());
+        let num_elements = u16::from_le_bytes(self.buf[4..6].try_into().unwrap()) as usize;
+        if num_elements != LP_HDR_NUMELE_UNKNOWN {
+            let num_elements = num_elements.checked_add_signed(delta).unwrap().min(LP_HDR_NUMELE_UNKNOWN) as u16;
+            self.buf[4..6].copy_from_slice(&num_elements.to_le_bytes());
+        }
+    }
+
+    fn eof(&self) -> usize {
+        self.buf.len() - 1
+    }
+
+    // Returns the size of the entry at `p`, back-length included.
+    fn entry_size(&self, p: usize) -> usize {
+        let size = encoded_size(&self.buf[p..]).expect("invalid listpack entry");
+        size + backlen_size(size)
+    }
+
+    /// Returns the offset of the first element, like `lpFirst`.
+    pub fn first(&self) -> Option<usize> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(LP_HDR_SIZE)
+        }
+    }
+
+    /// Returns the offset of the last element, like `lpLast`.
+    pub fn last(&self) -> Option<usize> {
+        self.prev(self.eof())
+    }
+
+    /// Returns the offset of the element after the one at `p`, like `lpNext`.
+    pub fn next(&self, p: usize) -> Option<usize> {
+        let next = p + self.entry_size(p);
+        if next < self.eof() {
+            Some(next)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the offset of the element before the one at `p`, like `lpPrev`. `p` may
+    /// also be the offset of the terminator.
+    pub fn prev(&self, p: usize) -> Option<usize> {
+        if p <= LP_HDR_SIZE {
+            return None;
+        }
+        let size = decode_backlen(&self.buf, p - 1, LP_HDR_SIZE).expect("invalid listpack back-length");
+        Some(p - backlen_size(size) - size)
+    }
+
+    /// Returns the offset of the element at `index`, like `lpSeek`. Negative indexes
+    /// count from the end, -1 being the last element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::listpack::{Listpack, ListpackEntry};
+    ///
+    /// let lp: Listpack = ["a", "b", "c"].into_iter().collect();
+    /// assert_eq!(lp.get(lp.seek(1).unwrap()), ListpackEntry::Str(b"b"));
+    /// assert_eq!(lp.get(lp.seek(-1).unwrap()), ListpackEntry::Str(b"c"));
+    /// assert_eq!(lp.seek(3), None);
+    /// assert_eq!(lp.seek(-4), None);
+    /// ```
+    pub fn seek(&self, index: i64) -> Option<usize> {
+        let len = self.len() as i64;
+        let index = if index < 0 { len + index } else { index };
+        if index < 0 || index >= len {
+            return None;
+        }
+        // Walk from whichever end is nearer
+        if index > len / 2 {
+            let mut p = self.last();
+            for _ in index + 1..len {
+                p = self.prev(p?);
+            }
+            p
+        } else {
+            let mut p = self.first();
+            for _ in 0..index {
+                p = self.next(p?);
+            }
+            p
+        }
+    }
+
+    /// Returns the element at `p`, like `lpGet`.
+    pub fn get(&self, p: usize) -> ListpackEntry<'_> {
+        let b = &self.buf[p..];
+        let int = |len: usize| u64::from_le_bytes({
+            let mut bytes = [0; 8];
+            bytes[..len].copy_from_slice(&b[1..1 + len]);
+            bytes
+        });
+        if b[0] & LP_ENCODING_7BIT_UINT_MASK == LP_ENCODING_7BIT_UINT {
+            ListpackEntry::Int((b[0] & 0x7F) as i64)
+        } else if b[0] & LP_ENCODING_6BIT_STR_MASK == LP_ENCODING_6BIT_STR {
+            let len = (b[0] & 0x3F) as usize;
+            ListpackEntry::Str(&b[1..1 + len])
+        } else if b[0] & LP_ENCODING_13BIT_INT_MASK == LP_ENCODING_13BIT_INT {
+            let value = ((b[0] & 0x1F) as u64) << 8 | b[1] as u64;
+            ListpackEntry::Int(sign_extend(value, 13))
+        } else if b[0] & LP_ENCODING_12BIT_STR_MASK == LP_ENCODING_12BIT_STR {
+            let len = ((b[0] & 0x0F) as usize) << 8 | b[1] as usize;
+            ListpackEntry::Str(&b[2..2 + len])
+        } else {
+            match b[0] {
+                LP_ENCODING_16BIT_INT => ListpackEntry::Int(sign_extend(int(2), 16)),
+                LP_ENCODING_24BIT_INT => ListpackEntry::Int(sign_extend(int(3), 24)),
+                LP_ENCODING_32BIT_INT => ListpackEntry::Int(sign_extend(int(4), 32)),
+                LP_ENCODING_64BIT_INT => ListpackEntry::Int(int(8) as i64),
+                LP_ENCODING_32BIT_STR => {
+                    let len = int(4) as usize;
+                    ListpackEntry::Str(&b[5..5 + len])
+                }
+                _ => panic!("invalid listpack entry encoding {:#x}", b[0]),
+            }
+        }
+    }
+
+    /// Returns the offset of the first element equal to `entry`, like `lpFind`. A string
+    /// that looks like an integer matches that integer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::listpack::Listpack;
+    ///
+    /// let lp: Listpack = ["a", "10", "b"].into_iter().collect();
+    /// assert_eq!(lp.find("10"), lp.seek(1));
+    /// assert_eq!(lp.find(10), lp.seek(1));
+    /// assert_eq!(lp.find("c"), None);
+    /// ```
+    pub fn find<'a, E: Into<ListpackEntry<'a>>>(&self, entry: E) -> Option<usize> {
+        self.first().and_then(|p| self.find_from(p, entry, 0))
+    }
+
+    /// Like `find`, starting at `p` and skipping `skip` elements after each one
+    /// compared, like `lpFind` does to look only at the keys of key/value pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::listpack::Listpack;
+    ///
+    /// let lp: Listpack = ["a", "b", "b", "c"].into_iter().collect();
+    /// assert_eq!(lp.find_from(lp.first().unwrap(), "b", 1), lp.seek(2));
+    /// assert_eq!(lp.find_from(lp.first().unwrap(), "c", 1), None);
+    /// ```
+    pub fn find_from<'a, E: Into<ListpackEntry<'a>>>(&self, p: usize, entry: E, skip: usize) -> Option<usize> {
+        let entry = entry.into();
+        let target = entry.as_int().map_or(entry, ListpackEntry::Int);
+        let mut p = Some(p);
+        while let Some(pos) = p {
+            if self.get(pos) == target {
+                return Some(pos);
+            }
+            p = self.next(pos);
+            for _ in 0..skip {
+                p = p.and_then(|pos| self.next(pos));
+            }
+        }
+        None
+    }
+
+    /// Returns the elements from first to last.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { lp: self, next: self.first() }
+    }
+
+    // Replaces the `remove` bytes at `p` with the encoded `entry`, if any.
+    fn splice(&mut self, p: usize, remove: usize, entry: Option<ListpackEntry>) {
+        let mut encoded = Vec::new();
+        if let Some(entry) = entry {
+            encode_entry(&entry, &mut encoded);
+            encode_backlen(encoded.len(), &mut encoded);
+        }
+        let delta = if entry.is_some() { 1 } else { -1 };
+        self.buf.splice(p..p + remove, encoded);
+        self.update_header(delta);
+    }
+
+    /// Appends an element at the end, like `lpAppend` and `lpAppendInteger`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::listpack::{Listpack, ListpackEntry};
+    ///
+    /// let mut lp = Listpack::new();
+    /// lp.append("hello");
+    /// lp.append("1024");
+    /// lp.append(-7);
+    /// assert_eq!(
+    ///     lp.iter().collect::<Vec<_>>(),
+    ///     vec![ListpackEntry::Str(b"hello"), ListpackEntry::Int(1024), ListpackEntry::Int(-7)]
+    /// );
+    /// ```
+    pub fn append<'a, E: Into<ListpackEntry<'a>>>(&mut self, entry: E) {
+        let eof = self.eof();
+        self.splice(eof, 0, Some(entry.into()));
+    }
+
+    /// Inserts an element at the front, like `lpPrepend`.
+    pub fn prepend<'a, E: Into<ListpackEntry<'a>>>(&mut self, entry: E) {
+        self.splice(LP_HDR_SIZE, 0, Some(entry.into()));
+    }
+
+    /// Inserts an element before or after the one at `p`, like `lpInsertString`.
+    /// Returns the offset of the new element.
+    pub fn insert<'a, E: Into<ListpackEntry<'a>>>(&mut self, p: usize, entry: E, wherefrom: ListpackWhere) -> usize {
+        let at = match wherefrom {
+            ListpackWhere::Before => p,
+            ListpackWhere::After => p + self.entry_size(p),
+        };
+        self.splice(at, 0, Some(entry.into()));
+        at
+    }
+
+    /// Replaces the element at `p`, like `lpReplace`. Returns the offset of the new
+    /// element, which is `p`.
+    pub fn replace<'a, E: Into<ListpackEntry<'a>>>(&mut self, p: usize, entry: E) -> usize {
+        let size = self.entry_size(p);
+        let mut encoded = Vec::new();
+        encode_entry(&entry.into(), &mut encoded);
+        encode_backlen(encoded.len(), &mut encoded);
+        self.buf.splice(p..p + size, encoded);
+        self.update_header(0);
+        p
+    }
+
+    /// Deletes the element at `p`, like `lpDelete`. Returns the offset of the element
+    /// that followed it, if any.
+    pub fn delete(&mut self, p: usize) -> Option<usize> {
+        let size = self.entry_size(p);
+        self.splice(p, size, None);
+        if p < self.eof() {
+            Some(p)
+        } else {
+            None
+        }
+    }
+
+    /// Deletes up to `num` elements starting at `index`, like `lpDeleteRange`.
+    /// Negative indexes count from the end. Returns the number of deleted elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::listpack::{Listpack, ListpackEntry};
+    ///
+    /// let mut lp: Listpack = (1..=5i64).collect();
+    /// assert_eq!(lp.delete_range(-2, 10), 2);
+    /// assert_eq!(lp.delete_range(0, 1), 1);
+    /// assert_eq!(lp.iter().collect::<Vec<_>>(), vec![ListpackEntry::Int(2), ListpackEntry::Int(3)]);
+    /// ```
+    pub fn delete_range(&mut self, index: i64, num: usize) -> usize {
+        let start = match self.seek(index) {
+            Some(p) => p,
+            None => return 0,
+        };
+        let eof = self.eof();
+        let mut end = start;
+        let mut deleted = 0;
+        while deleted < num && end < eof {
+            end += self.entry_size(end);
+            deleted += 1;
+        }
+        self.buf.drain(start..end);
+        self.update_header(-(deleted as isize));
+        deleted
+    }
+}
+
+// Decodes the back-length whose last byte is at `p`, reading no further back than
+// `start`.
+fn decode_backlen(buf: &[u8], mut p: usize, start: usize) -> Option<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        value |= ((buf[p] & 127) as usize) << shift;
+        if buf[p] & 128 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+        if shift > 28 || p <= start {
+            return None;
+        }
+        p -= 1;
+    }
+}
+
+pub struct Iter<'a> {
+    lp: &'a Listpack,
+    next: Option<usize>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = ListpackEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let p = self.next?;
+        self.next = self.lp.next(p);
+        Some(self.lp.get(p))
+    }
+}
+
+impl<'a, E: Into<ListpackEntry<'a>>> FromIterator<E> for Listpack {
+    fn from_iter<I: IntoIterator<Item = E>>(iter: I) -> Self {
+        let mut lp = Listpack::new();
+        for entry in iter {
+            lp.append(entry);
+        }
+        lp
+    }
+}
@@ -1,3 +1,20 @@
+pub mod blocking;
 pub mod hash;
+pub mod intset;
 pub mod list;
-pub mod skiplist;
\ No newline at end of file
+pub mod listpack;
+pub mod rhash;
+pub mod set;
+pub mod skiplist;
+pub mod ziplist;
+pub mod zset;
+
+/// Total of `sizes`, estimated from the first `samples` of them like
+/// `objectComputeSize` does for big containers, or exact if `samples` is 0.
+pub(crate) fn sampled_size<I: ExactSizeIterator<Item = usize>>(sizes: I, samples: usize) -> usize {
+    let len = sizes.len();
+    if samples == 0 || samples >= len {
+        return sizes.sum();
+    }
+    sizes.take(samples).sum::<usize>() * len / samples
+}
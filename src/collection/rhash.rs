@@ -0,0 +1,802 @@
+use rand::{thread_rng, Rng};
+
+use crate::clock::{Clock, SystemClock};
+use crate::collection::hash::Dict;
+use crate::collection::sampled_size;
+use crate::collection::listpack::{string_to_int, Listpack, ListpackEntry};
+use crate::collection::skiplist::zskiplist::{zrandmember_ranks, zrandmember_sample};
+
+// Fields picked by HRANDFIELD, with their values if asked for
+type RandomFields = Vec<(Vec<u8>, Option<Vec<u8>>)>;
+
+// Expire time stored in a listpackex for fields that have none
+const HASH_LP_NO_TTL: i64 = 0;
+
+/// The largest expire time a hash field can have, in milliseconds since the epoch.
+pub const HFE_MAX_ABS_TIME_MSEC: u64 = (1 << 48) - 1;
+
+/// Reply of HTTL, HPERSIST and HEXPIRE for a field that does not exist.
+pub const HFE_GET_NO_FIELD: i64 = -2;
+/// Reply of HTTL and HPERSIST for a field without an expire time.
+pub const HFE_GET_NO_TTL: i64 = -1;
+/// Reply of HEXPIRE when the NX, XX, GT or LT condition is not met.
+pub const HSETEX_NO_CONDITION_MET: i64 = 0;
+/// Reply of HEXPIRE when the expire time was set.
+pub const HSETEX_OK: i64 = 1;
+/// Reply of HEXPIRE when the field got deleted, the expire time being in the past.
+pub const HSETEX_DELETED: i64 = 2;
+/// Reply of HPERSIST when the expire time was removed.
+pub const HFE_PERSIST_OK: i64 = 1;
+
+/// The three encodings of a hash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashEncoding {
+    /// Field and value pairs in a listpack, for small hashes.
+    Listpack,
+    /// Field, value and expire time triplets in a listpack, for small hashes once a
+    /// field got an expire time.
+    ListpackEx,
+    /// A dict of the values, with a second dict of the expire times.
+    HashTable,
+}
+
+/// Thresholds above which a hash moves to a hash table, like
+/// `hash-max-listpack-entries` and `hash-max-listpack-value` in redis.conf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashConfig {
+    // Maximum number of fields of a listpack encoded hash
+    pub max_listpack_entries: usize,
+    // Maximum length in bytes of a field or value of a listpack encoded hash
+    pub max_listpack_value: usize,
+}
+
+impl Default for HashConfig {
+    fn default() -> Self {
+        HashConfig {
+            max_listpack_entries: 128,
+            max_listpack_value: 64,
+        }
+    }
+}
+
+/// The NX, XX, GT and LT options of HEXPIRE.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpireCondition {
+    /// Only when the field has no expire time.
+    Nx,
+    /// Only when the field has an expire time.
+    Xx,
+    /// Only when the new expire time is later. A field without one never expires,
+    /// so it is never set.
+    Gt,
+    /// Only when the new expire time is earlier. A field without one never expires,
+    /// so it is always set.
+    Lt,
+}
+
+#[derive(Clone)]
+enum HashRepr {
+    Listpack(Listpack),
+    ListpackEx(Listpack),
+    HashTable {
+        dict: Dict<Vec<u8>, Vec<u8>>,
+        expires: Dict<Vec<u8>, u64>,
+    },
+}
+
+/// A hash of byte string fields and values, starting listpack encoded and moving
+/// to a hash table once it grows past the thresholds of its `HashConfig`.
+///
+/// Fields can be given an expire time, as in Redis 7.4. Expired fields are hidden
+/// from every command as soon as the clock passes their expire time, and deleted by
+/// the next command changing the hash or by `delete_expired_fields`.
+///
+/// # Examples
+///
+/// ```
+/// use curly_giggle::clock::ManualClock;
+/// use curly_giggle::collection::rhash::{HashConfig, RHash};
+///
+/// let clock = ManualClock::new(0);
+/// let mut hash = RHash::with_clock(HashConfig::default(), clock.clone());
+/// hash.hset(&[("name", "redis"), ("session", "abc")]);
+/// assert_eq!(hash.hexpire(10, None, &["session"]), Ok(vec![1]));
+/// assert_eq!(hash.httl(&["name", "session"]), Ok(vec![-1, 10]));
+///
+/// clock.advance(10001);
+/// assert_eq!(hash.hget("session"), None);
+/// assert_eq!(hash.hlen(), 1);
+/// ```
+#[derive(Clone)]
+pub struct RHash<C = SystemClock> {
+    repr: HashRepr,
+    config: HashConfig,
+    clock: C,
+}
+
+impl Default for RHash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RHash {
+    /// Creates an empty hash with the default thresholds.
+    pub fn new() -> Self {
+        Self::with_config(HashConfig::default())
+    }
+
+    /// Creates an empty hash with the given thresholds.
+    pub fn with_config(config: HashConfig) -> Self {
+        Self::with_clock(config, SystemClock)
+    }
+}
+
+// Offset of `field` in a listpack holding `step` elements per field
+fn lp_find_field(lp: &Listpack, field: &[u8], step: usize) -> Option<usize> {
+    lp.first().and_then(|p| lp.find_from(p, field, step - 1))
+}
+
+// Deletes `field` and what follows it from a listpack holding `step` elements per field
+fn lp_delete_field(lp: &mut Listpack, field: &[u8], step: usize) -> bool {
+    match lp_find_field(lp, field, step) {
+        Some(p) => {
+            for _ in 0..step {
+                lp.delete(p);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+fn lp_expire(entry: ListpackEntry) -> Option<u64> {
+    match entry {
+        ListpackEntry::Int(HASH_LP_NO_TTL) => None,
+        ListpackEntry::Int(when) => Some(when as u64),
+        ListpackEntry::Str(_) => unreachable!("listpackex expire times are integers"),
+    }
+}
+
+impl<C: Clock> RHash<C> {
+    /// Creates an empty hash with the given thresholds, expiring fields by `clock`.
+    pub fn with_clock(config: HashConfig, clock: C) -> Self {
+        RHash {
+            repr: HashRepr::Listpack(Listpack::new()),
+            config,
+            clock,
+        }
+    }
+
+    pub fn encoding(&self) -> HashEncoding {
+        match self.repr {
+            HashRepr::Listpack(_) => HashEncoding::Listpack,
+            HashRepr::ListpackEx(_) => HashEncoding::ListpackEx,
+            HashRepr::HashTable { .. } => HashEncoding::HashTable,
+        }
+    }
+
+    // Number of fields, expired ones included
+    fn hash_type_length(&self) -> usize {
+        match &self.repr {
+            HashRepr::Listpack(lp) => lp.len() / 2,
+            HashRepr::ListpackEx(lp) => lp.len() / 3,
+            HashRepr::HashTable { dict, .. } => dict.size(),
+        }
+    }
+
+    // Every field with its value and expire time, expired ones included
+    fn entries(&self) -> Vec<(Vec<u8>, Vec<u8>, Option<u64>)> {
+        match &self.repr {
+            HashRepr::Listpack(lp) => {
+                let entries: Vec<ListpackEntry> = lp.iter().collect();
+                entries
+                    .chunks(2)
+                    .map(|pair| (pair[0].to_bytes().into_owned(), pair[1].to_bytes().into_owned(), None))
+                    .collect()
+            }
+            HashRepr::ListpackEx(lp) => {
+                let entries: Vec<ListpackEntry> = lp.iter().collect();
+                entries
+                    .chunks(3)
+                    .map(|triplet| {
+                        (triplet[0].to_bytes().into_owned(), triplet[1].to_bytes().into_owned(), lp_expire(triplet[2]))
+                    })
+                    .collect()
+            }
+            HashRepr::HashTable { dict, expires } => dict
+                .iter()
+                .map(|(field, value)| (field.clone(), value.clone(), expires.fetch_value(field).copied()))
+                .collect(),
+        }
+    }
+
+    fn live_entries(&self) -> Vec<(Vec<u8>, Vec<u8>, Option<u64>)> {
+        let mut entries = self.entries();
+        entries.retain(|(_, _, expire)| !self.is_expired(*expire));
+        entries
+    }
+
+    // The live fields and their values of a listpack holding `step` entries per
+    // field, borrowed from it
+    fn lp_live_pairs<'a>(&self, lp: &'a Listpack, step: usize) -> Vec<(ListpackEntry<'a>, ListpackEntry<'a>)> {
+        let mut iter = lp.iter();
+        let mut pairs = Vec::with_capacity(lp.len() / step);
+        while let (Some(field), Some(value)) = (iter.next(), iter.next()) {
+            let expire = if step == 3 { iter.next().and_then(lp_expire) } else { None };
+            if !self.is_expired(expire) {
+                pairs.push((field, value));
+            }
+        }
+        pairs
+    }
+
+    // The value and expire time of `field`, expired or not
+    fn lookup(&self, field: &[u8]) -> Option<(Vec<u8>, Option<u64>)> {
+        match &self.repr {
+            HashRepr::Listpack(lp) => {
+                let value = lp.next(lp_find_field(lp, field, 2)?)?;
+                Some((lp.get(value).to_bytes().into_owned(), None))
+            }
+            HashRepr::ListpackEx(lp) => {
+                let value = lp.next(lp_find_field(lp, field, 3)?)?;
+                let expire = lp.next(value)?;
+                Some((lp.get(value).to_bytes().into_owned(), lp_expire(lp.get(expire))))
+            }
+            HashRepr::HashTable { dict, expires } => {
+                let field = field.to_vec();
+                dict.fetch_value(&field)
+                    .map(|value| (value.clone(), expires.fetch_value(&field).copied()))
+            }
+        }
+    }
+
+    fn is_expired(&self, expire: Option<u64>) -> bool {
+        expire.is_some_and(|when| when < self.clock.now_ms())
+    }
+
+    // The value and expire time of `field`, unless it expired, like `hashTypeGetValue`
+    fn hash_type_get_value(&self, field: &[u8]) -> Option<(Vec<u8>, Option<u64>)> {
+        self.lookup(field).filter(|(_, expire)| !self.is_expired(*expire))
+    }
+
+    /// Converts the hash to `encoding`, like `hashTypeConvert`.
+    ///
+    /// # Errors
+    ///
+    /// Converting to a plain listpack fails if a field has an expire time, leaving
+    /// the hash unchanged.
+    pub fn hash_type_convert(&mut self, encoding: HashEncoding) -> Result<(), String> {
+        if self.encoding() == encoding {
+            return Ok(());
+        }
+        let entries = self.entries();
+        self.repr = match encoding {
+            HashEncoding::Listpack => {
+                if entries.iter().any(|(_, _, expire)| expire.is_some()) {
+                    return Err("hash fields have expire times".to_string());
+                }
+                let mut lp = Listpack::new();
+                for (field, value, _) in &entries {
+                    lp.append(&field[..]);
+                    lp.append(&value[..]);
+                }
+                HashRepr::Listpack(lp)
+            }
+            HashEncoding::ListpackEx => {
+                let mut lp = Listpack::new();
+                for (field, value, expire) in &entries {
+                    lp.append(&field[..]);
+                    lp.append(&value[..]);
+                    lp.append(expire.map_or(HASH_LP_NO_TTL, |when| when as i64));
+                }
+                HashRepr::ListpackEx(lp)
+            }
+            HashEncoding::HashTable => {
+                let mut dict = Dict::new();
+                let mut expires = Dict::new();
+                for (field, value, expire) in entries {
+                    if let Some(when) = expire {
+                        expires.add(field.clone(), when);
+                    }
+                    dict.add(field, value);
+                }
+                HashRepr::HashTable { dict, expires }
+            }
+        };
+        Ok(())
+    }
+
+    // Sets a field, converting the hash as needed, like `hashTypeSet`. The expire time
+    // of an existing field is removed unless `keep_ttl`. Returns true if the field is new.
+    fn hash_type_set(&mut self, field: &[u8], value: &[u8], keep_ttl: bool) -> bool {
+        let config = self.config;
+        if self.encoding() != HashEncoding::HashTable
+            && (field.len() > config.max_listpack_value || value.len() > config.max_listpack_value)
+        {
+            self.hash_type_convert(HashEncoding::HashTable).unwrap();
+        }
+        let is_new = match &mut self.repr {
+            HashRepr::Listpack(lp) => match lp_find_field(lp, field, 2) {
+                Some(p) => {
+                    let p = lp.next(p).unwrap();
+                    lp.replace(p, value);
+                    false
+                }
+                None => {
+                    lp.append(field);
+                    lp.append(value);
+                    true
+                }
+            },
+            HashRepr::ListpackEx(lp) => match lp_find_field(lp, field, 3) {
+                Some(p) => {
+                    let p = lp.replace(lp.next(p).unwrap(), value);
+                    if !keep_ttl {
+                        lp.replace(lp.next(p).unwrap(), HASH_LP_NO_TTL);
+                    }
+                    false
+                }
+                None => {
+                    lp.append(field);
+                    lp.append(value);
+                    lp.append(HASH_LP_NO_TTL);
+                    true
+                }
+            },
+            HashRepr::HashTable { dict, expires } => {
+                let field = field.to_vec();
+                if !keep_ttl {
+                    expires.delete(&field);
+                }
+                let is_new = !dict.contains(&field);
+                dict.add(field, value.to_vec());
+                is_new
+            }
+        };
+        if self.hash_type_length() > config.max_listpack_entries {
+            self.hash_type_convert(HashEncoding::HashTable).unwrap();
+        }
+        is_new
+    }
+
+    // Deletes a field, expired or not, like `hashTypeDelete`. Returns false if it
+    // was not present.
+    fn hash_type_delete(&mut self, field: &[u8]) -> bool {
+        match &mut self.repr {
+            HashRepr::Listpack(lp) => lp_delete_field(lp, field, 2),
+            HashRepr::ListpackEx(lp) => lp_delete_field(lp, field, 3),
+            HashRepr::HashTable { dict, expires } => {
+                let field = field.to_vec();
+                expires.delete(&field);
+                dict.unlink(&field).is_some()
+            }
+        }
+    }
+
+    // Replaces the expire time of an existing field
+    fn hash_type_set_expire(&mut self, field: &[u8], expire: Option<u64>) {
+        match &mut self.repr {
+            HashRepr::Listpack(_) => debug_assert!(expire.is_none()),
+            HashRepr::ListpackEx(lp) => {
+                let value = lp.next(lp_find_field(lp, field, 3).unwrap()).unwrap();
+                lp.replace(lp.next(value).unwrap(), expire.map_or(HASH_LP_NO_TTL, |when| when as i64));
+            }
+            HashRepr::HashTable { expires, .. } => match expire {
+                Some(when) => expires.add(field.to_vec(), when),
+                None => expires.delete(&field.to_vec()),
+            },
+        }
+    }
+
+    /// Deletes the fields whose expire time has passed, returning how many. The hash
+    /// may be left empty, in which case its key should be deleted.
+    pub fn delete_expired_fields(&mut self) -> usize {
+        let now = self.clock.now_ms();
+        let expired: Vec<Vec<u8>> = match &self.repr {
+            HashRepr::Listpack(_) => return 0,
+            HashRepr::ListpackEx(_) => self
+                .entries()
+                .into_iter()
+                .filter(|(_, _, expire)| expire.is_some_and(|when| when < now))
+                .map(|(field, _, _)| field)
+                .collect(),
+            HashRepr::HashTable { expires, .. } => expires
+                .iter()
+                .filter(|(_, &when)| when < now)
+                .map(|(field, _)| field.clone())
+                .collect(),
+        };
+        for field in &expired {
+            self.hash_type_delete(field);
+        }
+        expired.len()
+    }
+
+    /// HSET: set the fields, returning how many were not already present. Fields
+    /// that are set lose their expire time.
+    pub fn hset<F: AsRef<[u8]>, V: AsRef<[u8]>>(&mut self, pairs: &[(F, V)]) -> usize {
+        self.delete_expired_fields();
+        pairs
+            .iter()
+            .filter(|(field, value)| self.hash_type_set(field.as_ref(), value.as_ref(), false))
+            .count()
+    }
+
+    /// HSETNX: set the field only if it does not exist. Returns whether it was set.
+    pub fn hsetnx<F: AsRef<[u8]>, V: AsRef<[u8]>>(&mut self, field: F, value: V) -> bool {
+        self.delete_expired_fields();
+        if self.lookup(field.as_ref()).is_some() {
+            return false;
+        }
+        self.hash_type_set(field.as_ref(), value.as_ref(), false)
+    }
+
+    /// HGET
+    pub fn hget<F: AsRef<[u8]>>(&self, field: F) -> Option<Vec<u8>> {
+        self.hash_type_get_value(field.as_ref()).map(|(value, _)| value)
+    }
+
+    /// HMGET
+    pub fn hmget<F: AsRef<[u8]>>(&self, fields: &[F]) -> Vec<Option<Vec<u8>>> {
+        fields.iter().map(|field| self.hget(field)).collect()
+    }
+
+    /// HEXISTS
+    pub fn hexists<F: AsRef<[u8]>>(&self, field: F) -> bool {
+        self.hash_type_get_value(field.as_ref()).is_some()
+    }
+
+    /// HDEL: delete the fields, returning how many were present.
+    pub fn hdel<F: AsRef<[u8]>>(&mut self, fields: &[F]) -> usize {
+        self.delete_expired_fields();
+        fields.iter().filter(|field| self.hash_type_delete(field.as_ref())).count()
+    }
+
+    /// HLEN: the number of fields, not counting expired ones.
+    pub fn hlen(&self) -> usize {
+        match &self.repr {
+            HashRepr::Listpack(_) => self.hash_type_length(),
+            HashRepr::ListpackEx(_) => self.live_entries().len(),
+            HashRepr::HashTable { dict, expires } => {
+                dict.size() - expires.iter().filter(|(_, &when)| self.is_expired(Some(when))).count()
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hlen() == 0
+    }
+
+    /// Bytes used by the fields, values and expire times, and the structures holding
+    /// them, like the hash case of `objectComputeSize`. Expired fields not deleted yet
+    /// are counted. Hash table entries are estimated from the first `samples` of
+    /// them, or all of them if 0.
+    pub fn mem_usage(&self, samples: usize) -> usize {
+        match &self.repr {
+            HashRepr::Listpack(lp) | HashRepr::ListpackEx(lp) => lp.blob_len(),
+            HashRepr::HashTable { dict, expires } => {
+                dict.mem_usage()
+                    + expires.mem_usage()
+                    + sampled_size(dict.iter().map(|(field, value)| field.capacity() + value.capacity()), samples)
+                    + sampled_size(expires.keys().map(|field| field.capacity()), samples)
+            }
+        }
+    }
+
+    /// HSTRLEN: the length of the value of the field, 0 if it does not exist.
+    pub fn hstrlen<F: AsRef<[u8]>>(&self, field: F) -> usize {
+        self.hget(field).map_or(0, |value| value.len())
+    }
+
+    /// HINCRBY: add `increment` to the integer value of the field, a missing field
+    /// counting as 0. The field keeps its expire time.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the value is not an integer or the result would overflow.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::rhash::RHash;
+    ///
+    /// let mut hash = RHash::new();
+    /// assert_eq!(hash.hincrby("visits", 5), Ok(5));
+    /// assert_eq!(hash.hincrby("visits", -2), Ok(3));
+    /// assert_eq!(hash.hincrby("visits", i64::MAX), Err("increment or decrement would overflow".to_string()));
+    /// ```
+    pub fn hincrby<F: AsRef<[u8]>>(&mut self, field: F, increment: i64) -> Result<i64, String> {
+        self.delete_expired_fields();
+        let field = field.as_ref();
+        let current = match self.lookup(field) {
+            Some((value, _)) => string_to_int(&value).ok_or("hash value is not an integer")?,
+            None => 0,
+        };
+        let value = current.checked_add(increment).ok_or("increment or decrement would overflow")?;
+        self.hash_type_set(field, value.to_string().as_bytes(), true);
+        Ok(value)
+    }
+
+    /// HINCRBYFLOAT: add `increment` to the floating point value of the field, a
+    /// missing field counting as 0, and return the new value as stored. The field
+    /// keeps its expire time.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the value is not a number or the result is NaN or infinite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::rhash::RHash;
+    ///
+    /// let mut hash = RHash::new();
+    /// hash.hset(&[("price", "10.50")]);
+    /// assert_eq!(hash.hincrbyfloat("price", 0.1), Ok(b"10.6".to_vec()));
+    /// assert_eq!(hash.hincrbyfloat("price", -5.6), Ok(b"5".to_vec()));
+    /// ```
+    pub fn hincrbyfloat<F: AsRef<[u8]>>(&mut self, field: F, increment: f64) -> Result<Vec<u8>, String> {
+        self.delete_expired_fields();
+        let field = field.as_ref();
+        let current = match self.lookup(field) {
+            Some((value, _)) => std::str::from_utf8(&value)
+                .ok()
+                .and_then(|s| s.parse::<f64>().ok())
+                .filter(|value| !value.is_nan())
+                .ok_or("hash value is not a float")?,
+            None => 0.0,
+        };
+        let value = current + increment;
+        if !value.is_finite() {
+            return Err("increment would produce NaN or Infinity".to_string());
+        }
+        let value = value.to_string().into_bytes();
+        self.hash_type_set(field, &value, true);
+        Ok(value)
+    }
+
+    /// HKEYS
+    pub fn hkeys(&self) -> Vec<Vec<u8>> {
+        self.live_entries().into_iter().map(|(field, _, _)| field).collect()
+    }
+
+    /// HVALS
+    pub fn hvals(&self) -> Vec<Vec<u8>> {
+        self.live_entries().into_iter().map(|(_, value, _)| value).collect()
+    }
+
+    /// HGETALL: listpack encoded fields come in insertion order, hash table encoded
+    /// ones in no particular order.
+    pub fn hgetall(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.live_entries().into_iter().map(|(field, value, _)| (field, value)).collect()
+    }
+
+    /// HRANDFIELD with a count, following the same rules as ZRANDMEMBER: up to
+    /// `count` distinct fields if it is positive, or exactly `-count` fields possibly
+    /// repeated if it is negative. Values are returned along if `with_values`.
+    ///
+    /// # Errors
+    ///
+    /// Returns "value is out of range" for a negative count asking for more than
+    /// `ZRANDMEMBER_MAX_COUNT` fields.
+    pub fn hrandfield(&self, count: i64, with_values: bool) -> Result<RandomFields, String> {
+        self.hrandfield_with_rng(count, with_values, &mut thread_rng())
+    }
+
+    /// Like `hrandfield`, drawing from the given random number generator.
+    pub fn hrandfield_with_rng<R: Rng>(
+        &self,
+        count: i64,
+        with_values: bool,
+        rng: &mut R,
+    ) -> Result<RandomFields, String> {
+        let (lp, step) = match &self.repr {
+            HashRepr::Listpack(lp) => (lp, 2),
+            HashRepr::ListpackEx(lp) => (lp, 3),
+            HashRepr::HashTable { dict, expires } => {
+                // Sampled from random buckets, skipping expired fields. Counting the
+                // live ones only walks the fields with an expire time.
+                let is_live = |field: &Vec<u8>| !self.is_expired(expires.fetch_value(field).copied());
+                let expired = expires.iter().filter(|(_, when)| self.is_expired(Some(**when))).count();
+                let fields = zrandmember_sample(
+                    dict.size() - expired,
+                    count,
+                    with_values,
+                    rng,
+                    |rng| loop {
+                        let field = dict.get_random_key_with_rng(rng).unwrap();
+                        if is_live(field) {
+                            break field;
+                        }
+                    },
+                    || dict.keys().filter(|field| is_live(field)).collect(),
+                )?;
+                return Ok(fields
+                    .into_iter()
+                    .map(|field| (field.clone(), with_values.then(|| dict.fetch_value(field).unwrap().clone())))
+                    .collect());
+            }
+        };
+        // Only the picked fields are copied out
+        let pairs = self.lp_live_pairs(lp, step);
+        let ranks = zrandmember_ranks(pairs.len(), count, with_values, rng)?;
+        Ok(ranks
+            .into_iter()
+            .map(|rank| {
+                let (field, value) = pairs[rank];
+                (field.to_bytes().into_owned(), if with_values { Some(value.to_bytes().into_owned()) } else { None })
+            })
+            .collect())
+    }
+
+    fn check_num_fields<F>(fields: &[F]) -> Result<(), String> {
+        if fields.is_empty() {
+            return Err("Parameter `numFields` should be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// HEXPIRE: set the expire time of the fields to `seconds` from now. Replies for
+    /// each field with `HSETEX_OK`, `HSETEX_NO_CONDITION_MET`, `HSETEX_DELETED` if the
+    /// time is not in the future, or `HFE_GET_NO_FIELD`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the expire time is negative or past `HFE_MAX_ABS_TIME_MSEC`, or if
+    /// no field is given.
+    pub fn hexpire<F: AsRef<[u8]>>(
+        &mut self,
+        seconds: i64,
+        condition: Option<ExpireCondition>,
+        fields: &[F],
+    ) -> Result<Vec<i64>, String> {
+        let now = self.clock.now_ms();
+        self.hexpire_generic(seconds, now, true, condition, fields, "hexpire")
+    }
+
+    /// HPEXPIRE: like `hexpire`, in milliseconds.
+    pub fn hpexpire<F: AsRef<[u8]>>(
+        &mut self,
+        milliseconds: i64,
+        condition: Option<ExpireCondition>,
+        fields: &[F],
+    ) -> Result<Vec<i64>, String> {
+        let now = self.clock.now_ms();
+        self.hexpire_generic(milliseconds, now, false, condition, fields, "hpexpire")
+    }
+
+    /// HEXPIREAT: like `hexpire`, at a Unix time in seconds.
+    pub fn hexpireat<F: AsRef<[u8]>>(
+        &mut self,
+        unix_time_seconds: i64,
+        condition: Option<ExpireCondition>,
+        fields: &[F],
+    ) -> Result<Vec<i64>, String> {
+        self.hexpire_generic(unix_time_seconds, 0, true, condition, fields, "hexpireat")
+    }
+
+    /// HPEXPIREAT: like `hexpire`, at a Unix time in milliseconds.
+    pub fn hpexpireat<F: AsRef<[u8]>>(
+        &mut self,
+        unix_time_milliseconds: i64,
+        condition: Option<ExpireCondition>,
+        fields: &[F],
+    ) -> Result<Vec<i64>, String> {
+        self.hexpire_generic(unix_time_milliseconds, 0, false, condition, fields, "hpexpireat")
+    }
+
+    // `expire` is in seconds or milliseconds after `basetime`, like `hexpireGenericCommand`
+    fn hexpire_generic<F: AsRef<[u8]>>(
+        &mut self,
+        expire: i64,
+        basetime: u64,
+        in_seconds: bool,
+        condition: Option<ExpireCondition>,
+        fields: &[F],
+        command: &str,
+    ) -> Result<Vec<i64>, String> {
+        let invalid = || format!("invalid expire time in '{}' command", command);
+        if expire < 0 || expire as u64 > HFE_MAX_ABS_TIME_MSEC {
+            return Err(invalid());
+        }
+        let mut expire = expire as u64;
+        if in_seconds {
+            if expire > HFE_MAX_ABS_TIME_MSEC / 1000 {
+                return Err(invalid());
+            }
+            expire *= 1000;
+        }
+        if basetime > HFE_MAX_ABS_TIME_MSEC - expire {
+            return Err(invalid());
+        }
+        Self::check_num_fields(fields)?;
+        self.delete_expired_fields();
+        let when = basetime + expire;
+        Ok(fields
+            .iter()
+            .map(|field| self.hash_type_set_ex(field.as_ref(), when, condition))
+            .collect())
+    }
+
+    // Sets the expire time of one field, like `hashTypeSetEx`
+    fn hash_type_set_ex(&mut self, field: &[u8], when: u64, condition: Option<ExpireCondition>) -> i64 {
+        let current = match self.lookup(field) {
+            Some((_, expire)) => expire,
+            None => return HFE_GET_NO_FIELD,
+        };
+        let allowed = match condition {
+            None => true,
+            Some(ExpireCondition::Nx) => current.is_none(),
+            Some(ExpireCondition::Xx) => current.is_some(),
+            Some(ExpireCondition::Gt) => current.is_some_and(|current| when > current),
+            Some(ExpireCondition::Lt) => current.is_none_or(|current| when < current),
+        };
+        if !allowed {
+            return HSETEX_NO_CONDITION_MET;
+        }
+        if when <= self.clock.now_ms() {
+            self.hash_type_delete(field);
+            return HSETEX_DELETED;
+        }
+        if self.encoding() == HashEncoding::Listpack {
+            self.hash_type_convert(HashEncoding::ListpackEx).unwrap();
+        }
+        self.hash_type_set_expire(field, Some(when));
+        HSETEX_OK
+    }
+
+    // Replies for each field with `reply(expire time)`, like `httlGenericCommand`
+    fn httl_generic<F: AsRef<[u8]>>(&self, fields: &[F], reply: impl Fn(u64) -> i64) -> Result<Vec<i64>, String> {
+        Self::check_num_fields(fields)?;
+        Ok(fields
+            .iter()
+            .map(|field| match self.hash_type_get_value(field.as_ref()) {
+                None => HFE_GET_NO_FIELD,
+                Some((_, None)) => HFE_GET_NO_TTL,
+                Some((_, Some(when))) => reply(when),
+            })
+            .collect())
+    }
+
+    /// HTTL: the seconds left before each field expires, rounded up, or
+    /// `HFE_GET_NO_TTL` or `HFE_GET_NO_FIELD`.
+    pub fn httl<F: AsRef<[u8]>>(&self, fields: &[F]) -> Result<Vec<i64>, String> {
+        let now = self.clock.now_ms();
+        self.httl_generic(fields, |when| (when - now).div_ceil(1000) as i64)
+    }
+
+    /// HPTTL: like `httl`, in milliseconds.
+    pub fn hpttl<F: AsRef<[u8]>>(&self, fields: &[F]) -> Result<Vec<i64>, String> {
+        let now = self.clock.now_ms();
+        self.httl_generic(fields, |when| (when - now) as i64)
+    }
+
+    /// HEXPIRETIME: the Unix time in seconds at which each field expires, rounded up.
+    pub fn hexpiretime<F: AsRef<[u8]>>(&self, fields: &[F]) -> Result<Vec<i64>, String> {
+        self.httl_generic(fields, |when| when.div_ceil(1000) as i64)
+    }
+
+    /// HPEXPIRETIME: like `hexpiretime`, in milliseconds.
+    pub fn hpexpiretime<F: AsRef<[u8]>>(&self, fields: &[F]) -> Result<Vec<i64>, String> {
+        self.httl_generic(fields, |when| when as i64)
+    }
+
+    /// HPERSIST: remove the expire time of the fields. Replies for each field with
+    /// `HFE_PERSIST_OK`, `HFE_GET_NO_TTL` or `HFE_GET_NO_FIELD`.
+    pub fn hpersist<F: AsRef<[u8]>>(&mut self, fields: &[F]) -> Result<Vec<i64>, String> {
+        Self::check_num_fields(fields)?;
+        self.delete_expired_fields();
+        Ok(fields
+            .iter()
+            .map(|field| match self.lookup(field.as_ref()) {
+                None => HFE_GET_NO_FIELD,
+                Some((_, None)) => HFE_GET_NO_TTL,
+                Some(_) => {
+                    self.hash_type_set_expire(field.as_ref(), None);
+                    HFE_PERSIST_OK
+                }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,475 @@
+use std::ops::Range;
+
+use rand::{thread_rng, Rng};
+
+use crate::collection::hash::Dict;
+use crate::collection::sampled_size;
+use crate::collection::intset::IntSet;
+use crate::collection::listpack::{string_to_int, Listpack, ListpackEntry};
+use crate::collection::skiplist::zskiplist::{zrandmember_ranks, zrandmember_sample};
+
+/// The three encodings of a set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetEncoding {
+    /// A sorted array of integers, while every member is an integer.
+    IntSet,
+    /// A compact list of members, for small sets.
+    Listpack,
+    /// A dict whose keys are the members.
+    HashTable,
+}
+
+/// Thresholds above which a set moves to a larger encoding, like
+/// `set-max-intset-entries`, `set-max-listpack-entries` and `set-max-listpack-value`
+/// in redis.conf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SetConfig {
+    // Maximum number of members of an intset encoded set
+    pub max_intset_entries: usize,
+    // Maximum number of members of a listpack encoded set
+    pub max_listpack_entries: usize,
+    // Maximum length in bytes of a member of a listpack encoded set
+    pub max_listpack_value: usize,
+}
+
+impl Default for SetConfig {
+    fn default() -> Self {
+        SetConfig {
+            max_intset_entries: 512,
+            max_listpack_entries: 128,
+            max_listpack_value: 64,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum SetRepr {
+    IntSet(IntSet),
+    Listpack(Listpack),
+    HashTable(Dict<Vec<u8>, ()>),
+}
+
+/// A set of byte strings, starting intset encoded and moving to a listpack once a
+/// member is not an integer, then to a hash table once it grows past the thresholds
+/// of its `SetConfig`. Sets never move back to a smaller encoding on their own.
+///
+/// # Examples
+///
+/// ```
+/// use curly_giggle::collection::set::{RSet, SetEncoding};
+///
+/// let mut set = RSet::new();
+/// assert_eq!(set.sadd(&["1", "2", "3"]), 3);
+/// assert_eq!(set.encoding(), SetEncoding::IntSet);
+///
+/// set.sadd(&["four"]);
+/// assert_eq!(set.encoding(), SetEncoding::Listpack);
+/// assert!(set.sismember("four"));
+/// assert_eq!(set.scard(), 4);
+/// ```
+#[derive(Clone)]
+pub struct RSet {
+    repr: SetRepr,
+    config: SetConfig,
+}
+
+impl Default for RSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RSet {
+    /// Creates an empty set with the default thresholds.
+    pub fn new() -> Self {
+        Self::with_config(SetConfig::default())
+    }
+
+    /// Creates an empty set with the given thresholds.
+    pub fn with_config(config: SetConfig) -> Self {
+        RSet {
+            repr: SetRepr::IntSet(IntSet::new()),
+            config,
+        }
+    }
+
+    pub fn encoding(&self) -> SetEncoding {
+        match self.repr {
+            SetRepr::IntSet(_) => SetEncoding::IntSet,
+            SetRepr::Listpack(_) => SetEncoding::Listpack,
+            SetRepr::HashTable(_) => SetEncoding::HashTable,
+        }
+    }
+
+    /// Converts the set to `encoding`, like `setTypeConvert`.
+    ///
+    /// # Errors
+    ///
+    /// Converting to an intset fails if a member is not an integer, leaving the set
+    /// unchanged.
+    pub fn set_type_convert(&mut self, encoding: SetEncoding) -> Result<(), String> {
+        if self.encoding() == encoding {
+            return Ok(());
+        }
+        self.repr = match encoding {
+            SetEncoding::IntSet => {
+                let mut set = IntSet::new();
+                for member in self.iter() {
+                    set.add(string_to_int(&member).ok_or("set members are not all integers")?);
+                }
+                SetRepr::IntSet(set)
+            }
+            SetEncoding::Listpack => {
+                let mut lp = Listpack::new();
+                for member in self.iter() {
+                    lp.append(&member[..]);
+                }
+                SetRepr::Listpack(lp)
+            }
+            SetEncoding::HashTable => {
+                let mut dict = Dict::new();
+                for member in self.iter() {
+                    dict.add_key(member);
+                }
+                SetRepr::HashTable(dict)
+            }
+        };
+        Ok(())
+    }
+
+    /// Adds one member, converting the set as needed, like `setTypeAdd`. Returns false
+    /// if the member was already present.
+    pub fn set_type_add(&mut self, member: &[u8]) -> bool {
+        let config = self.config;
+        match &mut self.repr {
+            SetRepr::HashTable(dict) => dict.add_key(member.to_vec()),
+            SetRepr::Listpack(lp) => {
+                if lp.find(member).is_some() {
+                    return false;
+                }
+                if lp.len() < config.max_listpack_entries && member.len() <= config.max_listpack_value {
+                    lp.append(member);
+                } else {
+                    self.set_type_convert(SetEncoding::HashTable).unwrap();
+                    self.set_type_add(member);
+                }
+                true
+            }
+            SetRepr::IntSet(set) => {
+                if let Some(value) = string_to_int(member) {
+                    if !set.add(value) {
+                        return false;
+                    }
+                    if set.len() > config.max_intset_entries {
+                        self.set_type_convert(SetEncoding::HashTable).unwrap();
+                    }
+                    return true;
+                }
+                // Every integer must fit a listpack entry too
+                let max_len = set.min().into_iter().chain(set.max()).map(|v| v.to_string().len()).max().unwrap_or(0);
+                if set.len() < config.max_listpack_entries
+                    && member.len() <= config.max_listpack_value
+                    && max_len <= config.max_listpack_value
+                {
+                    self.set_type_convert(SetEncoding::Listpack).unwrap();
+                } else {
+                    self.set_type_convert(SetEncoding::HashTable).unwrap();
+                }
+                self.set_type_add(member)
+            }
+        }
+    }
+
+    /// Removes one member, like `setTypeRemove`. Returns false if it was not present.
+    pub fn set_type_remove(&mut self, member: &[u8]) -> bool {
+        match &mut self.repr {
+            SetRepr::IntSet(set) => string_to_int(member).is_some_and(|value| set.remove(value)),
+            SetRepr::Listpack(lp) => match lp.find(member) {
+                Some(p) => {
+                    lp.delete(p);
+                    true
+                }
+                None => false,
+            },
+            SetRepr::HashTable(dict) => dict.unlink(&member.to_vec()).is_some(),
+        }
+    }
+
+    /// Returns whether `member` is in the set, like `setTypeIsMember`.
+    pub fn set_type_is_member(&self, member: &[u8]) -> bool {
+        match &self.repr {
+            SetRepr::IntSet(set) => string_to_int(member).is_some_and(|value| set.find(value)),
+            SetRepr::Listpack(lp) => lp.find(member).is_some(),
+            SetRepr::HashTable(dict) => dict.contains(&member.to_vec()),
+        }
+    }
+
+    /// SADD: add the members, returning how many were not already present.
+    pub fn sadd<M: AsRef<[u8]>>(&mut self, members: &[M]) -> usize {
+        members.iter().filter(|member| self.set_type_add(member.as_ref())).count()
+    }
+
+    /// SREM: remove the members, returning how many were present.
+    pub fn srem<M: AsRef<[u8]>>(&mut self, members: &[M]) -> usize {
+        members.iter().filter(|member| self.set_type_remove(member.as_ref())).count()
+    }
+
+    /// SISMEMBER
+    pub fn sismember<M: AsRef<[u8]>>(&self, member: M) -> bool {
+        self.set_type_is_member(member.as_ref())
+    }
+
+    /// SMISMEMBER
+    pub fn smismember<M: AsRef<[u8]>>(&self, members: &[M]) -> Vec<bool> {
+        members.iter().map(|member| self.sismember(member)).collect()
+    }
+
+    /// SCARD
+    pub fn scard(&self) -> usize {
+        match &self.repr {
+            SetRepr::IntSet(set) => set.len(),
+            SetRepr::Listpack(lp) => lp.len(),
+            SetRepr::HashTable(dict) => dict.size(),
+        }
+    }
+
+    /// Bytes used by the members and the structure holding them, like the set case
+    /// of `objectComputeSize`. Hash table members are estimated from the first
+    /// `samples` of them, or all of them if 0.
+    pub fn mem_usage(&self, samples: usize) -> usize {
+        match &self.repr {
+            SetRepr::IntSet(set) => set.blob_len(),
+            SetRepr::Listpack(lp) => lp.blob_len(),
+            SetRepr::HashTable(dict) => dict.mem_usage() + sampled_size(dict.keys().map(|member| member.capacity()), samples),
+        }
+    }
+
+    /// SMEMBERS: intset members come in ascending order, listpack members in insertion
+    /// order and hash table members in no particular order.
+    pub fn smembers(&self) -> Vec<Vec<u8>> {
+        self.iter().collect()
+    }
+
+    /// Iterate over the members, in the same order as `smembers`.
+    pub fn iter(&self) -> Iter<'_> {
+        match &self.repr {
+            SetRepr::IntSet(set) => Iter::IntSet(set, 0..set.len()),
+            SetRepr::Listpack(lp) => Iter::Listpack(lp.iter()),
+            SetRepr::HashTable(dict) => Iter::HashTable(dict.keys()),
+        }
+    }
+
+    // Random members following the ZRANDMEMBER rules. Hash tables are sampled from
+    // random buckets, the other encodings by rank in iteration order.
+    fn random_members<R: Rng>(&self, count: i64, rng: &mut R) -> Result<Vec<Vec<u8>>, String> {
+        match &self.repr {
+            SetRepr::IntSet(set) => {
+                let ranks = zrandmember_ranks(set.len(), count, false, rng)?;
+                Ok(ranks.iter().map(|&rank| set.get(rank).unwrap().to_string().into_bytes()).collect())
+            }
+            SetRepr::Listpack(lp) => {
+                let ranks = zrandmember_ranks(lp.len(), count, false, rng)?;
+                // Walk the listpack once, visiting the ranks in ascending order
+                let mut order: Vec<usize> = (0..ranks.len()).collect();
+                order.sort_unstable_by_key(|&i| ranks[i]);
+                let mut members = vec![Vec::new(); ranks.len()];
+                let mut entries = lp.iter().enumerate();
+                let mut current: Option<(usize, ListpackEntry)> = None;
+                for i in order {
+                    while current.as_ref().is_none_or(|(rank, _)| *rank < ranks[i]) {
+                        current = entries.next();
+                    }
+                    members[i] = current.as_ref().unwrap().1.to_bytes().into_owned();
+                }
+                Ok(members)
+            }
+            SetRepr::HashTable(dict) => {
+                let keys = zrandmember_sample(
+                    dict.size(),
+                    count,
+                    false,
+                    rng,
+                    |rng| dict.get_random_key_with_rng(rng).unwrap(),
+                    || dict.keys().collect(),
+                )?;
+                Ok(keys.into_iter().cloned().collect())
+            }
+        }
+    }
+
+    /// SPOP with a count: remove and return up to `count` distinct random members.
+    pub fn spop(&mut self, count: usize) -> Vec<Vec<u8>> {
+        self.spop_with_rng(count, &mut thread_rng())
+    }
+
+    /// Like `spop`, drawing from the given random number generator.
+    pub fn spop_with_rng<R: Rng>(&mut self, count: usize, rng: &mut R) -> Vec<Vec<u8>> {
+        // Popping everything needs no sampling
+        if count >= self.scard() {
+            let members = self.smembers();
+            self.repr = SetRepr::IntSet(IntSet::new());
+            return members;
+        }
+        let members = self.random_members(count as i64, rng).unwrap();
+        for member in &members {
+            self.set_type_remove(member);
+        }
+        members
+    }
+
+    /// SRANDMEMBER with a count: up to `count` distinct members if it is positive, or
+    /// exactly `-count` members possibly repeated if it is negative.
+    ///
+    /// # Errors
+    ///
+    /// Returns "value is out of range" for a negative count asking for more than
+    /// `ZRANDMEMBER_MAX_COUNT` members.
+    pub fn srandmember(&self, count: i64) -> Result<Vec<Vec<u8>>, String> {
+        self.srandmember_with_rng(count, &mut thread_rng())
+    }
+
+    /// Like `srandmember`, drawing from the given random number generator.
+    pub fn srandmember_with_rng<R: Rng>(&self, count: i64, rng: &mut R) -> Result<Vec<Vec<u8>>, String> {
+        self.random_members(count, rng)
+    }
+
+    /// SMOVE: move `member` from `src` to `dst`. Returns false if it was not in `src`.
+    pub fn smove<M: AsRef<[u8]>>(src: &mut RSet, dst: &mut RSet, member: M) -> bool {
+        if !src.set_type_remove(member.as_ref()) {
+            return false;
+        }
+        dst.set_type_add(member.as_ref());
+        true
+    }
+
+    fn check_keys(sets: &[&RSet], command: &str) -> Result<(), String> {
+        if sets.is_empty() {
+            return Err(format!("wrong number of arguments for '{}' command", command));
+        }
+        Ok(())
+    }
+
+    // Members of the intersection, stopping after `limit` of them if not zero
+    fn inter(sets: &[&RSet], limit: usize) -> Vec<Vec<u8>> {
+        let mut sets = sets.to_vec();
+        // Iterate the smallest set, and look in the others from the smallest too
+        sets.sort_by_key(|set| set.scard());
+        if sets[0].scard() == 0 {
+            return Vec::new();
+        }
+        let mut members = Vec::new();
+        for member in sets[0].iter() {
+            if sets[1..].iter().all(|set| set.set_type_is_member(&member)) {
+                members.push(member);
+                if members.len() == limit {
+                    break;
+                }
+            }
+        }
+        members
+    }
+
+    /// SINTER: the members found in every set.
+    pub fn sinter(sets: &[&RSet]) -> Result<Vec<Vec<u8>>, String> {
+        Self::check_keys(sets, "sinter")?;
+        Ok(Self::inter(sets, 0))
+    }
+
+    /// SINTERCARD: the size of the intersection, counting no further than `limit`
+    /// unless it is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::set::RSet;
+    ///
+    /// let mut a = RSet::new();
+    /// let mut b = RSet::new();
+    /// a.sadd(&["1", "2", "3", "4"]);
+    /// b.sadd(&["2", "3", "4", "5"]);
+    /// assert_eq!(RSet::sintercard(&[&a, &b], 0), Ok(3));
+    /// assert_eq!(RSet::sintercard(&[&a, &b], 2), Ok(2));
+    /// ```
+    pub fn sintercard(sets: &[&RSet], limit: usize) -> Result<usize, String> {
+        if sets.is_empty() {
+            return Err("numkeys should be greater than 0".to_string());
+        }
+        Ok(Self::inter(sets, limit).len())
+    }
+
+    /// SUNION: the members found in any set.
+    pub fn sunion(sets: &[&RSet]) -> Result<Vec<Vec<u8>>, String> {
+        Self::check_keys(sets, "sunion")?;
+        Ok(Self::union_store(sets).smembers())
+    }
+
+    /// SDIFF: the members of the first set found in none of the others.
+    pub fn sdiff(sets: &[&RSet]) -> Result<Vec<Vec<u8>>, String> {
+        Self::check_keys(sets, "sdiff")?;
+        // The first set repeated among the others leaves nothing
+        if sets[1..].iter().any(|set| std::ptr::eq(sets[0], *set)) {
+            return Ok(Vec::new());
+        }
+        let mut others = sets[1..].to_vec();
+        // Look in the largest sets first, where a member is more likely found
+        others.sort_by_key(|set| std::cmp::Reverse(set.scard()));
+        Ok(sets[0]
+            .iter()
+            .filter(|member| !others.iter().any(|set| set.set_type_is_member(member)))
+            .collect())
+    }
+
+    // A new set with the thresholds of the first input set
+    fn store(sets: &[&RSet], members: Vec<Vec<u8>>) -> RSet {
+        let mut set = RSet::with_config(sets[0].config);
+        set.sadd(&members);
+        set
+    }
+
+    fn union_store(sets: &[&RSet]) -> RSet {
+        let mut union = RSet::with_config(sets[0].config);
+        for set in sets {
+            for member in set.iter() {
+                union.set_type_add(&member);
+            }
+        }
+        union
+    }
+
+    /// SINTERSTORE: like `sinter`, collected into a new set.
+    pub fn sinterstore(sets: &[&RSet]) -> Result<RSet, String> {
+        Self::check_keys(sets, "sinterstore")?;
+        Ok(Self::store(sets, Self::inter(sets, 0)))
+    }
+
+    /// SUNIONSTORE: like `sunion`, collected into a new set.
+    pub fn sunionstore(sets: &[&RSet]) -> Result<RSet, String> {
+        Self::check_keys(sets, "sunionstore")?;
+        Ok(Self::union_store(sets))
+    }
+
+    /// SDIFFSTORE: like `sdiff`, collected into a new set.
+    pub fn sdiffstore(sets: &[&RSet]) -> Result<RSet, String> {
+        Self::check_keys(sets, "sdiffstore")?;
+        Ok(Self::store(sets, Self::sdiff(sets)?))
+    }
+}
+
+/// Iterator over the members of an `RSet`.
+pub enum Iter<'a> {
+    IntSet(&'a IntSet, Range<usize>),
+    Listpack(crate::collection::listpack::Iter<'a>),
+    HashTable(crate::collection::hash::Keys<'a, Vec<u8>, ()>),
+}
+
+impl Iterator for Iter<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Iter::IntSet(set, positions) => positions.next().map(|pos| set.get(pos).unwrap().to_string().into_bytes()),
+            Iter::Listpack(entries) => entries.next().map(|entry| entry.to_bytes().into_owned()),
+            Iter::HashTable(keys) => keys.next().cloned(),
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use std::borrow::Borrow;
+use std::ops::{Bound, RangeBounds};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+
+use crate::collection::skiplist::ZSKIPLIST_MAXLEVEL;
+
+use super::level_generator::{DefaultLevelGenerator, LevelGenerator};
+
+// A node is logically removed once the forward pointer of its level 0 carries this tag.
+// Every level is tagged on removal so that nothing can be linked after a removed node.
+const MARK: usize = 1;
+
+// Per level predecessor towers and successors found by `find`
+type Preds<'g, K, V> = [&'g [Atomic<Node<K, V>>]; ZSKIPLIST_MAXLEVEL];
+type Succs<'g, K, V> = [Shared<'g, Node<K, V>>; ZSKIPLIST_MAXLEVEL];
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    // Number of levels the node is linked at, plus one while its inserter is still
+    // linking it. The node is handed to the garbage collector when it drops to zero.
+    refs: AtomicUsize,
+    tower: Box<[Atomic<Node<K, V>>]>,
+}
+
+impl<K, V> Node<K, V> {
+    fn is_removed(&self, guard: &Guard) -> bool {
+        self.tower[0].load(Ordering::Acquire, guard).tag() == MARK
+    }
+
+    /// Drops one reference, destroying the node once it is unreachable.
+    ///
+    /// # Safety
+    ///
+    /// `node` must point to a valid node that the caller holds a reference of.
+    unsafe fn release(node: Shared<'_, Node<K, V>>, guard: &Guard) {
+        if node.deref().refs.fetch_sub(1, Ordering::AcqRel) == 1 {
+            guard.defer_destroy(node);
+        }
+    }
+}
+
+/// A lock-free ordered map shared across threads, after "A Pragmatic Implementation
+/// of Non-Blocking Linked Lists" (Harris) extended to skip lists (Fraser).
+///
+/// Nodes are linked and unlinked with CAS, and memory is reclaimed with epoch based
+/// garbage collection, so readers never wait for writers. Only drawing the level of a
+/// new node takes a short lock, since `LevelGenerator::random` needs `&mut self`.
+///
+/// Reads are weakly consistent: `range` reflects every change completed before it
+/// started and may or may not reflect changes made while it runs.
+pub struct ConcurrentSkipMap<K, V> {
+    head: Box<[Atomic<Node<K, V>>]>,
+    len: AtomicUsize,
+    level_generator: Mutex<Box<dyn LevelGenerator + Send>>,
+}
+
+impl<K, V> ConcurrentSkipMap<K, V>
+where
+    K: Ord + Send + Sync,
+    V: Send + Sync,
+{
+    /// Creates an empty map using the Redis level parameters.
+    pub fn new() -> Self {
+        Self::with_level_generator(DefaultLevelGenerator::default())
+    }
+
+    /// Creates an empty map drawing node levels from `level_generator`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::concurrent::ConcurrentSkipMap;
+    /// use curly_giggle::collection::skiplist::level_generator::DefaultLevelGenerator;
+    ///
+    /// let g = DefaultLevelGenerator::with_seed(8, 0.5, 1).unwrap();
+    /// let map = ConcurrentSkipMap::with_level_generator(g);
+    /// assert!(map.insert(1, "one"));
+    /// assert_eq!(map.get(&1), Some("one"));
+    /// ```
+    pub fn with_level_generator<G>(level_generator: G) -> Self
+    where
+        G: LevelGenerator + Send + 'static,
+    {
+        let levels = level_generator.level_bound().clamp(1, ZSKIPLIST_MAXLEVEL);
+        ConcurrentSkipMap {
+            head: (0..levels).map(|_| Atomic::null()).collect(),
+            len: AtomicUsize::new(0),
+            level_generator: Mutex::new(Box::new(level_generator)),
+        }
+    }
+
+    /// Returns the number of entries. Under concurrent updates this is a snapshot.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn random_level(&self) -> usize {
+        let level = self.level_generator.lock().unwrap().random();
+        level.clamp(1, self.head.len())
+    }
+
+    /// Finds, at every level, the last node before `key` and the node following it,
+    /// unlinking removed nodes on the way. Returns whether the level 0 successor
+    /// holds `key`.
+    fn find<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> (bool, Preds<'g, K, V>, Succs<'g, K, V>)
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut preds: Preds<'g, K, V> = [&self.head[..]; ZSKIPLIST_MAXLEVEL];
+        let mut succs: Succs<'g, K, V> = [Shared::null(); ZSKIPLIST_MAXLEVEL];
+
+        'retry: loop {
+            let mut pred: &'g [Atomic<Node<K, V>>] = &self.head;
+            for level in (0..self.head.len()).rev() {
+                let mut curr = pred[level].load(Ordering::Acquire, guard);
+                // The predecessor was removed meanwhile
+                if curr.tag() == MARK {
+                    continue 'retry;
+                }
+                while let Some(c) = unsafe { curr.as_ref() } {
+                    let succ = c.tower[level].load(Ordering::Acquire, guard);
+                    if succ.tag() == MARK {
+                        // Unlink the removed node at this level
+                        match pred[level].compare_exchange(
+                            curr,
+                            succ.with_tag(0),
+                            Ordering::AcqRel,
+                            Ordering::Acquire,
+                            guard,
+                        ) {
+                            Ok(_) => {
+                                unsafe { Node::release(curr, guard) };
+                                curr = succ.with_tag(0);
+                            }
+                            Err(_) => continue 'retry,
+                        }
+                        continue;
+                    }
+                    if c.key.borrow() >= key {
+                        break;
+                    }
+                    pred = &c.tower;
+                    curr = succ;
+                }
+                preds[level] = pred;
+                succs[level] = curr;
+            }
+
+            let found = unsafe { succs[0].as_ref() }.is_some_and(|c| c.key.borrow() == key);
+            return (found, preds, succs);
+        }
+    }
+
+    /// Returns the first entry at level 0 that is not below `bound`, skipping removed
+    /// entries. Does not write, so readers do not contend with each other.
+    fn lower_bound<'g, Q>(&'g self, bound: Bound<&Q>, guard: &'g Guard) -> Option<&'g Node<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let below = |key: &Q| match bound {
+            Bound::Included(start) => key < start,
+            Bound::Excluded(start) => key <= start,
+            Bound::Unbounded => false,
+        };
+
+        let mut pred: &'g [Atomic<Node<K, V>>] = &self.head;
+        let mut curr = Shared::null();
+        for level in (0..self.head.len()).rev() {
+            curr = pred[level].load(Ordering::Acquire, guard).with_tag(0);
+            while let Some(c) = unsafe { curr.as_ref() } {
+                if !below(c.key.borrow()) {
+                    break;
+                }
+                pred = &c.tower;
+                curr = c.tower[level].load(Ordering::Acquire, guard).with_tag(0);
+            }
+        }
+        Self::first_live(curr, guard)
+    }
+
+    fn first_live<'g>(mut curr: Shared<'g, Node<K, V>>, guard: &'g Guard) -> Option<&'g Node<K, V>> {
+        while let Some(c) = unsafe { curr.as_ref() } {
+            if !c.is_removed(guard) {
+                return Some(c);
+            }
+            curr = c.tower[0].load(Ordering::Acquire, guard).with_tag(0);
+        }
+        None
+    }
+
+    /// Inserts `key` with `value` if the key is absent.
+    ///
+    /// Returns `false`, leaving the map unchanged, if the key was already present.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::concurrent::ConcurrentSkipMap;
+    ///
+    /// let map = ConcurrentSkipMap::new();
+    /// assert!(map.insert("a", 1));
+    /// assert!(!map.insert("a", 2));
+    /// assert_eq!(map.get("a"), Some(1));
+    /// ```
+    pub fn insert(&self, key: K, value: V) -> bool {
+        let guard = &epoch::pin();
+        let height = self.random_level();
+        let mut new = Owned::new(Node {
+            key,
+            value,
+            // One for level 0, one for this inserter
+            refs: AtomicUsize::new(2),
+            tower: (0..height).map(|_| Atomic::null()).collect(),
+        });
+
+        // Linking at level 0 makes the entry visible
+        let (node, mut preds, mut succs) = loop {
+            let (found, preds, succs) = self.find(&new.key, guard);
+            if found {
+                return false;
+            }
+            new.tower[0].store(succs[0], Ordering::Relaxed);
+            match preds[0][0].compare_exchange(succs[0], new, Ordering::AcqRel, Ordering::Acquire, guard) {
+                Ok(node) => break (node, preds, succs),
+                Err(e) => new = e.new,
+            }
+        };
+        self.len.fetch_add(1, Ordering::SeqCst);
+        let n = unsafe { node.deref() };
+
+        'build: for level in 1..height {
+            loop {
+                let next = n.tower[level].load(Ordering::Acquire, guard);
+                // Removed while being linked
+                if next.tag() == MARK {
+                    break 'build;
+                }
+                if n.tower[level]
+                    .compare_exchange(next, succs[level], Ordering::AcqRel, Ordering::Acquire, guard)
+                    .is_err()
+                {
+                    continue;
+                }
+                n.refs.fetch_add(1, Ordering::AcqRel);
+                if preds[level][level]
+                    .compare_exchange(succs[level], node, Ordering::AcqRel, Ordering::Acquire, guard)
+                    .is_ok()
+                {
+                    break;
+                }
+                n.refs.fetch_sub(1, Ordering::AcqRel);
+
+                let (_, p, s) = self.find(&n.key, guard);
+                if s[0] != node {
+                    break 'build;
+                }
+                preds = p;
+                succs = s;
+            }
+        }
+
+        // A concurrent remove may have missed the levels linked after it ran
+        if n.is_removed(guard) {
+            self.find(&n.key, guard);
+        }
+        unsafe { Node::release(node, guard) };
+        true
+    }
+
+    /// Removes `key`, returning its value if it was present.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::concurrent::ConcurrentSkipMap;
+    ///
+    /// let map = ConcurrentSkipMap::new();
+    /// map.insert(1, "one");
+    /// assert_eq!(map.remove(&1), Some("one"));
+    /// assert_eq!(map.remove(&1), None);
+    /// assert!(map.is_empty());
+    /// ```
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        V: Clone,
+    {
+        let guard = &epoch::pin();
+        let (found, _, succs) = self.find(key, guard);
+        if !found {
+            return None;
+        }
+        let n = unsafe { succs[0].deref() };
+
+        // Mark from the top so the node stays reachable at level 0 until removed
+        for level in (1..n.tower.len()).rev() {
+            let mut next = n.tower[level].load(Ordering::Acquire, guard);
+            while next.tag() != MARK {
+                match n.tower[level].compare_exchange(
+                    next,
+                    next.with_tag(MARK),
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                    guard,
+                ) {
+                    Ok(_) => break,
+                    Err(e) => next = e.current,
+                }
+            }
+        }
+
+        let mut next = n.tower[0].load(Ordering::Acquire, guard);
+        loop {
+            // Another thread removed it first
+            if next.tag() == MARK {
+                return None;
+            }
+            match n.tower[0].compare_exchange(next, next.with_tag(MARK), Ordering::AcqRel, Ordering::Acquire, guard) {
+                Ok(_) => break,
+                Err(e) => next = e.current,
+            }
+        }
+        self.len.fetch_sub(1, Ordering::SeqCst);
+        let value = n.value.clone();
+
+        // Unlink it at every level
+        self.find(key, guard);
+        Some(value)
+    }
+
+    /// Returns a copy of the value of `key`.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        V: Clone,
+    {
+        let guard = &epoch::pin();
+        self.lower_bound(Bound::Included(key), guard)
+            .filter(|n| n.key.borrow() == key)
+            .map(|n| n.value.clone())
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let guard = &epoch::pin();
+        self.lower_bound(Bound::Included(key), guard)
+            .is_some_and(|n| n.key.borrow() == key)
+    }
+
+    /// Returns copies of the entries whose keys are in `range`, in ascending order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::concurrent::ConcurrentSkipMap;
+    ///
+    /// let map = ConcurrentSkipMap::new();
+    /// for i in 0..10 {
+    ///     map.insert(i, i * i);
+    /// }
+    /// assert_eq!(map.range(3..6), vec![(3, 9), (4, 16), (5, 25)]);
+    /// assert_eq!(map.range(8..), vec![(8, 64), (9, 81)]);
+    /// ```
+    pub fn range<Q, R>(&self, range: R) -> Vec<(K, V)>
+    where
+        K: Borrow<Q> + Clone,
+        V: Clone,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let guard = &epoch::pin();
+        let mut entries = Vec::new();
+        let mut curr = self.lower_bound(range.start_bound(), guard);
+        while let Some(n) = curr {
+            let beyond = match range.end_bound() {
+                Bound::Included(end) => n.key.borrow() > end,
+                Bound::Excluded(end) => n.key.borrow() >= end,
+                Bound::Unbounded => false,
+            };
+            if beyond {
+                break;
+            }
+            entries.push((n.key.clone(), n.value.clone()));
+            curr = Self::first_live(n.tower[0].load(Ordering::Acquire, guard).with_tag(0), guard);
+        }
+        entries
+    }
+}
+
+impl<K, V> Default for ConcurrentSkipMap<K, V>
+where
+    K: Ord + Send + Sync,
+    V: Send + Sync,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Drop for ConcurrentSkipMap<K, V> {
+    fn drop(&mut self) {
+        // With `&mut self` no other thread can see the map, and every removed node was
+        // fully unlinked and handed to the garbage collector, so the nodes left at
+        // level 0 are exactly the ones to free.
+        unsafe {
+            let guard = epoch::unprotected();
+            let mut curr = self.head[0].load(Ordering::Relaxed, guard);
+            while !curr.is_null() {
+                let next = curr.deref().tower[0].load(Ordering::Relaxed, guard).with_tag(0);
+                drop(curr.into_owned());
+                curr = next;
+            }
+        }
+    }
+}
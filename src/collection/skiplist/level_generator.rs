@@ -1,32 +1,58 @@
+use std::fmt;
+
 use rand::rngs::SmallRng;
 use rand::{thread_rng, Rng};
 use rand::SeedableRng;
 
+use crate::collection::skiplist::{ZSKIPLIST_MAXLEVEL, ZSKIPLIST_P};
+
 pub trait LevelGenerator {
     fn level_bound(&self) -> usize;
     fn random(&mut self) -> usize;
 }
 
+/// Invalid parameters given to `DefaultLevelGenerator`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelGeneratorError {
+    /// `level_bound` was zero.
+    ZeroLevelBound,
+    /// `p` was not in the range (0, 1).
+    InvalidProbability(f64),
+}
+
+impl fmt::Display for LevelGeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelGeneratorError::ZeroLevelBound => write!(f, "total must be non-zero."),
+            LevelGeneratorError::InvalidProbability(p) => write!(f, "p must be in (0, 1), got {}.", p),
+        }
+    }
+}
+
+impl std::error::Error for LevelGeneratorError {}
+
 pub struct DefaultLevelGenerator {
     level_bound: usize,
     p: f64,
     rng: SmallRng,
 }
 
+/// Uses the Redis parameters, `ZSKIPLIST_MAXLEVEL` levels and `ZSKIPLIST_P`.
 impl Default for DefaultLevelGenerator {
     fn default() -> Self {
-        DefaultLevelGenerator::new(16, 1.0 / 2.0).unwrap()
+        DefaultLevelGenerator::new(ZSKIPLIST_MAXLEVEL, ZSKIPLIST_P).unwrap()
     }
 }
 
 /// The default level generator for the skip list.
 impl DefaultLevelGenerator {
-    /// Creates a new instance of the default level generator.
+    /// Creates a new instance of the default level generator, seeded from the
+    /// thread-local random number generator.
     ///
     /// # Arguments
     ///
     /// * `level_bound` - The maximum number of levels in the skip list.
-    /// * `p` - The probability used to determine the number of levels for a new node.
+    /// * `p` - The probability for a node of level `n` to reach level `n + 1`.
     ///
     /// # Errors
     ///
@@ -35,23 +61,46 @@ impl DefaultLevelGenerator {
     /// # Examples
     ///
     /// ```
-    /// use curly_giggle::collection::skiplist::level_generator::DefaultLevelGenerator;
+    /// use curly_giggle::collection::skiplist::level_generator::{DefaultLevelGenerator, LevelGeneratorError};
     ///
     /// let level_generator = DefaultLevelGenerator::new(4, 0.5);
     /// assert!(level_generator.is_ok());
+    /// assert_eq!(DefaultLevelGenerator::new(0, 0.5).err(), Some(LevelGeneratorError::ZeroLevelBound));
+    /// ```
+    pub fn new(level_bound: usize, p: f64) -> Result<Self, LevelGeneratorError> {
+        Self::with_rng(level_bound, p, SmallRng::from_rng(thread_rng()).unwrap())
+    }
+
+    /// Like `new`, but seeded so the same seed always yields the same levels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::level_generator::{DefaultLevelGenerator, LevelGenerator};
+    ///
+    /// let mut a = DefaultLevelGenerator::with_seed(32, 0.25, 42).unwrap();
+    /// let mut b = DefaultLevelGenerator::with_seed(32, 0.25, 42).unwrap();
+    /// for _ in 0..100 {
+    ///     assert_eq!(a.random(), b.random());
+    /// }
     /// ```
-    pub fn new(level_bound: usize, p: f64) -> Result<Self, String> {
+    pub fn with_seed(level_bound: usize, p: f64, seed: u64) -> Result<Self, LevelGeneratorError> {
+        Self::with_rng(level_bound, p, SmallRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(level_bound: usize, p: f64, rng: SmallRng) -> Result<Self, LevelGeneratorError> {
         if level_bound == 0 {
-            return Err("total must be non-zero.".to_string());
+            return Err(LevelGeneratorError::ZeroLevelBound);
         }
-        if (p - 0.0).abs() < 1e-3 || (p - 1.0).abs() < 1e-3 {
-            return Err("p must be in (0, 1).".to_string());
+        // Also rejects NaN
+        if !(p > 0.0 && p < 1.0) {
+            return Err(LevelGeneratorError::InvalidProbability(p));
         }
-        Ok(DefaultLevelGenerator {
-            level_bound,
-            p,
-            rng: SmallRng::from_rng(thread_rng()).unwrap(),
-        })
+        Ok(DefaultLevelGenerator { level_bound, p, rng })
+    }
+
+    pub fn p(&self) -> f64 {
+        self.p
     }
 }
 
@@ -62,28 +111,15 @@ impl LevelGenerator for DefaultLevelGenerator {
         self.level_bound
     }
 
-    /// Generates a random level for a new skiplist node.
+    /// Generates a random level for a new skiplist node, like `zslRandomLevel`.
+    ///
+    /// The level is between 1 and `level_bound` both inclusive, and is `n` or more
+    /// with probability `p^(n-1)` (a geometric distribution truncated at the bound).
     fn random(&mut self) -> usize {
         let mut level = 1;
-        let mut x = self.p;
-        let f = 1.0 - self.rng.gen::<f64>();
-        while x > f && level + 1 < self.level_bound {
+        while level < self.level_bound && self.rng.gen::<f64>() < self.p {
             level += 1;
-            x *= self.p
         }
         level
     }
 }
-
-pub fn generate_random_level(bound: usize) -> usize {
-    let mut level = 1;
-    // The random number generator used for generating levels in the skip list.
-    let mut rng = thread_rng();
-
-    for _i in 1..bound {
-        if rng.gen::<u32>() % 2 == 1 {
-            level += 1;
-        }
-    }
-    level
-}
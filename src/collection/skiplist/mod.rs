@@ -1,8 +1,11 @@
 pub mod zskiplist;
 pub mod zskipnode;
 pub mod level_generator;
+pub mod zrangespec;
+pub mod concurrent;
 
 
 // layer level
-const ZSKIPLIST_MAXLEVEL: usize = 32;
-const ZSKIPLIST_P: f64 = 0.25;
\ No newline at end of file
+pub const ZSKIPLIST_MAXLEVEL: usize = 32;
+// Skiplist P = 1/4
+pub const ZSKIPLIST_P: f64 = 0.25;
@@ -0,0 +1,155 @@
+/// A score range with optionally exclusive bounds, like `zrangespec` in Redis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ZRangeSpec {
+    pub min: f64,
+    pub max: f64,
+    // Are min or max exclusive?
+    pub minex: bool,
+    pub maxex: bool,
+}
+
+impl ZRangeSpec {
+    /// Creates an inclusive range `[min, max]`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::zrangespec::ZRangeSpec;
+    ///
+    /// let range = ZRangeSpec::new(1.0, 2.0);
+    /// assert!(range.value_gte_min(1.0));
+    /// assert!(range.value_lte_max(2.0));
+    /// ```
+    pub fn new(min: f64, max: f64) -> Self {
+        ZRangeSpec {
+            min,
+            max,
+            minex: false,
+            maxex: false,
+        }
+    }
+
+    pub fn value_gte_min(&self, value: f64) -> bool {
+        if self.minex {
+            value > self.min
+        } else {
+            value >= self.min
+        }
+    }
+
+    pub fn value_lte_max(&self, value: f64) -> bool {
+        if self.maxex {
+            value < self.max
+        } else {
+            value <= self.max
+        }
+    }
+
+    /// Whether no score can ever fall in this range.
+    pub fn is_empty(&self) -> bool {
+        self.min > self.max || (self.min == self.max && (self.minex || self.maxex))
+    }
+
+    /// Parses a ZRANGEBYSCORE style range, e.g. `("(1.5", "+inf")`.
+    ///
+    /// A leading `(` makes the bound exclusive. `-inf` and `+inf` are accepted,
+    /// NaN is not.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"min or max is not a float"` if either bound fails to parse.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::zrangespec::ZRangeSpec;
+    ///
+    /// let range = ZRangeSpec::parse("(1.5", "+inf").unwrap();
+    /// assert_eq!(range.min, 1.5);
+    /// assert!(range.minex);
+    /// assert_eq!(range.max, f64::INFINITY);
+    /// assert!(!range.maxex);
+    /// assert!(ZRangeSpec::parse("a", "1").is_err());
+    /// ```
+    pub fn parse(min: &str, max: &str) -> Result<Self, String> {
+        let (min, minex) = Self::parse_bound(min)?;
+        let (max, maxex) = Self::parse_bound(max)?;
+        Ok(ZRangeSpec { min, max, minex, maxex })
+    }
+
+    fn parse_bound(bound: &str) -> Result<(f64, bool), String> {
+        let (value, exclusive) = match bound.strip_prefix('(') {
+            Some(rest) => (rest, true),
+            None => (bound, false),
+        };
+        match value.parse::<f64>() {
+            Ok(v) if !v.is_nan() => Ok((v, exclusive)),
+            _ => Err("min or max is not a float".to_string()),
+        }
+    }
+}
+
+/// One end of a lexicographical range.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LexBound<T> {
+    /// `-`, smaller than any element.
+    NegInf,
+    /// `+`, greater than any element.
+    PosInf,
+    /// `[value`
+    Inclusive(T),
+    /// `(value`
+    Exclusive(T),
+}
+
+/// An element range for ZRANGEBYLEX and friends, like `zlexrangespec` in Redis.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZLexRangeSpec<T> {
+    pub min: LexBound<T>,
+    pub max: LexBound<T>,
+}
+
+impl<T> ZLexRangeSpec<T>
+where
+    T: for<'a> From<&'a str>,
+{
+    /// Parses a ZRANGEBYLEX style range, e.g. `("[a", "(b")` or `("-", "+")`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"min or max not valid string range item"` if a bound does not
+    /// start with `[` or `(` and is not `-` or `+`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::zrangespec::{LexBound, ZLexRangeSpec};
+    ///
+    /// let range: ZLexRangeSpec<String> = ZLexRangeSpec::parse("[a", "+").unwrap();
+    /// assert_eq!(range.min, LexBound::Inclusive("a".to_string()));
+    /// assert_eq!(range.max, LexBound::PosInf);
+    /// assert!(ZLexRangeSpec::<String>::parse("a", "+").is_err());
+    /// ```
+    pub fn parse(min: &str, max: &str) -> Result<Self, String> {
+        Ok(ZLexRangeSpec {
+            min: Self::parse_bound(min)?,
+            max: Self::parse_bound(max)?,
+        })
+    }
+
+    fn parse_bound(bound: &str) -> Result<LexBound<T>, String> {
+        match bound {
+            "-" => Ok(LexBound::NegInf),
+            "+" => Ok(LexBound::PosInf),
+            _ => {
+                if let Some(value) = bound.strip_prefix('[') {
+                    Ok(LexBound::Inclusive(T::from(value)))
+                } else if let Some(value) = bound.strip_prefix('(') {
+                    Ok(LexBound::Exclusive(T::from(value)))
+                } else {
+                    Err("min or max not valid string range item".to_string())
+                }
+            }
+        }
+    }
+}
@@ -1,16 +1,28 @@
 use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::fmt;
 use std::marker::PhantomData;
-use std::mem;
 use std::ptr::NonNull;
 
-use crate::collection::skiplist::{ZSKIPLIST_MAXLEVEL, ZSKIPLIST_P};
+use rand::{thread_rng, Rng};
 
+use crate::collection::skiplist::ZSKIPLIST_MAXLEVEL;
+
+use super::zrangespec::{LexBound, ZLexRangeSpec, ZRangeSpec};
 use super::zskipnode::{Link, ZSkipNode};
 use super::level_generator::{DefaultLevelGenerator, LevelGenerator};
 
 type Comparator<T> = Box<dyn Fn(&T, &T) -> Ordering>;
 
+// How many times the set must be larger than the requested count for ZRANDMEMBER
+// to pick random elements one by one instead of trimming a copy of the set.
+const ZRANDMEMBER_SUB_STRATEGY_MUL: usize = 3;
+
+/// Most elements a negative count may ask ZRANDMEMBER, SRANDMEMBER or HRANDFIELD
+/// for. The whole reply is built at once, so larger counts are refused before
+/// anything is allocated.
+pub const ZRANDMEMBER_MAX_COUNT: u64 = 1 << 20;
+
 #[allow(dead_code, unused_variables)]
 pub struct ZSkipList<T> {
     header: NonNull<ZSkipNode<T>>,
@@ -24,103 +36,495 @@ pub struct ZSkipList<T> {
 
 #[allow(dead_code, unused_variables)]
 impl<T: Ord> ZSkipList<T> {
+    /// Creates an empty skip list ordering elements with the same score by `Ord`.
     pub fn zsl_create() -> Self {
-        // TODO: Implementation of zsl_create here
-        // LevelGenerator
-        let g = DefaultLevelGenerator::default();
-        let new_node = NonNull::new(Box::into_raw(Box::new(ZSkipNode::head(g.level_bound())))).unwrap();
+        Self::with_comparator(|x: &T, y: &T| x.cmp(y))
+    }
+
+    /// Like `zsl_create`, drawing node levels from `level_generator`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::level_generator::DefaultLevelGenerator;
+    /// use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
+    ///
+    /// let g = DefaultLevelGenerator::new(4, 0.5).unwrap();
+    /// let mut skip_list = ZSkipList::with_level_generator(g);
+    /// skip_list.zsl_insert(1.0, 42);
+    /// assert_eq!(skip_list.get_len(), 1);
+    /// ```
+    pub fn with_level_generator<G>(level_generator: G) -> Self
+    where
+        G: LevelGenerator + 'static,
+    {
+        Self::with_comparator_and_level_generator(|x: &T, y: &T| x.cmp(y), level_generator)
+    }
+}
+
+impl<T> ZSkipList<T> {
+    /// Creates an empty skip list ordering elements with the same score by `cmp`,
+    /// so `T` does not need to be `Ord`.
+    ///
+    /// # Arguments
+    ///
+    /// * `cmp` - Total order used to break ties between elements with the same score.
+    ///   Two elements comparing equal are considered the same element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
+    ///
+    /// // Byte strings compared case-insensitively
+    /// let mut skip_list = ZSkipList::with_comparator(|a: &Vec<u8>, b: &Vec<u8>| {
+    ///     a.to_ascii_lowercase().cmp(&b.to_ascii_lowercase())
+    /// });
+    /// skip_list.zsl_insert(0.0, b"beta".to_vec());
+    /// skip_list.zsl_insert(0.0, b"ALPHA".to_vec());
+    /// assert!(skip_list.contains(0.0, &b"Beta".to_vec()));
+    /// assert_eq!(skip_list.zsl_get_rank(0.0, b"alpha".to_vec()), Some(0));
+    /// ```
+    pub fn with_comparator<F>(cmp: F) -> Self
+    where
+        F: Fn(&T, &T) -> Ordering + 'static,
+    {
+        Self::with_comparator_and_level_generator(cmp, DefaultLevelGenerator::default())
+    }
+
+    /// Creates an empty skip list ordering elements with the same score by the key
+    /// `key` extracts from them, e.g. one field of a struct.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
+    ///
+    /// struct Player { name: String, level: u32 }
+    ///
+    /// let mut skip_list = ZSkipList::with_key(|p: &Player| p.name.clone());
+    /// skip_list.zsl_insert(1.0, Player { name: "bob".to_string(), level: 3 });
+    /// skip_list.zsl_insert(1.0, Player { name: "alice".to_string(), level: 7 });
+    /// assert_eq!(skip_list.iter().next().unwrap().level, 7);
+    /// ```
+    pub fn with_key<K, F>(key: F) -> Self
+    where
+        K: Ord,
+        F: Fn(&T) -> K + 'static,
+    {
+        Self::with_comparator(move |a, b| key(a).cmp(&key(b)))
+    }
+
+    /// Creates an empty skip list with both a custom comparator and level generator.
+    ///
+    /// The header gets `level_generator.level_bound()` levels, capped at
+    /// `ZSKIPLIST_MAXLEVEL`, and the levels it draws are clamped to that range.
+    pub fn with_comparator_and_level_generator<F, G>(cmp: F, level_generator: G) -> Self
+    where
+        F: Fn(&T, &T) -> Ordering + 'static,
+        G: LevelGenerator + 'static,
+    {
+        let level_bound = level_generator.level_bound().clamp(1, ZSKIPLIST_MAXLEVEL);
+        let new_node = NonNull::new(Box::into_raw(Box::new(ZSkipNode::head(level_bound)))).unwrap();
         ZSkipList {
             header: new_node,
             tail: new_node,
             cur_level: 0,
             len: 0,
-            cmp: Box::new(|x, y| x.cmp(y)),
+            cmp: Box::new(cmp),
             _boo: PhantomData,
-            level_generator: Box::new(g),
+            level_generator: Box::new(level_generator),
         }
     }
 }
-   
-impl<T: fmt::Debug + std::clone::Clone> fmt::Debug for ZSkipList<T> {
+
+impl<T: fmt::Debug> fmt::Debug for ZSkipList<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         unsafe {
-            
-            println!("=====================");
+            writeln!(f, "=====================")?;
             for i in 0..self.cur_level {
-                println!("Level {}:", i);
+                writeln!(f, "Level {}:", i)?;
                 let mut cur = self.header.as_ref();
-                print!("None,({}),None -> ", cur.get_span(i));
+                write!(f, "None,({}),None -> ", cur.get_span(i))?;
                 while let Some(next_node) = cur.level[i].forward {
                     let next_node = next_node.as_ref();
                     // val, span, score
-                    write!(f, "{:?},({:?}),({:?}) -> ", <ZSkipNode<T> as Clone>::clone(&next_node).into_val(), <ZSkipNode<T> as Clone>::clone(&next_node).get_span(i), <ZSkipNode<T> as Clone>::clone(&next_node).score)?;
+                    write!(f, "{:?},({:?}),({:?}) -> ", next_node.val, next_node.get_span(i), next_node.score)?;
                     cur = next_node;
                 }
-                println!("\n");
+                writeln!(f)?;
             }
-            println!("=====================");
+            write!(f, "=====================")
         }
-        Ok(())
     }
 }
 
+/// A broken structural invariant found by `ZSkipList::validate`.
+///
+/// Ranks are 1-based positions on level 0, 0 standing for the header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// The header holds an element or a backward link.
+    Header,
+    /// The element at `rank` has a NaN score.
+    NanScore { rank: usize },
+    /// The element at `rank` does not sort strictly after the previous one.
+    Unordered { rank: usize },
+    /// The backward link of the element at `rank` is not the previous element.
+    Backward { rank: usize },
+    /// `tail` is not the last element, or the header when empty.
+    Tail,
+    /// `len` does not match the number of elements on level 0.
+    Len { expected: usize, found: usize },
+    /// `cur_level` is not the highest level of any element.
+    CurLevel { expected: usize, found: usize },
+    /// On `level`, the node at `rank` does not link to the next node tall enough.
+    Forward { level: usize, rank: usize },
+    /// On `level`, the span of the node at `rank` is not the rank distance to its
+    /// forward node, or to the end of the list when it has none.
+    Span {
+        level: usize,
+        rank: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantViolation::Header => write!(f, "header holds an element or a backward link"),
+            InvariantViolation::NanScore { rank } => write!(f, "element at rank {} has a NaN score", rank),
+            InvariantViolation::Unordered { rank } => write!(f, "element at rank {} is out of order", rank),
+            InvariantViolation::Backward { rank } => write!(f, "wrong backward link at rank {}", rank),
+            InvariantViolation::Tail => write!(f, "tail is not the last element"),
+            InvariantViolation::Len { expected, found } => write!(f, "len is {}, expected {}", found, expected),
+            InvariantViolation::CurLevel { expected, found } => {
+                write!(f, "cur_level is {}, expected {}", found, expected)
+            }
+            InvariantViolation::Forward { level, rank } => {
+                write!(f, "wrong forward link at rank {} on level {}", rank, level)
+            }
+            InvariantViolation::Span {
+                level,
+                rank,
+                expected,
+                found,
+            } => write!(
+                f,
+                "span at rank {} on level {} is {}, expected {}",
+                rank, level, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
+
 impl<T> ZSkipList<T> {
-    pub fn iter(&self) -> Iter<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
         let node = unsafe { self.header.as_ref().level[0].forward };
         
         Iter {
             head: node,
+            tail: self.tail_link(),
             len: self.len,
             _boo: PhantomData,
         }
     }
         
-    pub fn iter_mut(&mut self) -> IterMut<T> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
         let node = unsafe { self.header.as_ref().level[0].forward };
         
         IterMut {
             head: node,
+            tail: self.tail_link(),
             len: self.len,
             _boo: PhantomData,
         }
     }      
 
+    /// Iterate over the `(score, element)` pairs with score in `range`.
+    ///
+    /// Both ends are found in O(log N), so `.rev()` walks the range from its last
+    /// element (ZREVRANGEBYSCORE) without visiting the ones before it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
+    /// use curly_giggle::collection::skiplist::zrangespec::ZRangeSpec;
+    ///
+    /// let mut skip_list = ZSkipList::zsl_create();
+    /// for i in 0..10 {
+    ///     skip_list.zsl_insert(i as f64, i);
+    /// }
+    /// let range = ZRangeSpec::parse("(2", "5").unwrap();
+    /// let items: Vec<_> = skip_list.range_by_score(&range).rev().collect();
+    /// assert_eq!(items, vec![(5.0, &5), (4.0, &4), (3.0, &3)]);
+    /// ```
+    pub fn range_by_score(&self, range: &ZRangeSpec) -> Range<'_, T> {
+        if !self.zsl_is_in_range(range) {
+            return Range::empty();
+        }
+        unsafe {
+            let (before, before_rank) = self.zsl_last_where(|node| !range.value_gte_min(node.score));
+            let (last, last_rank) = self.zsl_last_where(|node| range.value_lte_max(node.score));
+            Range::between(before, last, last_rank.saturating_sub(before_rank))
+        }
+    }
+
+    /// Iterate over the `(score, element)` pairs with element in `range`.
+    ///
+    /// Like the other lex functions, this expects all the elements to share the same score.
+    pub fn range_by_lex(&self, range: &ZLexRangeSpec<T>) -> Range<'_, T> {
+        if !self.zsl_is_in_lex_range(range) {
+            return Range::empty();
+        }
+        unsafe {
+            let (before, before_rank) =
+                self.zsl_last_where(|node| !self.zsl_lex_value_gte_min(node.val.as_ref().unwrap(), &range.min));
+            let (last, last_rank) =
+                self.zsl_last_where(|node| self.zsl_lex_value_lte_max(node.val.as_ref().unwrap(), &range.max));
+            Range::between(before, last, last_rank.saturating_sub(before_rank))
+        }
+    }
+
+    /// Iterate over the `(score, element)` pairs from index `start` to `stop`, both
+    /// inclusive and 0-based, as in ZRANGE.
+    ///
+    /// Negative indexes count from the end, `-1` being the last element. Out of range
+    /// indexes are clamped, and an empty iterator is returned if nothing is left.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
+    ///
+    /// let mut skip_list = ZSkipList::zsl_create();
+    /// for i in 0..10 {
+    ///     skip_list.zsl_insert(i as f64, i);
+    /// }
+    /// let items: Vec<_> = skip_list.range_by_rank(-3, 100).map(|(_, e)| *e).collect();
+    /// assert_eq!(items, vec![7, 8, 9]);
+    /// ```
+    pub fn range_by_rank(&self, start: i64, stop: i64) -> Range<'_, T> {
+        let len = self.len as i64;
+        let start = if start < 0 { (start + len).max(0) } else { start };
+        let stop = if stop < 0 { stop + len } else { stop.min(len - 1) };
+        if start > stop || start >= len {
+            return Range::empty();
+        }
+        unsafe {
+            // Ranks of the nodes are 1-based
+            match (self.zsl_get_node_by_rank(start as usize + 1), self.zsl_get_node_by_rank(stop as usize + 1)) {
+                (Some(head), Some(tail)) => Range {
+                    head: Some(head),
+                    tail: Some(tail),
+                    len: (stop - start + 1) as usize,
+                    _boo: PhantomData,
+                },
+                _ => Range::empty(),
+            }
+        }
+    }
+
+    fn tail_link(&self) -> Link<T> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.tail)
+        }
+    }
+
+    /// Find the node at the given 1-based rank.
+    unsafe fn zsl_get_node_by_rank(&self, rank: usize) -> Link<T> {
+        let mut traversed: usize = 0;
+        let mut x = self.header;
+        for i in (0..self.cur_level).rev() {
+            while let Some(forward) = x.as_ref().level[i].forward {
+                if traversed + x.as_ref().level[i].span > rank {
+                    break;
+                }
+                traversed += x.as_ref().level[i].span;
+                x = forward;
+            }
+            if traversed == rank {
+                return if rank == 0 { None } else { Some(x) };
+            }
+        }
+        None
+    }
+
     pub fn pop_front(&mut self) -> Option<T> {
         unsafe {
-            let first_node = self.header.as_ref().level[0].forward.as_ref();
-            match first_node {
-                Some(node) => self.zsl_delete(node.as_ref().val.as_ref().unwrap()),
-                None => None,
+            let first_node = self.header.as_ref().level[0].forward?;
+            // The header is the predecessor of the first node on every level
+            let update = vec![self.header; self.cur_level];
+            self.zsl_delete_node(first_node, &update);
+            Self::zsl_detach_node(first_node).into_val()
+        }
+    }
+
+    /// Remove and return up to `count` elements with the lowest scores, lowest first,
+    /// as in ZPOPMIN.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
+    ///
+    /// let mut skip_list = ZSkipList::zsl_create();
+    /// for i in 0..5 {
+    ///     skip_list.zsl_insert(i as f64, i);
+    /// }
+    /// assert_eq!(skip_list.pop_min(2), vec![(0.0, 0), (1.0, 1)]);
+    /// assert_eq!(skip_list.pop_max(2), vec![(4.0, 4), (3.0, 3)]);
+    /// assert_eq!(skip_list.get_len(), 1);
+    /// ```
+    pub fn pop_min(&mut self, count: usize) -> Vec<(f64, T)> {
+        let mut popped = Vec::with_capacity(count.min(self.len));
+        if count > 0 {
+            self.zsl_delete_range_by_rank_with(1, count, |score, element| popped.push((score, element)));
+        }
+        popped
+    }
+
+    /// Remove and return up to `count` elements with the highest scores, highest
+    /// first, as in ZPOPMAX.
+    pub fn pop_max(&mut self, count: usize) -> Vec<(f64, T)> {
+        let mut popped = Vec::with_capacity(count.min(self.len));
+        if count > 0 && self.len > 0 {
+            let start = self.len.saturating_sub(count) + 1;
+            self.zsl_delete_range_by_rank_with(start, self.len, |score, element| popped.push((score, element)));
+        }
+        popped.reverse();
+        popped
+    }
+
+    /// Returns true if `element` is in the list with the given score.
+    pub fn contains(&self, score: f64, element: &T) -> bool {
+        unsafe {
+            let (x, _) = self.zsl_last_where(|node| self.zsl_node_lt(node, score, element));
+            match x.as_ref().level[0].forward {
+                Some(next) => {
+                    let next = next.as_ref();
+                    next.score == score && (self.cmp)(next.val.as_ref().unwrap(), element) == Ordering::Equal
+                }
+                None => false,
             }
         }
     }
 
-    // TODO: Optimization
-    pub fn contains(&self, v: &T) -> bool {
+    pub fn get_len(&self) -> usize {
+        self.len
+    }
+
+    /// Check every structural invariant of the list: ordering by (score, element),
+    /// forward links and spans on each level, backward links, `tail`, `len` and
+    /// `cur_level`.
+    ///
+    /// This walks the whole list, it is meant for tests and debugging.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
+    ///
+    /// let mut skip_list = ZSkipList::zsl_create();
+    /// for i in 0..100 {
+    ///     skip_list.zsl_insert((i % 7) as f64, i);
+    /// }
+    /// skip_list.zsl_delete(3.0, &3);
+    /// assert_eq!(skip_list.validate(), Ok(()));
+    /// ```
+    pub fn validate(&self) -> Result<(), InvariantViolation> {
         unsafe {
-            let mut cur: &ZSkipNode<T> = self.header.as_ref();
-            for i in (0..self.cur_level).rev() {
-                while let Some(next_node) = cur.level[i].forward {
-                    let next_node = next_node.as_ref();
-                    if (self.cmp)(next_node.val.as_ref().unwrap(), v) == Ordering::Less {
-                        cur = next_node;
-                    } else {
-                        break;
+            let header = self.header.as_ref();
+            if header.val.is_some() || header.backward.is_some() {
+                return Err(InvariantViolation::Header);
+            }
+
+            // Level 0 gives the rank of every node
+            let mut nodes: Vec<NonNull<ZSkipNode<T>>> = Vec::with_capacity(self.len);
+            let mut next = header.level.first().and_then(|level| level.forward);
+            while let Some(node) = next {
+                let rank = nodes.len() + 1;
+                let node_ref = node.as_ref();
+                if node_ref.score.is_nan() {
+                    return Err(InvariantViolation::NanScore { rank });
+                }
+                match nodes.last() {
+                    Some(prev) => {
+                        if !self.zsl_node_lt(prev.as_ref(), node_ref.score, node_ref.val.as_ref().unwrap()) {
+                            return Err(InvariantViolation::Unordered { rank });
+                        }
+                        if node_ref.backward != Some(*prev) {
+                            return Err(InvariantViolation::Backward { rank });
+                        }
+                    }
+                    None => {
+                        if node_ref.backward.is_some() {
+                            return Err(InvariantViolation::Backward { rank });
+                        }
                     }
                 }
-                if let Some(next_node) = cur.level[i].forward {
-                    let next_node = next_node.as_ref();
-                    if (self.cmp)(next_node.val.as_ref().unwrap(), v) == Ordering::Equal {
-                        return true;
+                nodes.push(node);
+                next = node_ref.level[0].forward;
+            }
+
+            if nodes.len() != self.len {
+                return Err(InvariantViolation::Len {
+                    expected: nodes.len(),
+                    found: self.len,
+                });
+            }
+            if self.tail != nodes.last().copied().unwrap_or(self.header) {
+                return Err(InvariantViolation::Tail);
+            }
+            let max_level = nodes.iter().map(|node| node.as_ref().level()).max().unwrap_or(0);
+            if self.cur_level != max_level {
+                return Err(InvariantViolation::CurLevel {
+                    expected: max_level,
+                    found: self.cur_level,
+                });
+            }
+
+            // Each level links exactly the nodes tall enough, in level 0 order
+            for level in 0..self.cur_level {
+                let mut x = self.header;
+                let mut x_rank = 0;
+                let mut expected = nodes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, node)| node.as_ref().level() > level)
+                    .map(|(i, node)| (*node, i + 1));
+                loop {
+                    let link = &x.as_ref().level[level];
+                    let next = expected.next();
+                    if link.forward != next.map(|(node, _)| node) {
+                        return Err(InvariantViolation::Forward { level, rank: x_rank });
+                    }
+                    let next_rank = next.map_or(self.len, |(_, rank)| rank);
+                    if link.span != next_rank - x_rank {
+                        return Err(InvariantViolation::Span {
+                            level,
+                            rank: x_rank,
+                            expected: next_rank - x_rank,
+                            found: link.span,
+                        });
+                    }
+                    match next {
+                        Some((node, rank)) => {
+                            x = node;
+                            x_rank = rank;
+                        }
+                        None => break,
                     }
                 }
             }
         }
-        false
-    }
-
-    pub fn get_len(&self) -> usize {
-        return self.len;
+        Ok(())
     }
 }
 
@@ -128,96 +532,189 @@ impl<T: std::clone::Clone> ZSkipList<T> {
     pub fn zsl_get_element_by_rank(&self, rank: usize) -> Option<T> {
         // Get the element at the given rank in the skip list
         // Return None if the rank is out of range
-        let mut traversed : usize = 0;
         unsafe {
-            let mut cur: &ZSkipNode<T> = self.header.as_ref();
-            for i in (0..self.cur_level).rev() {
-                while let Some(forward) = cur.level[i].forward {
-                    if cur.level[i].span + traversed <= rank {
-                        
-                        traversed += cur.level[i].span;
-                        cur = forward.as_ref();
-                    } else {
-                        break;
-                    }
-                }
-                if traversed == rank {
-                    return cur.val.as_ref().cloned();
-                }
-            }
+            self.zsl_get_node_by_rank(rank)
+                .and_then(|node| node.as_ref().val.as_ref().cloned())
         }
-        return None;
     }
 
-    pub fn zsl_first_in_range(&self, min: f64, max: f64) -> Option<T> {
-        // Get the first element in the skip list within the given score range
-        // Return None if there are no elements in the range
-        unsafe {
-            let mut cur = self.header.as_ref();
+    /// Return random elements, as in ZRANDMEMBER with a count.
+    ///
+    /// A positive `count` returns up to `count` distinct elements. A negative one
+    /// returns exactly `-count` elements, possibly repeated. Scores are only
+    /// returned with `with_scores`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"value is out of range"` if `count` is `i64::MIN`, or below
+    /// `-i64::MAX / 2` with `with_scores`, since the reply would hold twice as many
+    /// items. Also returned for a negative `count` asking for more than
+    /// `ZRANDMEMBER_MAX_COUNT` elements.
+    pub fn random_members(&self, count: i64, with_scores: bool) -> Result<Vec<(T, Option<f64>)>, String> {
+        self.random_members_with_rng(count, with_scores, &mut thread_rng())
+    }
 
-            // Start from the highest level and move downwards
-            for i in (0..1).rev() {
-                // Traverse forward nodes at the current level
-                while let Some(forward) = cur.level[i].forward {
-                    let forward_node = forward.as_ref();
-                    if forward_node.score > max {
-                        // If the next node's score is greater than max, break out of the loop
-                        break;
-                    }
+    /// Like `random_members`, drawing from the given random number generator.
+    pub fn random_members_with_rng<R: Rng>(
+        &self,
+        count: i64,
+        with_scores: bool,
+        rng: &mut R,
+    ) -> Result<Vec<(T, Option<f64>)>, String> {
+        let ranks = zrandmember_ranks(self.len, count, with_scores, rng)?;
+        Ok(ranks
+            .into_iter()
+            .map(|rank| {
+                let (score, element) = self.range_by_rank(rank as i64, rank as i64).next().unwrap();
+                (element.clone(), if with_scores { Some(score) } else { None })
+            })
+            .collect())
+    }
 
-                    if forward_node.score >= min {
-                        // If the next node's score is within the range, return value
-                        return forward_node.val.as_ref().cloned();
-                    }
+    /// Find the first element that is contained in the specified score range.
+    /// Returns None when no element is contained in the range.
+    pub fn zsl_first_in_range(&self, range: &ZRangeSpec) -> Option<T> {
+        // If everything is out of range, return early.
+        if !self.zsl_is_in_range(range) {
+            return None;
+        }
+        unsafe {
+            // Go forward while *OUT* of range.
+            let (x, _) = self.zsl_last_where(|node| !range.value_gte_min(node.score));
+            // This is an inner range, so the next node cannot be None.
+            let x = x.as_ref().level[0].forward?.as_ref();
+            // Check if score <= max.
+            if !range.value_lte_max(x.score) {
+                return None;
+            }
+            x.val.as_ref().cloned()
+        }
+    }
 
-                    // Move to the next node
-                    cur = forward_node;
-                }
+    /// Find the last element that is contained in the specified score range.
+    /// Returns None when no element is contained in the range.
+    pub fn zsl_last_in_range(&self, range: &ZRangeSpec) -> Option<T> {
+        // If everything is out of range, return early.
+        if !self.zsl_is_in_range(range) {
+            return None;
+        }
+        unsafe {
+            // Go forward while *IN* range.
+            let (x, _) = self.zsl_last_where(|node| range.value_lte_max(node.score));
+            let x = x.as_ref();
+            // Check if score >= min.
+            if x.is_head() || !range.value_gte_min(x.score) {
+                return None;
             }
+            x.val.as_ref().cloned()
         }
-        None
     }
 
-    pub fn zsl_last_in_range(&self, min: f64, max: f64) -> Option<T> {
-        // Get the last element in the skip list within the given score range
-        // Return None if there are no elements in the range
+    /// Find the `n`th element in the specified score range, 0-based.
+    ///
+    /// A negative `n` counts from the end of the range, `-1` being the last element.
+    /// Both ends of the range and the target are located by descending the
+    /// levels, so this is O(log N) whatever `n` is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
+    /// use curly_giggle::collection::skiplist::zrangespec::ZRangeSpec;
+    ///
+    /// let mut skip_list = ZSkipList::zsl_create();
+    /// for i in 0..10 {
+    ///     skip_list.zsl_insert(i as f64, i);
+    /// }
+    /// let range = ZRangeSpec::parse("(2", "7").unwrap();
+    /// assert_eq!(skip_list.zsl_nth_in_range(&range, 0), Some(3));
+    /// assert_eq!(skip_list.zsl_nth_in_range(&range, -1), Some(7));
+    /// assert_eq!(skip_list.zsl_nth_in_range(&range, 5), None);
+    /// ```
+    pub fn zsl_nth_in_range(&self, range: &ZRangeSpec, n: i64) -> Option<T> {
+        if !self.zsl_is_in_range(range) {
+            return None;
+        }
+        let (before, last) = unsafe {
+            let (_, before) = self.zsl_last_where(|node| !range.value_gte_min(node.score));
+            let (_, last) = self.zsl_last_where(|node| range.value_lte_max(node.score));
+            (before, last)
+        };
+        let count = last.saturating_sub(before);
+        let offset = if n >= 0 {
+            n as usize
+        } else {
+            count.checked_sub(n.unsigned_abs() as usize)?
+        };
+        if offset >= count {
+            return None;
+        }
+        self.zsl_get_element_by_rank(before + offset + 1)
+    }
+
+    /// Find the first element in the specified lex range.
+    ///
+    /// Lex ranges only make sense when all the elements share the same score.
+    pub fn zsl_first_in_lex_range(&self, range: &ZLexRangeSpec<T>) -> Option<T> {
+        if !self.zsl_is_in_lex_range(range) {
+            return None;
+        }
         unsafe {
-            let mut cur = self.header.as_ref();
-    
-            // Start from the highest level and move downwards
-            for i in (0..1).rev() {
-                // Traverse forward nodes at the current level
-                while let Some(forward) = cur.level[i].forward {
-                    let forward_node = forward.as_ref();
-                    
-                    if forward_node.score > max {
-                        // If the next node's score is greater than max, break out of the loop
-                        break;
-                    }
-                    cur = forward_node;
-                }
+            // Go forward while *OUT* of range.
+            let (x, _) = self.zsl_last_where(|node| !self.zsl_lex_value_gte_min(node.val.as_ref().unwrap(), &range.min));
+            // This is an inner range, so the next node cannot be None.
+            let x = x.as_ref().level[0].forward?.as_ref();
+            if !self.zsl_lex_value_lte_max(x.val.as_ref().unwrap(), &range.max) {
+                return None;
             }
-    
-            // Check if the current node is within the range
-            if cur.score >= min && cur.score <= max {
-                return cur.val.as_ref().cloned();
+            x.val.as_ref().cloned()
+        }
+    }
+
+    /// Find the last element in the specified lex range.
+    pub fn zsl_last_in_lex_range(&self, range: &ZLexRangeSpec<T>) -> Option<T> {
+        if !self.zsl_is_in_lex_range(range) {
+            return None;
+        }
+        unsafe {
+            // Go forward while *IN* range.
+            let (x, _) = self.zsl_last_where(|node| self.zsl_lex_value_lte_max(node.val.as_ref().unwrap(), &range.max));
+            let x = x.as_ref();
+            if x.is_head() || !self.zsl_lex_value_gte_min(x.val.as_ref().unwrap(), &range.min) {
+                return None;
             }
+            x.val.as_ref().cloned()
         }
-        None
     }
 }
 
 #[allow(dead_code, unused_variables)]
 impl<T> ZSkipList<T> {
 
+    /// Free every node and leave the list empty. The header is kept so the list
+    /// can be reused.
     pub fn zsl_free(&mut self) {
-        // TODO: Implementation of zsl_free here
-        // Free any allocated memory and clean up resources
+        // Walk level 0 only, spans are reset below anyway
+        unsafe {
+            let mut node = self.header.as_ref().level[0].forward;
+            while let Some(x) = node {
+                let boxed_node = Box::from_raw(x.as_ptr());
+                node = boxed_node.level[0].forward;
+            }
+            for level in self.header.as_mut().level.iter_mut() {
+                level.forward = None;
+                level.span = 0;
+            }
+        }
+        self.tail = self.header;
+        self.cur_level = 0;
+        self.len = 0;
     }
 
     /// Insert the element with the given score into the skip list.
     ///
-    /// If the element already exists in the skip list, it will not be inserted again.
+    /// The caller must make sure the element is not in the list yet, as with
+    /// `zslInsert`: an element inserted twice gets two nodes.
     ///
     /// # Arguments
     /// 
@@ -233,19 +730,29 @@ impl<T> ZSkipList<T> {
     /// skip_list.zsl_insert(1.0, 42);
     /// ```
     pub fn zsl_insert(&mut self, score: f64, element: T) -> NonNull<ZSkipNode<T>> {
+        // Never trust the generator to stay within the header
+        let level = unsafe { self.level_generator.random().clamp(1, self.header.as_ref().level()) };
+        let new_node = Box::new(ZSkipNode::new(element, level, score));
+        let new_node_ptr = NonNull::new(Box::into_raw(new_node)).unwrap();
+        unsafe { self.zsl_insert_node(new_node_ptr) };
+        new_node_ptr
+    }
+
+    /// Link an unlinked node into the list at the position given by its score and
+    /// element. The node keeps its own level, so a detached node can be reused.
+    unsafe fn zsl_insert_node(&mut self, mut new_node_ptr: NonNull<ZSkipNode<T>>) {
         let mut update = vec![self.header; ZSKIPLIST_MAXLEVEL];
         let mut rank: Vec<usize> = vec![0; ZSKIPLIST_MAXLEVEL];
+        let score = new_node_ptr.as_ref().score;
+        let element = new_node_ptr.as_ref().val.as_ref().unwrap();
 
-        let mut x = unsafe { self.header.as_ref() };
+        let mut x = self.header.as_ref();
         for i in (0..self.cur_level).rev() {
             rank[i] = if i == self.cur_level - 1 { 0 } else { rank[i + 1] };
             while let Some(forward) = x.level[i].forward {
-                let forward_node: &ZSkipNode<T> = unsafe {forward.as_ref()};
+                let forward_node: &ZSkipNode<T> = forward.as_ref();
                 // Check score and value
-                if forward_node.score < score 
-                    || (forward_node.score == score 
-                    && (self.cmp)(forward_node.val.as_ref().unwrap(), &element) == Ordering::Less) 
-                {
+                if self.zsl_node_lt(forward_node, score, element) {
                     rank[i] += x.level[i].span;
                     x = forward_node;
                 } else {
@@ -255,51 +762,116 @@ impl<T> ZSkipList<T> {
             update[i] = NonNull::from(x);
         }
 
-        let level = self.level_generator.random();
+        let level = new_node_ptr.as_ref().level();
 
         // initialize the level of the new node
         if level > self.cur_level {
             for i in self.cur_level..level {
                 rank[i] = 0;
                 update[i] = self.header;
-                unsafe { self.header.as_mut().level[i].span = self.len };
+                self.header.as_mut().level[i].span = self.len;
             }
             self.cur_level = level;
         }
 
-        let new_node = Box::new(ZSkipNode::new(element, level, score));
-        let mut new_node_ptr = NonNull::new(Box::into_raw(new_node)).unwrap();
-
         // Update the forward pointers
-        unsafe {
-            for i in 0..level {
-            
-                let update_node =  update[i].as_mut();
+        for i in 0..level {
+            let update_node = update[i].as_mut();
 
-                new_node_ptr.as_mut().level[i].forward = update_node.level[i].forward;
-                update_node.level[i].forward = Some(new_node_ptr);
+            new_node_ptr.as_mut().level[i].forward = update_node.level[i].forward;
+            update_node.level[i].forward = Some(new_node_ptr);
 
-                new_node_ptr.as_mut().level[i].span = update_node.level[i].span - (rank[0] - rank[i]);
-                update_node.level[i].span = (rank[0] - rank[i]) + 1;
-            }
+            new_node_ptr.as_mut().level[i].span = update_node.level[i].span - (rank[0] - rank[i]);
+            update_node.level[i].span = (rank[0] - rank[i]) + 1;
         }
 
-        for i in level..self.cur_level {
-            unsafe { update[i].as_mut().level[i].span += 1 };
+        // Increment span for untouched levels
+        for (i, update_node) in update.iter_mut().enumerate().take(self.cur_level).skip(level) {
+            update_node.as_mut().level[i].span += 1;
         }
 
-        unsafe {
-            new_node_ptr.as_mut().backward = if update[0] == self.header { None } else { Some(update[0]) };
-            if let Some(mut forward) = new_node_ptr.as_mut().level[0].forward {
-                forward.as_mut().backward = Some(new_node_ptr);
-            } else {
-                self.tail = new_node_ptr;
-            }
+        new_node_ptr.as_mut().backward = if update[0] == self.header { None } else { Some(update[0]) };
+        if let Some(mut forward) = new_node_ptr.as_mut().level[0].forward {
+            forward.as_mut().backward = Some(new_node_ptr);
+        } else {
+            self.tail = new_node_ptr;
         }
 
         self.len += 1;
+    }
 
-        new_node_ptr
+    /// Update the score of an element inside the list, like `zslUpdateScore`.
+    ///
+    /// If the node would stay between the same neighbors, only its score is changed.
+    /// Otherwise it is unlinked and linked again at its new position, reusing the
+    /// same allocation. Returns the updated node, or `None` if `element` is not in
+    /// the list with `cur_score`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
+    ///
+    /// let mut skip_list = ZSkipList::zsl_create();
+    /// skip_list.zsl_insert(1.0, "a");
+    /// skip_list.zsl_insert(2.0, "b");
+    /// skip_list.zsl_update_score(1.0, &"a", 3.0);
+    /// assert_eq!(skip_list.zsl_get_element_by_rank(2), Some("a"));
+    /// ```
+    pub fn zsl_update_score(&mut self, cur_score: f64, element: &T, new_score: f64) -> Option<NonNull<ZSkipNode<T>>> {
+        let mut update = vec![self.header; ZSKIPLIST_MAXLEVEL];
+
+        unsafe {
+            // We need to seek to element to update to start: this is useful anyway,
+            // we'll have to update or remove it.
+            let mut x = self.header;
+            for i in (0..self.cur_level).rev() {
+                while let Some(forward) = x.as_ref().level[i].forward {
+                    if self.zsl_node_lt(forward.as_ref(), cur_score, element) {
+                        x = forward;
+                    } else {
+                        break;
+                    }
+                }
+                update[i] = x;
+            }
+
+            // Jump to our element.
+            let mut x = x.as_ref().level[0].forward?;
+            let x_ref = x.as_ref();
+            if x_ref.score != cur_score || (self.cmp)(x_ref.val.as_ref().unwrap(), element) != Ordering::Equal {
+                return None;
+            }
+
+            // If the node, after the score update, would be still exactly
+            // at the same position, we can just update the score without
+            // actually removing and re-inserting the element in the skiplist.
+            let after_backward = match x_ref.backward {
+                None => true,
+                Some(backward) => self.zsl_node_lt(backward.as_ref(), new_score, element),
+            };
+            let before_forward = match x_ref.level[0].forward {
+                None => true,
+                Some(forward) => {
+                    let forward = forward.as_ref();
+                    new_score < forward.score
+                        || (new_score == forward.score
+                            && (self.cmp)(element, forward.val.as_ref().unwrap()) == Ordering::Less)
+                }
+            };
+            if after_backward && before_forward {
+                x.as_mut().score = new_score;
+                return Some(x);
+            }
+
+            // No way to keep the node in place: unlink it and link it again
+            // at its new position, reusing the allocation.
+            self.zsl_delete_node(x, &update);
+            Self::zsl_clear_links(x);
+            x.as_mut().score = new_score;
+            self.zsl_insert_node(x);
+            Some(x)
+        }
     }
 
     /// Delete the element with the given score from the skip list.
@@ -309,6 +881,7 @@ impl<T> ZSkipList<T> {
     ///
     /// # Arguments
     ///
+    /// * `score` - The score the element was inserted with.
     /// * `element` - The element to be deleted from the skip list.
     ///
     /// # Examples
@@ -318,64 +891,105 @@ impl<T> ZSkipList<T> {
     ///
     /// let mut skip_list = ZSkipList::zsl_create();
     /// skip_list.zsl_insert(1.0, 42);
-    /// assert_eq!(skip_list.zsl_delete(&42), Some(42));
-    /// assert_eq!(skip_list.zsl_delete(&42), None);
+    /// assert_eq!(skip_list.zsl_delete(1.0, &42), Some(42));
+    /// assert_eq!(skip_list.zsl_delete(1.0, &42), None);
     /// ```
-    pub fn zsl_delete(&mut self, element: &T) -> Option<T>{
-        if !self.contains(&element) {
-            return None;
-        }
+    pub fn zsl_delete(&mut self, score: f64, element: &T) -> Option<T> {
+        self.zsl_delete_detach(score, element).and_then(|node| node.into_val())
+    }
 
-        let mut cur = unsafe { self.header.as_mut() };
-        // TODO: level
-        let max_level = self.cur_level;
-        let mut update: Vec<Option<*mut ZSkipNode<T>>> = vec![None; max_level + 1];
-        let res_val;
+    /// Delete the element with the given score and hand back its node instead of freeing it.
+    ///
+    /// The returned node is unlinked: its forward and backward links are cleared, so
+    /// it can be reused for another insertion.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
+    ///
+    /// let mut skip_list = ZSkipList::zsl_create();
+    /// skip_list.zsl_insert(1.0, 42);
+    /// let node = skip_list.zsl_delete_detach(1.0, &42).unwrap();
+    /// assert_eq!(node.score, 1.0);
+    /// assert_eq!(node.into_val(), Some(42));
+    /// ```
+    pub fn zsl_delete_detach(&mut self, score: f64, element: &T) -> Option<Box<ZSkipNode<T>>> {
+        let mut update = vec![self.header; ZSKIPLIST_MAXLEVEL];
+
+        let mut x = self.header;
         unsafe {
-            for i in (0..=max_level).rev() {
-                while let Some(mut next_node) = cur.level[i].forward {
-                    let next_node = next_node.as_mut();
-                    if (self.cmp)(next_node.val.as_ref().unwrap(), &element) == Ordering::Less {
-                        cur = next_node;
+            for i in (0..self.cur_level).rev() {
+                while let Some(forward) = x.as_ref().level[i].forward {
+                    if self.zsl_node_lt(forward.as_ref(), score, element) {
+                        x = forward;
                     } else {
                         break;
                     }
                 }
-                update[i] = Some(cur as *mut ZSkipNode<T>);
-            }
-
-            let mut ret_val_ref = None;
-            if cur.level[0].forward.is_some()
-                && (self.cmp)(cur.level[0].forward.unwrap().as_mut().val.as_ref().unwrap(), &element)
-                    == Ordering::Equal
-            {
-                ret_val_ref = cur.level[0].forward;
-                for i in (0..=max_level).rev() {
-                    if update[i].is_some()
-                        && (*update[i].unwrap()).level[i].forward.is_some()
-                        && (self.cmp)(
-                            (*update[i].unwrap()).level[i].forward
-                                .unwrap()
-                                .as_mut()
-                                .val
-                                .as_ref()
-                                .unwrap(),
-                            &element,
-                        ) == Ordering::Equal
-                    {
-                        (*update[i].unwrap()).level[i].forward =                            (*update[i].unwrap()).level[i].forward.unwrap().as_mut().level[i].forward;
-                    }
-                }
+                update[i] = x;
             }
-            res_val = match ret_val_ref {
-                None => None,
-                Some(ret) => Box::from_raw(ret.as_ptr()).into_val(),
+
+            // We may have multiple elements with the same score, what we need
+            // is to find the element with both the right score and object.
+            let x = x.as_ref().level[0].forward?;
+            let x_ref = x.as_ref();
+            if x_ref.score != score || (self.cmp)(x_ref.val.as_ref().unwrap(), element) != Ordering::Equal {
+                return None;
+            }
+            self.zsl_delete_node(x, &update);
+            Some(Self::zsl_detach_node(x))
+        }
+    }
+
+    /// Internal function used by `zsl_delete`, `zsl_delete_range_by_*` and `pop_front`.
+    ///
+    /// `update[i]` must be the last node before `x` on level `i`.
+    unsafe fn zsl_delete_node(&mut self, x: NonNull<ZSkipNode<T>>, update: &[NonNull<ZSkipNode<T>>]) {
+        let x_ref = x.as_ref();
+        for (i, update_node) in update.iter().enumerate().take(self.cur_level) {
+            let update_node = &mut *update_node.as_ptr();
+            if update_node.level[i].forward == Some(x) {
+                update_node.level[i].span += x_ref.level[i].span;
+                update_node.level[i].span -= 1;
+                update_node.level[i].forward = x_ref.level[i].forward;
+            } else {
+                update_node.level[i].span -= 1;
             }
         }
 
+        if let Some(mut forward) = x_ref.level[0].forward {
+            forward.as_mut().backward = x_ref.backward;
+        } else {
+            self.tail = x_ref.backward.unwrap_or(self.header);
+        }
+
+        while self.cur_level > 0 && self.header.as_ref().level[self.cur_level - 1].forward.is_none() {
+            self.cur_level -= 1;
+        }
         self.len -= 1;
+    }
 
-        res_val
+    /// Take ownership of a node that was unlinked by `zsl_delete_node`.
+    unsafe fn zsl_detach_node(x: NonNull<ZSkipNode<T>>) -> Box<ZSkipNode<T>> {
+        Self::zsl_clear_links(x);
+        Box::from_raw(x.as_ptr())
+    }
+
+    /// Reset the links of a node that was unlinked by `zsl_delete_node`.
+    unsafe fn zsl_clear_links(mut x: NonNull<ZSkipNode<T>>) {
+        let node = x.as_mut();
+        node.backward = None;
+        for level in node.level.iter_mut() {
+            level.forward = None;
+            level.span = 0;
+        }
+    }
+
+    /// Whether `node` sorts before `(score, element)`: lower score, or same score and lower element.
+    fn zsl_node_lt(&self, node: &ZSkipNode<T>, score: f64, element: &T) -> bool {
+        node.score < score
+            || (node.score == score && (self.cmp)(node.val.as_ref().unwrap(), element) == Ordering::Less)
     }
 
     pub fn zsl_get_rank(&self, score: f64, element: T) -> Option<i128> {
@@ -400,184 +1014,415 @@ impl<T> ZSkipList<T> {
 
                 if let Some(next_node) = cur.level[i].forward {
                     let next_node = next_node.as_ref();
-                    if !cur.is_head() && (self.cmp)(next_node.val.as_ref().unwrap(), &element) == Ordering::Equal {
-                        return rank;
-                    } else if i == 0 && (self.cmp)(next_node.val.as_ref().unwrap(), &element) == Ordering::Equal {
-                        return Some(0);
+                    if next_node.score == score
+                        && (self.cmp)(next_node.val.as_ref().unwrap(), &element) == Ordering::Equal
+                    {
+                        // Ranks are 0-based, the span already counts the element itself
+                        return rank.map(|r| r + cur.level[i].span as i128 - 1);
                     }
                 }
             }
         }
-        return None;
+        None
+    }
+
+    /// Returns true if the score range overlaps the list, from its first and last
+    /// element only. A range falling in a gap between two elements still returns true.
+    pub fn zsl_is_in_range(&self, range: &ZRangeSpec) -> bool {
+        // Test for ranges that will always be empty.
+        if range.is_empty() || self.len == 0 {
+            return false;
+        }
+        unsafe {
+            let last = self.tail.as_ref();
+            if !range.value_gte_min(last.score) {
+                return false;
+            }
+            let first = self.header.as_ref().level[0].forward.unwrap().as_ref();
+            if !range.value_lte_max(first.score) {
+                return false;
+            }
+        }
+        true
     }
 
-    pub fn zsl_is_in_range(&self, min: f64, max: f64) -> bool {
-        // Check if there are any elements in the skip list within the given score range
+    /// Returns the number of elements with score in the range, in O(log N).
+    pub fn zsl_count_in_range(&self, range: &ZRangeSpec) -> usize {
+        if !self.zsl_is_in_range(range) {
+            return 0;
+        }
         unsafe {
-            let mut cur = self.header.as_ref();
+            let (_, before) = self.zsl_last_where(|node| !range.value_gte_min(node.score));
+            let (_, last) = self.zsl_last_where(|node| range.value_lte_max(node.score));
+            last.saturating_sub(before)
+        }
+    }
 
-            // Start from the highest level and move downwards
-            for i in (0..self.cur_level).rev() {
-                // Traverse forward nodes at the current level
-                while let Some(forward) = cur.level[i].forward {
-                    let forward_node = forward.as_ref();
+    /// Returns true if the lex range overlaps the list, see `zsl_is_in_range`.
+    pub fn zsl_is_in_lex_range(&self, range: &ZLexRangeSpec<T>) -> bool {
+        if self.zsl_lex_range_is_empty(range) || self.len == 0 {
+            return false;
+        }
+        unsafe {
+            let last = self.tail.as_ref();
+            if !self.zsl_lex_value_gte_min(last.val.as_ref().unwrap(), &range.min) {
+                return false;
+            }
+            let first = self.header.as_ref().level[0].forward.unwrap().as_ref();
+            if !self.zsl_lex_value_lte_max(first.val.as_ref().unwrap(), &range.max) {
+                return false;
+            }
+        }
+        true
+    }
 
-                    if forward_node.score > max {
-                        // If the next node's score is greater than max, break out of the loop
-                        break;
-                    }
+    /// Returns the number of elements in the lex range, in O(log N).
+    pub fn zsl_count_in_lex_range(&self, range: &ZLexRangeSpec<T>) -> usize {
+        if !self.zsl_is_in_lex_range(range) {
+            return 0;
+        }
+        unsafe {
+            let (_, before) = self.zsl_last_where(|node| !self.zsl_lex_value_gte_min(node.val.as_ref().unwrap(), &range.min));
+            let (_, last) = self.zsl_last_where(|node| self.zsl_lex_value_lte_max(node.val.as_ref().unwrap(), &range.max));
+            last.saturating_sub(before)
+        }
+    }
 
-                    if forward_node.score >= min {
-                        // If the next node's score is within the range, return true
-                        return true;
-                    }
+    pub fn zsl_lex_value_gte_min(&self, value: &T, min: &LexBound<T>) -> bool {
+        match min {
+            LexBound::NegInf => true,
+            LexBound::PosInf => false,
+            LexBound::Inclusive(min) => (self.cmp)(value, min) != Ordering::Less,
+            LexBound::Exclusive(min) => (self.cmp)(value, min) == Ordering::Greater,
+        }
+    }
 
-                    // Move to the next node
-                    cur = forward_node;
+    pub fn zsl_lex_value_lte_max(&self, value: &T, max: &LexBound<T>) -> bool {
+        match max {
+            LexBound::NegInf => false,
+            LexBound::PosInf => true,
+            LexBound::Inclusive(max) => (self.cmp)(value, max) != Ordering::Greater,
+            LexBound::Exclusive(max) => (self.cmp)(value, max) == Ordering::Less,
+        }
+    }
+
+    fn zsl_lex_range_is_empty(&self, range: &ZLexRangeSpec<T>) -> bool {
+        let (min, minex, max, maxex) = match (&range.min, &range.max) {
+            (LexBound::PosInf, _) | (_, LexBound::NegInf) => return true,
+            (LexBound::NegInf, _) | (_, LexBound::PosInf) => return false,
+            (LexBound::Inclusive(min), LexBound::Inclusive(max)) => (min, false, max, false),
+            (LexBound::Inclusive(min), LexBound::Exclusive(max)) => (min, false, max, true),
+            (LexBound::Exclusive(min), LexBound::Inclusive(max)) => (min, true, max, false),
+            (LexBound::Exclusive(min), LexBound::Exclusive(max)) => (min, true, max, true),
+        };
+        match (self.cmp)(min, max) {
+            Ordering::Greater => true,
+            Ordering::Equal => minex || maxex,
+            Ordering::Less => false,
+        }
+    }
+
+    /// Descend from the top level, moving forward while `before` holds for the
+    /// next node. Returns the last node visited and its rank (0 for the header).
+    ///
+    /// `before` must hold for a prefix of the list and fail for the rest of it.
+    unsafe fn zsl_last_where<F>(&self, mut before: F) -> (NonNull<ZSkipNode<T>>, usize)
+    where
+        F: FnMut(&ZSkipNode<T>) -> bool,
+    {
+        let mut x = self.header;
+        let mut rank = 0;
+        for i in (0..self.cur_level).rev() {
+            while let Some(forward) = x.as_ref().level[i].forward {
+                if !before(forward.as_ref()) {
+                    break;
                 }
+                rank += x.as_ref().level[i].span;
+                x = forward;
             }
         }
+        (x, rank)
+    }
 
-        false
+    /// Delete all the elements with score in the given range, freeing their nodes.
+    ///
+    /// Returns the number of elements deleted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
+    /// use curly_giggle::collection::skiplist::zrangespec::ZRangeSpec;
+    ///
+    /// let mut skip_list = ZSkipList::zsl_create();
+    /// for i in 0..10 {
+    ///     skip_list.zsl_insert(i as f64, i);
+    /// }
+    /// let range = ZRangeSpec { min: 2.0, max: 5.0, minex: true, maxex: false };
+    /// assert_eq!(skip_list.zsl_delete_range_by_score(&range), 3);
+    /// ```
+    pub fn zsl_delete_range_by_score(&mut self, range: &ZRangeSpec) -> usize {
+        self.zsl_delete_range_by_score_with(range, |_, _| {})
     }
 
-    pub fn zsl_delete_range_by_score(&mut self, min: f64, max: f64) -> usize {
-        // Delete all elements in the skip list within the given score range
-        // Return the number of elements deleted
-        let mut removed_count = 0;
-        unsafe {
-            let mut update = vec![self.header; self.cur_level];
+    /// Delete all the elements with score in the given range, handing each removed
+    /// `(score, element)` to `on_delete`, e.g. so an owning dict can drop its entry.
+    pub fn zsl_delete_range_by_score_with<F>(&mut self, range: &ZRangeSpec, mut on_delete: F) -> usize
+    where
+        F: FnMut(f64, T),
+    {
+        let mut update = vec![self.header; ZSKIPLIST_MAXLEVEL];
+        let mut removed = 0;
 
+        let mut x = self.header;
+        unsafe {
             for i in (0..self.cur_level).rev() {
-                let mut cur = self.header.as_ref();
-                while let Some(forward) = cur.level[i].forward {
-                    let forward_node = forward.as_ref();
-                    if forward_node.score >= min {
-                        update[i] = NonNull::from(cur);
+                while let Some(forward) = x.as_ref().level[i].forward {
+                    if range.value_gte_min(forward.as_ref().score) {
                         break;
                     }
-                    if forward_node.score > max {
-                        break;
-                    }
-                    cur = forward_node;
+                    x = forward;
                 }
+                update[i] = x;
             }
 
-            for i in 0..self.cur_level {
-                let mut cur = update[i].as_mut();
-                let base_update_node = update[i].as_mut();
-                while let Some(mut forward) = cur.level[i].forward {
-                    let forward_node = forward.as_mut();
+            // Current node is the last with score < or <= min.
+            let mut next = x.as_ref().level[0].forward;
 
-                    if forward_node.score > max || forward_node.score < min {
-                        break;
-                    }
-                    // Update
-                    // TODO: Optimization
-                    base_update_node.level[i].forward = forward_node.level[i].forward;
-                    forward_node.backward = Some(base_update_node.into());
-                    base_update_node.level[i].span += forward_node.level[i].span;
-
-                    if i == 0 {
-                        removed_count += 1;
-                        if forward_node.level[i].span == 0 {
-                            self.tail = update[i];
-                        }
-                    }
-                    cur = forward_node;
-                    // TODO: free forward_node?
+            // Delete nodes while in range.
+            while let Some(node) = next {
+                if !range.value_lte_max(node.as_ref().score) {
+                    break;
                 }
-                base_update_node.level[i].span -= removed_count;
+                next = node.as_ref().level[0].forward;
+                self.zsl_delete_node(node, &update);
+                let node = Self::zsl_detach_node(node);
+                on_delete(node.score, node.into_item());
+                removed += 1;
             }
         }
-    
-        self.len -= removed_count;
-        removed_count
+        removed
     }
-    
 
+    /// Delete all the elements with rank between `start` and `end`, both inclusive.
+    ///
+    /// Ranks are 1-based, like `zsl_get_element_by_rank`. Returns the number of
+    /// elements deleted.
     pub fn zsl_delete_range_by_rank(&mut self, start: usize, end: usize) -> usize {
-        // Delete all elements in the skip list within the given rank range
-        // Return the number of elements deleted
-        let mut removed_count = 0;
+        self.zsl_delete_range_by_rank_with(start, end, |_, _| {})
+    }
+
+    /// Like `zsl_delete_range_by_rank`, handing each removed `(score, element)` to `on_delete`.
+    pub fn zsl_delete_range_by_rank_with<F>(&mut self, start: usize, end: usize, mut on_delete: F) -> usize
+    where
+        F: FnMut(f64, T),
+    {
+        let mut update = vec![self.header; ZSKIPLIST_MAXLEVEL];
+        let mut traversed: usize = 0;
+        let mut removed = 0;
+
+        let mut x = self.header;
         unsafe {
-            let mut update = vec![self.header; self.cur_level];
-            let mut rank = vec![0; self.cur_level];
-    
-            // Find update points and starting node
             for i in (0..self.cur_level).rev() {
-                let mut traversed: usize = 0;
-                // TODO: Optimization
-                let mut cur = self.header.as_ref();
-                while let Some(forward) = cur.level[i].forward {
-                    let forward_node = forward.as_ref();
-                    let predict_span = traversed + forward_node.level[i].span;
-                    if predict_span >= start {
-                        update[i] = NonNull::from(cur);
+                while let Some(forward) = x.as_ref().level[i].forward {
+                    if traversed + x.as_ref().level[i].span >= start {
                         break;
                     }
-                    if predict_span > end {
-                        break;
-                    }
-                    update[i] = NonNull::from(cur);
-                    traversed += forward_node.level[i].span;
-                    cur = forward_node;
+                    traversed += x.as_ref().level[i].span;
+                    x = forward;
+                }
+                update[i] = x;
+            }
+
+            traversed += 1;
+            let mut next = x.as_ref().level[0].forward;
+            while let Some(node) = next {
+                if traversed > end {
+                    break;
                 }
-                
-                rank[i] = traversed;
+                next = node.as_ref().level[0].forward;
+                self.zsl_delete_node(node, &update);
+                let node = Self::zsl_detach_node(node);
+                on_delete(node.score, node.into_item());
+                removed += 1;
+                traversed += 1;
             }
+        }
+        removed
+    }
 
-            for i in 0..1 {
-                let mut cur = update[i].as_mut();
-                let base_update_node = update[i].as_mut();
-                while let Some(mut forward) = cur.level[i].forward {
-                    let forward_node = forward.as_mut();
-                    let predict_span = rank[i] + base_update_node.level[i].span;
-                    if predict_span > end || predict_span < start {
+    /// Delete all the elements in the lex range, freeing their nodes.
+    ///
+    /// Returns the number of elements deleted.
+    pub fn zsl_delete_range_by_lex(&mut self, range: &ZLexRangeSpec<T>) -> usize {
+        self.zsl_delete_range_by_lex_with(range, |_, _| {})
+    }
+
+    /// Like `zsl_delete_range_by_lex`, handing each removed `(score, element)` to `on_delete`.
+    pub fn zsl_delete_range_by_lex_with<F>(&mut self, range: &ZLexRangeSpec<T>, mut on_delete: F) -> usize
+    where
+        F: FnMut(f64, T),
+    {
+        let mut update = vec![self.header; ZSKIPLIST_MAXLEVEL];
+        let mut removed = 0;
+
+        let mut x = self.header;
+        unsafe {
+            for i in (0..self.cur_level).rev() {
+                while let Some(forward) = x.as_ref().level[i].forward {
+                    if self.zsl_lex_value_gte_min(forward.as_ref().val.as_ref().unwrap(), &range.min) {
                         break;
                     }
-                    base_update_node.level[i].forward = forward_node.level[i].forward;
-                    forward_node.backward = Some(base_update_node.into());
-                    base_update_node.level[i].span += forward_node.level[i].span;
-                    if i == 0 {
-                        removed_count += 1;
-                        if forward_node.level[i].span == 0 {
-                            self.tail = update[i];
-                        }
-                    }
-                    cur = forward_node;
+                    x = forward;
                 }
-                base_update_node.level[i].span -= removed_count;
+                update[i] = x;
+            }
+
+            // Current node is the last with value < or <= min.
+            let mut next = x.as_ref().level[0].forward;
+
+            // Delete nodes while in range.
+            while let Some(node) = next {
+                if !self.zsl_lex_value_lte_max(node.as_ref().val.as_ref().unwrap(), &range.max) {
+                    break;
+                }
+                next = node.as_ref().level[0].forward;
+                self.zsl_delete_node(node, &update);
+                let node = Self::zsl_detach_node(node);
+                on_delete(node.score, node.into_item());
+                removed += 1;
             }
         }
-    
-        self.len -= removed_count;
-        removed_count
+        removed
     }
 }
 
-impl<T> Drop for ZSkipList<T> {
-    fn drop(&mut self) {
-        struct DropGuard<'a, T>(&'a mut ZSkipList<T>);
+/// Pick the 0-based ranks ZRANDMEMBER returns from a set of `size` elements,
+/// following the Redis sampling strategies. Shared by every sorted set encoding,
+/// and by SRANDMEMBER, SPOP and HRANDFIELD which sample the same way.
+pub(crate) fn zrandmember_ranks<R: Rng>(size: usize, count: i64, with_scores: bool, rng: &mut R) -> Result<Vec<usize>, String> {
+    zrandmember_check_count(count, with_scores)?;
 
-        impl<'a, T> Drop for DropGuard<'a, T> {
-            fn drop(&mut self) {
-                // Continue the same loop we do below. This only runs when a destructor has
-                // panicked. If another one panics this will abort.
-                while self.0.pop_front().is_some() {}
-            }
+    // If count is zero, serve it ASAP to avoid special cases later.
+    if count == 0 || size == 0 {
+        return Ok(Vec::new());
+    }
+
+    // CASE 1: The count was negative, so the extraction method is just:
+    // "return N random elements" sampling the whole set every time.
+    // This case is trivial and can be served without auxiliary data
+    // structures.
+    if count < 0 {
+        return Ok((0..count.unsigned_abs()).map(|_| rng.gen_range(0..size)).collect());
+    }
+    let count = count as usize;
+
+    // CASE 2: The number of requested elements is greater than the number
+    // of elements inside the zset: simply return the whole zset.
+    if count >= size {
+        return Ok((0..size).collect());
+    }
+
+    // CASE 3: The number of elements inside the zset is not greater than
+    // ZRANDMEMBER_SUB_STRATEGY_MUL times the number of requested elements.
+    // In this case we create a copy of the ranks, and subtract random
+    // elements to reach the requested number of elements.
+    //
+    // This is done because if the number of requested elements is just
+    // a bit less than the number of elements in the set, the natural approach
+    // used into CASE 4 is highly inefficient.
+    if count * ZRANDMEMBER_SUB_STRATEGY_MUL > size {
+        let mut ranks: Vec<usize> = (0..size).collect();
+        while ranks.len() > count {
+            ranks.swap_remove(rng.gen_range(0..ranks.len()));
         }
+        return Ok(ranks);
+    }
+
+    // CASE 4: We have a big zset compared to the requested number of elements.
+    // In this case we can simply get random elements from the zset and add
+    // to the temporary set, trying to eventually get enough unique elements
+    // to reach the specified count.
+    let mut seen = HashSet::with_capacity(count);
+    let mut ranks = Vec::with_capacity(count);
+    while ranks.len() < count {
+        let rank = rng.gen_range(0..size);
+        if seen.insert(rank) {
+            ranks.push(rank);
+        }
+    }
+    Ok(ranks)
+}
+
+// Refuses the counts ZRANDMEMBER replies "value is out of range" to
+fn zrandmember_check_count(count: i64, with_scores: bool) -> Result<(), String> {
+    if count == i64::MIN
+        || (with_scores && count < -(i64::MAX / 2))
+        || (count < 0 && count.unsigned_abs() > ZRANDMEMBER_MAX_COUNT)
+    {
+        return Err("value is out of range".to_string());
+    }
+    Ok(())
+}
+
+/// Like `zrandmember_ranks`, for hash tables which cannot be indexed by rank:
+/// `random` picks one of the `size` elements at random, and `all` lists every one
+/// of them. Only CASE 2 and CASE 3 list them all, when the reply holds at least a
+/// third of them anyway.
+pub(crate) fn zrandmember_sample<'a, K, R, F, A>(
+    size: usize,
+    count: i64,
+    with_scores: bool,
+    rng: &mut R,
+    mut random: F,
+    all: A,
+) -> Result<Vec<&'a K>, String>
+where
+    K: Eq + std::hash::Hash + ?Sized,
+    R: Rng,
+    F: FnMut(&mut R) -> &'a K,
+    A: FnOnce() -> Vec<&'a K>,
+{
+    zrandmember_check_count(count, with_scores)?;
+    if count == 0 || size == 0 {
+        return Ok(Vec::new());
+    }
+
+    // CASE 1: N random elements, possibly repeated.
+    if count < 0 {
+        return Ok((0..count.unsigned_abs()).map(|_| random(rng)).collect());
+    }
+
+    // CASE 2 and CASE 3: most of the elements, picked by rank among all of them.
+    let count = count as usize;
+    if count >= size || count * ZRANDMEMBER_SUB_STRATEGY_MUL > size {
+        let all = all();
+        let ranks = zrandmember_ranks(all.len(), count as i64, with_scores, rng)?;
+        return Ok(ranks.into_iter().map(|rank| all[rank]).collect());
+    }
 
-        while let Some(node) = self.pop_front() {
-            let guard = DropGuard(self);
-            drop(node);
-            mem::forget(guard);
+    // CASE 4: random elements until enough distinct ones were found.
+    let mut seen = HashSet::with_capacity(count);
+    let mut sample = Vec::with_capacity(count);
+    while sample.len() < count {
+        let element = random(rng);
+        if seen.insert(element) {
+            sample.push(element);
         }
     }
+    Ok(sample)
+}
+
+impl<T> Drop for ZSkipList<T> {
+    fn drop(&mut self) {
+        self.zsl_free();
+        unsafe { drop(Box::from_raw(self.header.as_ptr())) };
+    }
 }
 
 pub struct Iter<'a, T: 'a> {
     head: Link<T>,
+    tail: Link<T>,
     len: usize,
     _boo: PhantomData<&'a ZSkipList<T>>,
 }
@@ -611,8 +1456,30 @@ impl<'a, T> Iterator for Iter<'a, T> {
     }
 }
 
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            None
+        } else {
+            self.tail.map(|node| {
+                self.len -= 1;
+
+                unsafe {
+                    let node = &*node.as_ptr();
+                    self.tail = node.backward;
+                    node.val.as_ref().unwrap()
+                }
+            })
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
 pub struct IterMut<'a, T: 'a> {
     head: Link<T>,
+    tail: Link<T>,
     len: usize,
     _boo: PhantomData<&'a mut ZSkipNode<T>>,
 }
@@ -665,6 +1532,113 @@ impl<'a, T> Iterator for IterMut<'a, T> {
     }
 }
 
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            None
+        } else {
+            self.tail.map(|node| {
+                self.len -= 1;
+
+                unsafe {
+                    let node = &mut *node.as_ptr();
+                    self.tail = node.backward;
+                    node.val.as_mut().unwrap()
+                }
+            })
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {}
+
+/// An iterator over the `(score, element)` pairs of a slice of a `ZSkipList`.
+///
+/// This struct is created by [`range_by_score`], [`range_by_rank`] and [`range_by_lex`]
+/// on [`ZSkipList`].
+///
+/// [`range_by_score`]: ZSkipList::range_by_score
+/// [`range_by_rank`]: ZSkipList::range_by_rank
+/// [`range_by_lex`]: ZSkipList::range_by_lex
+pub struct Range<'a, T: 'a> {
+    head: Link<T>,
+    tail: Link<T>,
+    len: usize,
+    _boo: PhantomData<&'a ZSkipList<T>>,
+}
+
+impl<'a, T> Range<'a, T> {
+    fn empty() -> Self {
+        Range {
+            head: None,
+            tail: None,
+            len: 0,
+            _boo: PhantomData,
+        }
+    }
+
+    /// The `len` nodes following `before`, the last one being `last`.
+    unsafe fn between(before: NonNull<ZSkipNode<T>>, last: NonNull<ZSkipNode<T>>, len: usize) -> Self {
+        if len == 0 {
+            return Range::empty();
+        }
+        Range {
+            head: before.as_ref().level[0].forward,
+            tail: Some(last),
+            len,
+            _boo: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Iterator for Range<'a, T> {
+    type Item = (f64, &'a T);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            None
+        } else {
+            self.head.map(|node| {
+                self.len -= 1;
+
+                unsafe {
+                    let node = &*node.as_ptr();
+                    self.head = node.level[0].forward;
+                    (node.score, node.val.as_ref().unwrap())
+                }
+            })
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Range<'a, T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            None
+        } else {
+            self.tail.map(|node| {
+                self.len -= 1;
+
+                unsafe {
+                    let node = &*node.as_ptr();
+                    self.tail = node.backward;
+                    (node.score, node.val.as_ref().unwrap())
+                }
+            })
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Range<'a, T> {}
+
 pub struct IntoIter<T> {
     list: ZSkipList<T>,
 }
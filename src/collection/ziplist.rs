@@ -0,0 +1,315 @@
+use std::collections::HashSet;
+
+use super::hash::Dict;
+use super::list::LinkedList;
+use super::listpack::{Listpack, ListpackEntry};
+use super::skiplist::zskiplist::ZSkipList;
+
+// Header: total bytes (u32 LE), offset of the last entry (u32 LE) and number of
+// entries (u16 LE)
+const ZIPLIST_HEADER_SIZE: usize = 10;
+// Stored as the number of entries once there are too many to fit the header
+const ZIPLIST_LEN_UNKNOWN: usize = u16::MAX as usize;
+const ZIP_END: u8 = 0xFF;
+// First byte of a 5 byte prevlen
+const ZIP_BIG_PREVLEN: u8 = 0xFE;
+
+const ZIP_STR_06B: u8 = 0x00;
+const ZIP_STR_14B: u8 = 0x40;
+const ZIP_STR_32B: u8 = 0x80;
+const ZIP_INT_16B: u8 = 0xC0;
+const ZIP_INT_32B: u8 = 0xD0;
+const ZIP_INT_64B: u8 = 0xE0;
+const ZIP_INT_24B: u8 = 0xF0;
+const ZIP_INT_8B: u8 = 0xFE;
+// 1111xxxx with xxxx between 0001 and 1101 holds the integers 0 to 12
+const ZIP_INT_IMM_MIN: u8 = 0xF1;
+const ZIP_INT_IMM_MAX: u8 = 0xFD;
+
+// An entry decoded by `decode_entry`
+struct ZipEntry<'a> {
+    prevlen: usize,
+    // prevlen, encoding and data
+    size: usize,
+    value: ListpackEntry<'a>,
+}
+
+// Decodes the entry at `p` without reading past `end`, like `zipEntrySafe`.
+fn decode_entry(buf: &[u8], p: usize, end: usize) -> Result<ZipEntry<'_>, String> {
+    let truncated = || "ziplist entry overflows the ziplist".to_string();
+    let bytes = |from: usize, len: usize| -> Result<&[u8], String> {
+        from.checked_add(len)
+            .filter(|&to| to <= end)
+            .map(|to| &buf[from..to])
+            .ok_or_else(truncated)
+    };
+
+    let (prevlen, prevlen_size) = match *bytes(p, 1)?.first().unwrap() {
+        ZIP_END => return Err("invalid ziplist prevlen".to_string()),
+        ZIP_BIG_PREVLEN => {
+            let prevlen = u32::from_le_bytes(bytes(p + 1, 4)?.try_into().unwrap());
+            (prevlen as usize, 5)
+        }
+        prevlen => (prevlen as usize, 1),
+    };
+
+    let q = p + prevlen_size;
+    let encoding = bytes(q, 1)?[0];
+    let int = |len: usize| -> Result<i64, String> {
+        let data = bytes(q + 1, len)?;
+        let mut le = [0; 8];
+        le[..len].copy_from_slice(data);
+        let shift = 64 - 8 * len as u32;
+        Ok((i64::from_le_bytes(le) << shift) >> shift)
+    };
+    // Sizes of the encoding and of the data, and the value
+    let (encoding_size, data_size, value) = match encoding {
+        b if b & 0xC0 == ZIP_STR_06B => {
+            let len = (b & 0x3F) as usize;
+            (1, len, ListpackEntry::Str(bytes(q + 1, len)?))
+        }
+        b if b & 0xC0 == ZIP_STR_14B => {
+            let len = ((b & 0x3F) as usize) << 8 | bytes(q + 1, 1)?[0] as usize;
+            (2, len, ListpackEntry::Str(bytes(q + 2, len)?))
+        }
+        ZIP_STR_32B => {
+            let len = u32::from_be_bytes(bytes(q + 1, 4)?.try_into().unwrap()) as usize;
+            (5, len, ListpackEntry::Str(bytes(q + 5, len)?))
+        }
+        ZIP_INT_8B => (1, 1, ListpackEntry::Int(int(1)?)),
+        ZIP_INT_16B => (1, 2, ListpackEntry::Int(int(2)?)),
+        ZIP_INT_24B => (1, 3, ListpackEntry::Int(int(3)?)),
+        ZIP_INT_32B => (1, 4, ListpackEntry::Int(int(4)?)),
+        ZIP_INT_64B => (1, 8, ListpackEntry::Int(int(8)?)),
+        b @ ZIP_INT_IMM_MIN..=ZIP_INT_IMM_MAX => (1, 0, ListpackEntry::Int((b & 0x0F) as i64 - 1)),
+        b => return Err(format!("invalid ziplist entry encoding {:#x}", b)),
+    };
+
+    Ok(ZipEntry { prevlen, size: prevlen_size + encoding_size + data_size, value })
+}
+
+/// A read-only view of a legacy Redis `ziplist`, as found in RDB files written before
+/// Redis 7, with conversions to the structures that replaced it.
+///
+/// ```text
+/// <zlbytes: u32 LE> <zltail: u32 LE> <zllen: u16 LE> <entry> ... <entry> <0xFF>
+/// ```
+///
+/// Each entry starts with the size of the previous entry (`prevlen`), one byte when
+/// below 254 and otherwise `0xFE` followed by a u32. After a cascade update Redis may
+/// leave a 5 byte `prevlen` holding a small size, which is accepted.
+///
+/// The buffer is fully validated when the view is created, so reading it never fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ziplist<'a> {
+    buf: &'a [u8],
+    len: usize,
+}
+
+impl<'a> Ziplist<'a> {
+    /// Checks that `bytes` is a well formed ziplist, like `ziplistValidateIntegrity`
+    /// with a deep check, and returns a view of it. Meant for buffers read from
+    /// untrusted input.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::listpack::ListpackEntry;
+    /// use curly_giggle::collection::ziplist::Ziplist;
+    ///
+    /// // "hello" followed by the immediate integer 1
+    /// let bytes = [20, 0, 0, 0, 17, 0, 0, 0, 2, 0, 0, 5, b'h', b'e', b'l', b'l', b'o', 7, 0xF2, 0xFF];
+    /// let zl = Ziplist::from_bytes(&bytes).unwrap();
+    /// assert_eq!(zl.iter().collect::<Vec<_>>(), vec![ListpackEntry::Str(b"hello"), ListpackEntry::Int(1)]);
+    ///
+    /// assert!(Ziplist::from_bytes(&bytes[..19]).is_err());
+    /// ```
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, String> {
+        if bytes.len() < ZIPLIST_HEADER_SIZE + 1 {
+            return Err("ziplist is smaller than its header".to_string());
+        }
+        let header = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+        if header(0) != bytes.len() {
+            return Err("ziplist total bytes does not match its size".to_string());
+        }
+        let end = bytes.len() - 1;
+        if bytes[end] != ZIP_END {
+            return Err("ziplist is not terminated".to_string());
+        }
+        let tail = header(4);
+        if tail > end {
+            return Err("ziplist tail offset is out of range".to_string());
+        }
+
+        let mut p = ZIPLIST_HEADER_SIZE;
+        let mut prev_size = 0;
+        let mut last = ZIPLIST_HEADER_SIZE;
+        let mut count = 0;
+        while bytes[p] != ZIP_END {
+            let entry = decode_entry(bytes, p, end)?;
+            if entry.prevlen != prev_size {
+                return Err("ziplist entry prevlen does not match the previous entry".to_string());
+            }
+            prev_size = entry.size;
+            last = p;
+            p += entry.size;
+            count += 1;
+        }
+        if p != end {
+            return Err("ziplist end marker found before its end".to_string());
+        }
+        if tail != last {
+            return Err("ziplist tail offset does not point to the last entry".to_string());
+        }
+        let zllen = u16::from_le_bytes(bytes[8..10].try_into().unwrap()) as usize;
+        if zllen != ZIPLIST_LEN_UNKNOWN && zllen != count {
+            return Err("ziplist number of entries does not match".to_string());
+        }
+
+        Ok(Ziplist { buf: bytes, len: count })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn entry(&self, p: usize) -> ZipEntry<'a> {
+        decode_entry(self.buf, p, self.buf.len() - 1).unwrap()
+    }
+
+    /// Returns the entries from head to tail. Iterating backwards follows the
+    /// `prevlen` fields from the tail.
+    pub fn iter(&self) -> Iter<'a> {
+        Iter {
+            zl: *self,
+            front: ZIPLIST_HEADER_SIZE,
+            back: u32::from_le_bytes(self.buf[4..8].try_into().unwrap()) as usize,
+            remaining: self.len,
+        }
+    }
+
+    /// Converts to a listpack holding the same entries, as Redis 7 does when loading
+    /// ziplist encoded objects.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::ziplist::Ziplist;
+    ///
+    /// let bytes = [20, 0, 0, 0, 17, 0, 0, 0, 2, 0, 0, 5, b'h', b'e', b'l', b'l', b'o', 7, 0xF2, 0xFF];
+    /// let lp = Ziplist::from_bytes(&bytes).unwrap().to_listpack();
+    /// assert_eq!(lp.len(), 2);
+    /// ```
+    pub fn to_listpack(&self) -> Listpack {
+        self.iter().collect()
+    }
+
+    /// Converts a ziplist encoded list.
+    pub fn to_list(&self) -> LinkedList<Vec<u8>> {
+        let mut list = LinkedList::new();
+        for entry in self.iter() {
+            list.push_back(entry.to_bytes().into_owned());
+        }
+        list
+    }
+
+    // Pairs up the entries, rejecting an odd count or a repeated first element.
+    fn pairs(&self) -> Result<Vec<(Vec<u8>, ListpackEntry<'a>)>, String> {
+        if !self.len.is_multiple_of(2) {
+            return Err("ziplist has an odd number of entries".to_string());
+        }
+        let mut seen = HashSet::new();
+        let mut entries = self.iter();
+        let mut pairs = Vec::with_capacity(self.len / 2);
+        while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+            let key = key.to_bytes().into_owned();
+            if !seen.insert(key.clone()) {
+                return Err("ziplist has duplicate entries".to_string());
+            }
+            pairs.push((key, value));
+        }
+        Ok(pairs)
+    }
+
+    /// Converts a ziplist encoded hash, made of field and value entries.
+    pub fn to_dict(&self) -> Result<Dict<Vec<u8>, Vec<u8>>, String> {
+        let mut dict = Dict::new();
+        for (field, value) in self.pairs()? {
+            dict.add(field, value.to_bytes().into_owned());
+        }
+        Ok(dict)
+    }
+
+    /// Converts a ziplist encoded sorted set, made of member and score entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::ziplist::Ziplist;
+    ///
+    /// // "a" with score 1, "b" with score "2.5"
+    /// let bytes = [
+    ///     24, 0, 0, 0, 18, 0, 0, 0, 4, 0, 0, 1, b'a', 3, 0xF2, 2, 1, b'b', 3, 3, b'2', b'.', b'5', 0xFF,
+    /// ];
+    /// let zsl = Ziplist::from_bytes(&bytes).unwrap().to_zskiplist().unwrap();
+    /// assert_eq!(zsl.zsl_get_rank(2.5, b"b".to_vec()), Some(1));
+    /// ```
+    pub fn to_zskiplist(&self) -> Result<ZSkipList<Vec<u8>>, String> {
+        let mut zsl = ZSkipList::zsl_create();
+        for (member, score) in self.pairs()? {
+            let score = match score {
+                ListpackEntry::Int(score) => score as f64,
+                ListpackEntry::Str(s) => std::str::from_utf8(s)
+                    .ok()
+                    .and_then(|s| s.parse::<f64>().ok())
+                    .filter(|score| !score.is_nan())
+                    .ok_or("ziplist has an invalid score")?,
+            };
+            zsl.zsl_insert(score, member);
+        }
+        Ok(zsl)
+    }
+}
+
+pub struct Iter<'a> {
+    zl: Ziplist<'a>,
+    front: usize,
+    back: usize,
+    remaining: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = ListpackEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let entry = self.zl.entry(self.front);
+        self.front += entry.size;
+        self.remaining -= 1;
+        Some(entry.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let entry = self.zl.entry(self.back);
+        self.back -= entry.prevlen;
+        self.remaining -= 1;
+        Some(entry.value)
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
@@ -0,0 +1,868 @@
+use std::cmp::Ordering;
+use std::hash::Hash;
+use std::mem;
+use std::slice;
+
+use rand::{thread_rng, Rng};
+
+use crate::collection::hash::Dict;
+use crate::collection::sampled_size;
+use crate::collection::skiplist::zrangespec::{LexBound, ZLexRangeSpec, ZRangeSpec};
+use crate::collection::skiplist::zskiplist::{zrandmember_ranks, Range, ZSkipList};
+use crate::collection::skiplist::zskipnode::{ZSkipLevel, ZSkipNode};
+
+/// Input flags of ZADD.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ZAddFlags {
+    // Only add new elements
+    pub nx: bool,
+    // Only update existing elements
+    pub xx: bool,
+    // Only update if the new score is greater
+    pub gt: bool,
+    // Only update if the new score is less
+    pub lt: bool,
+    // Count changed elements, not only added ones
+    pub ch: bool,
+    // Increment the score instead of setting it
+    pub incr: bool,
+}
+
+/// What `zset_add` did with one element.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ZAddOutcome {
+    /// The element was new and got added.
+    Added(f64),
+    /// The element existed and its score changed.
+    Updated(f64),
+    /// The element existed with the resulting score already.
+    Unchanged(f64),
+    /// Nothing was done because of NX, XX, GT or LT.
+    Nop,
+}
+
+/// Which end of a sorted set to pop from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZSetWhere {
+    Min,
+    Max,
+}
+
+/// Reply of ZADD.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ZAddReply {
+    /// Number of added elements, or changed ones with CH.
+    Count(usize),
+    /// The new score with INCR, `None` if the operation was aborted.
+    Score(Option<f64>),
+}
+
+/// The two encodings of a sorted set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZSetEncoding {
+    /// A compact array of `(score, member)` pairs sorted by score, for small sets.
+    /// Redis uses a listpack there.
+    SortedArray,
+    /// A dict mapping members to scores, plus a skiplist ordering them by score.
+    SkipList,
+}
+
+/// Thresholds above which a sorted array encoded set is converted to a skiplist.
+/// They play the part of `zset-max-listpack-entries` and `zset-max-listpack-value`
+/// in redis.conf, the small encoding being a sorted array rather than a listpack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ZSetConfig {
+    // Maximum number of members
+    pub max_sorted_array_entries: usize,
+    // Maximum length in bytes of a member
+    pub max_sorted_array_value: usize,
+}
+
+impl Default for ZSetConfig {
+    fn default() -> Self {
+        ZSetConfig {
+            max_sorted_array_entries: 128,
+            max_sorted_array_value: 64,
+        }
+    }
+}
+
+enum ZSetRepr<T> {
+    // Sorted by score, then by member
+    SortedArray(Vec<(f64, T)>),
+    SkipList { dict: Dict<T, f64>, zsl: ZSkipList<T> },
+}
+
+/// A sorted set, starting as a sorted array and converted to a dict plus a skiplist
+/// once it grows past the thresholds of its `ZSetConfig`.
+///
+/// With the skiplist encoding ZSCORE is O(1) and ZRANK is O(log N). The sorted array
+/// encoding does both in O(N), which is cheap for a few small members.
+///
+/// # Examples
+///
+/// ```
+/// use curly_giggle::collection::zset::{ZAddFlags, ZAddReply, ZSet, ZSetEncoding};
+///
+/// let mut zset = ZSet::new();
+/// let reply = zset.zadd(ZAddFlags::default(), vec![(10.0, "alice"), (20.0, "bob")]);
+/// assert_eq!(reply, Ok(ZAddReply::Count(2)));
+/// assert_eq!(zset.zscore(&"bob"), Some(20.0));
+/// assert_eq!(zset.zrevrank(&"bob"), Some(0));
+/// assert_eq!(zset.encoding(), ZSetEncoding::SortedArray);
+/// ```
+pub struct ZSet<T> {
+    repr: ZSetRepr<T>,
+    config: ZSetConfig,
+}
+
+impl<T> Default for ZSet<T>
+where
+    T: Ord + Hash + Clone + AsRef<[u8]>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ZSet<T>
+where
+    T: Ord + Hash + Clone + AsRef<[u8]>,
+{
+    /// Creates an empty sorted set with the default thresholds.
+    pub fn new() -> Self {
+        Self::with_config(ZSetConfig::default())
+    }
+
+    /// Creates an empty sorted set with the given thresholds.
+    ///
+    /// With `max_sorted_array_entries` set to 0 the set is skiplist encoded from the start.
+    pub fn with_config(config: ZSetConfig) -> Self {
+        let repr = if config.max_sorted_array_entries == 0 {
+            ZSetRepr::SkipList {
+                dict: Dict::new(),
+                zsl: ZSkipList::zsl_create(),
+            }
+        } else {
+            ZSetRepr::SortedArray(Vec::new())
+        };
+        ZSet { repr, config }
+    }
+
+    pub fn encoding(&self) -> ZSetEncoding {
+        match self.repr {
+            ZSetRepr::SortedArray(_) => ZSetEncoding::SortedArray,
+            ZSetRepr::SkipList { .. } => ZSetEncoding::SkipList,
+        }
+    }
+
+    /// Converts the set to `encoding`, keeping its members and their order.
+    ///
+    /// Converting a set over the thresholds to a sorted array is allowed, the next
+    /// addition converts it back.
+    pub fn zset_convert(&mut self, encoding: ZSetEncoding) {
+        if self.encoding() == encoding {
+            return;
+        }
+        let repr = std::mem::replace(&mut self.repr, ZSetRepr::SortedArray(Vec::new()));
+        self.repr = match repr {
+            ZSetRepr::SortedArray(entries) => {
+                let mut dict = Dict::new();
+                let mut zsl = ZSkipList::zsl_create();
+                for (score, member) in entries {
+                    zsl.zsl_insert(score, member.clone());
+                    dict.add(member, score);
+                }
+                ZSetRepr::SkipList { dict, zsl }
+            }
+            ZSetRepr::SkipList { zsl, .. } => {
+                ZSetRepr::SortedArray(zsl.range_by_rank(0, -1).map(|(score, member)| (score, member.clone())).collect())
+            }
+        };
+    }
+
+    /// Converts a skiplist encoded set back to a sorted array if it fits the
+    /// thresholds, like `zsetConvertToListpackIfNeeded`. Returns true if the set is
+    /// sorted array encoded afterwards.
+    pub fn zset_convert_to_sorted_array_if_needed(&mut self) -> bool {
+        if let ZSetRepr::SkipList { zsl, .. } = &self.repr {
+            let fits = zsl.get_len() <= self.config.max_sorted_array_entries
+                && zsl.iter().all(|member| member.as_ref().len() <= self.config.max_sorted_array_value);
+            if fits {
+                self.zset_convert(ZSetEncoding::SortedArray);
+            }
+        }
+        self.encoding() == ZSetEncoding::SortedArray
+    }
+
+    /// ZADD with the given flags and `(score, member)` pairs.
+    ///
+    /// # Errors
+    ///
+    /// Returns the Redis error message for incompatible flags, a NaN input score or
+    /// an increment resulting in NaN. Flags are checked before anything is added.
+    pub fn zadd(&mut self, flags: ZAddFlags, elements: Vec<(f64, T)>) -> Result<ZAddReply, String> {
+        if flags.nx && flags.xx {
+            return Err("XX and NX options at the same time are not compatible".to_string());
+        }
+        if ((flags.gt || flags.lt) && flags.nx) || (flags.gt && flags.lt) {
+            return Err("GT, LT, and/or NX options at the same time are not compatible".to_string());
+        }
+        if flags.incr && elements.len() > 1 {
+            return Err("INCR option supports a single increment-element pair".to_string());
+        }
+        if elements.iter().any(|(score, _)| score.is_nan()) {
+            return Err("value is not a valid float".to_string());
+        }
+
+        let mut added = 0;
+        let mut updated = 0;
+        let mut score = None;
+        for (increment, member) in elements {
+            match self.zset_add(increment, member, flags)? {
+                ZAddOutcome::Added(new_score) => {
+                    added += 1;
+                    score = Some(new_score);
+                }
+                ZAddOutcome::Updated(new_score) => {
+                    updated += 1;
+                    score = Some(new_score);
+                }
+                ZAddOutcome::Unchanged(new_score) => score = Some(new_score),
+                ZAddOutcome::Nop => {}
+            }
+        }
+
+        if flags.incr {
+            Ok(ZAddReply::Score(score))
+        } else if flags.ch {
+            Ok(ZAddReply::Count(added + updated))
+        } else {
+            Ok(ZAddReply::Count(added))
+        }
+    }
+
+    /// Add or update a single member, like `zsetAdd`.
+    ///
+    /// A sorted array encoded set is converted first if the new member would break
+    /// one of the thresholds.
+    pub fn zset_add(&mut self, score: f64, member: T, flags: ZAddFlags) -> Result<ZAddOutcome, String> {
+        match self.zscore(&member) {
+            Some(cur_score) => {
+                // NX? Return, same element already exists.
+                if flags.nx {
+                    return Ok(ZAddOutcome::Nop);
+                }
+
+                let mut score = score;
+                // Prepare the score for the increment if needed.
+                if flags.incr {
+                    score += cur_score;
+                    if score.is_nan() {
+                        return Err("resulting score is not a number (NaN)".to_string());
+                    }
+                }
+
+                // GT/LT? Only update if score is greater/less than current.
+                if (flags.lt && score >= cur_score) || (flags.gt && score <= cur_score) {
+                    return Ok(ZAddOutcome::Nop);
+                }
+
+                if score == cur_score {
+                    return Ok(ZAddOutcome::Unchanged(score));
+                }
+                match &mut self.repr {
+                    ZSetRepr::SortedArray(entries) => {
+                        // Remove and re-insert when score changes.
+                        let (_, member) = entries.remove(Self::zzl_find(entries, &member).unwrap());
+                        Self::zzl_insert(entries, score, member);
+                    }
+                    ZSetRepr::SkipList { dict, zsl } => {
+                        zsl.zsl_update_score(cur_score, &member, score);
+                        *dict.fetch_value_mut(&member).unwrap() = score;
+                    }
+                }
+                Ok(ZAddOutcome::Updated(score))
+            }
+            None if !flags.xx => {
+                if let ZSetRepr::SortedArray(entries) = &self.repr {
+                    if entries.len() + 1 > self.config.max_sorted_array_entries
+                        || member.as_ref().len() > self.config.max_sorted_array_value
+                    {
+                        self.zset_convert(ZSetEncoding::SkipList);
+                    }
+                }
+                match &mut self.repr {
+                    ZSetRepr::SortedArray(entries) => Self::zzl_insert(entries, score, member),
+                    ZSetRepr::SkipList { dict, zsl } => {
+                        zsl.zsl_insert(score, member.clone());
+                        dict.add(member, score);
+                    }
+                }
+                Ok(ZAddOutcome::Added(score))
+            }
+            None => Ok(ZAddOutcome::Nop),
+        }
+    }
+
+    /// ZREM, returns the number of members removed.
+    pub fn zrem(&mut self, members: &[T]) -> usize {
+        members.iter().filter(|member| self.zset_del(member)).count()
+    }
+
+    /// Remove a single member, returning whether it was there.
+    pub fn zset_del(&mut self, member: &T) -> bool {
+        match &mut self.repr {
+            ZSetRepr::SortedArray(entries) => match Self::zzl_find(entries, member) {
+                Some(pos) => {
+                    entries.remove(pos);
+                    true
+                }
+                None => false,
+            },
+            ZSetRepr::SkipList { dict, zsl } => match dict.unlink(member) {
+                Some((_, score)) => {
+                    zsl.zsl_delete(score, member);
+                    true
+                }
+                None => false,
+            },
+        }
+    }
+
+    pub fn zscore(&self, member: &T) -> Option<f64> {
+        match &self.repr {
+            ZSetRepr::SortedArray(entries) => Self::zzl_find(entries, member).map(|pos| entries[pos].0),
+            ZSetRepr::SkipList { dict, .. } => dict.fetch_value(member).copied(),
+        }
+    }
+
+    pub fn zmscore(&self, members: &[T]) -> Vec<Option<f64>> {
+        members.iter().map(|member| self.zscore(member)).collect()
+    }
+
+    /// 0-based rank of `member`, ordered from the lowest score.
+    pub fn zrank(&self, member: &T) -> Option<usize> {
+        self.zrank_withscore(member, false).map(|(rank, _)| rank)
+    }
+
+    /// 0-based rank of `member`, ordered from the highest score.
+    pub fn zrevrank(&self, member: &T) -> Option<usize> {
+        self.zrank_withscore(member, true).map(|(rank, _)| rank)
+    }
+
+    /// ZRANK / ZREVRANK with WITHSCORE: the rank of `member` and its score.
+    pub fn zrank_withscore(&self, member: &T, reverse: bool) -> Option<(usize, f64)> {
+        let (rank, score) = match &self.repr {
+            ZSetRepr::SortedArray(entries) => {
+                let pos = Self::zzl_find(entries, member)?;
+                (pos, entries[pos].0)
+            }
+            ZSetRepr::SkipList { dict, zsl } => {
+                let score = *dict.fetch_value(member)?;
+                (zsl.zsl_get_rank(score, member.clone())? as usize, score)
+            }
+        };
+        if reverse {
+            Some((self.zcard() - rank - 1, score))
+        } else {
+            Some((rank, score))
+        }
+    }
+
+    pub fn zcard(&self) -> usize {
+        match &self.repr {
+            ZSetRepr::SortedArray(entries) => entries.len(),
+            ZSetRepr::SkipList { zsl, .. } => zsl.get_len(),
+        }
+    }
+
+    /// Bytes used by the members and the structures holding them, like the sorted set
+    /// case of `objectComputeSize`. Skiplist nodes are counted with a single level,
+    /// and estimated from the first `samples` members, or all of them if 0.
+    pub fn mem_usage(&self, samples: usize) -> usize {
+        match &self.repr {
+            ZSetRepr::SortedArray(entries) => {
+                entries.capacity() * mem::size_of::<(f64, T)>()
+                    + entries.iter().map(|(_, member)| member.as_ref().len()).sum::<usize>()
+            }
+            ZSetRepr::SkipList { dict, zsl } => {
+                let node = mem::size_of::<ZSkipNode<T>>() + mem::size_of::<ZSkipLevel<T>>();
+                // Members are held by both the dict and the skiplist
+                dict.mem_usage() + sampled_size(zsl.iter().map(|member| node + 2 * member.as_ref().len()), samples)
+            }
+        }
+    }
+
+    /// Number of members with score in `range`.
+    pub fn zcount(&self, range: &ZRangeSpec) -> usize {
+        match &self.repr {
+            ZSetRepr::SortedArray(entries) => {
+                let first = entries.partition_point(|(score, _)| !range.value_gte_min(*score));
+                let last = entries.partition_point(|(score, _)| range.value_lte_max(*score));
+                last.saturating_sub(first)
+            }
+            ZSetRepr::SkipList { zsl, .. } => zsl.zsl_count_in_range(range),
+        }
+    }
+
+    /// Number of members in the lex `range`, expecting all scores to be equal.
+    pub fn zlexcount(&self, range: &ZLexRangeSpec<T>) -> usize {
+        match &self.repr {
+            ZSetRepr::SortedArray(entries) => {
+                let first = entries.partition_point(|(_, member)| !Self::zzl_lex_value_gte_min(member, &range.min));
+                let last = entries.partition_point(|(_, member)| Self::zzl_lex_value_lte_max(member, &range.max));
+                last.saturating_sub(first)
+            }
+            ZSetRepr::SkipList { zsl, .. } => zsl.zsl_count_in_lex_range(range),
+        }
+    }
+
+    /// ZPOPMIN: remove and return up to `count` members with the lowest scores,
+    /// lowest first.
+    pub fn zpopmin(&mut self, count: usize) -> Vec<(f64, T)> {
+        self.zset_pop(ZSetWhere::Min, count)
+    }
+
+    /// ZPOPMAX: remove and return up to `count` members with the highest scores,
+    /// highest first.
+    pub fn zpopmax(&mut self, count: usize) -> Vec<(f64, T)> {
+        self.zset_pop(ZSetWhere::Max, count)
+    }
+
+    /// Pop up to `count` members from the given end, like `genericZpopCommand`.
+    pub fn zset_pop(&mut self, wherefrom: ZSetWhere, count: usize) -> Vec<(f64, T)> {
+        match &mut self.repr {
+            ZSetRepr::SortedArray(entries) => {
+                let count = count.min(entries.len());
+                match wherefrom {
+                    ZSetWhere::Min => entries.drain(..count).collect(),
+                    ZSetWhere::Max => entries.drain(entries.len() - count..).rev().collect(),
+                }
+            }
+            ZSetRepr::SkipList { dict, zsl } => {
+                let popped = match wherefrom {
+                    ZSetWhere::Min => zsl.pop_min(count),
+                    ZSetWhere::Max => zsl.pop_max(count),
+                };
+                for (_, member) in popped.iter() {
+                    dict.delete(member);
+                }
+                popped
+            }
+        }
+    }
+
+    /// ZRANDMEMBER with a count, see `ZSkipList::random_members` for the rules.
+    pub fn zrandmember(&self, count: i64, with_scores: bool) -> Result<Vec<(T, Option<f64>)>, String> {
+        self.zrandmember_with_rng(count, with_scores, &mut thread_rng())
+    }
+
+    /// Like `zrandmember`, drawing from the given random number generator.
+    pub fn zrandmember_with_rng<R: Rng>(
+        &self,
+        count: i64,
+        with_scores: bool,
+        rng: &mut R,
+    ) -> Result<Vec<(T, Option<f64>)>, String> {
+        match &self.repr {
+            ZSetRepr::SortedArray(entries) => {
+                let ranks = zrandmember_ranks(entries.len(), count, with_scores, rng)?;
+                Ok(ranks
+                    .into_iter()
+                    .map(|rank| {
+                        let (score, member) = &entries[rank];
+                        (member.clone(), if with_scores { Some(*score) } else { None })
+                    })
+                    .collect())
+            }
+            ZSetRepr::SkipList { zsl, .. } => zsl.random_members_with_rng(count, with_scores, rng),
+        }
+    }
+
+    /// Iterate over the `(score, member)` pairs from the lowest score.
+    pub fn iter(&self) -> Iter<'_, T> {
+        match &self.repr {
+            ZSetRepr::SortedArray(entries) => Iter::SortedArray(entries.iter()),
+            ZSetRepr::SkipList { zsl, .. } => Iter::SkipList(zsl.range_by_rank(0, -1)),
+        }
+    }
+
+    fn zzl_find(entries: &[(f64, T)], member: &T) -> Option<usize> {
+        entries.iter().position(|(_, cur)| cur == member)
+    }
+
+    fn zzl_insert(entries: &mut Vec<(f64, T)>, score: f64, member: T) {
+        let pos = entries.partition_point(|(cur_score, cur)| {
+            *cur_score < score || (*cur_score == score && *cur < member)
+        });
+        entries.insert(pos, (score, member));
+    }
+
+    fn zzl_lex_value_gte_min(value: &T, min: &LexBound<T>) -> bool {
+        match min {
+            LexBound::NegInf => true,
+            LexBound::PosInf => false,
+            LexBound::Inclusive(min) => value.cmp(min) != Ordering::Less,
+            LexBound::Exclusive(min) => value.cmp(min) == Ordering::Greater,
+        }
+    }
+
+    fn zzl_lex_value_lte_max(value: &T, max: &LexBound<T>) -> bool {
+        match max {
+            LexBound::NegInf => false,
+            LexBound::PosInf => true,
+            LexBound::Inclusive(max) => value.cmp(max) != Ordering::Greater,
+            LexBound::Exclusive(max) => value.cmp(max) == Ordering::Less,
+        }
+    }
+}
+
+/// Iterator over the `(score, member)` pairs of a `ZSet`, whatever its encoding.
+pub enum Iter<'a, T> {
+    SortedArray(slice::Iter<'a, (f64, T)>),
+    SkipList(Range<'a, T>),
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (f64, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Iter::SortedArray(iter) => iter.next().map(|(score, member)| (*score, member)),
+            Iter::SkipList(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Iter::SortedArray(iter) => iter.size_hint(),
+            Iter::SkipList(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self {
+            Iter::SortedArray(iter) => iter.next_back().map(|(score, member)| (*score, member)),
+            Iter::SkipList(iter) => iter.next_back(),
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<T> Clone for ZSet<T>
+where
+    T: Ord + Hash + Clone + AsRef<[u8]>,
+{
+    /// Copies the set with the same thresholds and encoding, like `zsetDup`.
+    fn clone(&self) -> Self {
+        let entries = self.iter().map(|(score, member)| (score, member.clone())).collect();
+        let mut zset = ZSet {
+            repr: ZSetRepr::SortedArray(entries),
+            config: self.config,
+        };
+        zset.zset_convert(self.encoding());
+        zset
+    }
+}
+
+impl<T> FromIterator<(f64, T)> for ZSet<T>
+where
+    T: Ord + Hash + Clone + AsRef<[u8]>,
+{
+    /// Builds a set with the default thresholds, a repeated member keeps its last score.
+    fn from_iter<I: IntoIterator<Item = (f64, T)>>(iter: I) -> Self {
+        let mut zset = ZSet::new();
+        for (score, member) in iter {
+            zset.zset_add(score, member, ZAddFlags::default()).unwrap();
+        }
+        zset
+    }
+}
+
+/// How the scores of a member found in several sets are combined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ZAggregate {
+    #[default]
+    Sum,
+    Min,
+    Max,
+}
+
+impl ZAggregate {
+    /// Combine `value` into `target`, like `zunionInterAggregate`.
+    pub fn aggregate(&self, target: &mut f64, value: f64) {
+        match self {
+            ZAggregate::Sum => {
+                *target += value;
+                // The result of adding two doubles is NaN when one variable
+                // is +inf and the other is -inf. When these numbers are added,
+                // we maintain the convention of the result being 0.0.
+                if target.is_nan() {
+                    *target = 0.0;
+                }
+            }
+            ZAggregate::Min => *target = target.min(value),
+            ZAggregate::Max => *target = target.max(value),
+        }
+    }
+}
+
+/// The WEIGHTS and AGGREGATE options of ZUNIONSTORE and ZINTERSTORE.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ZStoreOptions {
+    // One multiplication factor per input set, all 1 when `None`
+    pub weights: Option<Vec<f64>>,
+    pub aggregate: ZAggregate,
+}
+
+/// The two ways of computing ZDIFF, see `ZSet::zset_choose_diff_algorithm`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZDiffAlgorithm {
+    /// For each member of the first set, look it up in all the other sets.
+    /// O(N*K) with N the size of the first set and K the number of sets.
+    One,
+    /// Copy the first set, then remove every member of the other sets.
+    /// O(L) with L the total number of members of all the sets.
+    Two,
+}
+
+impl<T> ZSet<T>
+where
+    T: Ord + Hash + Clone + AsRef<[u8]>,
+{
+    /// ZUNION: every member of `sets`, with its weighted scores aggregated.
+    ///
+    /// The iterator yields `(score, member)` pairs in no particular order, collect it
+    /// into a `ZSet` to get them sorted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `sets` is empty, or if the number of weights does not
+    /// match the number of sets.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::collection::zset::{ZAddFlags, ZAggregate, ZSet, ZStoreOptions};
+    ///
+    /// let mut a = ZSet::new();
+    /// a.zadd(ZAddFlags::default(), vec![(1.0, "x"), (2.0, "y")]).unwrap();
+    /// let mut b = ZSet::new();
+    /// b.zadd(ZAddFlags::default(), vec![(10.0, "y"), (3.0, "z")]).unwrap();
+    ///
+    /// let options = ZStoreOptions { weights: Some(vec![1.0, 2.0]), aggregate: ZAggregate::Sum };
+    /// let union: ZSet<_> = ZSet::zunion(&[&a, &b], &options).unwrap().collect();
+    /// let items: Vec<_> = union.iter().map(|(score, member)| (score, *member)).collect();
+    /// assert_eq!(items, vec![(1.0, "x"), (6.0, "z"), (22.0, "y")]);
+    /// ```
+    pub fn zunion<'a>(sets: &[&'a ZSet<T>], options: &ZStoreOptions) -> Result<ZSetOpIter<'a, T>, String> {
+        let weights = Self::zset_op_weights(sets, options, "zunion")?;
+        let mut dict: Dict<T, f64> = Dict::new();
+        for (set, weight) in sets.iter().zip(weights) {
+            for (score, member) in set.iter() {
+                let mut score = weight * score;
+                // inf * 0 is NaN, count it as 0
+                if score.is_nan() {
+                    score = 0.0;
+                }
+                match dict.fetch_value_mut(member) {
+                    Some(existing) => options.aggregate.aggregate(existing, score),
+                    None => dict.add(member.clone(), score),
+                }
+            }
+        }
+        Ok(ZSetOpIter::Materialized(dict.into_iter()))
+    }
+
+    /// ZINTER: the members found in all of `sets`, with their weighted scores
+    /// aggregated.
+    ///
+    /// The result is computed lazily, walking the smallest set and looking its
+    /// members up in the others.
+    ///
+    /// # Errors
+    ///
+    /// See `zunion`.
+    pub fn zinter<'a>(sets: &[&'a ZSet<T>], options: &ZStoreOptions) -> Result<ZSetOpIter<'a, T>, String> {
+        let weights = Self::zset_op_weights(sets, options, "zinter")?;
+        let mut sets: Vec<_> = sets.iter().copied().zip(weights).collect();
+        // Start with the smallest set, so the fewest lookups are needed
+        sets.sort_by_key(|(set, _)| set.zcard());
+        let (first, weight) = sets.remove(0);
+        Ok(ZSetOpIter::Inter {
+            first: first.iter(),
+            weight,
+            others: sets,
+            aggregate: options.aggregate,
+        })
+    }
+
+    /// ZDIFF: the members of the first set not found in any of the others, with
+    /// their score in the first set.
+    ///
+    /// The algorithm is chosen by `zset_choose_diff_algorithm`. Only algorithm 1
+    /// is lazy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `sets` is empty.
+    pub fn zdiff<'a>(sets: &[&'a ZSet<T>]) -> Result<ZSetOpIter<'a, T>, String> {
+        if sets.is_empty() {
+            return Err("at least 1 input key is needed for 'zdiff' command".to_string());
+        }
+        match Self::zset_choose_diff_algorithm(sets) {
+            None => Ok(ZSetOpIter::Materialized(Dict::new().into_iter())),
+            Some(ZDiffAlgorithm::One) => {
+                let mut others = sets[1..].to_vec();
+                // Look in the largest sets first, where a member is more likely found
+                others.sort_by_key(|set| std::cmp::Reverse(set.zcard()));
+                Ok(ZSetOpIter::Diff {
+                    first: sets[0].iter(),
+                    others,
+                })
+            }
+            Some(ZDiffAlgorithm::Two) => {
+                let mut dict: Dict<T, f64> = Dict::new();
+                for (score, member) in sets[0].iter() {
+                    dict.add(member.clone(), score);
+                }
+                for set in &sets[1..] {
+                    if dict.is_empty() {
+                        break;
+                    }
+                    for (_, member) in set.iter() {
+                        dict.delete(member);
+                    }
+                }
+                Ok(ZSetOpIter::Materialized(dict.into_iter()))
+            }
+        }
+    }
+
+    /// ZUNIONSTORE: like `zunion`, collected into a new set.
+    pub fn zunionstore(sets: &[&ZSet<T>], options: &ZStoreOptions) -> Result<ZSet<T>, String> {
+        Self::zset_op_weights(sets, options, "zunionstore")?;
+        Ok(Self::zunion(sets, options)?.collect())
+    }
+
+    /// ZINTERSTORE: like `zinter`, collected into a new set.
+    pub fn zinterstore(sets: &[&ZSet<T>], options: &ZStoreOptions) -> Result<ZSet<T>, String> {
+        Self::zset_op_weights(sets, options, "zinterstore")?;
+        Ok(Self::zinter(sets, options)?.collect())
+    }
+
+    /// ZDIFFSTORE: like `zdiff`, collected into a new set.
+    pub fn zdiffstore(sets: &[&ZSet<T>]) -> Result<ZSet<T>, String> {
+        if sets.is_empty() {
+            return Err("at least 1 input key is needed for 'zdiffstore' command".to_string());
+        }
+        Ok(Self::zdiff(sets)?.collect())
+    }
+
+    /// Pick the cheapest ZDIFF algorithm for `sets`, like `zsetChooseDiffAlgorithm`.
+    ///
+    /// Returns `None` when the result is known to be empty, because the first set
+    /// is repeated among the others.
+    pub fn zset_choose_diff_algorithm(sets: &[&ZSet<T>]) -> Option<ZDiffAlgorithm> {
+        let mut algo_one_work = 0;
+        let mut algo_two_work = 0;
+        for (j, set) in sets.iter().enumerate() {
+            // If any other set is equal to the first set, there is nothing to be
+            // done, since we would remove all elements anyway.
+            if j > 0 && std::ptr::eq(sets[0], *set) {
+                return None;
+            }
+            algo_one_work += sets[0].zcard();
+            algo_two_work += set.zcard();
+        }
+        // Algorithm 1 has better constant times and performs less operations
+        // if there are elements in common. Give it some advantage.
+        algo_one_work /= 2;
+        if algo_one_work <= algo_two_work {
+            Some(ZDiffAlgorithm::One)
+        } else {
+            Some(ZDiffAlgorithm::Two)
+        }
+    }
+
+    fn zset_op_weights(sets: &[&ZSet<T>], options: &ZStoreOptions, command: &str) -> Result<Vec<f64>, String> {
+        if sets.is_empty() {
+            return Err(format!("at least 1 input key is needed for '{}' command", command));
+        }
+        match &options.weights {
+            None => Ok(vec![1.0; sets.len()]),
+            Some(weights) if weights.len() != sets.len() => Err("syntax error".to_string()),
+            Some(weights) if weights.iter().any(|weight| weight.is_nan()) => {
+                Err("weight value is not a float".to_string())
+            }
+            Some(weights) => Ok(weights.clone()),
+        }
+    }
+}
+
+/// Iterator over the `(score, member)` pairs resulting from `ZSet::zunion`,
+/// `ZSet::zinter` or `ZSet::zdiff`, in no particular order.
+pub enum ZSetOpIter<'a, T> {
+    /// Results already computed in full.
+    Materialized(crate::collection::hash::IntoIter<T, f64>),
+    /// Members of the smallest set, kept if found in all the others.
+    Inter {
+        first: Iter<'a, T>,
+        weight: f64,
+        others: Vec<(&'a ZSet<T>, f64)>,
+        aggregate: ZAggregate,
+    },
+    /// Members of the first set, kept if found in none of the others.
+    Diff {
+        first: Iter<'a, T>,
+        others: Vec<&'a ZSet<T>>,
+    },
+}
+
+impl<'a, T> Iterator for ZSetOpIter<'a, T>
+where
+    T: Ord + Hash + Clone + AsRef<[u8]>,
+{
+    type Item = (f64, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ZSetOpIter::Materialized(iter) => iter.next().map(|(member, score)| (score, member)),
+            ZSetOpIter::Inter {
+                first,
+                weight,
+                others,
+                aggregate,
+            } => 'members: loop {
+                let (score, member) = first.next()?;
+                let mut score = *weight * score;
+                if score.is_nan() {
+                    score = 0.0;
+                }
+                for (other, other_weight) in others.iter() {
+                    match other.zscore(member) {
+                        Some(other_score) => {
+                            let mut value = other_weight * other_score;
+                            if value.is_nan() {
+                                value = 0.0;
+                            }
+                            aggregate.aggregate(&mut score, value);
+                        }
+                        None => continue 'members,
+                    }
+                }
+                return Some((score, member.clone()));
+            },
+            ZSetOpIter::Diff { first, others } => loop {
+                let (score, member) = first.next()?;
+                if others.iter().all(|other| other.zscore(member).is_none()) {
+                    return Some((score, member.clone()));
+                }
+            },
+        }
+    }
+}
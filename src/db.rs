@@ -0,0 +1,730 @@
+use std::collections::VecDeque;
+use std::mem;
+
+use rand::{thread_rng, Rng};
+
+use crate::clock::{Clock, SystemClock};
+use crate::collection::hash::{self, Dict};
+use crate::evict::{EvictionState, MaxmemoryPolicy, CONFIG_DEFAULT_MAXMEMORY_SAMPLES};
+use crate::expire::ActiveExpireState;
+use crate::object::{dup_object, object_compute_size, AccessTracking, LfuConfig, Robj};
+
+/// Number of databases, like the `databases` directive in redis.conf.
+pub const CONFIG_DEFAULT_DBNUM: usize = 16;
+/// Default frequency of the server cron, and so of slow expire cycles.
+pub const CONFIG_DEFAULT_HZ: u32 = 10;
+/// Default effort of the active expire cycle, from 1 to 10.
+pub const CONFIG_DEFAULT_ACTIVE_EXPIRE_EFFORT: u32 = 1;
+
+/// Server settings, like the matching directives in redis.conf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServerConfig {
+    // Number of databases
+    pub dbnum: usize,
+    // Server cron calls per second
+    pub hz: u32,
+    // From 1 to 10, how hard to work at removing expired keys
+    pub active_expire_effort: u32,
+    // Memory limit in bytes, 0 for none
+    pub maxmemory: usize,
+    // Which keys to evict once over the limit
+    pub maxmemory_policy: MaxmemoryPolicy,
+    // From 1 to 64, keys sampled per database to pick one to evict
+    pub maxmemory_samples: usize,
+    // How many hits it takes to saturate the access counter
+    pub lfu_log_factor: u32,
+    // Minutes after which the access counter is decremented, 0 to never decay
+    pub lfu_decay_time: u32,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        let lfu = LfuConfig::default();
+        ServerConfig {
+            dbnum: CONFIG_DEFAULT_DBNUM,
+            hz: CONFIG_DEFAULT_HZ,
+            active_expire_effort: CONFIG_DEFAULT_ACTIVE_EXPIRE_EFFORT,
+            maxmemory: 0,
+            maxmemory_policy: MaxmemoryPolicy::default(),
+            maxmemory_samples: CONFIG_DEFAULT_MAXMEMORY_SAMPLES,
+            lfu_log_factor: lfu.lfu_log_factor,
+            lfu_decay_time: lfu.lfu_decay_time,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// What the LRU bits of objects hold under `maxmemory_policy`.
+    pub fn access_tracking(&self) -> AccessTracking {
+        if self.maxmemory_policy.is_lfu() {
+            AccessTracking::Lfu(LfuConfig {
+                lfu_log_factor: self.lfu_log_factor,
+                lfu_decay_time: self.lfu_decay_time,
+            })
+        } else {
+            AccessTracking::Lru
+        }
+    }
+}
+
+/// How FLUSHDB and FLUSHALL free the keys they remove.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FlushMode {
+    /// Free every key before replying.
+    #[default]
+    Sync,
+    /// Detach the keys and free them later, see `RedisServer::lazyfree_free_objects`.
+    Async,
+}
+
+impl FlushMode {
+    /// Parses the arguments following FLUSHDB or FLUSHALL, like
+    /// `getFlushCommandFlags`. Without any the flush is synchronous.
+    ///
+    /// # Errors
+    ///
+    /// Returns "syntax error" for more than one argument or anything but ASYNC and SYNC.
+    pub fn from_args<A: AsRef<[u8]>>(args: &[A]) -> Result<FlushMode, String> {
+        match args {
+            [] => Ok(FlushMode::Sync),
+            [arg] if arg.as_ref().eq_ignore_ascii_case(b"sync") => Ok(FlushMode::Sync),
+            [arg] if arg.as_ref().eq_ignore_ascii_case(b"async") => Ok(FlushMode::Async),
+            _ => Err("syntax error".to_string()),
+        }
+    }
+}
+
+/// One database: the keyspace mapping keys to objects, and the expire times in
+/// unix milliseconds of the keys that have one.
+///
+/// A key is expired once the clock passes its expire time. Every access to an
+/// expired key deletes it first, as `expireIfNeeded` does, so commands never see
+/// it. `dbsize` and `keys` still count expired keys nobody accessed yet.
+///
+/// Lookups record the access in the LRU bits of the value, as an LRU clock or an
+/// LFU counter depending on the access tracking. `exists` does not count as one.
+///
+/// # Examples
+///
+/// ```
+/// use curly_giggle::clock::ManualClock;
+/// use curly_giggle::db::RedisDb;
+/// use curly_giggle::object::create_string_object;
+///
+/// let clock = ManualClock::new(1000);
+/// let mut db = RedisDb::with_clock(0, clock.clone());
+/// db.set_key(b"greeting", create_string_object(b"hello"), false);
+/// db.set_expire(b"greeting", 5000);
+/// assert_eq!(db.rename(b"greeting", b"welcome", false), Ok(true));
+/// assert_eq!(db.get_expire(b"welcome"), Some(5000));
+///
+/// clock.set(5001);
+/// assert!(db.lookup_read(b"welcome").is_none());
+/// assert_eq!(db.dbsize(), 0);
+/// ```
+pub struct RedisDb<C = SystemClock> {
+    pub(crate) id: usize,
+    pub(crate) dict: Dict<Vec<u8>, Robj>,
+    pub(crate) expires: Dict<Vec<u8>, u64>,
+    // Bytes of the keys and values, kept up to date as they are added and removed
+    used_memory: usize,
+    // Key last handed out by lookup_write, whose value is left out of used_memory
+    // as it may change size
+    written_key: Option<Vec<u8>>,
+    // Running average of the TTL of the keys sampled by the active expire cycle
+    pub(crate) avg_ttl: u64,
+    pub(crate) expired_keys: usize,
+    pub(crate) tracking: AccessTracking,
+    pub(crate) clock: C,
+}
+
+impl RedisDb {
+    pub fn new(id: usize) -> Self {
+        Self::with_clock(id, SystemClock)
+    }
+}
+
+impl<C: Clock> RedisDb<C> {
+    /// An empty database expiring keys by `clock`.
+    pub fn with_clock(id: usize, clock: C) -> Self {
+        RedisDb {
+            id,
+            dict: Dict::new(),
+            expires: Dict::new(),
+            used_memory: 0,
+            written_key: None,
+            avg_ttl: 0,
+            expired_keys: 0,
+            tracking: AccessTracking::Lru,
+            clock,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn access_tracking(&self) -> AccessTracking {
+        self.tracking
+    }
+
+    /// Sets what lookups record in the LRU bits of values. Values already stored keep
+    /// their bits until accessed.
+    pub fn set_access_tracking(&mut self, tracking: AccessTracking) {
+        self.tracking = tracking;
+    }
+
+    /// Whether `key` has an expire time the clock has passed, like `keyIsExpired`.
+    pub fn key_is_expired(&self, key: &[u8]) -> bool {
+        self.get_expire(key).is_some_and(|when| self.clock.now_ms() > when)
+    }
+
+    /// Deletes `key` if it is expired, like `expireIfNeeded`. Returns true if it was.
+    pub fn expire_if_needed(&mut self, key: &[u8]) -> bool {
+        if !self.key_is_expired(key) {
+            return false;
+        }
+        self.delete_expired_key(key);
+        true
+    }
+
+    pub(crate) fn delete_expired_key(&mut self, key: &[u8]) {
+        self.unlink(key);
+        self.expired_keys += 1;
+    }
+
+    /// The number of keys deleted because they expired, lazily or by the active
+    /// expire cycle.
+    pub fn expired_keys(&self) -> usize {
+        self.expired_keys
+    }
+
+    /// The value of `key` for a read-only command, like `lookupKeyRead`.
+    pub fn lookup_read(&mut self, key: &[u8]) -> Option<&Robj> {
+        self.expire_if_needed(key);
+        let val = self.dict.fetch_value(key)?;
+        val.touch(self.tracking, self.clock.now_ms());
+        Some(val)
+    }
+
+    /// The value of `key` for a command about to change it, like `lookupKeyWrite`.
+    /// A value with other handles has to be copied before being changed in place.
+    pub fn lookup_write(&mut self, key: &[u8]) -> Option<&mut Robj> {
+        self.expire_if_needed(key);
+        self.settle_used_memory();
+        let val = self.dict.fetch_value_mut(key)?;
+        val.touch(self.tracking, self.clock.now_ms());
+        self.used_memory -= object_compute_size(val);
+        self.written_key = Some(key.to_vec());
+        Some(val)
+    }
+
+    // Counts the value handed out by lookup_write again, now that the caller is
+    // done changing it
+    fn settle_used_memory(&mut self) {
+        if let Some(key) = self.written_key.take() {
+            if let Some(val) = self.dict.fetch_value(&key) {
+                self.used_memory += object_compute_size(val);
+            }
+        }
+    }
+
+    pub fn exists(&mut self, key: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        self.dict.contains(key)
+    }
+
+    /// Adds `key` if it does not exist yet, like `dbAdd`. Returns false, leaving the
+    /// database unchanged, if it does. The value counts as just accessed.
+    pub fn add(&mut self, key: &[u8], val: Robj) -> bool {
+        if self.exists(key) {
+            return false;
+        }
+        val.init_access(self.tracking, self.clock.now_ms());
+        self.insert(key, val, None);
+        true
+    }
+
+    /// Sets `key` to `val` whether it exists or not, like `setKey`. Its expire time is
+    /// removed unless `keepttl`. The value counts as just accessed, and under LFU it
+    /// takes over the access counter of the value it replaces.
+    pub fn set_key(&mut self, key: &[u8], val: Robj, keepttl: bool) {
+        self.expire_if_needed(key);
+        self.settle_used_memory();
+        match self.dict.fetch_value_mut(key) {
+            Some(old) => {
+                match self.tracking {
+                    AccessTracking::Lru => val.init_access(self.tracking, self.clock.now_ms()),
+                    AccessTracking::Lfu(_) => val.set_lru(old.lru()),
+                }
+                self.used_memory = self.used_memory - object_compute_size(old) + object_compute_size(&val);
+                *old = val;
+            }
+            None => {
+                val.init_access(self.tracking, self.clock.now_ms());
+                self.insert(key, val, None);
+            }
+        }
+        if !keepttl {
+            self.remove_expire(key);
+        }
+    }
+
+    /// Removes `key` and its expire time, like `dbDelete`. Returns false if it did
+    /// not exist.
+    pub fn delete(&mut self, key: &[u8]) -> bool {
+        !self.expire_if_needed(key) && self.unlink(key).is_some()
+    }
+
+    // Removes `key` and hands it back with its value and expire time
+    pub(crate) fn unlink(&mut self, key: &[u8]) -> Option<(Vec<u8>, Robj, Option<u64>)> {
+        self.settle_used_memory();
+        let (key, val) = self.dict.unlink(key)?;
+        self.used_memory -= key.capacity() + object_compute_size(&val);
+        let expire = self.expires.unlink(&key).map(|(expire_key, when)| {
+            self.used_memory -= expire_key.capacity();
+            when
+        });
+        Some((key, val, expire))
+    }
+
+    // Stores a value under a key that does not exist, as is: a value moved from
+    // another key keeps its LRU bits
+    fn insert(&mut self, key: &[u8], val: Robj, expire: Option<u64>) {
+        self.settle_used_memory();
+        let key = key.to_vec();
+        self.used_memory += key.capacity() + object_compute_size(&val);
+        if let Some(when) = expire {
+            self.expires.add(key.clone(), when);
+            self.used_memory += key.capacity();
+        }
+        self.dict.add(key, val);
+    }
+
+    /// Sets the expire time of `key` in unix milliseconds. Returns false, without
+    /// setting anything, if the key does not exist.
+    pub fn set_expire(&mut self, key: &[u8], when: u64) -> bool {
+        if !self.exists(key) {
+            return false;
+        }
+        match self.expires.fetch_value_mut(key) {
+            Some(old) => *old = when,
+            None => {
+                let key = key.to_vec();
+                self.used_memory += key.capacity();
+                self.expires.add(key, when);
+            }
+        }
+        true
+    }
+
+    /// The expire time of `key` in unix milliseconds, if it has one.
+    pub fn get_expire(&self, key: &[u8]) -> Option<u64> {
+        self.expires.fetch_value(key).copied()
+    }
+
+    /// Makes `key` persistent. Returns false if it had no expire time.
+    pub fn remove_expire(&mut self, key: &[u8]) -> bool {
+        match self.expires.unlink(key) {
+            Some((key, _)) => {
+                self.used_memory -= key.capacity();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// A random key, like RANDOMKEY. Expired keys picked along the way are deleted.
+    pub fn random_key(&mut self) -> Option<Vec<u8>> {
+        self.random_key_with_rng(&mut thread_rng())
+    }
+
+    pub fn random_key_with_rng<R: Rng>(&mut self, rng: &mut R) -> Option<Vec<u8>> {
+        loop {
+            let key = self.dict.get_random_key_with_rng(rng).cloned()?;
+            if !self.expire_if_needed(&key) {
+                return Some(key);
+            }
+        }
+    }
+
+    /// The number of keys, like DBSIZE.
+    pub fn dbsize(&self) -> usize {
+        self.dict.size()
+    }
+
+    /// The number of keys with an expire time.
+    pub fn expires_size(&self) -> usize {
+        self.expires.size()
+    }
+
+    pub fn keys(&self) -> hash::Keys<'_, Vec<u8>, Robj> {
+        self.dict.keys()
+    }
+
+    /// RENAME, or RENAMENX if `nx`. The key keeps its expire time, and a value
+    /// already at `dst` is overwritten. Returns false if `nx` and `dst` exists, or
+    /// if `nx` and both keys are the same.
+    ///
+    /// # Errors
+    ///
+    /// Returns "no such key" if `src` does not exist.
+    pub fn rename(&mut self, src: &[u8], dst: &[u8], nx: bool) -> Result<bool, String> {
+        if !self.exists(src) {
+            return Err("no such key".to_string());
+        }
+        if src == dst {
+            return Ok(!nx);
+        }
+        if self.exists(dst) {
+            if nx {
+                return Ok(false);
+            }
+            self.delete(dst);
+        }
+        let (_, val, expire) = self.unlink(src).unwrap();
+        self.insert(dst, val, expire);
+        Ok(true)
+    }
+
+    /// Bytes used by `key`, its value and its expire time, like MEMORY USAGE.
+    pub fn memory_usage(&mut self, key: &[u8]) -> Option<usize> {
+        self.expire_if_needed(key);
+        let val = self.dict.fetch_value(key)?;
+        let mut size = mem::size_of::<(Vec<u8>, Robj)>() + key.len() + object_compute_size(val);
+        if self.expires.contains(key) {
+            size += mem::size_of::<(Vec<u8>, u64)>() + key.len();
+        }
+        Some(size)
+    }
+
+    /// Bytes used by the database: both dicts, the keys and the values. Only the
+    /// value last changed in place is measured again, the rest is a running count.
+    pub fn used_memory(&self) -> usize {
+        let written = self.written_key.as_ref().and_then(|key| self.dict.fetch_value(key));
+        self.dict.mem_usage()
+            + self.expires.mem_usage()
+            + self.used_memory
+            + written.map_or(0, |val| object_compute_size(val))
+    }
+
+    /// Removes every key, returning how many there were.
+    pub fn empty(&mut self) -> usize {
+        let removed = self.dict.size();
+        self.take_dict();
+        removed
+    }
+
+    // Detaches the keyspace for lazy freeing, leaving the database empty
+    fn take_dict(&mut self) -> Dict<Vec<u8>, Robj> {
+        self.used_memory = 0;
+        self.written_key = None;
+        self.expires.release();
+        std::mem::take(&mut self.dict)
+    }
+}
+
+/// The connection state the keyspace commands depend on: the selected database.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Client {
+    db: usize,
+}
+
+impl Client {
+    /// A client using database 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn db(&self) -> usize {
+        self.db
+    }
+}
+
+/// The databases of a server, with the commands working across them.
+///
+/// Keys removed by an async flush are detached at once and freed a batch at a time
+/// by `lazyfree_free_objects`, standing in for the lazyfree thread of Redis.
+///
+/// Keys with an expire time are deleted when accessed after it, and by
+/// `active_expire_cycle` which the server cron runs.
+///
+/// # Examples
+///
+/// ```
+/// use curly_giggle::db::{Client, FlushMode, RedisServer};
+/// use curly_giggle::object::create_string_object;
+///
+/// let mut server = RedisServer::new(16);
+/// let mut client = Client::new();
+/// server.db_mut(0).set_key(b"key", create_string_object(b"value"), false);
+///
+/// assert_eq!(server.move_key(&client, b"key", 3), Ok(true));
+/// server.select_db(&mut client, 3).unwrap();
+/// assert!(server.current_db_mut(&client).exists(b"key"));
+///
+/// assert_eq!(server.flushall(FlushMode::Async), 1);
+/// assert_eq!(server.lazyfree_pending_objects(), 1);
+/// server.lazyfree_free_objects(usize::MAX);
+/// assert_eq!(server.lazyfree_pending_objects(), 0);
+/// ```
+pub struct RedisServer<C = SystemClock> {
+    pub(crate) db: Vec<RedisDb<C>>,
+    pub(crate) config: ServerConfig,
+    lazyfree_queue: VecDeque<hash::IntoIter<Vec<u8>, Robj>>,
+    lazyfreed_objects: usize,
+    pub(crate) expire_state: ActiveExpireState,
+    pub(crate) evict_state: EvictionState,
+    pub(crate) clock: C,
+}
+
+impl Default for RedisServer {
+    fn default() -> Self {
+        Self::new(CONFIG_DEFAULT_DBNUM)
+    }
+}
+
+impl RedisServer {
+    /// A server with `dbnum` empty databases and default settings otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dbnum` is 0.
+    pub fn new(dbnum: usize) -> Self {
+        Self::with_config(ServerConfig {
+            dbnum,
+            ..ServerConfig::default()
+        })
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `config.dbnum` is 0.
+    pub fn with_config(config: ServerConfig) -> Self {
+        Self::with_clock(config, SystemClock)
+    }
+}
+
+impl<C: Clock + Clone> RedisServer<C> {
+    /// A server expiring keys by `clock`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config.dbnum` is 0.
+    pub fn with_clock(config: ServerConfig, clock: C) -> Self {
+        assert!(config.dbnum > 0, "at least one database is needed");
+        let mut db: Vec<_> = (0..config.dbnum).map(|id| RedisDb::with_clock(id, clock.clone())).collect();
+        for db in &mut db {
+            db.tracking = config.access_tracking();
+        }
+        RedisServer {
+            db,
+            config,
+            lazyfree_queue: VecDeque::new(),
+            lazyfreed_objects: 0,
+            expire_state: ActiveExpireState::default(),
+            evict_state: EvictionState::default(),
+            clock,
+        }
+    }
+
+    pub fn config(&self) -> &ServerConfig {
+        &self.config
+    }
+
+    pub fn dbnum(&self) -> usize {
+        self.db.len()
+    }
+
+    /// CONFIG SET maxmemory: the limit in bytes, 0 for none.
+    pub fn set_maxmemory(&mut self, bytes: usize) {
+        self.config.maxmemory = bytes;
+    }
+
+    /// CONFIG SET maxmemory-policy. Switching between LRU and LFU changes what the
+    /// LRU bits of values mean; values keep their old bits until accessed.
+    pub fn set_maxmemory_policy(&mut self, policy: MaxmemoryPolicy) {
+        self.config.maxmemory_policy = policy;
+        let tracking = self.config.access_tracking();
+        for db in &mut self.db {
+            db.tracking = tracking;
+        }
+        self.evict_state.clear_pool();
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `id` is out of range.
+    pub fn db(&self, id: usize) -> &RedisDb<C> {
+        &self.db[id]
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `id` is out of range.
+    pub fn db_mut(&mut self, id: usize) -> &mut RedisDb<C> {
+        &mut self.db[id]
+    }
+
+    /// The database selected by `client`.
+    pub fn current_db(&self, client: &Client) -> &RedisDb<C> {
+        &self.db[client.db]
+    }
+
+    pub fn current_db_mut(&mut self, client: &Client) -> &mut RedisDb<C> {
+        &mut self.db[client.db]
+    }
+
+    fn db_index(&self, id: i64) -> Result<usize, String> {
+        if id < 0 || id >= self.db.len() as i64 {
+            return Err("DB index is out of range".to_string());
+        }
+        Ok(id as usize)
+    }
+
+    /// SELECT: makes `client` use database `id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns "DB index is out of range", leaving the client unchanged.
+    pub fn select_db(&self, client: &mut Client, id: i64) -> Result<(), String> {
+        client.db = self.db_index(id)?;
+        Ok(())
+    }
+
+    /// SWAPDB: exchanges the keys of two databases, so clients using one of them see
+    /// the keys of the other right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns "DB index is out of range" if either index is.
+    pub fn swapdb(&mut self, id1: i64, id2: i64) -> Result<(), String> {
+        let (id1, id2) = (self.db_index(id1)?, self.db_index(id2)?);
+        if id1 != id2 {
+            self.db.swap(id1, id2);
+            // The ids belong to the slots, not to the keys
+            self.db[id1].id = id1;
+            self.db[id2].id = id2;
+        }
+        Ok(())
+    }
+
+    /// MOVE: moves `key` from the database of `client` to `dbid`, along with its
+    /// expire time. Returns false if the key does not exist or already exists in the
+    /// target database.
+    ///
+    /// # Errors
+    ///
+    /// Returns "DB index is out of range", or "source and destination objects are the
+    /// same" if `dbid` is the database of `client`.
+    pub fn move_key(&mut self, client: &Client, key: &[u8], dbid: i64) -> Result<bool, String> {
+        let dst = self.db_index(dbid)?;
+        let src = client.db;
+        if src == dst {
+            return Err("source and destination objects are the same".to_string());
+        }
+        if !self.db[src].exists(key) || self.db[dst].exists(key) {
+            return Ok(false);
+        }
+        let (_, val, expire) = self.db[src].unlink(key).unwrap();
+        self.db[dst].insert(key, val, expire);
+        Ok(true)
+    }
+
+    /// COPY: copies the value of `src`, in the database of `client`, to `dst` in
+    /// database `dbid`, or in the same database if `None`. The copy gets the expire
+    /// time of `src`. Returns false if `src` does not exist, or if `dst` exists and
+    /// not `replace`.
+    ///
+    /// # Errors
+    ///
+    /// Returns "DB index is out of range", or "source and destination objects are the
+    /// same" when copying a key onto itself.
+    pub fn copy(&mut self, client: &Client, src: &[u8], dst: &[u8], dbid: Option<i64>, replace: bool) -> Result<bool, String> {
+        let src_db = client.db;
+        let dst_db = match dbid {
+            Some(id) => self.db_index(id)?,
+            None => src_db,
+        };
+        if src_db == dst_db && src == dst {
+            return Err("source and destination objects are the same".to_string());
+        }
+        let (val, expire) = match self.db[src_db].lookup_read(src) {
+            Some(val) => (dup_object(val), self.db[src_db].get_expire(src)),
+            None => return Ok(false),
+        };
+        let target = &mut self.db[dst_db];
+        if target.exists(dst) {
+            if !replace {
+                return Ok(false);
+            }
+            target.delete(dst);
+        }
+        target.add(dst, val);
+        if let Some(when) = expire {
+            target.set_expire(dst, when);
+        }
+        Ok(true)
+    }
+
+    /// FLUSHDB on the database of `client`, returning the number of keys removed.
+    pub fn flushdb(&mut self, client: &Client, mode: FlushMode) -> usize {
+        self.empty_db(client.db, mode)
+    }
+
+    /// FLUSHALL, returning the number of keys removed.
+    pub fn flushall(&mut self, mode: FlushMode) -> usize {
+        (0..self.db.len()).map(|id| self.empty_db(id, mode)).sum()
+    }
+
+    fn empty_db(&mut self, id: usize, mode: FlushMode) -> usize {
+        match mode {
+            FlushMode::Sync => self.db[id].empty(),
+            FlushMode::Async => {
+                let dict = self.db[id].take_dict();
+                let removed = dict.size();
+                if removed > 0 {
+                    self.lazyfree_queue.push_back(dict.into_iter());
+                }
+                removed
+            }
+        }
+    }
+
+    /// The number of keys deleted because they expired, over every database.
+    pub fn stat_expiredkeys(&self) -> usize {
+        self.db.iter().map(|db| db.expired_keys).sum()
+    }
+
+    /// The number of keys detached by async flushes and not freed yet.
+    pub fn lazyfree_pending_objects(&self) -> usize {
+        self.lazyfree_queue.iter().map(|keys| keys.len()).sum()
+    }
+
+    /// The number of keys freed by `lazyfree_free_objects` so far.
+    pub fn lazyfreed_objects(&self) -> usize {
+        self.lazyfreed_objects
+    }
+
+    /// Frees up to `count` keys detached by async flushes, oldest flush first.
+    /// Returns the number freed.
+    pub fn lazyfree_free_objects(&mut self, count: usize) -> usize {
+        let mut freed = 0;
+        while freed < count {
+            let Some(keys) = self.lazyfree_queue.front_mut() else {
+                break;
+            };
+            match keys.next() {
+                Some(entry) => {
+                    drop(entry);
+                    freed += 1;
+                }
+                None => {
+                    self.lazyfree_queue.pop_front();
+                }
+            }
+        }
+        self.lazyfreed_objects += freed;
+        freed
+    }
+}
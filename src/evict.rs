@@ -0,0 +1,302 @@
+use rand::{thread_rng, Rng};
+
+use crate::clock::Clock;
+use crate::db::{RedisDb, RedisServer, ServerConfig};
+
+/// Candidates kept between eviction rounds.
+pub const EVPOOL_SIZE: usize = 16;
+/// Keys sampled per database to fill the eviction pool.
+pub const CONFIG_DEFAULT_MAXMEMORY_SAMPLES: usize = 5;
+
+/// Which keys may be evicted once `maxmemory` is reached, and in what order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MaxmemoryPolicy {
+    // Least recently used among the keys with an expire time
+    VolatileLru,
+    // Least frequently used among the keys with an expire time
+    VolatileLfu,
+    // Any key with an expire time
+    VolatileRandom,
+    // The key with the nearest expire time
+    VolatileTtl,
+    // Least recently used among all keys
+    AllkeysLru,
+    // Least frequently used among all keys
+    AllkeysLfu,
+    // Any key
+    AllkeysRandom,
+    // Reject writes instead
+    #[default]
+    NoEviction,
+}
+
+impl MaxmemoryPolicy {
+    const ALL: [MaxmemoryPolicy; 8] = [
+        MaxmemoryPolicy::VolatileLru,
+        MaxmemoryPolicy::VolatileLfu,
+        MaxmemoryPolicy::VolatileRandom,
+        MaxmemoryPolicy::VolatileTtl,
+        MaxmemoryPolicy::AllkeysLru,
+        MaxmemoryPolicy::AllkeysLfu,
+        MaxmemoryPolicy::AllkeysRandom,
+        MaxmemoryPolicy::NoEviction,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MaxmemoryPolicy::VolatileLru => "volatile-lru",
+            MaxmemoryPolicy::VolatileLfu => "volatile-lfu",
+            MaxmemoryPolicy::VolatileRandom => "volatile-random",
+            MaxmemoryPolicy::VolatileTtl => "volatile-ttl",
+            MaxmemoryPolicy::AllkeysLru => "allkeys-lru",
+            MaxmemoryPolicy::AllkeysLfu => "allkeys-lfu",
+            MaxmemoryPolicy::AllkeysRandom => "allkeys-random",
+            MaxmemoryPolicy::NoEviction => "noeviction",
+        }
+    }
+
+    /// Parses a `maxmemory-policy` value, ignoring case.
+    ///
+    /// # Errors
+    ///
+    /// Returns the CONFIG SET error listing the valid names.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::evict::MaxmemoryPolicy;
+    ///
+    /// assert_eq!(MaxmemoryPolicy::from_name("allkeys-LRU"), Ok(MaxmemoryPolicy::AllkeysLru));
+    /// assert!(MaxmemoryPolicy::from_name("lru").is_err());
+    /// ```
+    pub fn from_name(name: &str) -> Result<MaxmemoryPolicy, String> {
+        Self::ALL
+            .into_iter()
+            .find(|policy| policy.as_str().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(|policy| policy.as_str()).collect();
+                format!("argument(s) must be one of the following: {}", names.join(", "))
+            })
+    }
+
+    /// Whether keys without an expire time may be evicted.
+    pub fn is_allkeys(&self) -> bool {
+        matches!(
+            self,
+            MaxmemoryPolicy::AllkeysLru | MaxmemoryPolicy::AllkeysLfu | MaxmemoryPolicy::AllkeysRandom
+        )
+    }
+
+    pub fn is_lru(&self) -> bool {
+        matches!(self, MaxmemoryPolicy::VolatileLru | MaxmemoryPolicy::AllkeysLru)
+    }
+
+    pub fn is_lfu(&self) -> bool {
+        matches!(self, MaxmemoryPolicy::VolatileLfu | MaxmemoryPolicy::AllkeysLfu)
+    }
+
+    fn is_random(&self) -> bool {
+        matches!(self, MaxmemoryPolicy::VolatileRandom | MaxmemoryPolicy::AllkeysRandom)
+    }
+}
+
+// A sampled key waiting to be evicted
+struct EvictionPoolEntry {
+    // Higher means a better candidate
+    idle: u64,
+    key: Vec<u8>,
+    dbid: usize,
+}
+
+/// What eviction keeps between calls: the pool of best candidates sampled so far,
+/// sorted by ascending idle score, and the next database for random policies.
+#[derive(Default)]
+pub(crate) struct EvictionState {
+    pool: Vec<EvictionPoolEntry>,
+    next_db: usize,
+    stat_evictedkeys: usize,
+}
+
+impl EvictionState {
+    /// Forgets the pooled candidates, whose scores only make sense under the policy
+    /// that sampled them.
+    pub(crate) fn clear_pool(&mut self) {
+        self.pool.clear();
+    }
+
+    /// Samples keys of `db` into the pool, like `evictionPoolPopulate`. A sampled key
+    /// only makes it in if the pool has room or it beats the worst candidate.
+    fn populate<C: Clock, R: Rng>(&mut self, db: &RedisDb<C>, config: &ServerConfig, rng: &mut R) {
+        let policy = config.maxmemory_policy;
+        let now = db.clock.now_ms();
+        // Sampling nothing would never fill the pool
+        let samples = config.maxmemory_samples.clamp(1, 64);
+        let sample = if policy.is_allkeys() {
+            db.dict.get_some_keys(rng, samples)
+        } else {
+            db.expires.get_some_keys(rng, samples)
+        };
+        for key in sample {
+            let idle = if policy == MaxmemoryPolicy::VolatileTtl {
+                // The sooner the key expires, the better a candidate it is
+                u64::MAX - db.expires.fetch_value(key).copied().unwrap_or(0)
+            } else {
+                let Some(val) = db.dict.fetch_value(key) else {
+                    continue;
+                };
+                if policy.is_lfu() {
+                    255 - val.lfu_decr_and_return(config.lfu_decay_time, now) as u64
+                } else {
+                    val.estimate_idle_time(now)
+                }
+            };
+            self.insert(idle, key, db.id());
+        }
+    }
+
+    fn insert(&mut self, idle: u64, key: &[u8], dbid: usize) {
+        // A key sampled again replaces its older score
+        if let Some(pos) = self.pool.iter().position(|entry| entry.dbid == dbid && entry.key == key) {
+            self.pool.remove(pos);
+        }
+        let mut k = self.pool.partition_point(|entry| entry.idle < idle);
+        if self.pool.len() == EVPOOL_SIZE {
+            // Worse than every candidate in a full pool
+            if k == 0 {
+                return;
+            }
+            // Make room by dropping the worst candidate
+            self.pool.remove(0);
+            k -= 1;
+        }
+        self.pool.insert(k, EvictionPoolEntry { idle, key: key.to_vec(), dbid });
+    }
+}
+
+impl<C: Clock + Clone> RedisServer<C> {
+    /// Evicts keys until the used memory is within `maxmemory`, like
+    /// `performEvictions`, returning how many were evicted. Does nothing without a
+    /// limit.
+    ///
+    /// The LRU, LFU and TTL policies only approximate the best candidate: they sample
+    /// `maxmemory_samples` keys from each database and evict the best one among
+    /// those and the pool of candidates left over by previous rounds.
+    ///
+    /// # Errors
+    ///
+    /// Returns the OOM error if the used memory is over the limit and the policy is
+    /// `noeviction`, or no key left can be evicted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::clock::ManualClock;
+    /// use curly_giggle::db::{RedisServer, ServerConfig};
+    /// use curly_giggle::evict::MaxmemoryPolicy;
+    /// use curly_giggle::object::create_string_object;
+    ///
+    /// let clock = ManualClock::new(0);
+    /// let mut server = RedisServer::with_clock(ServerConfig::default(), clock.clone());
+    /// server.set_maxmemory_policy(MaxmemoryPolicy::AllkeysLru);
+    /// server.db_mut(0).set_key(b"old", create_string_object(b"v"), false);
+    /// clock.advance(5000);
+    /// server.db_mut(0).set_key(b"new", create_string_object(b"v"), false);
+    ///
+    /// server.set_maxmemory(server.used_memory() - 1);
+    /// assert_eq!(server.perform_evictions(), Ok(1));
+    /// assert!(server.db_mut(0).exists(b"new"));
+    /// assert!(!server.db_mut(0).exists(b"old"));
+    /// ```
+    pub fn perform_evictions(&mut self) -> Result<usize, String> {
+        self.perform_evictions_with_rng(&mut thread_rng())
+    }
+
+    pub fn perform_evictions_with_rng<R: Rng>(&mut self, rng: &mut R) -> Result<usize, String> {
+        let maxmemory = self.config.maxmemory;
+        if maxmemory == 0 {
+            return Ok(0);
+        }
+        if self.used_memory() <= maxmemory {
+            return Ok(0);
+        }
+        if self.config.maxmemory_policy == MaxmemoryPolicy::NoEviction {
+            return Err(OOM_ERROR.to_string());
+        }
+
+        let mut keys_freed = 0;
+        while self.used_memory() > maxmemory {
+            let Some((dbid, key)) = self.find_eviction_key(rng) else {
+                break;
+            };
+            self.db[dbid].unlink(&key);
+            self.evict_state.stat_evictedkeys += 1;
+            keys_freed += 1;
+        }
+        if self.used_memory() > maxmemory {
+            return Err(OOM_ERROR.to_string());
+        }
+        Ok(keys_freed)
+    }
+
+    // Picks the next key to evict under the policy, None if there are none left
+    fn find_eviction_key<R: Rng>(&mut self, rng: &mut R) -> Option<(usize, Vec<u8>)> {
+        let policy = self.config.maxmemory_policy;
+        let dbnum = self.db.len();
+        if policy.is_random() {
+            // Go through the databases in turn, so each gives up keys
+            for _ in 0..dbnum {
+                let db = &self.db[self.evict_state.next_db % dbnum];
+                self.evict_state.next_db += 1;
+                let key = if policy.is_allkeys() {
+                    db.dict.get_random_key_with_rng(rng)
+                } else {
+                    db.expires.get_random_key_with_rng(rng)
+                };
+                if let Some(key) = key {
+                    return Some((db.id(), key.clone()));
+                }
+            }
+            return None;
+        }
+
+        loop {
+            let mut total_keys = 0;
+            for db in &self.db {
+                let keys = if policy.is_allkeys() { db.dbsize() } else { db.expires_size() };
+                if keys > 0 {
+                    self.evict_state.populate(db, &self.config, rng);
+                    total_keys += keys;
+                }
+            }
+            if total_keys == 0 {
+                return None;
+            }
+
+            // Best candidate first, skipping keys deleted since they were pooled
+            while let Some(entry) = self.evict_state.pool.pop() {
+                let db = &self.db[entry.dbid];
+                let exists = if policy.is_allkeys() {
+                    db.dict.contains(&entry.key)
+                } else {
+                    db.expires.contains(&entry.key)
+                };
+                if exists {
+                    return Some((entry.dbid, entry.key));
+                }
+            }
+        }
+    }
+
+    /// Bytes used by every database, the base of `maxmemory` checks. Keys detached by
+    /// async flushes no longer count.
+    pub fn used_memory(&self) -> usize {
+        self.db.iter().map(|db| db.used_memory()).sum()
+    }
+
+    /// The number of keys evicted because of `maxmemory`.
+    pub fn stat_evictedkeys(&self) -> usize {
+        self.evict_state.stat_evictedkeys
+    }
+}
+
+const OOM_ERROR: &str = "-OOM command not allowed when used memory > 'maxmemory'.";
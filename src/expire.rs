@@ -0,0 +1,359 @@
+use rand::{thread_rng, Rng};
+
+use crate::clock::Clock;
+use crate::db::{RedisDb, RedisServer};
+
+/// Keys sampled from a database per loop of the active expire cycle.
+pub const ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP: usize = 20;
+/// Time limit of a fast cycle in microseconds.
+pub const ACTIVE_EXPIRE_CYCLE_FAST_DURATION: u64 = 1000;
+/// Share of the CPU time, in percent, a slow cycle may use.
+pub const ACTIVE_EXPIRE_CYCLE_SLOW_TIME_PERC: u64 = 25;
+/// Percentage of expired keys among the sampled ones below which a database is
+/// considered clean enough.
+pub const ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE: u64 = 10;
+/// Databases visited by a cycle, unless there are fewer.
+pub const CRON_DBS_PER_CALL: usize = 16;
+
+/// The NX, XX, GT and LT options of EXPIRE. Unlike for HEXPIRE, XX can be
+/// combined with GT or LT.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExpireFlags {
+    // Only when the key has no expire time
+    pub nx: bool,
+    // Only when the key has an expire time
+    pub xx: bool,
+    // Only when the new expire time is later, a key without one never expires
+    pub gt: bool,
+    // Only when the new expire time is earlier, a key without one never expires
+    pub lt: bool,
+}
+
+impl ExpireFlags {
+    /// Parses the options following the expire time, like
+    /// `parseExtendedExpireArgumentsOrReply`.
+    ///
+    /// # Errors
+    ///
+    /// Returns "Unsupported option <arg>" for an unknown option, and the Redis error
+    /// message for incompatible ones.
+    pub fn from_args<A: AsRef<[u8]>>(args: &[A]) -> Result<ExpireFlags, String> {
+        let mut flags = ExpireFlags::default();
+        for arg in args {
+            let arg = arg.as_ref();
+            match arg.to_ascii_lowercase().as_slice() {
+                b"nx" => flags.nx = true,
+                b"xx" => flags.xx = true,
+                b"gt" => flags.gt = true,
+                b"lt" => flags.lt = true,
+                _ => return Err(format!("Unsupported option {}", String::from_utf8_lossy(arg))),
+            }
+        }
+        flags.validate()?;
+        Ok(flags)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.nx && (self.xx || self.gt || self.lt) {
+            return Err("NX and XX, GT or LT options at the same time are not compatible".to_string());
+        }
+        if self.gt && self.lt {
+            return Err("GT and LT options at the same time are not compatible".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The two kinds of active expire cycle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActiveExpireCycleType {
+    /// Run by the server cron, with a time limit proportional to its period.
+    Slow,
+    /// Run before the event loop sleeps, for at most a millisecond and only if the
+    /// last cycle hit its time limit or left too many expired keys.
+    Fast,
+}
+
+// What the active expire cycle keeps between calls, static variables in Redis
+#[derive(Debug, Default)]
+pub(crate) struct ActiveExpireState {
+    // The database to start the next cycle from
+    current_db: usize,
+    // Whether the last cycle stopped on its time limit
+    timelimit_exit: bool,
+    // Start of the last fast cycle in microseconds
+    last_fast_cycle: u64,
+    // Running average of the ratio of expired keys among the sampled ones
+    stale_perc: f64,
+    time_cap_reached_count: usize,
+}
+
+impl<C: Clock> RedisDb<C> {
+    /// EXPIRE: sets the time to live of `key` in seconds. Returns false if the key
+    /// does not exist or the flags prevent it. An expire time already in the past
+    /// deletes the key.
+    ///
+    /// # Errors
+    ///
+    /// Returns the Redis error message for incompatible flags, or if the expire time
+    /// overflows.
+    pub fn expire(&mut self, key: &[u8], seconds: i64, flags: ExpireFlags) -> Result<bool, String> {
+        let now = self.clock.now_ms() as i64;
+        self.expire_generic(key, seconds, now, true, flags, "expire")
+    }
+
+    /// PEXPIRE: like `expire`, in milliseconds.
+    pub fn pexpire(&mut self, key: &[u8], milliseconds: i64, flags: ExpireFlags) -> Result<bool, String> {
+        let now = self.clock.now_ms() as i64;
+        self.expire_generic(key, milliseconds, now, false, flags, "pexpire")
+    }
+
+    /// EXPIREAT: like `expire`, with a unix time in seconds.
+    pub fn expireat(&mut self, key: &[u8], timestamp: i64, flags: ExpireFlags) -> Result<bool, String> {
+        self.expire_generic(key, timestamp, 0, true, flags, "expireat")
+    }
+
+    /// PEXPIREAT: like `expire`, with a unix time in milliseconds.
+    pub fn pexpireat(&mut self, key: &[u8], timestamp: i64, flags: ExpireFlags) -> Result<bool, String> {
+        self.expire_generic(key, timestamp, 0, false, flags, "pexpireat")
+    }
+
+    // Like `expireGenericCommand`
+    fn expire_generic(
+        &mut self,
+        key: &[u8],
+        when: i64,
+        basetime: i64,
+        seconds: bool,
+        flags: ExpireFlags,
+        cmd: &str,
+    ) -> Result<bool, String> {
+        flags.validate()?;
+        let invalid = || format!("invalid expire time in '{}' command", cmd);
+        let when = if seconds { when.checked_mul(1000).ok_or_else(invalid)? } else { when };
+        let when = when.checked_add(basetime).ok_or_else(invalid)?;
+
+        if !self.exists(key) {
+            return Ok(false);
+        }
+        let current = self.get_expire(key).map(|current| current as i64);
+        if (flags.nx && current.is_some())
+            || (flags.xx && current.is_none())
+            || (flags.gt && current.is_none_or(|current| when <= current))
+            || (flags.lt && current.is_some_and(|current| when >= current))
+        {
+            return Ok(false);
+        }
+
+        if when <= self.clock.now_ms() as i64 {
+            self.delete(key);
+        } else {
+            self.set_expire(key, when as u64);
+        }
+        Ok(true)
+    }
+
+    /// TTL: the remaining time to live of `key` in seconds, rounded, or -1 if it has
+    /// no expire time and -2 if it does not exist.
+    pub fn ttl(&mut self, key: &[u8]) -> i64 {
+        self.ttl_generic(key, false, false)
+    }
+
+    /// PTTL: like `ttl`, in milliseconds.
+    pub fn pttl(&mut self, key: &[u8]) -> i64 {
+        self.ttl_generic(key, true, false)
+    }
+
+    /// EXPIRETIME: the unix time in seconds at which `key` expires, or -1 if it has
+    /// no expire time and -2 if it does not exist.
+    pub fn expiretime(&mut self, key: &[u8]) -> i64 {
+        self.ttl_generic(key, false, true)
+    }
+
+    /// PEXPIRETIME: like `expiretime`, in milliseconds.
+    pub fn pexpiretime(&mut self, key: &[u8]) -> i64 {
+        self.ttl_generic(key, true, true)
+    }
+
+    // Like `ttlGenericCommand`
+    fn ttl_generic(&mut self, key: &[u8], output_ms: bool, output_abs: bool) -> i64 {
+        if !self.exists(key) {
+            return -2;
+        }
+        let Some(when) = self.get_expire(key) else {
+            return -1;
+        };
+        let ttl = if output_abs { when } else { when.saturating_sub(self.clock.now_ms()) } as i64;
+        if output_ms {
+            ttl
+        } else {
+            (ttl + 500) / 1000
+        }
+    }
+
+    /// PERSIST: removes the expire time of `key`. Returns false if the key does not
+    /// exist or has no expire time.
+    pub fn persist(&mut self, key: &[u8]) -> bool {
+        self.exists(key) && self.remove_expire(key)
+    }
+
+    /// The average time to live in milliseconds of the keys sampled by the active
+    /// expire cycle, as shown by INFO keyspace.
+    pub fn avg_ttl(&self) -> u64 {
+        self.avg_ttl
+    }
+}
+
+impl<C: Clock + Clone> RedisServer<C> {
+    /// Deletes expired keys nobody accessed, like `activeExpireCycle`, returning how
+    /// many were deleted.
+    ///
+    /// Each visited database is sampled repeatedly, until fewer than 10% of the
+    /// sampled keys are expired or the cycle runs out of time. The next cycle starts
+    /// from the database after the last one visited. Raising `active_expire_effort`
+    /// samples more keys per loop, gives cycles more time and lowers the acceptable
+    /// share of expired keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::clock::ManualClock;
+    /// use curly_giggle::db::{RedisServer, ServerConfig};
+    /// use curly_giggle::expire::{ActiveExpireCycleType, ExpireFlags};
+    /// use curly_giggle::object::create_string_object;
+    ///
+    /// let clock = ManualClock::new(0);
+    /// let mut server = RedisServer::with_clock(ServerConfig::default(), clock.clone());
+    /// for i in 0..100u8 {
+    ///     let db = server.db_mut(0);
+    ///     db.set_key(&[i], create_string_object(b"v"), false);
+    ///     db.expire(&[i], 10, ExpireFlags::default()).unwrap();
+    /// }
+    ///
+    /// clock.advance(10_001);
+    /// assert_eq!(server.active_expire_cycle(ActiveExpireCycleType::Slow), 100);
+    /// assert_eq!(server.db(0).dbsize(), 0);
+    /// ```
+    pub fn active_expire_cycle(&mut self, cycle_type: ActiveExpireCycleType) -> usize {
+        self.active_expire_cycle_with_rng(cycle_type, &mut thread_rng())
+    }
+
+    pub fn active_expire_cycle_with_rng<R: Rng>(&mut self, cycle_type: ActiveExpireCycleType, rng: &mut R) -> usize {
+        // Effort 1 is the default, each step above it does 25% more work
+        let effort = self.config.active_expire_effort.clamp(1, 10) as u64 - 1;
+        let keys_per_loop = ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP + ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP / 4 * effort as usize;
+        let fast_duration = ACTIVE_EXPIRE_CYCLE_FAST_DURATION + ACTIVE_EXPIRE_CYCLE_FAST_DURATION / 4 * effort;
+        let slow_time_perc = ACTIVE_EXPIRE_CYCLE_SLOW_TIME_PERC + 2 * effort;
+        let acceptable_stale = ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE - effort;
+
+        // Times are in microseconds, as in Redis
+        let start = self.clock.now_ms() * 1000;
+        let state = &mut self.expire_state;
+        if cycle_type == ActiveExpireCycleType::Fast {
+            // Only worth it if the last cycle left work undone, and not more often
+            // than twice its duration
+            if !state.timelimit_exit && state.stale_perc * 100.0 < acceptable_stale as f64 {
+                return 0;
+            }
+            if start < state.last_fast_cycle + fast_duration * 2 {
+                return 0;
+            }
+            state.last_fast_cycle = start;
+        }
+
+        let dbnum = self.db.len();
+        let dbs_per_call = if CRON_DBS_PER_CALL > dbnum || state.timelimit_exit {
+            dbnum
+        } else {
+            CRON_DBS_PER_CALL
+        };
+        let timelimit = match cycle_type {
+            ActiveExpireCycleType::Slow => (slow_time_perc * 1_000_000 / self.config.hz.max(1) as u64 / 100).max(1),
+            ActiveExpireCycleType::Fast => fast_duration,
+        };
+        state.timelimit_exit = false;
+
+        let mut total_sampled = 0;
+        let mut total_expired = 0;
+        let mut iteration = 0u64;
+        let mut dbs_performed = 0;
+        let mut j = 0;
+        while dbs_performed < dbs_per_call && !self.expire_state.timelimit_exit && j < dbnum {
+            j += 1;
+            let db = &mut self.db[self.expire_state.current_db % dbnum];
+            self.expire_state.current_db += 1;
+            if db.expires_size() > 0 {
+                dbs_performed += 1;
+            }
+
+            loop {
+                let num = db.expires_size();
+                if num == 0 {
+                    db.avg_ttl = 0;
+                    break;
+                }
+                let now = db.clock.now_ms();
+                let sample: Vec<Vec<u8>> =
+                    db.expires.get_some_keys(rng, num.min(keys_per_loop)).into_iter().cloned().collect();
+                let sampled = sample.len();
+                let mut expired = 0;
+                let mut ttl_sum = 0;
+                let mut ttl_samples = 0;
+                for key in sample {
+                    // A key sampled twice may be gone already
+                    let Some(when) = db.get_expire(&key) else {
+                        continue;
+                    };
+                    if now > when {
+                        db.delete_expired_key(&key);
+                        expired += 1;
+                    } else {
+                        ttl_sum += when - now;
+                        ttl_samples += 1;
+                    }
+                }
+                total_sampled += sampled;
+                total_expired += expired;
+
+                if let Some(avg_ttl) = ttl_sum.checked_div(ttl_samples) {
+                    if db.avg_ttl == 0 {
+                        db.avg_ttl = avg_ttl;
+                    }
+                    db.avg_ttl = (db.avg_ttl / 50) * 49 + avg_ttl / 50;
+                }
+
+                // Check the time limit once every 16 loops
+                iteration += 1;
+                if iteration & 0xf == 0 && (self.clock.now_ms() * 1000).saturating_sub(start) > timelimit {
+                    self.expire_state.timelimit_exit = true;
+                    self.expire_state.time_cap_reached_count += 1;
+                    break;
+                }
+                // Move on once the database looks mostly clean
+                if expired * 100 <= sampled * acceptable_stale as usize {
+                    break;
+                }
+            }
+        }
+
+        let current_perc = if total_sampled > 0 {
+            total_expired as f64 / total_sampled as f64
+        } else {
+            0.0
+        };
+        let state = &mut self.expire_state;
+        state.stale_perc = current_perc * 0.05 + state.stale_perc * 0.95;
+        total_expired
+    }
+
+    /// Running estimate, in percent, of the expired keys among those sampled by the
+    /// active expire cycle, like `expired_stale_perc` in INFO.
+    pub fn stat_expired_stale_perc(&self) -> f64 {
+        self.expire_state.stale_perc * 100.0
+    }
+
+    /// The number of active expire cycles that stopped on their time limit.
+    pub fn stat_expired_time_cap_reached_count(&self) -> usize {
+        self.expire_state.time_cap_reached_count
+    }
+}
@@ -1,2 +1,8 @@
 pub mod sds;
-pub mod collection; 
\ No newline at end of file
+pub mod collection; 
+pub mod clock;
+pub mod db;
+pub mod evict;
+pub mod expire;
+pub mod object;
+pub mod protocol;
\ No newline at end of file
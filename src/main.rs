@@ -23,6 +23,7 @@ fn exec_sds() {
 }
 
 fn exec_zskiplist() {
+    use curly_giggle::collection::skiplist::zrangespec::ZRangeSpec;
     use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
     let mut list: ZSkipList<i32> = ZSkipList::zsl_create();
     println!("Initial ZSkipList: {:?}", list);
@@ -30,17 +31,17 @@ fn exec_zskiplist() {
     list.zsl_insert(1.0, 1);
     println!("After insert 1:\n{:?}", list);
     println!("ZSkipList length: {}", list.get_len());
-    println!("ZSkipList contains 1: {}", list.contains(&1));
-    println!("ZSkipList contains 2: {}", list.contains(&2));
+    println!("ZSkipList contains 1: {}", list.contains(1.0, &1));
+    println!("ZSkipList contains 2: {}", list.contains(2.0, &2));
 
     list.zsl_insert(2.0, 2);
     println!("After insert 2:\n{:?}", list);
     list.zsl_insert(3.0, 3);
     println!("After insert 3:\n{:?}", list);
-    println!("ZSkipList contains 2: {}", list.contains(&2));
-    println!("ZSkipList contains 3: {}", list.contains(&3));
+    println!("ZSkipList contains 2: {}", list.contains(2.0, &2));
+    println!("ZSkipList contains 3: {}", list.contains(3.0, &3));
 
-    list.zsl_delete(&2);
+    list.zsl_delete(2.0, &2);
     println!("After delete 2:\n{:?}", list);
 
     // Loop
@@ -56,14 +57,14 @@ fn exec_zskiplist() {
         println!("ZSkipList get_element_by_rank {:?} -> {:?}", i, loop_list.zsl_get_element_by_rank(i));
     }
 
-    print!("ZSkipList is in range: {:?}\n", loop_list.zsl_is_in_range(1.0, 15.0));
+    print!("ZSkipList is in range: {:?}\n", loop_list.zsl_is_in_range(&ZRangeSpec::new(1.0, 15.0)));
 
     print!("Zskiplist before delete range by score:\n{:?}", loop_list);
-    print!("Delete range by score: {:?}\n", loop_list.zsl_delete_range_by_score(1.0, 3.0));
+    print!("Delete range by score: {:?}\n", loop_list.zsl_delete_range_by_score(&ZRangeSpec::new(1.0, 3.0)));
     print!("Zskiplist after delete range by score:\n{:?}", loop_list);
-    print!("Delete range by score: {:?}\n", loop_list.zsl_delete_range_by_score(2.0, 10.0));
+    print!("Delete range by score: {:?}\n", loop_list.zsl_delete_range_by_score(&ZRangeSpec::new(2.0, 10.0)));
     print!("Zskiplist after delete range by score:\n{:?}", loop_list);
-    print!("Delete range by score: {:?}\n", loop_list.zsl_delete_range_by_score(21.0, 23.0));
+    print!("Delete range by score: {:?}\n", loop_list.zsl_delete_range_by_score(&ZRangeSpec::new(21.0, 23.0)));
     print!("Zskiplist after delete range by score:\n{:?}", loop_list);
 
     let mut loop_list: ZSkipList<i32> = ZSkipList::zsl_create();
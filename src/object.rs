@@ -0,0 +1,500 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::mem;
+use std::rc::Rc;
+
+use rand::{thread_rng, Rng};
+
+use crate::collection::list::LinkedList;
+use crate::collection::listpack::string_to_int;
+use crate::collection::sampled_size;
+use crate::collection::rhash::{HashEncoding, RHash};
+use crate::collection::set::{RSet, SetEncoding};
+use crate::collection::zset::{ZSet, ZSetEncoding};
+use crate::protocol::resp::Reply;
+use crate::sds::SDS;
+
+/// Integers from 0 to `OBJ_SHARED_INTEGERS - 1` are shared objects.
+pub const OBJ_SHARED_INTEGERS: i64 = 10000;
+/// The refcount reported for shared objects, which are never freed.
+pub const OBJ_SHARED_REFCOUNT: i32 = i32::MAX;
+/// Strings up to this length are embstr encoded.
+pub const OBJ_ENCODING_EMBSTR_SIZE_LIMIT: usize = 44;
+/// Elements `object_compute_size` measures in big containers to estimate the rest.
+pub const OBJ_COMPUTE_SIZE_DEF_SAMPLES: usize = 5;
+
+pub const LRU_BITS: u32 = 24;
+/// Largest value of the LRU clock, which then wraps around.
+pub const LRU_CLOCK_MAX: u32 = (1 << LRU_BITS) - 1;
+/// Resolution of the LRU clock in milliseconds.
+pub const LRU_CLOCK_RESOLUTION: u64 = 1000;
+/// Access counter of new objects under an LFU policy, so they are not evicted
+/// before having a chance to be accessed.
+pub const LFU_INIT_VAL: u8 = 5;
+
+/// The type of a value, as reported by TYPE.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectType {
+    String,
+    List,
+    Set,
+    ZSet,
+    Hash,
+    Stream,
+}
+
+impl ObjectType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjectType::String => "string",
+            ObjectType::List => "list",
+            ObjectType::Set => "set",
+            ObjectType::ZSet => "zset",
+            ObjectType::Hash => "hash",
+            ObjectType::Stream => "stream",
+        }
+    }
+}
+
+/// How a value is stored, as reported by OBJECT ENCODING.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectEncoding {
+    /// A string in its own SDS.
+    Raw,
+    /// A short string, allocated along with its object in Redis.
+    Embstr,
+    /// A string holding an integer, stored as one.
+    Int,
+    Listpack,
+    /// A hash listpack with field expire times.
+    ListpackEx,
+    /// A linked list, standing for the quicklist.
+    Quicklist,
+    Intset,
+    Hashtable,
+    Skiplist,
+}
+
+impl ObjectEncoding {
+    /// The name OBJECT ENCODING replies with, like `strEncoding`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjectEncoding::Raw => "raw",
+            ObjectEncoding::Embstr => "embstr",
+            ObjectEncoding::Int => "int",
+            ObjectEncoding::Listpack => "listpack",
+            ObjectEncoding::ListpackEx => "listpackex",
+            ObjectEncoding::Quicklist => "quicklist",
+            ObjectEncoding::Intset => "intset",
+            ObjectEncoding::Hashtable => "hashtable",
+            ObjectEncoding::Skiplist => "skiplist",
+        }
+    }
+}
+
+/// `lfu-log-factor` and `lfu-decay-time` in redis.conf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LfuConfig {
+    // How many hits it takes to saturate the access counter
+    pub lfu_log_factor: u32,
+    // Minutes after which the access counter is decremented, 0 to never decay
+    pub lfu_decay_time: u32,
+}
+
+impl Default for LfuConfig {
+    fn default() -> Self {
+        LfuConfig {
+            lfu_log_factor: 10,
+            lfu_decay_time: 1,
+        }
+    }
+}
+
+/// What the LRU bits of objects hold, which depends on the maxmemory policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessTracking {
+    /// The LRU clock at the last access.
+    Lru,
+    /// The time of the last decrement in minutes, in the high 16 bits, and a
+    /// logarithmic access counter, in the low 8 bits.
+    Lfu(LfuConfig),
+}
+
+/// The value of an object, one variant per encoding of strings and one per type of
+/// container.
+pub enum ObjectValue {
+    Raw(SDS),
+    Embstr(SDS),
+    Int(i64),
+    List(LinkedList<Vec<u8>>),
+    Set(RSet),
+    ZSet(ZSet<Vec<u8>>),
+    Hash(RHash),
+}
+
+/// A value stored in the keyspace, like `robj`: a type and encoding, the LRU bits
+/// and a refcount. Objects are handed around as `Robj`, the refcount being the
+/// number of `Rc` handles.
+///
+/// # Examples
+///
+/// ```
+/// use curly_giggle::object::*;
+///
+/// let obj = create_string_object(b"12345");
+/// assert_eq!(obj.encoding(), ObjectEncoding::Embstr);
+///
+/// let obj = try_object_encoding(obj, true);
+/// assert_eq!(obj.encoding(), ObjectEncoding::Int);
+/// assert_eq!(obj.string_value().unwrap().as_ref(), b"12345");
+/// assert_eq!(get_refcount(&obj), 1);
+/// ```
+pub struct RedisObject {
+    value: ObjectValue,
+    lru: Cell<u32>,
+    shared: bool,
+}
+
+pub type Robj = Rc<RedisObject>;
+
+thread_local! {
+    // Like `shared.integers`
+    static SHARED_INTEGERS: Vec<Robj> = (0..OBJ_SHARED_INTEGERS)
+        .map(|value| Rc::new(RedisObject { value: ObjectValue::Int(value), lru: Cell::new(0), shared: true }))
+        .collect();
+}
+
+/// The shared object holding `value`, if there is one.
+pub fn shared_integer(value: i64) -> Option<Robj> {
+    if (0..OBJ_SHARED_INTEGERS).contains(&value) {
+        Some(SHARED_INTEGERS.with(|integers| integers[value as usize].clone()))
+    } else {
+        None
+    }
+}
+
+/// Wraps a value in a new object, with its LRU bits cleared.
+pub fn create_object(value: ObjectValue) -> Robj {
+    Rc::new(RedisObject {
+        value,
+        lru: Cell::new(0),
+        shared: false,
+    })
+}
+
+pub fn create_raw_string_object(s: &[u8]) -> Robj {
+    create_object(ObjectValue::Raw(SDS::sdsnewlen(s)))
+}
+
+pub fn create_embedded_string_object(s: &[u8]) -> Robj {
+    create_object(ObjectValue::Embstr(SDS::sdsnewlen(s)))
+}
+
+/// A string object, embstr encoded when short enough and raw otherwise, like
+/// `createStringObject`.
+pub fn create_string_object(s: &[u8]) -> Robj {
+    if s.len() <= OBJ_ENCODING_EMBSTR_SIZE_LIMIT {
+        create_embedded_string_object(s)
+    } else {
+        create_raw_string_object(s)
+    }
+}
+
+/// An int encoded string object, shared if `value` is small enough.
+pub fn create_string_object_from_long_long(value: i64) -> Robj {
+    create_string_object_from_long_long_with_options(value, true)
+}
+
+/// Like `create_string_object_from_long_long`, sharing the object only if
+/// `allow_shared`. Values stored in the keyspace must not be shared under a
+/// maxmemory policy, since every key needs its own LRU bits.
+pub fn create_string_object_from_long_long_with_options(value: i64, allow_shared: bool) -> Robj {
+    match shared_integer(value) {
+        Some(obj) if allow_shared => obj,
+        _ => create_object(ObjectValue::Int(value)),
+    }
+}
+
+pub fn create_list_object() -> Robj {
+    create_object(ObjectValue::List(LinkedList::new()))
+}
+
+pub fn create_set_object() -> Robj {
+    create_object(ObjectValue::Set(RSet::new()))
+}
+
+pub fn create_zset_object() -> Robj {
+    create_object(ObjectValue::ZSet(ZSet::new()))
+}
+
+pub fn create_hash_object() -> Robj {
+    create_object(ObjectValue::Hash(RHash::new()))
+}
+
+/// A copy of `obj` with the same type and encoding, for COPY. The copy is never
+/// shared and starts with cleared LRU bits.
+pub fn dup_object(obj: &RedisObject) -> Robj {
+    create_object(match &obj.value {
+        ObjectValue::Raw(s) => ObjectValue::Raw(SDS::sdsnewlen(s.as_bytes())),
+        ObjectValue::Embstr(s) => ObjectValue::Embstr(SDS::sdsnewlen(s.as_bytes())),
+        ObjectValue::Int(value) => ObjectValue::Int(*value),
+        ObjectValue::List(list) => ObjectValue::List(list.clone()),
+        ObjectValue::Set(set) => ObjectValue::Set(set.clone()),
+        ObjectValue::ZSet(zset) => ObjectValue::ZSet(zset.clone()),
+        ObjectValue::Hash(hash) => ObjectValue::Hash(hash.clone()),
+    })
+}
+
+/// Bytes used by `obj`, like `objectComputeSize`: the object itself, the SDS
+/// buffer of strings and the size of containers. The elements of lists and hash
+/// tables are estimated from the first `OBJ_COMPUTE_SIZE_DEF_SAMPLES` of them, so
+/// big values are measured in constant time.
+pub fn object_compute_size(obj: &RedisObject) -> usize {
+    let samples = OBJ_COMPUTE_SIZE_DEF_SAMPLES;
+    // The object and the refcounts of its `Rc`
+    let header = mem::size_of::<RedisObject>() + 2 * mem::size_of::<usize>();
+    header
+        + match &obj.value {
+            ObjectValue::Raw(s) | ObjectValue::Embstr(s) => s.sdsalloc(),
+            ObjectValue::Int(_) => 0,
+            ObjectValue::List(list) => {
+                // A node holds the element and links to both neighbours
+                let node = mem::size_of::<Vec<u8>>() + 2 * mem::size_of::<usize>();
+                sampled_size(list.iter().map(|elem| node + elem.capacity()), samples)
+            }
+            ObjectValue::Set(set) => set.mem_usage(samples),
+            ObjectValue::ZSet(zset) => zset.mem_usage(samples),
+            ObjectValue::Hash(hash) => hash.mem_usage(samples),
+        }
+}
+
+/// Tries to store a string object in less space, like `tryObjectEncoding`: as a
+/// shared integer if `allow_shared`, as an integer, or as an embstr. Objects that are
+/// not strings, or that have other handles, are returned unchanged.
+pub fn try_object_encoding(obj: Robj, allow_shared: bool) -> Robj {
+    let bytes = match &obj.value {
+        ObjectValue::Raw(s) | ObjectValue::Embstr(s) if Rc::strong_count(&obj) == 1 => s.as_bytes(),
+        _ => return obj,
+    };
+    if bytes.len() <= 20 {
+        if let Some(value) = string_to_int(bytes) {
+            return create_string_object_from_long_long_with_options(value, allow_shared);
+        }
+    }
+    if bytes.len() <= OBJ_ENCODING_EMBSTR_SIZE_LIMIT && obj.encoding() == ObjectEncoding::Raw {
+        return create_embedded_string_object(bytes);
+    }
+    obj
+}
+
+/// The refcount OBJECT REFCOUNT replies with.
+pub fn get_refcount(obj: &Robj) -> i32 {
+    if obj.shared {
+        OBJ_SHARED_REFCOUNT
+    } else {
+        Rc::strong_count(obj).min(i32::MAX as usize) as i32
+    }
+}
+
+/// The LRU clock at `now_ms`, like `getLRUClock`.
+pub fn get_lru_clock(now_ms: u64) -> u32 {
+    ((now_ms / LRU_CLOCK_RESOLUTION) & LRU_CLOCK_MAX as u64) as u32
+}
+
+/// The time in minutes kept by the LFU bits, like `LFUGetTimeInMinutes`.
+pub fn lfu_get_time_in_minutes(now_ms: u64) -> u32 {
+    ((now_ms / 1000 / 60) & 65535) as u32
+}
+
+/// Minutes since `ldt`, wrapping around like the 16 bits holding it, like
+/// `LFUTimeElapsed`.
+pub fn lfu_time_elapsed(ldt: u32, now_ms: u64) -> u32 {
+    let now = lfu_get_time_in_minutes(now_ms);
+    if now >= ldt {
+        now - ldt
+    } else {
+        65535 - ldt + now
+    }
+}
+
+/// Increments an access counter with a probability decreasing as it grows, so that
+/// 8 bits count up to millions of hits, like `LFULogIncr`.
+pub fn lfu_log_incr<R: Rng>(counter: u8, lfu_log_factor: u32, rng: &mut R) -> u8 {
+    if counter == 255 {
+        return 255;
+    }
+    let r: f64 = rng.gen();
+    let baseval = (counter as f64 - LFU_INIT_VAL as f64).max(0.0);
+    let p = 1.0 / (baseval * lfu_log_factor as f64 + 1.0);
+    if r < p {
+        counter + 1
+    } else {
+        counter
+    }
+}
+
+impl RedisObject {
+    pub fn obj_type(&self) -> ObjectType {
+        match self.value {
+            ObjectValue::Raw(_) | ObjectValue::Embstr(_) | ObjectValue::Int(_) => ObjectType::String,
+            ObjectValue::List(_) => ObjectType::List,
+            ObjectValue::Set(_) => ObjectType::Set,
+            ObjectValue::ZSet(_) => ObjectType::ZSet,
+            ObjectValue::Hash(_) => ObjectType::Hash,
+        }
+    }
+
+    pub fn encoding(&self) -> ObjectEncoding {
+        match &self.value {
+            ObjectValue::Raw(_) => ObjectEncoding::Raw,
+            ObjectValue::Embstr(_) => ObjectEncoding::Embstr,
+            ObjectValue::Int(_) => ObjectEncoding::Int,
+            ObjectValue::List(_) => ObjectEncoding::Quicklist,
+            ObjectValue::Set(set) => match set.encoding() {
+                SetEncoding::IntSet => ObjectEncoding::Intset,
+                SetEncoding::Listpack => ObjectEncoding::Listpack,
+                SetEncoding::HashTable => ObjectEncoding::Hashtable,
+            },
+            ObjectValue::ZSet(zset) => match zset.encoding() {
+                // Stands in for the listpack of small sorted sets
+                ZSetEncoding::SortedArray => ObjectEncoding::Listpack,
+                ZSetEncoding::SkipList => ObjectEncoding::Skiplist,
+            },
+            ObjectValue::Hash(hash) => match hash.encoding() {
+                HashEncoding::Listpack => ObjectEncoding::Listpack,
+                HashEncoding::ListpackEx => ObjectEncoding::ListpackEx,
+                HashEncoding::HashTable => ObjectEncoding::Hashtable,
+            },
+        }
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    pub fn value(&self) -> &ObjectValue {
+        &self.value
+    }
+
+    /// The value, to be modified in place. Shared objects must be copied first.
+    pub fn value_mut(&mut self) -> &mut ObjectValue {
+        debug_assert!(!self.shared, "shared objects are immutable");
+        &mut self.value
+    }
+
+    /// The bytes of a string object, integers formatted in decimal.
+    pub fn string_value(&self) -> Option<Cow<'_, [u8]>> {
+        match &self.value {
+            ObjectValue::Raw(s) | ObjectValue::Embstr(s) => Some(Cow::Borrowed(s.as_bytes())),
+            ObjectValue::Int(value) => Some(Cow::Owned(value.to_string().into_bytes())),
+            _ => None,
+        }
+    }
+
+    /// The raw 24 LRU bits.
+    pub fn lru(&self) -> u32 {
+        self.lru.get()
+    }
+
+    pub fn set_lru(&self, lru: u32) {
+        self.lru.set(lru & LRU_CLOCK_MAX);
+    }
+
+    /// Sets the LRU bits of a new object, like `createObject`.
+    pub fn init_access(&self, tracking: AccessTracking, now_ms: u64) {
+        match tracking {
+            AccessTracking::Lru => self.set_lru(get_lru_clock(now_ms)),
+            AccessTracking::Lfu(_) => self.set_lru((lfu_get_time_in_minutes(now_ms) << 8) | LFU_INIT_VAL as u32),
+        }
+    }
+
+    /// Records an access, like `lookupKey` does.
+    pub fn touch(&self, tracking: AccessTracking, now_ms: u64) {
+        self.touch_with_rng(tracking, now_ms, &mut thread_rng())
+    }
+
+    /// Like `touch`, drawing from the given random number generator.
+    pub fn touch_with_rng<R: Rng>(&self, tracking: AccessTracking, now_ms: u64, rng: &mut R) {
+        match tracking {
+            AccessTracking::Lru => self.set_lru(get_lru_clock(now_ms)),
+            AccessTracking::Lfu(config) => self.update_lfu(config, now_ms, rng),
+        }
+    }
+
+    /// Decays the access counter then increments it, like `updateLFU`.
+    pub fn update_lfu<R: Rng>(&self, config: LfuConfig, now_ms: u64, rng: &mut R) {
+        let counter = self.lfu_decr_and_return(config.lfu_decay_time, now_ms);
+        let counter = lfu_log_incr(counter, config.lfu_log_factor, rng);
+        self.set_lru((lfu_get_time_in_minutes(now_ms) << 8) | counter as u32);
+    }
+
+    /// The access counter, less one for each `lfu_decay_time` minutes since it was
+    /// last decremented, like `LFUDecrAndReturn`. The object is left unchanged.
+    pub fn lfu_decr_and_return(&self, lfu_decay_time: u32, now_ms: u64) -> u8 {
+        let ldt = self.lru() >> 8;
+        let counter = (self.lru() & 255) as u8;
+        let num_periods = lfu_time_elapsed(ldt, now_ms).checked_div(lfu_decay_time).unwrap_or(0);
+        counter.saturating_sub(num_periods.min(255) as u8)
+    }
+
+    /// Milliseconds since the last access under an LRU policy, like
+    /// `estimateObjectIdleTime`.
+    pub fn estimate_idle_time(&self, now_ms: u64) -> u64 {
+        let lruclock = get_lru_clock(now_ms);
+        let lru = self.lru();
+        if lruclock >= lru {
+            (lruclock - lru) as u64 * LRU_CLOCK_RESOLUTION
+        } else {
+            (lruclock as u64 + (LRU_CLOCK_MAX - lru) as u64) * LRU_CLOCK_RESOLUTION
+        }
+    }
+}
+
+/// OBJECT ENCODING, REFCOUNT, IDLETIME and FREQ on `obj`, `None` standing for a
+/// missing key.
+///
+/// # Errors
+///
+/// IDLETIME needs an LRU tracking and FREQ an LFU one. Other subcommands are unknown.
+///
+/// # Examples
+///
+/// ```
+/// use curly_giggle::object::{create_string_object, object_command, AccessTracking};
+/// use curly_giggle::protocol::resp::Reply;
+///
+/// let obj = create_string_object(b"hello");
+/// obj.init_access(AccessTracking::Lru, 0);
+/// assert_eq!(object_command("ENCODING", Some(&obj), AccessTracking::Lru, 0), Ok(Reply::bulk("embstr")));
+/// assert_eq!(object_command("idletime", Some(&obj), AccessTracking::Lru, 61_000), Ok(Reply::Integer(61)));
+/// assert_eq!(object_command("REFCOUNT", None, AccessTracking::Lru, 0), Ok(Reply::Null));
+/// ```
+pub fn object_command(
+    subcommand: &str,
+    obj: Option<&Robj>,
+    tracking: AccessTracking,
+    now_ms: u64,
+) -> Result<Reply, String> {
+    let name = subcommand.to_ascii_lowercase();
+    if !matches!(name.as_str(), "encoding" | "refcount" | "idletime" | "freq") {
+        return Err(format!("unknown subcommand '{:.128}'. Try OBJECT HELP.", subcommand));
+    }
+    let obj = match obj {
+        Some(obj) => obj,
+        None => return Ok(Reply::Null),
+    };
+    match (name.as_str(), tracking) {
+        ("encoding", _) => Ok(Reply::bulk(obj.encoding().as_str())),
+        ("refcount", _) => Ok(Reply::Integer(get_refcount(obj) as i64)),
+        ("idletime", AccessTracking::Lfu(_)) => Err("An LRU maxmemory policy is not selected, access time not tracked. \
+             Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust."
+            .to_string()),
+        ("idletime", AccessTracking::Lru) => Ok(Reply::Integer((obj.estimate_idle_time(now_ms) / 1000) as i64)),
+        ("freq", AccessTracking::Lfu(config)) => {
+            Ok(Reply::Integer(obj.lfu_decr_and_return(config.lfu_decay_time, now_ms) as i64))
+        }
+        _ => Err("An LFU maxmemory policy is not selected, access frequency not tracked. \
+             Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust."
+            .to_string()),
+    }
+}
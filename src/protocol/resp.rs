@@ -0,0 +1,556 @@
+use crate::sds::SDS;
+
+/// Longest inline request, or count line of a multibulk request, before the
+/// newline shows up.
+pub const PROTO_INLINE_MAX_SIZE: usize = 1024 * 64;
+
+/// Limits on the requests `RequestParser` accepts, like `proto-max-bulk-len` in
+/// redis.conf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RespConfig {
+    // Maximum length in bytes of one argument
+    pub proto_max_bulk_len: i64,
+    // Maximum number of arguments of a multibulk request
+    pub max_multibulk_len: i64,
+}
+
+impl Default for RespConfig {
+    fn default() -> Self {
+        RespConfig {
+            proto_max_bulk_len: 512 * 1024 * 1024,
+            max_multibulk_len: i32::MAX as i64,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RequestType {
+    Inline,
+    Multibulk,
+}
+
+/// Splits the bytes read from a client into commands, accepting both multibulk
+/// requests (`*2\r\n$4\r\nECHO\r\n$2\r\nhi\r\n`) and inline ones (`ECHO hi\r\n`).
+///
+/// Input can be fed in pieces of any size. The parser remembers how far it got in
+/// the current request, the number of arguments and the length of the next one, so
+/// that each new piece only costs the parsing of the new bytes.
+///
+/// # Examples
+///
+/// ```
+/// use curly_giggle::protocol::resp::RequestParser;
+///
+/// let mut parser = RequestParser::new();
+/// parser.feed(b"*2\r\n$4\r\nECHO\r\n$5\r\nhel");
+/// assert!(parser.next_command().unwrap().is_none());
+///
+/// parser.feed(b"lo\r\nPING\r\n");
+/// let argv = parser.next_command().unwrap().unwrap();
+/// assert_eq!(argv.iter().map(|arg| arg.as_bytes()).collect::<Vec<_>>(), vec![&b"ECHO"[..], b"hello"]);
+/// let argv = parser.next_command().unwrap().unwrap();
+/// assert_eq!(argv[0].as_bytes(), b"PING");
+/// ```
+pub struct RequestParser {
+    config: RespConfig,
+    querybuf: Vec<u8>,
+    // Offset of the first byte not parsed yet
+    qb_pos: usize,
+    // Offset up to which the current line was searched for its terminator, so
+    // that a line arriving in pieces is not scanned again from its start
+    scanned: usize,
+    req_type: Option<RequestType>,
+    // Arguments left to read in the current multibulk request
+    multibulk_len: i64,
+    // Length of the argument being read, -1 before its header is parsed
+    bulk_len: i64,
+    argv: Vec<SDS>,
+}
+
+impl Default for RequestParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestParser {
+    pub fn new() -> Self {
+        Self::with_config(RespConfig::default())
+    }
+
+    pub fn with_config(config: RespConfig) -> Self {
+        RequestParser {
+            config,
+            querybuf: Vec::new(),
+            qb_pos: 0,
+            scanned: 0,
+            req_type: None,
+            multibulk_len: 0,
+            bulk_len: -1,
+            argv: Vec::new(),
+        }
+    }
+
+    /// Appends bytes read from the client.
+    pub fn feed(&mut self, data: &[u8]) {
+        // Drop what was already parsed, like `sdsrange` on the query buffer
+        if self.qb_pos > 0 {
+            self.querybuf.drain(..self.qb_pos);
+            self.scanned = self.scanned.saturating_sub(self.qb_pos);
+            self.qb_pos = 0;
+        }
+        self.querybuf.extend_from_slice(data);
+    }
+
+    /// Number of bytes fed but not consumed by a complete command yet.
+    pub fn pending(&self) -> usize {
+        self.querybuf.len() - self.qb_pos
+    }
+
+    /// Parses the next complete command, like `processInputBuffer`. Returns
+    /// `Ok(None)` when more input is needed. Empty requests are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns the protocol error Redis replies with before closing the connection.
+    /// The parser should not be used after an error.
+    pub fn next_command(&mut self) -> Result<Option<Vec<SDS>>, String> {
+        while self.qb_pos < self.querybuf.len() {
+            let req_type = *self.req_type.get_or_insert(if self.querybuf[self.qb_pos] == b'*' {
+                RequestType::Multibulk
+            } else {
+                RequestType::Inline
+            });
+            let complete = match req_type {
+                RequestType::Inline => self.process_inline_buffer()?,
+                RequestType::Multibulk => self.process_multibulk_buffer()?,
+            };
+            if !complete {
+                return Ok(None);
+            }
+            let argv = std::mem::take(&mut self.argv);
+            self.reset();
+            if !argv.is_empty() {
+                return Ok(Some(argv));
+            }
+        }
+        Ok(None)
+    }
+
+    fn reset(&mut self) {
+        self.req_type = None;
+        self.multibulk_len = 0;
+        self.bulk_len = -1;
+        self.scanned = self.qb_pos;
+    }
+
+    // Offset of the next `byte` at or after the parse position, only searching the
+    // bytes fed since the last search
+    fn find(&mut self, byte: u8) -> Option<usize> {
+        let start = self.scanned.max(self.qb_pos);
+        match self.querybuf[start..].iter().position(|&c| c == byte) {
+            Some(i) => {
+                self.scanned = start + i;
+                Some(start + i)
+            }
+            None => {
+                self.scanned = self.querybuf.len();
+                None
+            }
+        }
+    }
+
+    // Like `processInlineBuffer`: returns true once a whole line was read into argv
+    fn process_inline_buffer(&mut self) -> Result<bool, String> {
+        let newline = match self.find(b'\n') {
+            Some(newline) => newline,
+            None => {
+                if self.pending() > PROTO_INLINE_MAX_SIZE {
+                    return Err("Protocol error: too big inline request".to_string());
+                }
+                return Ok(false);
+            }
+        };
+        let mut line = &self.querybuf[self.qb_pos..newline];
+        if let Some(stripped) = line.strip_suffix(b"\r") {
+            line = stripped;
+        }
+        let args = split_args(line).ok_or("Protocol error: unbalanced quotes in request")?;
+        self.argv = args.iter().map(|arg| SDS::sdsnewlen(arg)).collect();
+        self.qb_pos = newline + 1;
+        Ok(true)
+    }
+
+    // Reads the line starting after the type byte at the parse position, and parses
+    // it as an integer. Returns None until the whole line is there.
+    fn read_count_line(&mut self, too_big: &str) -> Result<Option<Option<i64>>, String> {
+        let newline = match self.find(b'\r') {
+            Some(newline) if newline + 1 < self.querybuf.len() => newline,
+            Some(_) => return Ok(None),
+            None => {
+                if self.pending() > PROTO_INLINE_MAX_SIZE {
+                    return Err(too_big.to_string());
+                }
+                return Ok(None);
+            }
+        };
+        let count = parse_count(&self.querybuf[self.qb_pos + 1..newline]);
+        self.qb_pos = newline + 2;
+        Ok(Some(count))
+    }
+
+    // Like `processMultibulkBuffer`: returns true once every argument was read
+    fn process_multibulk_buffer(&mut self) -> Result<bool, String> {
+        if self.multibulk_len == 0 {
+            let count = match self.read_count_line("Protocol error: too big mbulk count string")? {
+                Some(Some(count)) if count <= self.config.max_multibulk_len => count,
+                Some(_) => return Err("Protocol error: invalid multibulk length".to_string()),
+                None => return Ok(false),
+            };
+            if count <= 0 {
+                return Ok(true);
+            }
+            self.multibulk_len = count;
+            self.argv = Vec::with_capacity(count.min(1024) as usize);
+        }
+
+        while self.multibulk_len > 0 {
+            if self.bulk_len == -1 {
+                if self.qb_pos >= self.querybuf.len() {
+                    return Ok(false);
+                }
+                let first = self.querybuf[self.qb_pos];
+                let len = match self.read_count_line("Protocol error: too big bulk count string")? {
+                    Some(len) => len,
+                    None => return Ok(false),
+                };
+                if first != b'$' {
+                    return Err(format!("Protocol error: expected '$', got '{}'", first as char));
+                }
+                self.bulk_len = match len {
+                    Some(len) if (0..=self.config.proto_max_bulk_len).contains(&len) => len,
+                    _ => return Err("Protocol error: invalid bulk length".to_string()),
+                };
+            }
+
+            // The argument and its trailing CRLF
+            let len = self.bulk_len as usize;
+            if self.pending() < len + 2 {
+                return Ok(false);
+            }
+            self.argv.push(SDS::sdsnewlen(&self.querybuf[self.qb_pos..self.qb_pos + len]));
+            self.qb_pos += len + 2;
+            self.bulk_len = -1;
+            self.multibulk_len -= 1;
+        }
+        Ok(true)
+    }
+}
+
+// Parses a count the way `string2ll` does
+fn parse_count(s: &[u8]) -> Option<i64> {
+    if s.is_empty() || s.len() > 20 {
+        return None;
+    }
+    let digits = s.strip_prefix(b"-").unwrap_or(s);
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) || (digits[0] == b'0' && s.len() > 1) {
+        return None;
+    }
+    std::str::from_utf8(s).ok()?.parse().ok()
+}
+
+fn is_space(c: u8) -> bool {
+    matches!(c, b' ' | b'\n' | b'\r' | b'\t' | 0x0B | 0x0C)
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Splits an inline request into arguments like `sdssplitargs`: arguments are
+/// separated by spaces or NUL bytes and may be quoted. Double quotes understand the `\n`, `\r`,
+/// `\t`, `\b`, `\a` and `\xHH` escapes, single quotes only `\'`. Returns None on
+/// unbalanced quotes, or a closing quote not followed by a space.
+///
+/// # Examples
+///
+/// ```
+/// use curly_giggle::protocol::resp::split_args;
+///
+/// let args = split_args(br#"SET "a key" 'it\'s' "\x41\n""#).unwrap();
+/// assert_eq!(args, vec![b"SET".to_vec(), b"a key".to_vec(), b"it's".to_vec(), b"A\n".to_vec()]);
+/// assert_eq!(split_args(b"GET \"key"), None);
+/// ```
+pub fn split_args(line: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut args = Vec::new();
+    let mut p = 0;
+    loop {
+        // NUL ends the string in Redis, here it separates arguments
+        while p < line.len() && (is_space(line[p]) || line[p] == b'\0') {
+            p += 1;
+        }
+        if p == line.len() {
+            return Some(args);
+        }
+        let mut current = Vec::new();
+        let mut in_quotes = false;
+        let mut in_single_quotes = false;
+        loop {
+            if in_quotes {
+                let c = *line.get(p)?;
+                if c == b'\\'
+                    && p + 3 < line.len()
+                    && line[p + 1] == b'x'
+                    && hex_digit(line[p + 2]).is_some()
+                    && hex_digit(line[p + 3]).is_some()
+                {
+                    current.push(hex_digit(line[p + 2])? * 16 + hex_digit(line[p + 3])?);
+                    p += 3;
+                } else if c == b'\\' && p + 1 < line.len() {
+                    p += 1;
+                    current.push(match line[p] {
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'b' => 0x08,
+                        b'a' => 0x07,
+                        other => other,
+                    });
+                } else if c == b'"' {
+                    // The closing quote must be followed by a space or nothing
+                    if p + 1 < line.len() && !is_space(line[p + 1]) {
+                        return None;
+                    }
+                    p += 1;
+                    break;
+                } else {
+                    current.push(c);
+                }
+            } else if in_single_quotes {
+                let c = *line.get(p)?;
+                if c == b'\\' && line.get(p + 1) == Some(&b'\'') {
+                    p += 1;
+                    current.push(b'\'');
+                } else if c == b'\'' {
+                    if p + 1 < line.len() && !is_space(line[p + 1]) {
+                        return None;
+                    }
+                    p += 1;
+                    break;
+                } else {
+                    current.push(c);
+                }
+            } else {
+                match line.get(p) {
+                    None => break,
+                    // Step past the separator, like `if (*p) p++`
+                    Some(b' ' | b'\n' | b'\r' | b'\t' | b'\0') => {
+                        p += 1;
+                        break;
+                    }
+                    Some(b'"') => in_quotes = true,
+                    Some(b'\'') => in_single_quotes = true,
+                    Some(&c) => current.push(c),
+                }
+            }
+            p += 1;
+        }
+        args.push(current);
+    }
+}
+
+/// The protocol version a client speaks, chosen with HELLO.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RespVersion {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+/// A reply to a client. Types RESP2 does not have are sent as the closest RESP2
+/// type, the way Redis does when a client did not switch to RESP3.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reply {
+    /// `+OK`
+    Simple(String),
+    /// `-ERR message`, holding the text after the `-`.
+    Error(String),
+    /// `:42`
+    Integer(i64),
+    /// `$5\r\nhello`
+    Bulk(Vec<u8>),
+    /// A missing value: `$-1` in RESP2.
+    Null,
+    /// A missing array: `*-1` in RESP2.
+    NullArray,
+    /// `*2\r\n...`
+    Array(Vec<Reply>),
+    /// `%1\r\n...`, a flat array of keys and values in RESP2.
+    Map(Vec<(Reply, Reply)>),
+    /// `~2\r\n...`, an array in RESP2.
+    Set(Vec<Reply>),
+    /// `,3.14`, a bulk string in RESP2.
+    Double(f64),
+    /// `(3492890328409238509324850943850943825024385`, a bulk string in RESP2.
+    BigNumber(String),
+    /// `=15\r\ntxt:Some string`, where the format is three bytes. A bulk string of
+    /// the text alone in RESP2.
+    Verbatim { format: [u8; 3], text: Vec<u8> },
+    /// `#t`, `:1` or `:0` in RESP2.
+    Boolean(bool),
+    /// `>3\r\n...`, out of band data such as Pub/Sub messages. An array in RESP2.
+    Push(Vec<Reply>),
+    /// `|1\r\n...` followed by the reply it describes. RESP2 only gets the reply.
+    Attribute { attributes: Vec<(Reply, Reply)>, reply: Box<Reply> },
+}
+
+impl Reply {
+    /// An error reply as `addReplyError` sends it: prefixed by `ERR` unless the
+    /// message starts with `-` followed by its own error code.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::protocol::resp::{Reply, RespVersion};
+    ///
+    /// assert_eq!(Reply::error("syntax error").to_bytes(RespVersion::Resp2), b"-ERR syntax error\r\n");
+    /// assert_eq!(Reply::error("-WRONGTYPE bad").to_bytes(RespVersion::Resp2), b"-WRONGTYPE bad\r\n");
+    /// ```
+    pub fn error(message: &str) -> Reply {
+        match message.strip_prefix('-') {
+            Some(message) => Reply::Error(message.to_string()),
+            None => Reply::Error(format!("ERR {}", message)),
+        }
+    }
+
+    /// A bulk string reply of anything that looks like bytes.
+    pub fn bulk<B: AsRef<[u8]>>(bytes: B) -> Reply {
+        Reply::Bulk(bytes.as_ref().to_vec())
+    }
+
+    /// Serializes the reply for a client speaking `version`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use curly_giggle::protocol::resp::{Reply, RespVersion};
+    ///
+    /// let reply = Reply::Map(vec![(Reply::bulk("score"), Reply::Double(1.5))]);
+    /// assert_eq!(reply.to_bytes(RespVersion::Resp2), b"*2\r\n$5\r\nscore\r\n$3\r\n1.5\r\n");
+    /// assert_eq!(reply.to_bytes(RespVersion::Resp3), b"%1\r\n$5\r\nscore\r\n,1.5\r\n");
+    /// ```
+    pub fn to_bytes(&self, version: RespVersion) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(version, &mut out);
+        out
+    }
+
+    /// Like `to_bytes`, appending to `out`.
+    pub fn encode(&self, version: RespVersion, out: &mut Vec<u8>) {
+        let resp3 = version == RespVersion::Resp3;
+        match self {
+            Reply::Simple(s) => write_line(out, b'+', &sanitize(s)),
+            Reply::Error(s) => write_line(out, b'-', &sanitize(s)),
+            Reply::Integer(value) => write_line(out, b':', &value.to_string()),
+            Reply::Bulk(bytes) => write_bulk(out, b'$', bytes),
+            Reply::Null if resp3 => out.extend_from_slice(b"_\r\n"),
+            Reply::Null => out.extend_from_slice(b"$-1\r\n"),
+            Reply::NullArray if resp3 => out.extend_from_slice(b"_\r\n"),
+            Reply::NullArray => out.extend_from_slice(b"*-1\r\n"),
+            Reply::Array(items) => write_aggregate(out, b'*', items, version),
+            Reply::Set(items) => write_aggregate(out, if resp3 { b'~' } else { b'*' }, items, version),
+            Reply::Push(items) => write_aggregate(out, if resp3 { b'>' } else { b'*' }, items, version),
+            Reply::Map(pairs) if resp3 => write_pairs(out, b'%', pairs, version),
+            Reply::Map(pairs) => {
+                write_line(out, b'*', &(pairs.len() * 2).to_string());
+                for (key, value) in pairs {
+                    key.encode(version, out);
+                    value.encode(version, out);
+                }
+            }
+            Reply::Double(value) if resp3 => write_line(out, b',', &format_double(*value)),
+            Reply::Double(value) => write_bulk(out, b'$', format_double(*value).as_bytes()),
+            Reply::BigNumber(digits) if resp3 => write_line(out, b'(', digits),
+            Reply::BigNumber(digits) => write_bulk(out, b'$', digits.as_bytes()),
+            Reply::Verbatim { format, text } if resp3 => {
+                let mut payload = format.to_vec();
+                payload.push(b':');
+                payload.extend_from_slice(text);
+                write_bulk(out, b'=', &payload);
+            }
+            Reply::Verbatim { text, .. } => write_bulk(out, b'$', text),
+            Reply::Boolean(value) if resp3 => write_line(out, b'#', if *value { "t" } else { "f" }),
+            Reply::Boolean(value) => write_line(out, b':', if *value { "1" } else { "0" }),
+            Reply::Attribute { attributes, reply } => {
+                if resp3 {
+                    write_pairs(out, b'|', attributes, version);
+                }
+                reply.encode(version, out);
+            }
+        }
+    }
+}
+
+// Simple strings and errors cannot hold newlines
+fn sanitize(s: &str) -> String {
+    s.replace(['\r', '\n'], " ")
+}
+
+fn write_line(out: &mut Vec<u8>, prefix: u8, s: &str) {
+    out.push(prefix);
+    out.extend_from_slice(s.as_bytes());
+    out.extend_from_slice(b"\r\n");
+}
+
+fn write_bulk(out: &mut Vec<u8>, prefix: u8, bytes: &[u8]) {
+    write_line(out, prefix, &bytes.len().to_string());
+    out.extend_from_slice(bytes);
+    out.extend_from_slice(b"\r\n");
+}
+
+fn write_aggregate(out: &mut Vec<u8>, prefix: u8, items: &[Reply], version: RespVersion) {
+    write_line(out, prefix, &items.len().to_string());
+    for item in items {
+        item.encode(version, out);
+    }
+}
+
+fn write_pairs(out: &mut Vec<u8>, prefix: u8, pairs: &[(Reply, Reply)], version: RespVersion) {
+    write_line(out, prefix, &pairs.len().to_string());
+    for (key, value) in pairs {
+        key.encode(version, out);
+        value.encode(version, out);
+    }
+}
+
+/// Formats a double the way Redis replies with it: `inf`, `-inf` or `nan`, or the
+/// fewest digits reading back as the same value, in exponent notation when `%.17g`
+/// would use it.
+///
+/// # Examples
+///
+/// ```
+/// use curly_giggle::protocol::resp::format_double;
+///
+/// assert_eq!(format_double(0.1), "0.1");
+/// assert_eq!(format_double(3.0), "3");
+/// assert_eq!(format_double(1e20), "1e+20");
+/// assert_eq!(format_double(-2.5e-7), "-2.5e-07");
+/// assert_eq!(format_double(f64::NEG_INFINITY), "-inf");
+/// ```
+pub fn format_double(value: f64) -> String {
+    if value.is_nan() {
+        return "nan".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    let scientific = format!("{:e}", value);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    if (-4..17).contains(&exponent) {
+        value.to_string()
+    } else {
+        format!("{}e{}{:02}", mantissa, if exponent < 0 { '-' } else { '+' }, exponent.abs())
+    }
+}
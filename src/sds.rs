@@ -43,6 +43,29 @@ impl SDS {
         sds
     }
 
+    // Create a new SDS holding the given bytes, which need not be valid UTF-8
+    pub fn sdsnewlen(init: &[u8]) -> Self {
+        let buf_len = init.len() * 2;
+        let mut sds = SDS {
+            len: init.len() as u64,
+            free: (buf_len - init.len()) as u64,
+            buf: Vec::with_capacity(buf_len),
+        };
+        sds.buf.extend_from_slice(init);
+        sds.buf.resize(buf_len, 0);
+        sds
+    }
+
+    // The bytes of the string, without the free space
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len as usize]
+    }
+
+    // Size of the allocated buffer, used and free space included
+    pub fn sdsalloc(&self) -> usize {
+        self.buf.capacity()
+    }
+
     #[inline]
     pub fn sdslen(&self) -> u64 {
         self.len
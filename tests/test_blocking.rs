@@ -0,0 +1,215 @@
+use curly_giggle::clock::ManualClock;
+use curly_giggle::collection::blocking::{BlockResult, BlockingLists, BlockingZSets, ListWhere, Served};
+use curly_giggle::collection::zset::{ZAddFlags, ZSetWhere};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lists() -> (BlockingLists<&'static str, i32, ManualClock>, ManualClock) {
+        let clock = ManualClock::new(1_000);
+        (BlockingLists::new(clock.clone()), clock)
+    }
+
+    #[test]
+    fn test_blpop_served_immediately() {
+        let (mut lists, _) = lists();
+        lists.rpush("a", vec![1, 2]);
+
+        assert_eq!(lists.blpop(1, vec!["a"], 0), BlockResult::Served { key: "a", value: 1 });
+        assert_eq!(lists.brpop(1, vec!["a"], 0), BlockResult::Served { key: "a", value: 2 });
+        assert!(!lists.exists(&"a"));
+        assert!(!lists.blocked().is_blocked(1));
+    }
+
+    #[test]
+    fn test_blpop_checks_keys_in_order() {
+        let (mut lists, _) = lists();
+        lists.rpush("b", vec![2]);
+        lists.rpush("c", vec![3]);
+
+        assert_eq!(lists.blpop(1, vec!["a", "c", "b"], 0), BlockResult::Served { key: "c", value: 3 });
+    }
+
+    #[test]
+    fn test_waiters_served_fifo() {
+        let (mut lists, _) = lists();
+        assert_eq!(lists.blpop(1, vec!["q"], 0), BlockResult::Blocked);
+        assert_eq!(lists.blpop(2, vec!["q"], 0), BlockResult::Blocked);
+        assert_eq!(lists.blpop(3, vec!["q"], 0), BlockResult::Blocked);
+
+        lists.rpush("q", vec![10, 20]);
+        let served = lists.handle_clients_blocked_on_keys();
+        assert_eq!(
+            served,
+            vec![
+                Served { client: 1, key: "q", value: 10 },
+                Served { client: 2, key: "q", value: 20 },
+            ]
+        );
+        assert!(lists.blocked().is_blocked(3));
+        assert_eq!(lists.llen(&"q"), 0);
+    }
+
+    #[test]
+    fn test_push_without_waiters_is_not_ready() {
+        let (mut lists, _) = lists();
+        lists.lpush("q", vec![1, 2, 3]);
+        assert!(lists.handle_clients_blocked_on_keys().is_empty());
+        assert_eq!(lists.lpop(&"q"), Some(3));
+        assert_eq!(lists.rpop(&"q"), Some(1));
+    }
+
+    #[test]
+    fn test_multi_key_served_by_first_ready_key() {
+        let (mut lists, _) = lists();
+        assert_eq!(lists.blpop(1, vec!["a", "b"], 0), BlockResult::Blocked);
+        assert_eq!(lists.blpop(2, vec!["b", "a"], 0), BlockResult::Blocked);
+
+        // b becomes ready before a, both clients wait on it
+        lists.rpush("b", vec![1]);
+        lists.rpush("a", vec![2]);
+        let served = lists.handle_clients_blocked_on_keys();
+        assert_eq!(
+            served,
+            vec![
+                Served { client: 1, key: "b", value: 1 },
+                Served { client: 2, key: "a", value: 2 },
+            ]
+        );
+        assert_eq!(lists.blocked().blocked_clients(), 0);
+        assert_eq!(lists.blocked().waiting_on(&"a"), 0);
+    }
+
+    #[test]
+    fn test_blmove_chains_to_destination_waiters() {
+        let (mut lists, _) = lists();
+        assert_eq!(lists.blmove(1, "src", "dst", ListWhere::Tail, ListWhere::Head, 0), BlockResult::Blocked);
+        assert_eq!(lists.blpop(2, vec!["dst"], 0), BlockResult::Blocked);
+
+        lists.rpush("src", vec![1, 2]);
+        let served = lists.handle_clients_blocked_on_keys();
+        assert_eq!(
+            served,
+            vec![
+                Served { client: 1, key: "src", value: 2 },
+                Served { client: 2, key: "dst", value: 2 },
+            ]
+        );
+        assert_eq!(lists.llen(&"src"), 1);
+        assert_eq!(lists.llen(&"dst"), 0);
+    }
+
+    #[test]
+    fn test_blmove_immediate() {
+        let (mut lists, _) = lists();
+        lists.rpush("src", vec![1, 2, 3]);
+        assert_eq!(
+            lists.blmove(1, "src", "src", ListWhere::Head, ListWhere::Tail, 0),
+            BlockResult::Served { key: "src", value: 1 }
+        );
+        assert_eq!(lists.lpop(&"src"), Some(2));
+        assert_eq!(lists.rpop(&"src"), Some(1));
+    }
+
+    #[test]
+    fn test_timeouts_with_fake_clock() {
+        let (mut lists, clock) = lists();
+        assert_eq!(lists.blpop(1, vec!["q"], 500), BlockResult::Blocked);
+        assert_eq!(lists.blpop(2, vec!["q"], 100), BlockResult::Blocked);
+        assert_eq!(lists.blpop(3, vec!["q"], 0), BlockResult::Blocked);
+
+        clock.advance(99);
+        assert!(lists.expire_blocked_clients().is_empty());
+        clock.advance(1);
+        assert_eq!(lists.expire_blocked_clients(), vec![2]);
+        clock.advance(10_000);
+        assert_eq!(lists.expire_blocked_clients(), vec![1]);
+
+        // Timeout 0 blocks forever
+        assert!(lists.blocked().is_blocked(3));
+        lists.rpush("q", vec![7]);
+        assert_eq!(
+            lists.handle_clients_blocked_on_keys(),
+            vec![Served { client: 3, key: "q", value: 7 }]
+        );
+    }
+
+    #[test]
+    fn test_disconnect() {
+        let (mut lists, _) = lists();
+        assert_eq!(lists.blpop(1, vec!["q"], 0), BlockResult::Blocked);
+        assert!(lists.disconnect(1));
+        assert!(!lists.disconnect(1));
+
+        lists.rpush("q", vec![1]);
+        assert!(lists.handle_clients_blocked_on_keys().is_empty());
+        assert_eq!(lists.llen(&"q"), 1);
+    }
+
+    fn zsets() -> (BlockingZSets<&'static str, &'static str, ManualClock>, ManualClock) {
+        let clock = ManualClock::new(1_000);
+        (BlockingZSets::new(clock.clone()), clock)
+    }
+
+    #[test]
+    fn test_zmpop() {
+        let (mut zsets, _) = zsets();
+        zsets.zadd("b", ZAddFlags::default(), vec![(1.0, "x"), (2.0, "y"), (3.0, "z")]).unwrap();
+
+        assert_eq!(zsets.zmpop(&["a"], ZSetWhere::Min, 1), Ok(None));
+        assert_eq!(
+            zsets.zmpop(&["a", "b"], ZSetWhere::Max, 2),
+            Ok(Some(("b", vec![(3.0, "z"), (2.0, "y")])))
+        );
+        assert_eq!(zsets.zmpop(&["b"], ZSetWhere::Min, 10), Ok(Some(("b", vec![(1.0, "x")]))));
+        assert!(!zsets.exists(&"b"));
+
+        assert_eq!(zsets.zmpop(&[], ZSetWhere::Min, 1), Err("numkeys should be greater than 0".to_string()));
+        assert_eq!(zsets.zmpop(&["b"], ZSetWhere::Min, 0), Err("count should be greater than 0".to_string()));
+    }
+
+    #[test]
+    fn test_bzmpop_served_fifo() {
+        let (mut zsets, _) = zsets();
+        assert_eq!(zsets.bzpopmin(1, vec!["z"], 0), Ok(BlockResult::Blocked));
+        assert_eq!(zsets.bzmpop(2, vec!["other", "z"], ZSetWhere::Max, 2, 0), Ok(BlockResult::Blocked));
+        assert_eq!(zsets.bzpopmax(3, vec!["z"], 0), Ok(BlockResult::Blocked));
+
+        zsets.zadd("z", ZAddFlags::default(), vec![(1.0, "a"), (2.0, "b"), (3.0, "c")]).unwrap();
+        assert_eq!(
+            zsets.handle_clients_blocked_on_keys(),
+            vec![
+                Served { client: 1, key: "z", value: vec![(1.0, "a")] },
+                Served { client: 2, key: "z", value: vec![(3.0, "c"), (2.0, "b")] },
+            ]
+        );
+        assert!(!zsets.exists(&"z"));
+        assert!(zsets.blocked().is_blocked(3));
+        assert!(!zsets.blocked().is_blocked(2));
+    }
+
+    #[test]
+    fn test_bzmpop_served_immediately_and_timeout() {
+        let (mut zsets, clock) = zsets();
+        zsets.zadd("z", ZAddFlags::default(), vec![(5.0, "a")]).unwrap();
+        assert_eq!(
+            zsets.bzpopmax(1, vec!["z"], 100),
+            Ok(BlockResult::Served { key: "z", value: vec![(5.0, "a")] })
+        );
+        assert!(zsets.bzmpop(1, vec!["z"], ZSetWhere::Min, 0, 100).is_err());
+        assert!(!zsets.blocked().is_blocked(1));
+
+        assert_eq!(zsets.bzpopmin(1, vec!["z"], 100), Ok(BlockResult::Blocked));
+        clock.advance(100);
+        assert_eq!(zsets.expire_blocked_clients(), vec![1]);
+
+        // ZADD XX on a missing key does not create it nor wake anyone
+        assert_eq!(zsets.bzpopmin(2, vec!["z"], 0), Ok(BlockResult::Blocked));
+        let xx = ZAddFlags { xx: true, ..Default::default() };
+        zsets.zadd("z", xx, vec![(1.0, "a")]).unwrap();
+        assert!(!zsets.exists(&"z"));
+        assert!(zsets.handle_clients_blocked_on_keys().is_empty());
+        assert!(zsets.disconnect(2));
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+use curly_giggle::collection::skiplist::concurrent::ConcurrentSkipMap;
+use curly_giggle::collection::skiplist::level_generator::DefaultLevelGenerator;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THREADS: usize = 8;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    fn assert_strictly_increasing(entries: &[(u32, u32)]) {
+        assert!(entries.windows(2).all(|w| w[0].0 < w[1].0), "{:?}", entries);
+    }
+
+    #[test]
+    fn test_is_send_and_sync() {
+        assert_send_sync::<ConcurrentSkipMap<u32, String>>();
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let map = ConcurrentSkipMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.remove(&1), None);
+
+        assert!(map.insert(2, "two"));
+        assert!(map.insert(1, "one"));
+        assert!(map.insert(3, "three"));
+        assert!(!map.insert(2, "deux"));
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(&2), Some("two"));
+        assert!(map.contains_key(&3));
+        assert!(!map.contains_key(&4));
+
+        assert_eq!(map.remove(&2), Some("two"));
+        assert_eq!(map.get(&2), None);
+        assert!(map.insert(2, "deux"));
+        assert_eq!(map.get(&2), Some("deux"));
+        assert_eq!(map.range(..), vec![(1, "one"), (2, "deux"), (3, "three")]);
+    }
+
+    #[test]
+    fn test_range_bounds() {
+        use std::ops::Bound::{Excluded, Included, Unbounded};
+
+        let map = ConcurrentSkipMap::new();
+        for i in (0..20).step_by(2) {
+            map.insert(i, i);
+        }
+        let keys = |entries: Vec<(i32, i32)>| entries.into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+        assert_eq!(keys(map.range(4..10)), vec![4, 6, 8]);
+        assert_eq!(keys(map.range(3..=10)), vec![4, 6, 8, 10]);
+        assert_eq!(keys(map.range(..3)), vec![0, 2]);
+        assert_eq!(keys(map.range(15..)), vec![16, 18]);
+        assert_eq!(keys(map.range((Excluded(4), Included(8)))), vec![6, 8]);
+        assert_eq!(keys(map.range((Excluded(18), Unbounded))), Vec::<i32>::new());
+        assert_eq!(keys(map.range(7..7)), Vec::<i32>::new());
+        assert_eq!(map.range(..).len(), 10);
+    }
+
+    #[test]
+    fn test_borrowed_keys() {
+        use std::ops::Bound::{Excluded, Included};
+
+        let map = ConcurrentSkipMap::new();
+        map.insert(String::from("b"), 2);
+        map.insert(String::from("a"), 1);
+        assert_eq!(map.get("a"), Some(1));
+        assert_eq!(map.range::<str, _>((Included("a"), Excluded("b"))), vec![(String::from("a"), 1)]);
+        assert_eq!(map.remove("b"), Some(2));
+    }
+
+    #[test]
+    fn test_against_model() {
+        let mut rng = StdRng::seed_from_u64(0xc0c0);
+        let map = ConcurrentSkipMap::with_level_generator(DefaultLevelGenerator::with_seed(12, 0.5, 3).unwrap());
+        let mut model = BTreeMap::new();
+        for _ in 0..20_000 {
+            let key: u32 = rng.gen_range(0..500);
+            match rng.gen_range(0..4) {
+                0 | 1 => {
+                    let inserted = !model.contains_key(&key);
+                    if inserted {
+                        model.insert(key, key * 3);
+                    }
+                    assert_eq!(map.insert(key, key * 3), inserted);
+                }
+                2 => assert_eq!(map.remove(&key), model.remove(&key)),
+                _ => assert_eq!(map.get(&key), model.get(&key).copied()),
+            }
+            assert_eq!(map.len(), model.len());
+        }
+        let lo = 100;
+        let hi = 300;
+        assert_eq!(
+            map.range(lo..hi),
+            model.range(lo..hi).map(|(&k, &v)| (k, v)).collect::<Vec<_>>()
+        );
+        assert_eq!(map.range(..), model.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_concurrent_disjoint_inserts() {
+        let map = Arc::new(ConcurrentSkipMap::new());
+        let handles: Vec<_> = (0..THREADS as u32)
+            .map(|t| {
+                let map = Arc::clone(&map);
+                thread::spawn(move || {
+                    for i in 0..2_000u32 {
+                        let key = i * THREADS as u32 + t;
+                        assert!(map.insert(key, key));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let total = 2_000 * THREADS;
+        assert_eq!(map.len(), total);
+        let entries = map.range(..);
+        assert_eq!(entries.len(), total);
+        assert!(entries.iter().enumerate().all(|(i, &(k, v))| k == i as u32 && v == k));
+    }
+
+    #[test]
+    fn test_concurrent_same_key_has_single_winner() {
+        for round in 0..50u32 {
+            let map = Arc::new(ConcurrentSkipMap::new());
+            let barrier = Arc::new(Barrier::new(THREADS));
+            let inserted = Arc::new(AtomicUsize::new(0));
+            let removed = Arc::new(AtomicUsize::new(0));
+            let handles: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let (map, barrier) = (Arc::clone(&map), Arc::clone(&barrier));
+                    let (inserted, removed) = (Arc::clone(&inserted), Arc::clone(&removed));
+                    thread::spawn(move || {
+                        barrier.wait();
+                        if map.insert(round, t) {
+                            inserted.fetch_add(1, Ordering::SeqCst);
+                        }
+                        barrier.wait();
+                        if map.remove(&round).is_some() {
+                            removed.fetch_add(1, Ordering::SeqCst);
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+            assert_eq!(inserted.load(Ordering::SeqCst), 1);
+            assert_eq!(removed.load(Ordering::SeqCst), 1);
+            assert!(map.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_concurrent_insert_remove_stress() {
+        let map = Arc::new(ConcurrentSkipMap::with_level_generator(
+            DefaultLevelGenerator::with_seed(8, 0.5, 7).unwrap(),
+        ));
+        let net = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..THREADS as u64)
+            .map(|t| {
+                let (map, net) = (Arc::clone(&map), Arc::clone(&net));
+                thread::spawn(move || {
+                    let mut rng = StdRng::seed_from_u64(t);
+                    for _ in 0..20_000 {
+                        // A small key space keeps the threads contending on the same nodes
+                        let key: u32 = rng.gen_range(0..64);
+                        if rng.gen_bool(0.5) {
+                            if map.insert(key, key + 1) {
+                                net.fetch_add(1, Ordering::SeqCst);
+                            }
+                        } else if let Some(value) = map.remove(&key) {
+                            assert_eq!(value, key + 1);
+                            net.fetch_sub(1, Ordering::SeqCst);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let entries = map.range(..);
+        assert_strictly_increasing(&entries);
+        assert!(entries.iter().all(|&(k, v)| v == k + 1));
+        assert_eq!(entries.len(), net.load(Ordering::SeqCst));
+        assert_eq!(map.len(), entries.len());
+        for key in 0..64 {
+            assert_eq!(map.contains_key(&key), entries.iter().any(|&(k, _)| k == key));
+        }
+    }
+
+    #[test]
+    fn test_readers_see_sorted_entries_during_writes() {
+        let map = Arc::new(ConcurrentSkipMap::new());
+        // Keys below 1000 are never removed
+        for key in (0..1000u32).step_by(10) {
+            map.insert(key, key);
+        }
+        let done = Arc::new(AtomicUsize::new(0));
+        let writers: Vec<_> = (0..THREADS as u64 / 2)
+            .map(|t| {
+                let (map, done) = (Arc::clone(&map), Arc::clone(&done));
+                thread::spawn(move || {
+                    let mut rng = StdRng::seed_from_u64(100 + t);
+                    for _ in 0..20_000 {
+                        let key = rng.gen_range(0..2000u32) | 1;
+                        if rng.gen_bool(0.5) {
+                            map.insert(key, key);
+                        } else {
+                            map.remove(&key);
+                        }
+                    }
+                    done.fetch_add(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        let readers: Vec<_> = (0..THREADS / 2)
+            .map(|_| {
+                let (map, done) = (Arc::clone(&map), Arc::clone(&done));
+                thread::spawn(move || {
+                    while done.load(Ordering::SeqCst) < THREADS / 2 {
+                        let entries = map.range(..1000);
+                        assert_strictly_increasing(&entries);
+                        assert!(entries.iter().all(|&(k, v)| k == v));
+                        let stable = entries.iter().filter(|&&(k, _)| k % 10 == 0).count();
+                        assert_eq!(stable, 100);
+                        assert_eq!(map.get(&500), Some(500));
+                    }
+                })
+            })
+            .collect();
+        for handle in writers.into_iter().chain(readers) {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_drop_frees_values() {
+        let value = Arc::new(());
+        {
+            let map = ConcurrentSkipMap::new();
+            for i in 0..100 {
+                map.insert(i, Arc::clone(&value));
+            }
+            assert_eq!(Arc::strong_count(&value), 101);
+        }
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    // Add more tests here...
+}
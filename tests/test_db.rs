@@ -0,0 +1,358 @@
+use std::rc::Rc;
+
+use curly_giggle::clock::ManualClock;
+use curly_giggle::collection::zset::{ZAddFlags, ZSetEncoding};
+use curly_giggle::db::*;
+use curly_giggle::object::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_db() -> RedisDb<ManualClock> {
+        RedisDb::with_clock(0, ManualClock::new(0))
+    }
+
+    fn new_server(dbnum: usize) -> RedisServer<ManualClock> {
+        RedisServer::with_clock(ServerConfig { dbnum, ..ServerConfig::default() }, ManualClock::new(0))
+    }
+
+    fn string(db: &mut RedisDb<ManualClock>, key: &[u8]) -> Option<Vec<u8>> {
+        db.lookup_read(key).map(|val| val.string_value().unwrap().into_owned())
+    }
+
+    fn set(db: &mut RedisDb<ManualClock>, key: &[u8], val: &[u8]) {
+        db.set_key(key, create_string_object(val), false);
+    }
+
+    #[test]
+    fn test_set_lookup_delete() {
+        let mut db = new_db();
+        assert!(db.lookup_read(b"k").is_none());
+        set(&mut db, b"k", b"v1");
+        assert_eq!(string(&mut db, b"k"), Some(b"v1".to_vec()));
+        assert!(!db.add(b"k", create_string_object(b"v2")));
+        assert!(db.add(b"other", create_string_object(b"v2")));
+        assert_eq!(db.dbsize(), 2);
+
+        *db.lookup_write(b"k").unwrap() = create_string_object_from_long_long(10);
+        assert_eq!(db.lookup_read(b"k").unwrap().encoding(), ObjectEncoding::Int);
+
+        assert!(db.delete(b"k"));
+        assert!(!db.delete(b"k"));
+        assert!(!db.exists(b"k"));
+        assert_eq!(db.dbsize(), 1);
+    }
+
+    #[test]
+    fn test_set_key_and_expire() {
+        let mut db = new_db();
+        assert!(!db.set_expire(b"k", 1000));
+        set(&mut db, b"k", b"v");
+        assert!(db.set_expire(b"k", 1000));
+        assert_eq!(db.get_expire(b"k"), Some(1000));
+        assert_eq!(db.expires_size(), 1);
+
+        db.set_key(b"k", create_string_object(b"v2"), true);
+        assert_eq!(db.get_expire(b"k"), Some(1000));
+        set(&mut db, b"k", b"v3");
+        assert_eq!(db.get_expire(b"k"), None);
+
+        db.set_expire(b"k", 2000);
+        assert!(db.remove_expire(b"k"));
+        assert!(!db.remove_expire(b"k"));
+
+        db.set_expire(b"k", 3000);
+        db.delete(b"k");
+        assert_eq!(db.expires_size(), 0);
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut db = new_db();
+        assert_eq!(db.rename(b"a", b"b", false), Err("no such key".to_string()));
+        set(&mut db, b"a", b"1");
+        db.set_expire(b"a", 5000);
+        assert_eq!(db.rename(b"a", b"a", false), Ok(true));
+        assert_eq!(db.rename(b"a", b"a", true), Ok(false));
+
+        assert_eq!(db.rename(b"a", b"b", false), Ok(true));
+        assert!(!db.exists(b"a"));
+        assert_eq!(string(&mut db, b"b"), Some(b"1".to_vec()));
+        assert_eq!(db.get_expire(b"b"), Some(5000));
+
+        // RENAME overwrites the destination and its TTL
+        set(&mut db, b"c", b"2");
+        db.set_expire(b"c", 9000);
+        assert_eq!(db.rename(b"c", b"b", true), Ok(false));
+        assert_eq!(db.rename(b"c", b"b", false), Ok(true));
+        assert_eq!(string(&mut db, b"b"), Some(b"2".to_vec()));
+        assert_eq!(db.get_expire(b"b"), Some(9000));
+
+        set(&mut db, b"d", b"3");
+        assert_eq!(db.rename(b"d", b"b", false), Ok(true));
+        assert_eq!(db.get_expire(b"b"), None);
+        assert_eq!(db.dbsize(), 1);
+    }
+
+    #[test]
+    fn test_random_key() {
+        let mut db = new_db();
+        assert_eq!(db.random_key(), None);
+        for key in [&b"a"[..], b"b", b"c"] {
+            set(&mut db, key, b"v");
+        }
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut seen: Vec<Vec<u8>> = (0..100).map(|_| db.random_key_with_rng(&mut rng).unwrap()).collect();
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+    }
+
+    #[test]
+    fn test_flush_mode_args() {
+        assert_eq!(FlushMode::from_args::<&str>(&[]), Ok(FlushMode::Sync));
+        assert_eq!(FlushMode::from_args(&["ASYNC"]), Ok(FlushMode::Async));
+        assert_eq!(FlushMode::from_args(&["sync"]), Ok(FlushMode::Sync));
+        assert_eq!(FlushMode::from_args(&["now"]), Err("syntax error".to_string()));
+        assert_eq!(FlushMode::from_args(&["sync", "async"]), Err("syntax error".to_string()));
+    }
+
+    #[test]
+    fn test_select() {
+        let mut server = new_server(4);
+        let mut client = Client::new();
+        assert_eq!(server.dbnum(), 4);
+        assert_eq!(RedisServer::default().dbnum(), CONFIG_DEFAULT_DBNUM);
+
+        server.current_db_mut(&client).set_key(b"k", create_string_object(b"zero"), false);
+        server.select_db(&mut client, 3).unwrap();
+        assert_eq!(client.db(), 3);
+        assert!(!server.current_db_mut(&client).exists(b"k"));
+        assert_eq!(server.select_db(&mut client, 4), Err("DB index is out of range".to_string()));
+        assert_eq!(server.select_db(&mut client, -1), Err("DB index is out of range".to_string()));
+        assert_eq!(client.db(), 3);
+    }
+
+    #[test]
+    fn test_swapdb() {
+        let mut server = new_server(4);
+        let client = Client::new();
+        server.db_mut(0).set_key(b"k", create_string_object(b"zero"), false);
+        server.db_mut(0).set_expire(b"k", 100);
+        server.db_mut(2).set_key(b"two", create_string_object(b"2"), false);
+
+        assert_eq!(server.swapdb(0, 2), Ok(()));
+        assert_eq!(server.db(0).id(), 0);
+        assert_eq!(server.db(2).id(), 2);
+        assert!(server.current_db_mut(&client).exists(b"two"));
+        assert_eq!(server.db(2).get_expire(b"k"), Some(100));
+
+        assert_eq!(server.swapdb(1, 1), Ok(()));
+        assert_eq!(server.swapdb(0, 4), Err("DB index is out of range".to_string()));
+        assert_eq!(server.swapdb(-1, 0), Err("DB index is out of range".to_string()));
+    }
+
+    #[test]
+    fn test_move() {
+        let mut server = new_server(4);
+        let client = Client::new();
+        server.db_mut(0).set_key(b"k", create_string_object(b"v"), false);
+        server.db_mut(0).set_expire(b"k", 100);
+
+        assert_eq!(server.move_key(&client, b"k", 0), Err("source and destination objects are the same".to_string()));
+        assert_eq!(server.move_key(&client, b"k", 9), Err("DB index is out of range".to_string()));
+        assert_eq!(server.move_key(&client, b"missing", 1), Ok(false));
+
+        assert_eq!(server.move_key(&client, b"k", 1), Ok(true));
+        assert!(!server.db_mut(0).exists(b"k"));
+        assert_eq!(server.db(1).get_expire(b"k"), Some(100));
+        assert_eq!(server.db(0).expires_size(), 0);
+
+        // The key is left alone when the target already has it
+        server.db_mut(0).set_key(b"k", create_string_object(b"new"), false);
+        assert_eq!(server.move_key(&client, b"k", 1), Ok(false));
+        assert!(server.db_mut(0).exists(b"k"));
+        assert_eq!(string(server.db_mut(1), b"k"), Some(b"v".to_vec()));
+    }
+
+    #[test]
+    fn test_copy() {
+        let mut server = new_server(4);
+        let client = Client::new();
+        let mut zset = create_zset_object();
+        if let ObjectValue::ZSet(zset) = Rc::get_mut(&mut zset).unwrap().value_mut() {
+            zset.zadd(ZAddFlags::default(), vec![(1.0, b"a".to_vec()), (2.0, b"b".to_vec())]).unwrap();
+            zset.zset_convert(ZSetEncoding::SkipList);
+        }
+        server.db_mut(0).set_key(b"z", zset, false);
+        server.db_mut(0).set_expire(b"z", 100);
+
+        assert_eq!(server.copy(&client, b"z", b"z", None, false), Err("source and destination objects are the same".to_string()));
+        assert_eq!(server.copy(&client, b"z", b"z", Some(7), false), Err("DB index is out of range".to_string()));
+        assert_eq!(server.copy(&client, b"missing", b"x", None, false), Ok(false));
+
+        assert_eq!(server.copy(&client, b"z", b"z", Some(1), false), Ok(true));
+        assert_eq!(server.copy(&client, b"z", b"copy", None, false), Ok(true));
+        assert_eq!(server.db(0).get_expire(b"copy"), Some(100));
+        assert_eq!(server.db(1).get_expire(b"z"), Some(100));
+
+        // The copy has its own value, with the same encoding
+        let copy = server.db_mut(0).lookup_write(b"copy").unwrap();
+        assert_eq!(get_refcount(copy), 1);
+        assert_eq!(copy.encoding(), ObjectEncoding::Skiplist);
+        if let ObjectValue::ZSet(zset) = Rc::get_mut(copy).unwrap().value_mut() {
+            zset.zrem(&[b"a".to_vec()]);
+        }
+        let original = server.db_mut(0).lookup_read(b"z").unwrap();
+        assert!(matches!(original.value(), ObjectValue::ZSet(zset) if zset.zcard() == 2));
+
+        server.db_mut(0).set_key(b"s", create_string_object(b"str"), false);
+        assert_eq!(server.copy(&client, b"s", b"copy", None, false), Ok(false));
+        assert_eq!(server.copy(&client, b"s", b"copy", None, true), Ok(true));
+        assert_eq!(string(server.db_mut(0), b"copy"), Some(b"str".to_vec()));
+        assert_eq!(server.db(0).get_expire(b"copy"), None);
+    }
+
+    #[test]
+    fn test_copy_strings_are_not_shared() {
+        let mut server = new_server(1);
+        let client = Client::new();
+        server.db_mut(0).set_key(b"n", create_string_object_from_long_long(5), false);
+        server.copy(&client, b"n", b"m", None, false).unwrap();
+        let copy = server.db_mut(0).lookup_read(b"m").unwrap();
+        assert!(!copy.is_shared());
+        assert_eq!(copy.encoding(), ObjectEncoding::Int);
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut server = new_server(3);
+        let mut client = Client::new();
+        for id in 0..3 {
+            server.db_mut(id).set_key(b"a", create_string_object(b"1"), false);
+            server.db_mut(id).set_key(b"b", create_string_object(b"2"), false);
+            server.db_mut(id).set_expire(b"b", 100);
+        }
+        server.select_db(&mut client, 1).unwrap();
+        assert_eq!(server.flushdb(&client, FlushMode::Sync), 2);
+        assert_eq!(server.db(1).dbsize(), 0);
+        assert_eq!(server.db(1).expires_size(), 0);
+        assert_eq!(server.db(0).dbsize(), 2);
+
+        assert_eq!(server.flushall(FlushMode::Sync), 4);
+        assert_eq!(server.flushall(FlushMode::Sync), 0);
+    }
+
+    #[test]
+    fn test_flush_async() {
+        let mut server = new_server(2);
+        let client = Client::new();
+        for i in 0..10u8 {
+            server.db_mut(0).set_key(&[i], create_string_object(b"v"), false);
+        }
+        server.db_mut(1).set_key(b"k", create_string_object(b"v"), false);
+
+        // A handle held elsewhere outlives the flush
+        let held = server.db_mut(0).lookup_read(&[0]).unwrap().clone();
+        assert_eq!(get_refcount(&held), 2);
+
+        assert_eq!(server.flushdb(&client, FlushMode::Async), 10);
+        assert_eq!(server.db(0).dbsize(), 0);
+        assert_eq!(server.lazyfree_pending_objects(), 10);
+        server.db_mut(0).set_key(b"new", create_string_object(b"v"), false);
+
+        assert_eq!(server.flushall(FlushMode::Async), 2);
+        assert_eq!(server.lazyfree_pending_objects(), 12);
+        assert_eq!(server.lazyfree_free_objects(4), 4);
+        assert_eq!(server.lazyfree_pending_objects(), 8);
+        assert_eq!(server.lazyfree_free_objects(100), 8);
+        assert_eq!(server.lazyfree_free_objects(100), 0);
+        assert_eq!(server.lazyfreed_objects(), 12);
+        assert_eq!(get_refcount(&held), 1);
+    }
+
+    #[test]
+    fn test_lazy_expiry() {
+        let clock = ManualClock::new(1000);
+        let mut db = RedisDb::with_clock(0, clock.clone());
+        for key in [&b"a"[..], b"b", b"c", b"d", b"e"] {
+            set(&mut db, key, b"v");
+            db.set_expire(key, 2000);
+        }
+        set(&mut db, b"persistent", b"v");
+
+        // Still there at the expire time itself
+        clock.set(2000);
+        assert!(!db.key_is_expired(b"a"));
+        assert!(db.exists(b"a"));
+
+        clock.set(2001);
+        assert!(db.key_is_expired(b"a"));
+        assert_eq!(db.dbsize(), 6);
+        assert!(db.lookup_read(b"a").is_none());
+        assert!(db.lookup_write(b"b").is_none());
+        assert!(!db.delete(b"c"));
+        assert_eq!(db.rename(b"d", b"x", false), Err("no such key".to_string()));
+        assert!(!db.set_expire(b"e", 5000));
+        assert_eq!(db.expired_keys(), 5);
+        assert_eq!(db.dbsize(), 1);
+        assert_eq!(db.expires_size(), 0);
+
+        // SET on an expired key starts over without a TTL, even with KEEPTTL
+        set(&mut db, b"k", b"v");
+        db.set_expire(b"k", 3000);
+        clock.set(3001);
+        db.set_key(b"k", create_string_object(b"new"), true);
+        assert_eq!(db.get_expire(b"k"), None);
+    }
+
+    #[test]
+    fn test_random_key_skips_expired() {
+        let clock = ManualClock::new(0);
+        let mut db = RedisDb::with_clock(0, clock.clone());
+        for i in 0..10u8 {
+            set(&mut db, &[i], b"v");
+            db.set_expire(&[i], 100);
+        }
+        set(&mut db, b"live", b"v");
+        clock.set(101);
+        let mut rng = StdRng::seed_from_u64(3);
+        assert_eq!(db.random_key_with_rng(&mut rng), Some(b"live".to_vec()));
+
+        db.delete(b"live");
+        for i in 0..10u8 {
+            set(&mut db, &[i], b"v");
+            db.set_expire(&[i], 200);
+        }
+        clock.set(201);
+        assert_eq!(db.random_key_with_rng(&mut rng), None);
+        assert_eq!(db.dbsize(), 0);
+    }
+
+    #[test]
+    fn test_expired_keys_across_databases() {
+        let clock = ManualClock::new(0);
+        let mut server = RedisServer::with_clock(ServerConfig { dbnum: 2, ..ServerConfig::default() }, clock.clone());
+        let client = Client::new();
+        server.db_mut(0).set_key(b"k", create_string_object(b"v"), false);
+        server.db_mut(0).set_expire(b"k", 100);
+        server.db_mut(1).set_key(b"k", create_string_object(b"old"), false);
+        server.db_mut(1).set_expire(b"k", 50);
+
+        // The expired key in the target does not block MOVE
+        clock.set(60);
+        assert_eq!(server.move_key(&client, b"k", 1), Ok(true));
+        assert_eq!(string(server.db_mut(1), b"k"), Some(b"v".to_vec()));
+
+        clock.set(101);
+        server.db_mut(0).set_key(b"src", create_string_object(b"v"), false);
+        assert_eq!(server.copy(&client, b"src", b"k", Some(1), false), Ok(true));
+        assert_eq!(server.db(1).get_expire(b"k"), None);
+        assert_eq!(server.stat_expiredkeys(), 2);
+    }
+
+    // Add more tests here...
+}
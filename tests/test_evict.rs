@@ -0,0 +1,308 @@
+use std::rc::Rc;
+
+use curly_giggle::clock::ManualClock;
+use curly_giggle::db::*;
+use curly_giggle::evict::*;
+use curly_giggle::object::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OOM: &str = "-OOM command not allowed when used memory > 'maxmemory'.";
+
+    // Samples every key, and counts every access with a log factor of 0
+    fn new_server(clock: &ManualClock, policy: MaxmemoryPolicy) -> RedisServer<ManualClock> {
+        let config = ServerConfig {
+            dbnum: 2,
+            maxmemory_policy: policy,
+            maxmemory_samples: 16,
+            lfu_log_factor: 0,
+            ..ServerConfig::default()
+        };
+        RedisServer::with_clock(config, clock.clone())
+    }
+
+    fn set(server: &mut RedisServer<ManualClock>, key: &[u8]) {
+        server.db_mut(0).set_key(key, create_string_object(b"value"), false);
+    }
+
+    // Lowers the limit just enough to evict one key, and returns it
+    fn evict_one(server: &mut RedisServer<ManualClock>) -> Vec<u8> {
+        let before: Vec<Vec<u8>> = server.db(0).keys().cloned().collect();
+        server.set_maxmemory(server.used_memory() - 1);
+        assert_eq!(server.perform_evictions_with_rng(&mut StdRng::seed_from_u64(1)), Ok(1));
+        let after = server.db(0);
+        before.into_iter().find(|key| !after.keys().any(|k| k == key)).unwrap()
+    }
+
+    #[test]
+    fn test_policy_names() {
+        for name in [
+            "volatile-lru",
+            "volatile-lfu",
+            "volatile-random",
+            "volatile-ttl",
+            "allkeys-lru",
+            "allkeys-lfu",
+            "allkeys-random",
+            "noeviction",
+        ] {
+            assert_eq!(MaxmemoryPolicy::from_name(name).unwrap().as_str(), name);
+        }
+        assert_eq!(MaxmemoryPolicy::from_name("VOLATILE-TTL"), Ok(MaxmemoryPolicy::VolatileTtl));
+        assert!(MaxmemoryPolicy::from_name("allkeys").unwrap_err().starts_with("argument(s) must be one of the following: volatile-lru,"));
+        assert_eq!(MaxmemoryPolicy::default(), MaxmemoryPolicy::NoEviction);
+        assert!(MaxmemoryPolicy::AllkeysRandom.is_allkeys() && !MaxmemoryPolicy::VolatileLfu.is_allkeys());
+
+        let config = ServerConfig { maxmemory_policy: MaxmemoryPolicy::VolatileLfu, ..ServerConfig::default() };
+        assert_eq!(config.access_tracking(), AccessTracking::Lfu(LfuConfig::default()));
+        assert_eq!(ServerConfig::default().access_tracking(), AccessTracking::Lru);
+    }
+
+    #[test]
+    fn test_used_memory() {
+        let clock = ManualClock::new(0);
+        let mut server = new_server(&clock, MaxmemoryPolicy::AllkeysLru);
+        let empty = server.used_memory();
+
+        set(&mut server, b"k");
+        let one = server.used_memory();
+        assert!(one > empty);
+        let usage = server.db_mut(0).memory_usage(b"k").unwrap();
+        assert!(usage > b"kvalue".len());
+        assert_eq!(server.db_mut(0).memory_usage(b"missing"), None);
+
+        // The expire time and bigger values take more
+        server.db_mut(0).set_expire(b"k", 1000);
+        assert!(server.db_mut(0).memory_usage(b"k").unwrap() > usage);
+        server.db_mut(0).set_key(b"k", create_string_object(&[b'x'; 1000]), true);
+        assert!(server.used_memory() >= one + 1000);
+
+        let mut list = create_list_object();
+        if let ObjectValue::List(list) = Rc::get_mut(&mut list).unwrap().value_mut() {
+            for _ in 0..10 {
+                list.push_back(vec![b'e'; 100]);
+            }
+        }
+        assert!(object_compute_size(&list) > 1000);
+        server.db_mut(1).add(b"list", list);
+        assert!(server.db(1).used_memory() > 1000);
+
+        // Keys detached by an async flush no longer count
+        server.flushall(FlushMode::Async);
+        assert!(server.used_memory() < one);
+    }
+
+    #[test]
+    fn test_used_memory_follows_writes() {
+        let clock = ManualClock::new(0);
+        let mut server = new_server(&clock, MaxmemoryPolicy::AllkeysLru);
+        set(&mut server, b"k");
+        server.db_mut(0).set_expire(b"k", 1000);
+        let base = server.used_memory();
+
+        // A value grown in place counts right away, and after the next write
+        server.db_mut(0).add(b"list", create_list_object());
+        if let Some(val) = server.db_mut(0).lookup_write(b"list") {
+            if let ObjectValue::List(list) = Rc::get_mut(val).unwrap().value_mut() {
+                for _ in 0..10 {
+                    list.push_back(vec![b'e'; 100]);
+                }
+            }
+        }
+        assert!(server.used_memory() > base + 1000);
+        server.db_mut(0).set_expire(b"k", 2000);
+        assert!(server.used_memory() > base + 1000);
+
+        server.db_mut(0).delete(b"list");
+        assert_eq!(server.used_memory(), base);
+    }
+
+    #[test]
+    fn test_within_limit() {
+        let clock = ManualClock::new(0);
+        let mut server = new_server(&clock, MaxmemoryPolicy::AllkeysLru);
+        set(&mut server, b"k");
+        assert_eq!(server.perform_evictions(), Ok(0));
+        server.set_maxmemory(server.used_memory());
+        assert_eq!(server.perform_evictions(), Ok(0));
+        assert_eq!(server.db(0).dbsize(), 1);
+    }
+
+    #[test]
+    fn test_noeviction() {
+        let clock = ManualClock::new(0);
+        let mut server = new_server(&clock, MaxmemoryPolicy::NoEviction);
+        set(&mut server, b"k");
+        server.set_maxmemory(1);
+        assert_eq!(server.perform_evictions(), Err(OOM.to_string()));
+        assert_eq!(server.db(0).dbsize(), 1);
+        assert_eq!(server.stat_evictedkeys(), 0);
+    }
+
+    #[test]
+    fn test_allkeys_lru() {
+        let clock = ManualClock::new(0);
+        let mut server = new_server(&clock, MaxmemoryPolicy::AllkeysLru);
+        for key in [b"a", b"b", b"c"] {
+            set(&mut server, key);
+            clock.advance(1000);
+        }
+        // Reading a key makes it the most recently used
+        server.db_mut(0).lookup_read(b"a");
+        clock.advance(1000);
+
+        assert_eq!(evict_one(&mut server), b"b");
+        assert_eq!(evict_one(&mut server), b"c");
+        assert_eq!(evict_one(&mut server), b"a");
+        assert_eq!(server.stat_evictedkeys(), 3);
+    }
+
+    #[test]
+    fn test_allkeys_lfu() {
+        let clock = ManualClock::new(0);
+        let mut server = new_server(&clock, MaxmemoryPolicy::AllkeysLfu);
+        for key in [b"a", b"b", b"c"] {
+            set(&mut server, key);
+        }
+        for _ in 0..3 {
+            server.db_mut(0).lookup_write(b"a");
+        }
+        server.db_mut(0).lookup_read(b"c");
+        assert_eq!(server.db(0).access_tracking(), server.config().access_tracking());
+
+        assert_eq!(evict_one(&mut server), b"b");
+
+        // After 5 minutes the counter of a is down to 3, below a new key
+        clock.advance(5 * 60_000);
+        set(&mut server, b"d");
+        assert_eq!(evict_one(&mut server), b"c");
+        assert_eq!(evict_one(&mut server), b"a");
+        assert_eq!(evict_one(&mut server), b"d");
+    }
+
+    #[test]
+    fn test_volatile_ttl() {
+        let clock = ManualClock::new(0);
+        let mut server = new_server(&clock, MaxmemoryPolicy::VolatileTtl);
+        set(&mut server, b"persistent");
+        for (key, when) in [(b"x", 3000), (b"y", 1000), (b"z", 2000)] {
+            set(&mut server, key);
+            server.db_mut(0).set_expire(key, when);
+        }
+
+        assert_eq!(evict_one(&mut server), b"y");
+        assert_eq!(evict_one(&mut server), b"z");
+        assert_eq!(evict_one(&mut server), b"x");
+
+        // Keys without an expire time are never evicted
+        server.set_maxmemory(1);
+        assert_eq!(server.perform_evictions(), Err(OOM.to_string()));
+        assert!(server.db_mut(0).exists(b"persistent"));
+    }
+
+    #[test]
+    fn test_volatile_lru() {
+        let clock = ManualClock::new(0);
+        let mut server = new_server(&clock, MaxmemoryPolicy::VolatileLru);
+        set(&mut server, b"persistent");
+        clock.advance(1000);
+        for key in [b"old", b"new"] {
+            set(&mut server, key);
+            server.db_mut(0).set_expire(key, 100_000);
+            clock.advance(1000);
+        }
+        assert_eq!(evict_one(&mut server), b"old");
+        assert_eq!(evict_one(&mut server), b"new");
+    }
+
+    #[test]
+    fn test_pool_skips_deleted_keys() {
+        let clock = ManualClock::new(0);
+        let mut server = new_server(&clock, MaxmemoryPolicy::AllkeysLru);
+        for key in [b"a", b"b", b"c", b"d"] {
+            set(&mut server, key);
+            clock.advance(1000);
+        }
+        assert_eq!(evict_one(&mut server), b"a");
+        // b is still pooled as the next candidate
+        server.db_mut(0).delete(b"b");
+        assert_eq!(evict_one(&mut server), b"c");
+    }
+
+    #[test]
+    fn test_zero_samples() {
+        let clock = ManualClock::new(0);
+        let config = ServerConfig {
+            maxmemory_policy: MaxmemoryPolicy::AllkeysLru,
+            maxmemory_samples: 0,
+            ..ServerConfig::default()
+        };
+        let mut server = RedisServer::with_clock(config, clock.clone());
+        set(&mut server, b"a");
+        set(&mut server, b"b");
+        // Still samples one key at a time
+        server.set_maxmemory(1);
+        assert_eq!(server.perform_evictions(), Err(OOM.to_string()));
+        assert_eq!(server.db(0).dbsize(), 0);
+        assert_eq!(server.stat_evictedkeys(), 2);
+    }
+
+    #[test]
+    fn test_moved_keys_keep_access_time() {
+        let clock = ManualClock::new(0);
+        let mut server = new_server(&clock, MaxmemoryPolicy::AllkeysLru);
+        set(&mut server, b"a");
+        clock.advance(1000);
+        set(&mut server, b"b");
+        clock.advance(1000);
+        server.db_mut(0).rename(b"a", b"z", false).unwrap();
+        assert_eq!(evict_one(&mut server), b"z");
+
+        // A copy counts as just accessed, and so does its source, read by COPY
+        let client = Client::new();
+        server.copy(&client, b"b", b"c", None, false).unwrap();
+        clock.advance(1000);
+        let mut evicted = vec![evict_one(&mut server), evict_one(&mut server)];
+        evicted.sort();
+        assert_eq!(evicted, vec![b"b".to_vec(), b"c".to_vec()]);
+    }
+
+    #[test]
+    fn test_random() {
+        let clock = ManualClock::new(0);
+        let mut server = new_server(&clock, MaxmemoryPolicy::VolatileRandom);
+        let mut rng = StdRng::seed_from_u64(3);
+        for id in 0..2 {
+            for i in 0..10u8 {
+                server.db_mut(id).set_key(&[i], create_string_object(b"value"), false);
+                if i % 2 == 0 {
+                    server.db_mut(id).set_expire(&[i], 100_000);
+                }
+            }
+        }
+
+        // Both databases give up volatile keys in turn
+        server.set_maxmemory(server.used_memory() - 1);
+        assert_eq!(server.perform_evictions_with_rng(&mut rng), Ok(1));
+        server.set_maxmemory(server.used_memory() - 1);
+        assert_eq!(server.perform_evictions_with_rng(&mut rng), Ok(1));
+        assert_eq!((server.db(0).expires_size(), server.db(1).expires_size()), (4, 4));
+
+        server.set_maxmemory(1);
+        assert_eq!(server.perform_evictions_with_rng(&mut rng), Err(OOM.to_string()));
+        assert_eq!((server.db(0).dbsize(), server.db(1).dbsize()), (5, 5));
+        assert_eq!(server.stat_evictedkeys(), 10);
+
+        server.set_maxmemory_policy(MaxmemoryPolicy::AllkeysRandom);
+        server.set_maxmemory(server.used_memory() - 200);
+        let evicted = server.perform_evictions_with_rng(&mut rng).unwrap();
+        assert!(evicted > 1 && evicted < 10, "evicted {}", evicted);
+        assert!(server.used_memory() <= server.config().maxmemory);
+    }
+
+    // Add more tests here...
+}
@@ -0,0 +1,270 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use curly_giggle::clock::{Clock, ManualClock};
+use curly_giggle::db::*;
+use curly_giggle::expire::*;
+use curly_giggle::object::create_string_object;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+// A clock moving forward by `step` milliseconds every time it is read
+#[derive(Clone)]
+struct TickingClock {
+    now: Rc<Cell<u64>>,
+    step: u64,
+}
+
+impl Clock for TickingClock {
+    fn now_ms(&self) -> u64 {
+        let now = self.now.get();
+        self.now.set(now + self.step);
+        now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONE: ExpireFlags = ExpireFlags { nx: false, xx: false, gt: false, lt: false };
+
+    fn flags(args: &[&str]) -> ExpireFlags {
+        ExpireFlags::from_args(args).unwrap()
+    }
+
+    fn new_db(clock: &ManualClock) -> RedisDb<ManualClock> {
+        let mut db = RedisDb::with_clock(0, clock.clone());
+        db.set_key(b"k", create_string_object(b"v"), false);
+        db
+    }
+
+    fn volatile_keys<C: Clock>(db: &mut RedisDb<C>, prefix: &str, count: usize, when: u64) {
+        for i in 0..count {
+            let key = format!("{}{}", prefix, i);
+            db.set_key(key.as_bytes(), create_string_object(b"v"), false);
+            db.set_expire(key.as_bytes(), when);
+        }
+    }
+
+    #[test]
+    fn test_expire_and_ttl() {
+        let clock = ManualClock::new(1_000_000);
+        let mut db = new_db(&clock);
+        assert_eq!(db.ttl(b"missing"), -2);
+        assert_eq!(db.pttl(b"k"), -1);
+        assert_eq!(db.expiretime(b"k"), -1);
+        assert_eq!(db.expire(b"missing", 10, NONE), Ok(false));
+
+        assert_eq!(db.expire(b"k", 10, NONE), Ok(true));
+        assert_eq!(db.get_expire(b"k"), Some(1_010_000));
+        assert_eq!(db.ttl(b"k"), 10);
+        clock.advance(1499);
+        assert_eq!(db.pttl(b"k"), 8501);
+        assert_eq!(db.ttl(b"k"), 9);
+        assert_eq!(db.expiretime(b"k"), 1010);
+        assert_eq!(db.pexpiretime(b"k"), 1_010_000);
+
+        assert_eq!(db.pexpire(b"k", 500, NONE), Ok(true));
+        assert_eq!(db.pttl(b"k"), 500);
+        assert_eq!(db.ttl(b"k"), 1);
+        assert_eq!(db.expireat(b"k", 2000, NONE), Ok(true));
+        assert_eq!(db.pexpiretime(b"k"), 2_000_000);
+        assert_eq!(db.pexpireat(b"k", 1_600_000, NONE), Ok(true));
+        assert_eq!(db.pttl(b"k"), 598_501);
+
+        clock.set(1_600_000);
+        assert_eq!(db.pttl(b"k"), 0);
+        clock.set(1_600_001);
+        assert_eq!(db.ttl(b"k"), -2);
+    }
+
+    #[test]
+    fn test_expire_in_the_past_deletes() {
+        let clock = ManualClock::new(10_000);
+        let mut db = new_db(&clock);
+        assert_eq!(db.expire(b"k", -1, NONE), Ok(true));
+        assert!(!db.exists(b"k"));
+        assert_eq!(db.expired_keys(), 0);
+
+        db.set_key(b"k", create_string_object(b"v"), false);
+        assert_eq!(db.pexpireat(b"k", 10_000, NONE), Ok(true));
+        assert_eq!(db.ttl(b"k"), -2);
+
+        db.set_key(b"k", create_string_object(b"v"), false);
+        assert_eq!(db.expireat(b"k", 0, NONE), Ok(true));
+        assert_eq!(db.dbsize(), 0);
+    }
+
+    #[test]
+    fn test_expire_overflow() {
+        let clock = ManualClock::new(1000);
+        let mut db = new_db(&clock);
+        assert_eq!(db.expire(b"k", i64::MAX / 1000 + 1, NONE), Err("invalid expire time in 'expire' command".to_string()));
+        assert_eq!(db.expire(b"k", i64::MIN / 1000 - 1, NONE), Err("invalid expire time in 'expire' command".to_string()));
+        assert_eq!(db.pexpire(b"k", i64::MAX, NONE), Err("invalid expire time in 'pexpire' command".to_string()));
+        assert_eq!(db.expireat(b"k", i64::MAX, NONE), Err("invalid expire time in 'expireat' command".to_string()));
+        assert_eq!(db.pexpireat(b"k", i64::MAX, NONE), Ok(true));
+        assert_eq!(db.ttl(b"k"), (i64::MAX - 1000 + 500) / 1000);
+    }
+
+    #[test]
+    fn test_expire_flags_parsing() {
+        assert_eq!(flags(&[]), NONE);
+        assert_eq!(flags(&["xx", "GT"]), ExpireFlags { xx: true, gt: true, ..NONE });
+        assert_eq!(
+            ExpireFlags::from_args(&["NX", "XX"]),
+            Err("NX and XX, GT or LT options at the same time are not compatible".to_string())
+        );
+        assert_eq!(
+            ExpireFlags::from_args(&["gt", "lt"]),
+            Err("GT and LT options at the same time are not compatible".to_string())
+        );
+        assert_eq!(ExpireFlags::from_args(&["ex"]), Err("Unsupported option ex".to_string()));
+
+        let clock = ManualClock::new(0);
+        let mut db = new_db(&clock);
+        assert_eq!(
+            db.expire(b"k", 10, ExpireFlags { nx: true, lt: true, ..NONE }),
+            Err("NX and XX, GT or LT options at the same time are not compatible".to_string())
+        );
+    }
+
+    #[test]
+    fn test_expire_conditions() {
+        let clock = ManualClock::new(0);
+        let mut db = new_db(&clock);
+
+        // Without an expire time
+        assert_eq!(db.expire(b"k", 100, flags(&["XX"])), Ok(false));
+        assert_eq!(db.expire(b"k", 100, flags(&["GT"])), Ok(false));
+        assert_eq!(db.expire(b"k", 100, flags(&["LT"])), Ok(true));
+        db.persist(b"k");
+        assert_eq!(db.expire(b"k", 100, flags(&["NX"])), Ok(true));
+
+        // With one
+        assert_eq!(db.expire(b"k", 50, flags(&["NX"])), Ok(false));
+        assert_eq!(db.expire(b"k", 100, flags(&["GT"])), Ok(false));
+        assert_eq!(db.expire(b"k", 200, flags(&["GT"])), Ok(true));
+        assert_eq!(db.expire(b"k", 200, flags(&["LT"])), Ok(false));
+        assert_eq!(db.expire(b"k", 150, flags(&["XX", "LT"])), Ok(true));
+        assert_eq!(db.ttl(b"k"), 150);
+        assert_eq!(db.expire(b"k", 300, flags(&["XX"])), Ok(true));
+        assert_eq!(db.ttl(b"k"), 300);
+    }
+
+    #[test]
+    fn test_persist() {
+        let clock = ManualClock::new(0);
+        let mut db = new_db(&clock);
+        assert!(!db.persist(b"missing"));
+        assert!(!db.persist(b"k"));
+        db.expire(b"k", 10, NONE).unwrap();
+        assert!(db.persist(b"k"));
+        assert_eq!(db.ttl(b"k"), -1);
+
+        db.expire(b"k", 10, NONE).unwrap();
+        clock.advance(10_001);
+        assert!(!db.persist(b"k"));
+        assert!(!db.exists(b"k"));
+    }
+
+    #[test]
+    fn test_active_expire_cycle_slow() {
+        let clock = ManualClock::new(0);
+        let mut server = RedisServer::with_clock(ServerConfig { dbnum: 3, ..ServerConfig::default() }, clock.clone());
+        volatile_keys(server.db_mut(0), "a", 500, 1000);
+        volatile_keys(server.db_mut(2), "b", 300, 1000);
+        volatile_keys(server.db_mut(2), "live", 10, 60_000);
+        server.db_mut(1).set_key(b"persistent", create_string_object(b"v"), false);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(server.active_expire_cycle_with_rng(ActiveExpireCycleType::Slow, &mut rng), 0);
+        assert!(server.db(2).avg_ttl() > 0);
+
+        // A database where every sampled key is expired is emptied, one where few
+        // are is left for later
+        clock.set(1001);
+        let expired = server.active_expire_cycle_with_rng(ActiveExpireCycleType::Slow, &mut rng);
+        assert_eq!(server.db(0).dbsize(), 0);
+        let left = server.db(2).dbsize() - 10;
+        assert!(left < 300);
+        assert_eq!(expired, 800 - left);
+        assert_eq!(server.db(1).dbsize(), 1);
+        assert_eq!(server.stat_expiredkeys(), expired);
+        assert!(server.stat_expired_stale_perc() > 0.0);
+        assert_eq!(server.stat_expired_time_cap_reached_count(), 0);
+
+        while server.db(2).expires_size() > 10 {
+            server.active_expire_cycle_with_rng(ActiveExpireCycleType::Slow, &mut rng);
+        }
+        assert_eq!(server.db(2).dbsize(), 10);
+        assert_eq!(server.db(0).avg_ttl(), 0);
+    }
+
+    #[test]
+    fn test_active_expire_cycle_time_limit() {
+        let clock = TickingClock { now: Rc::new(Cell::new(0)), step: 1 };
+        let mut server = RedisServer::with_clock(ServerConfig { dbnum: 2, ..ServerConfig::default() }, clock.clone());
+        volatile_keys(server.db_mut(0), "k", 2000, 1);
+        volatile_keys(server.db_mut(1), "k", 2000, 1);
+
+        // 25% of a 100 ms cron period, with the clock moving on every loop
+        let mut rng = StdRng::seed_from_u64(2);
+        clock.now.set(10_000);
+        let expired = server.active_expire_cycle_with_rng(ActiveExpireCycleType::Slow, &mut rng);
+        assert!(expired > 0 && expired < 2000, "expired {}", expired);
+        assert_eq!(server.stat_expired_time_cap_reached_count(), 1);
+        assert_eq!(server.db(1).dbsize(), 2000);
+
+        // The next cycle starts from the database after the last one visited
+        server.active_expire_cycle_with_rng(ActiveExpireCycleType::Slow, &mut rng);
+        assert!(server.db(1).dbsize() < 2000);
+    }
+
+    #[test]
+    fn test_active_expire_cycle_fast() {
+        let clock = ManualClock::new(0);
+        let mut server = RedisServer::with_clock(ServerConfig { dbnum: 1, ..ServerConfig::default() }, clock.clone());
+        let mut rng = StdRng::seed_from_u64(3);
+        volatile_keys(server.db_mut(0), "k", 100, 1000);
+
+        // Nothing left over by a previous cycle
+        clock.set(2000);
+        assert_eq!(server.active_expire_cycle_with_rng(ActiveExpireCycleType::Fast, &mut rng), 0);
+
+        // Slow cycles that keep finding expired keys raise the stale estimate
+        for i in 0..20 {
+            volatile_keys(server.db_mut(0), &format!("s{}-", i), 100, 2000);
+            clock.advance(1000);
+            server.active_expire_cycle_with_rng(ActiveExpireCycleType::Slow, &mut rng);
+        }
+        assert!(server.stat_expired_stale_perc() >= ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE as f64);
+
+        volatile_keys(server.db_mut(0), "f", 50, 0);
+        assert!(server.active_expire_cycle_with_rng(ActiveExpireCycleType::Fast, &mut rng) > 0);
+        // No more than once every two fast durations
+        volatile_keys(server.db_mut(0), "g", 50, 0);
+        assert_eq!(server.active_expire_cycle_with_rng(ActiveExpireCycleType::Fast, &mut rng), 0);
+        clock.advance(2);
+        assert!(server.active_expire_cycle_with_rng(ActiveExpireCycleType::Fast, &mut rng) > 0);
+    }
+
+    #[test]
+    fn test_active_expire_effort() {
+        let clock = ManualClock::new(0);
+        let config = ServerConfig { dbnum: 1, active_expire_effort: 10, ..ServerConfig::default() };
+        let mut server = RedisServer::with_clock(config, clock.clone());
+        volatile_keys(server.db_mut(0), "k", 1000, 10);
+        volatile_keys(server.db_mut(0), "live", 9000, 100_000);
+        clock.set(11);
+
+        // 25 keys per loop, and sampling goes on while more than 1% are expired
+        let mut rng = StdRng::seed_from_u64(4);
+        let expired = server.active_expire_cycle_with_rng(ActiveExpireCycleType::Slow, &mut rng);
+        assert!(expired > 0);
+        assert_eq!(server.config().active_expire_effort, 10);
+    }
+
+    // Add more tests here...
+}
@@ -1,4 +1,6 @@
 use curly_giggle::collection::hash::Dict;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 
 #[cfg(test)]
 mod tests {
@@ -75,4 +77,89 @@ mod tests {
         dict.release();
         assert_eq!(dict.fetch_value(&"key1"), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_unlink() {
+        let mut dict = Dict::new();
+        dict.create("key1", "value1");
+        assert_eq!(dict.unlink(&"key1"), Some(("key1", "value1")));
+        assert_eq!(dict.unlink(&"key1"), None);
+        assert!(dict.is_empty());
+    }
+
+    #[test]
+    fn test_fetch_value_mut() {
+        let mut dict = Dict::new();
+        dict.create("key1", 1);
+        *dict.fetch_value_mut(&"key1").unwrap() += 1;
+        assert_eq!(dict.fetch_value(&"key1"), Some(&2));
+        assert_eq!(dict.fetch_value_mut(&"key2"), None);
+    }
+
+    #[test]
+    fn test_size_and_iter() {
+        let mut dict = Dict::new();
+        dict.create("key1", 1);
+        dict.create("key2", 2);
+        assert_eq!(dict.size(), 2);
+        assert!(dict.contains(&"key2"));
+        let mut entries: Vec<_> = dict.iter().map(|(k, v)| (*k, *v)).collect();
+        entries.sort();
+        assert_eq!(entries, vec![("key1", 1), ("key2", 2)]);
+    }
+
+    #[test]
+    fn test_add_key_and_keys() {
+        let mut dict: Dict<&str, ()> = Dict::new();
+        assert!(dict.add_key("a"));
+        assert!(dict.add_key("b"));
+        assert!(!dict.add_key("a"));
+        assert_eq!(dict.size(), 2);
+        let mut keys: Vec<_> = dict.keys().copied().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_random_sampling() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut dict = Dict::new();
+        assert_eq!(dict.get_random_key_with_rng(&mut rng), None);
+        assert!(dict.get_some_keys(&mut rng, 5).is_empty());
+        for i in 0..1000 {
+            dict.add_key(i);
+        }
+        for _ in 0..100 {
+            assert!(dict.contains(dict.get_random_key_with_rng(&mut rng).unwrap()));
+        }
+        let some = dict.get_some_keys(&mut rng, 20);
+        assert!(!some.is_empty() && some.len() <= 20);
+        assert!(some.iter().all(|key| dict.contains(*key)));
+        // Never more keys than the dict holds
+        let mut small = Dict::new();
+        small.add_key(1);
+        small.add_key(2);
+        let mut keys: Vec<_> = small.get_some_keys(&mut rng, 10).into_iter().copied().collect();
+        keys.sort();
+        assert_eq!(keys, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_grow_and_shrink() {
+        let mut dict = Dict::new();
+        for i in 0..100 {
+            dict.create(i, i * 2);
+        }
+        assert_eq!(dict.size(), 100);
+        assert_eq!(dict.iter().len(), 100);
+        assert!((0..100).all(|i| dict.fetch_value(&i) == Some(&(i * 2))));
+        let full = dict.mem_usage();
+        for i in 0..98 {
+            dict.delete(&i);
+        }
+        assert!(dict.mem_usage() < full);
+        let mut entries: Vec<_> = dict.into_iter().collect();
+        entries.sort();
+        assert_eq!(entries, vec![(98, 196), (99, 198)]);
+    }
+}
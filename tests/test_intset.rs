@@ -0,0 +1,157 @@
+use std::collections::BTreeSet;
+
+use curly_giggle::collection::intset::{IntSet, IntSetEncoding};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_encodings() {
+        assert_eq!(IntSetEncoding::for_value(-32768), IntSetEncoding::Int16);
+        assert_eq!(IntSetEncoding::for_value(32767), IntSetEncoding::Int16);
+        assert_eq!(IntSetEncoding::for_value(-32769), IntSetEncoding::Int32);
+        assert_eq!(IntSetEncoding::for_value(32768), IntSetEncoding::Int32);
+        assert_eq!(IntSetEncoding::for_value(-2147483648), IntSetEncoding::Int32);
+        assert_eq!(IntSetEncoding::for_value(2147483647), IntSetEncoding::Int32);
+        assert_eq!(IntSetEncoding::for_value(-2147483649), IntSetEncoding::Int64);
+        assert_eq!(IntSetEncoding::for_value(2147483648), IntSetEncoding::Int64);
+        assert_eq!(IntSetEncoding::for_value(i64::MIN), IntSetEncoding::Int64);
+        assert_eq!(IntSetEncoding::for_value(i64::MAX), IntSetEncoding::Int64);
+    }
+
+    #[test]
+    fn test_new_is_empty() {
+        let set = IntSet::new();
+        assert!(set.is_empty());
+        assert_eq!(set.encoding(), IntSetEncoding::Int16);
+        assert_eq!(set.blob_len(), 8);
+        assert_eq!(set.get(0), None);
+        assert_eq!(set.min(), None);
+        assert_eq!(set.max(), None);
+        assert_eq!(set.random(), None);
+        assert!(!set.find(0));
+    }
+
+    #[test]
+    fn test_add_keeps_elements_sorted() {
+        let mut set = IntSet::new();
+        for value in [5, 6, 4, 4, -1, 100, 6] {
+            set.add(value);
+        }
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![-1, 4, 5, 6, 100]);
+        assert_eq!(set.len(), 5);
+        assert_eq!(set.blob_len(), 8 + 5 * 2);
+        assert_eq!(set.min(), Some(-1));
+        assert_eq!(set.max(), Some(100));
+        assert!(set.find(5));
+        assert!(!set.find(7));
+        assert!(!set.find(1 << 40));
+    }
+
+    #[test]
+    fn test_upgrade_appends_or_prepends() {
+        let mut set: IntSet = [32, 1, 7].into_iter().collect();
+        assert!(set.add(65535));
+        assert_eq!(set.encoding(), IntSetEncoding::Int32);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![1, 7, 32, 65535]);
+
+        assert!(set.add(-4294967295));
+        assert_eq!(set.encoding(), IntSetEncoding::Int64);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![-4294967295, 1, 7, 32, 65535]);
+        assert_eq!(set.blob_len(), 8 + 5 * 8);
+        for value in [-4294967295, 1, 7, 32, 65535] {
+            assert!(set.find(value));
+        }
+    }
+
+    #[test]
+    fn test_remove_never_downgrades() {
+        let mut set: IntSet = [1, 2, 3, i64::MAX].into_iter().collect();
+        assert!(!set.remove(4));
+        assert!(set.remove(i64::MAX));
+        assert!(!set.remove(i64::MAX));
+        assert!(set.remove(1));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(set.encoding(), IntSetEncoding::Int64);
+        assert_eq!(set.blob_len(), 8 + 2 * 8);
+
+        let mut small: IntSet = [1, 2].into_iter().collect();
+        assert!(!small.remove(1 << 20));
+        assert_eq!(small.len(), 2);
+    }
+
+    #[test]
+    fn test_little_endian_layout() {
+        let set: IntSet = [-2, 0x0102].into_iter().collect();
+        assert_eq!(
+            set.as_bytes(),
+            &[2, 0, 0, 0, 2, 0, 0, 0, 0xfe, 0xff, 0x02, 0x01]
+        );
+
+        let set: IntSet = [70000].into_iter().collect();
+        assert_eq!(set.as_bytes(), &[4, 0, 0, 0, 1, 0, 0, 0, 0x70, 0x11, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn test_from_bytes_validates_integrity() {
+        let set: IntSet = [-5, 3, 1 << 33].into_iter().collect();
+        assert_eq!(IntSet::from_bytes(set.as_bytes()), Ok(set.clone()));
+
+        assert!(IntSet::from_bytes(&[2, 0, 0]).is_err());
+        // No elements
+        assert_eq!(IntSet::from_bytes(IntSet::new().as_bytes()), Err("intset is empty".to_string()));
+        assert!(IntSet::from_bytes(&[4, 0, 0, 0, 0, 0, 0, 0]).is_err());
+        // Unknown encoding
+        assert!(IntSet::from_bytes(&[3, 0, 0, 0, 0, 0, 0, 0]).is_err());
+        // Length past the end, and trailing bytes
+        assert!(IntSet::from_bytes(&[2, 0, 0, 0, 2, 0, 0, 0, 1, 0]).is_err());
+        assert!(IntSet::from_bytes(&[2, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0]).is_err());
+        assert!(IntSet::from_bytes(&[2, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]).is_err());
+        // Unsorted and duplicated elements
+        assert!(IntSet::from_bytes(&[2, 0, 0, 0, 2, 0, 0, 0, 2, 0, 1, 0]).is_err());
+        assert!(IntSet::from_bytes(&[2, 0, 0, 0, 2, 0, 0, 0, 1, 0, 1, 0]).is_err());
+    }
+
+    #[test]
+    fn test_random() {
+        let set: IntSet = (0..10).collect();
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut seen = BTreeSet::new();
+        for _ in 0..1000 {
+            let value = set.random_with_rng(&mut rng).unwrap();
+            assert!(set.find(value));
+            seen.insert(value);
+        }
+        assert_eq!(seen.len(), 10);
+    }
+
+    #[test]
+    fn test_against_model() {
+        let mut rng = StdRng::seed_from_u64(0x1e7);
+        let mut set = IntSet::new();
+        let mut model = BTreeSet::new();
+        for i in 0..5000 {
+            // Widen the range over time so every upgrade path is taken
+            let value = match i / 1000 {
+                0 => rng.gen_range(-100..100),
+                1 => rng.gen_range(-100_000..100_000),
+                _ => rng.gen_range(-(1i64 << 40)..(1 << 40)),
+            };
+            if rng.gen_bool(0.6) {
+                assert_eq!(set.add(value), model.insert(value));
+            } else {
+                assert_eq!(set.remove(value), model.remove(&value));
+            }
+            assert_eq!(set.len(), model.len());
+        }
+        assert_eq!(set.iter().collect::<Vec<_>>(), model.iter().copied().collect::<Vec<_>>());
+        assert_eq!(set.iter().next_back(), model.iter().next_back().copied());
+        assert_eq!(set.blob_len(), 8 + set.len() * set.encoding().size());
+        assert_eq!(IntSet::from_bytes(set.as_bytes()), Ok(set));
+    }
+
+    // Add more tests here...
+}
@@ -0,0 +1,152 @@
+use curly_giggle::collection::skiplist::level_generator::{DefaultLevelGenerator, LevelGenerator, LevelGeneratorError};
+use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
+use curly_giggle::collection::skiplist::{ZSKIPLIST_MAXLEVEL, ZSKIPLIST_P};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: usize = 200_000;
+
+    // Chi-squared statistic of the level histogram against the truncated geometric
+    // distribution. Levels whose expected count is below 5 are merged into the
+    // last bin. Returns the statistic and the degrees of freedom.
+    fn chi_squared(g: &mut DefaultLevelGenerator, p: f64) -> (f64, usize) {
+        let bound = g.level_bound();
+        let mut histogram = vec![0usize; bound + 1];
+        for _ in 0..SAMPLES {
+            let level = g.random();
+            assert!((1..=bound).contains(&level));
+            histogram[level] += 1;
+        }
+
+        // P(level = k) = p^(k-1) * (1 - p), the bound taking all of P(level >= bound)
+        let n = SAMPLES as f64;
+        let mut bins: Vec<(f64, f64)> = Vec::new();
+        let mut tail_expected = n;
+        let mut tail_observed = SAMPLES as f64;
+        for (k, &observed) in histogram.iter().enumerate().take(bound).skip(1) {
+            let expected = n * p.powi(k as i32 - 1) * (1.0 - p);
+            if expected < 5.0 || tail_expected - expected < 5.0 {
+                break;
+            }
+            bins.push((observed as f64, expected));
+            tail_expected -= expected;
+            tail_observed -= observed as f64;
+        }
+        bins.push((tail_observed, tail_expected));
+
+        let statistic = bins.iter().map(|(o, e)| (o - e) * (o - e) / e).sum();
+        (statistic, bins.len() - 1)
+    }
+
+    // Critical value of the chi-squared distribution at the 0.1% significance level,
+    // from the Wilson-Hilferty approximation.
+    fn chi_squared_critical(df: usize) -> f64 {
+        let z = 3.090; // 99.9th percentile of the standard normal
+        let k = df as f64;
+        k * (1.0 - 2.0 / (9.0 * k) + z * (2.0 / (9.0 * k)).sqrt()).powi(3)
+    }
+
+    #[test]
+    fn test_default_uses_redis_constants() {
+        let g = DefaultLevelGenerator::default();
+        assert_eq!(g.level_bound(), ZSKIPLIST_MAXLEVEL);
+        assert_eq!(g.level_bound(), 32);
+        assert_eq!(g.p(), ZSKIPLIST_P);
+        assert_eq!(g.p(), 0.25);
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert_eq!(DefaultLevelGenerator::new(0, 0.5).err(), Some(LevelGeneratorError::ZeroLevelBound));
+        assert_eq!(
+            DefaultLevelGenerator::with_seed(4, 1.0, 1).err(),
+            Some(LevelGeneratorError::InvalidProbability(1.0))
+        );
+        assert_eq!(
+            DefaultLevelGenerator::with_seed(4, 0.0, 1).err(),
+            Some(LevelGeneratorError::InvalidProbability(0.0))
+        );
+        assert_eq!(
+            DefaultLevelGenerator::new(4, -0.5).err(),
+            Some(LevelGeneratorError::InvalidProbability(-0.5))
+        );
+        assert!(DefaultLevelGenerator::new(4, f64::NAN).is_err());
+        assert!(DefaultLevelGenerator::new(1, 0.999).is_ok());
+        assert_eq!(LevelGeneratorError::ZeroLevelBound.to_string(), "total must be non-zero.");
+    }
+
+    #[test]
+    fn test_with_seed_is_deterministic() {
+        let levels = |seed| {
+            let mut g = DefaultLevelGenerator::with_seed(32, 0.5, seed).unwrap();
+            (0..1000).map(|_| g.random()).collect::<Vec<_>>()
+        };
+        assert_eq!(levels(7), levels(7));
+        assert_ne!(levels(7), levels(8));
+    }
+
+    #[test]
+    fn test_levels_reach_but_never_exceed_bound() {
+        let mut g = DefaultLevelGenerator::with_seed(3, 0.9, 1).unwrap();
+        let levels: Vec<_> = (0..1000).map(|_| g.random()).collect();
+        assert!(levels.contains(&3));
+        assert!(levels.iter().all(|&level| (1..=3).contains(&level)));
+
+        let mut g = DefaultLevelGenerator::with_seed(1, 0.9, 1).unwrap();
+        assert!((0..100).all(|_| g.random() == 1));
+    }
+
+    #[test]
+    fn test_geometric_distribution_redis_parameters() {
+        for seed in [1, 2, 3] {
+            let mut g = DefaultLevelGenerator::with_seed(ZSKIPLIST_MAXLEVEL, ZSKIPLIST_P, seed).unwrap();
+            let (statistic, df) = chi_squared(&mut g, ZSKIPLIST_P);
+            assert!(df >= 5, "too few bins: {}", df);
+            assert!(
+                statistic < chi_squared_critical(df),
+                "seed {}: chi-squared {} with {} degrees of freedom",
+                seed,
+                statistic,
+                df
+            );
+        }
+    }
+
+    #[test]
+    fn test_geometric_distribution_half() {
+        let mut g = DefaultLevelGenerator::with_seed(16, 0.5, 11).unwrap();
+        let (statistic, df) = chi_squared(&mut g, 0.5);
+        assert!(df >= 10);
+        assert!(statistic < chi_squared_critical(df), "chi-squared {} with {} df", statistic, df);
+    }
+
+    #[test]
+    fn test_truncated_distribution() {
+        // With a low bound, the last level takes the whole tail
+        let mut g = DefaultLevelGenerator::with_seed(3, 0.5, 5).unwrap();
+        let (statistic, df) = chi_squared(&mut g, 0.5);
+        assert_eq!(df, 2);
+        assert!(statistic < chi_squared_critical(df), "chi-squared {} with {} df", statistic, df);
+    }
+
+    #[test]
+    fn test_chi_squared_rejects_biased_generator() {
+        // Levels drawn with p = 0.5 must not pass as p = 0.25
+        let mut g = DefaultLevelGenerator::with_seed(32, 0.5, 1).unwrap();
+        let (statistic, df) = chi_squared(&mut g, 0.25);
+        assert!(statistic > chi_squared_critical(df));
+    }
+
+    #[test]
+    fn test_seeded_skiplist() {
+        let mut list = ZSkipList::with_level_generator(DefaultLevelGenerator::with_seed(32, 0.25, 3).unwrap());
+        for i in 0..1000 {
+            list.zsl_insert(i as f64, i);
+        }
+        for i in (0..1000).step_by(97) {
+            assert_eq!(list.zsl_get_rank(i as f64, i), Some(i as i128));
+        }
+    }
+}
@@ -37,4 +37,38 @@ mod tests {
         let back_elem = *list.back().unwrap();
         assert_eq!(back_elem, 1);
     }
+
+    #[test]
+    fn test_push_back_pop_back() {
+        let mut list: LinkedList<i32> = LinkedList::new();
+        assert!(list.is_empty());
+        list.push_back(1);
+        list.push_back(2);
+        list.push_front(0);
+
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.pop_back(), Some(2));
+        assert_eq!(list.pop_back(), Some(1));
+        assert_eq!(list.pop_back(), Some(0));
+        assert_eq!(list.pop_back(), None);
+        assert!(list.is_empty());
+        assert_eq!(list.front(), None);
+    }
+
+    #[test]
+    fn test_clone() {
+        let mut list: LinkedList<String> = LinkedList::new();
+        list.push_back("b".to_string());
+        list.push_front("a".to_string());
+        list.push_back("c".to_string());
+
+        let mut copy = list.clone();
+        copy.push_back("d".to_string());
+        assert_eq!(list.len(), 3);
+        assert_eq!(copy.pop_front().as_deref(), Some("a"));
+        assert_eq!(copy.pop_front().as_deref(), Some("b"));
+        assert_eq!(copy.pop_front().as_deref(), Some("c"));
+        assert_eq!(copy.pop_front().as_deref(), Some("d"));
+        assert_eq!(list.back().map(String::as_str), Some("c"));
+    }
 }
@@ -0,0 +1,314 @@
+use curly_giggle::collection::listpack::{Listpack, ListpackEntry, ListpackWhere};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(lp: &Listpack) -> Vec<Vec<u8>> {
+        lp.iter().map(|entry| entry.to_bytes().into_owned()).collect()
+    }
+
+    fn assert_valid(lp: &Listpack) {
+        assert_eq!(Listpack::validate_integrity(lp.as_bytes(), true), Ok(()));
+    }
+
+    #[test]
+    fn test_new_is_empty() {
+        let lp = Listpack::new();
+        assert_eq!(lp.as_bytes(), &[7, 0, 0, 0, 0, 0, 0xFF]);
+        assert_eq!(lp.len(), 0);
+        assert!(lp.is_empty());
+        assert_eq!(lp.first(), None);
+        assert_eq!(lp.last(), None);
+        assert_eq!(lp.seek(0), None);
+        assert_eq!(lp.seek(-1), None);
+        assert_valid(&lp);
+    }
+
+    #[test]
+    fn test_entry_layout() {
+        let mut lp = Listpack::new();
+        lp.append("hello");
+        lp.append(1024);
+        lp.append(-7);
+        lp.append(5);
+        assert_eq!(
+            &lp.as_bytes()[6..],
+            &[
+                0x85, b'h', b'e', b'l', b'l', b'o', 0x06, // 6 bit string
+                0xC4, 0x00, 0x02, // 13 bit integer
+                0xDF, 0xF9, 0x02, // negative 13 bit integer
+                0x05, 0x01, // 7 bit unsigned integer
+                0xFF,
+            ]
+        );
+        assert_eq!(lp.blob_len(), 22);
+        assert_eq!(&lp.as_bytes()[..6], &[22, 0, 0, 0, 4, 0]);
+    }
+
+    #[test]
+    fn test_integer_encodings_round_trip() {
+        let values = [
+            0,
+            127,
+            128,
+            -1,
+            4095,
+            -4096,
+            4096,
+            -4097,
+            32767,
+            -32768,
+            32768,
+            8388607,
+            -8388608,
+            8388608,
+            2147483647,
+            -2147483648,
+            2147483648,
+            i64::MAX,
+            i64::MIN,
+        ];
+        let sizes = [2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 6, 6, 6, 10, 10, 10];
+        for (&value, &size) in values.iter().zip(&sizes) {
+            let mut lp = Listpack::new();
+            lp.append(value);
+            assert_eq!(lp.blob_len(), 7 + size, "{}", value);
+            assert_eq!(lp.get(lp.first().unwrap()), ListpackEntry::Int(value));
+            assert_valid(&lp);
+        }
+        let lp: Listpack = values.iter().copied().collect();
+        assert_eq!(lp.iter().collect::<Vec<_>>(), values.map(ListpackEntry::Int).to_vec());
+    }
+
+    #[test]
+    fn test_integer_strings_are_stored_as_integers() {
+        let lp: Listpack = ["12", "-9223372036854775808", "0"].into_iter().collect();
+        assert_eq!(
+            lp.iter().collect::<Vec<_>>(),
+            vec![ListpackEntry::Int(12), ListpackEntry::Int(i64::MIN), ListpackEntry::Int(0)]
+        );
+
+        // Strings that would not format back the same stay strings
+        for s in ["007", "-0", "+1", " 1", "1 ", "1.0", "", "-", "9223372036854775808", "123456789012345678901"] {
+            let lp: Listpack = [s].into_iter().collect();
+            assert_eq!(lp.get(lp.first().unwrap()), ListpackEntry::Str(s.as_bytes()), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_string_encodings_round_trip() {
+        // Each length crosses the next string encoding or back-length size
+        for len in [0, 63, 64, 125, 126, 4095, 4096, 16381, 16382, 70000] {
+            let s: Vec<u8> = (0..len).map(|i| b'a' + (i % 26) as u8).collect();
+            let mut lp = Listpack::new();
+            lp.append(&s[..]);
+            lp.append(&s[..]);
+            assert_eq!(strings(&lp), vec![s.clone(), s.clone()]);
+            assert_eq!(lp.prev(lp.last().unwrap()), lp.first());
+            assert_valid(&lp);
+        }
+    }
+
+    #[test]
+    fn test_navigation() {
+        let lp: Listpack = ["a", "1000", "ccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc"]
+            .into_iter()
+            .collect();
+        let first = lp.first().unwrap();
+        let second = lp.next(first).unwrap();
+        let third = lp.next(second).unwrap();
+        assert_eq!(lp.next(third), None);
+        assert_eq!(lp.last(), Some(third));
+        assert_eq!(lp.prev(third), Some(second));
+        assert_eq!(lp.prev(second), Some(first));
+        assert_eq!(lp.prev(first), None);
+
+        assert_eq!(lp.get(second), ListpackEntry::Int(1000));
+        for (index, p) in [(0, first), (1, second), (2, third), (-1, third), (-2, second), (-3, first)] {
+            assert_eq!(lp.seek(index), Some(p));
+        }
+        assert_eq!(lp.seek(3), None);
+        assert_eq!(lp.seek(-4), None);
+    }
+
+    #[test]
+    fn test_insert_replace_delete() {
+        let mut lp: Listpack = ["b", "d"].into_iter().collect();
+        lp.prepend("a");
+        let d = lp.seek(2).unwrap();
+        let c = lp.insert(d, "c", ListpackWhere::Before);
+        assert_eq!(lp.get(c), ListpackEntry::Str(b"c"));
+        let e = lp.insert(lp.seek(-1).unwrap(), "e", ListpackWhere::After);
+        assert_eq!(lp.get(e), ListpackEntry::Str(b"e"));
+        assert_eq!(strings(&lp), vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec(), b"e".to_vec()]);
+        assert_eq!(lp.len(), 5);
+
+        // Replacing with an element of a different size moves the rest
+        let b = lp.seek(1).unwrap();
+        let long = "x".repeat(100);
+        assert_eq!(lp.replace(b, long.as_str()), b);
+        assert_eq!(lp.replace(lp.seek(2).unwrap(), 300000), lp.seek(2).unwrap());
+        assert_eq!(lp.get(lp.seek(1).unwrap()), ListpackEntry::Str(long.as_bytes()));
+        assert_eq!(lp.get(lp.seek(2).unwrap()), ListpackEntry::Int(300000));
+        assert_eq!(lp.len(), 5);
+        assert_valid(&lp);
+
+        let next = lp.delete(lp.seek(1).unwrap());
+        assert_eq!(next.map(|p| lp.get(p)), Some(ListpackEntry::Int(300000)));
+        assert_eq!(lp.delete(lp.last().unwrap()), None);
+        assert_eq!(strings(&lp), vec![b"a".to_vec(), b"300000".to_vec(), b"d".to_vec()]);
+        assert_eq!(lp.len(), 3);
+        assert_valid(&lp);
+    }
+
+    #[test]
+    fn test_delete_range() {
+        let mut lp: Listpack = (0..10i64).collect();
+        assert_eq!(lp.delete_range(10, 1), 0);
+        assert_eq!(lp.delete_range(-11, 1), 0);
+        assert_eq!(lp.delete_range(2, 3), 3);
+        assert_eq!(lp.delete_range(-2, 5), 2);
+        assert_eq!(lp.delete_range(0, 0), 0);
+        assert_eq!(lp.iter().collect::<Vec<_>>(), [0, 1, 5, 6, 7].map(ListpackEntry::Int).to_vec());
+        assert_eq!(lp.delete_range(0, usize::MAX), 5);
+        assert_eq!(lp, Listpack::new());
+    }
+
+    #[test]
+    fn test_unknown_number_of_elements() {
+        let mut lp: Listpack = (0..65536i64).map(|i| i % 100).collect();
+        assert_eq!(&lp.as_bytes()[4..6], &[0xFF, 0xFF]);
+        assert_eq!(lp.len(), 65536);
+        assert_valid(&lp);
+
+        lp.delete_range(0, 10);
+        assert_eq!(lp.len(), 65526);
+        assert_eq!(lp.seek(-1).map(|p| lp.get(p)), Some(ListpackEntry::Int(35)));
+        assert_valid(&lp);
+    }
+
+    #[test]
+    fn test_validate_integrity() {
+        let lp: Listpack = ["hello", "12345"].into_iter().collect();
+        let bytes = lp.as_bytes().to_vec();
+        assert_eq!(Listpack::from_bytes(&bytes), Ok(lp.clone()));
+
+        let corrupt = |f: &dyn Fn(&mut Vec<u8>)| {
+            let mut bytes = bytes.clone();
+            f(&mut bytes);
+            bytes
+        };
+        // Header problems are found without a deep check
+        assert!(Listpack::validate_integrity(&bytes[..5], false).is_err());
+        assert!(Listpack::validate_integrity(&corrupt(&|b| b[0] += 1), false).is_err());
+        assert!(Listpack::validate_integrity(&corrupt(&|b| *b.last_mut().unwrap() = 0), false).is_err());
+
+        // Entry problems need one
+        let shallow_only = [
+            corrupt(&|b| b[6] = 0xF5),       // invalid encoding
+            corrupt(&|b| b[6] = 0xBF),       // string running past the end
+            corrupt(&|b| b[12] = 0x07),      // back-length mismatch
+            corrupt(&|b| b[4] = 3),          // wrong number of elements
+            corrupt(&|b| b[12] = 0x86 | 128), // back-length running into the entry
+        ];
+        for bytes in &shallow_only {
+            assert_eq!(Listpack::validate_integrity(bytes, false), Ok(()));
+            assert!(Listpack::validate_integrity(bytes, true).is_err());
+            assert!(Listpack::from_bytes(bytes).is_err());
+        }
+
+        // An unknown number of elements is accepted
+        assert_eq!(Listpack::validate_integrity(&corrupt(&|b| b[4..6].copy_from_slice(&[0xFF, 0xFF])), true), Ok(()));
+    }
+
+    #[test]
+    fn test_against_model() {
+        let mut rng = StdRng::seed_from_u64(0x11);
+        let mut lp = Listpack::new();
+        let mut model: Vec<Vec<u8>> = Vec::new();
+        let random_entry = |rng: &mut StdRng| -> Vec<u8> {
+            match rng.gen_range(0..3) {
+                0 => rng.gen_range(-100_000i64..100_000).to_string().into_bytes(),
+                1 => rng.gen::<i64>().to_string().into_bytes(),
+                _ => (0..rng.gen_range(0..200)).map(|_| rng.gen_range(b'a'..=b'z')).collect(),
+            }
+        };
+
+        for _ in 0..3000 {
+            let index = rng.gen_range(0..model.len() + 1);
+            match rng.gen_range(0..6) {
+                0 => {
+                    let entry = random_entry(&mut rng);
+                    lp.append(&entry[..]);
+                    model.push(entry);
+                }
+                1 => {
+                    let entry = random_entry(&mut rng);
+                    lp.prepend(&entry[..]);
+                    model.insert(0, entry);
+                }
+                2 if index < model.len() => {
+                    let entry = random_entry(&mut rng);
+                    let p = lp.seek(index as i64).unwrap();
+                    if rng.gen_bool(0.5) {
+                        lp.insert(p, &entry[..], ListpackWhere::Before);
+                        model.insert(index, entry);
+                    } else {
+                        lp.insert(p, &entry[..], ListpackWhere::After);
+                        model.insert(index + 1, entry);
+                    }
+                }
+                3 if index < model.len() => {
+                    let entry = random_entry(&mut rng);
+                    let p = lp.seek(index as i64 - model.len() as i64).unwrap();
+                    lp.replace(p, &entry[..]);
+                    model[index] = entry;
+                }
+                4 if index < model.len() => {
+                    lp.delete(lp.seek(index as i64).unwrap());
+                    model.remove(index);
+                }
+                5 => {
+                    let num = rng.gen_range(0..3);
+                    let deleted = lp.delete_range(index as i64, num);
+                    let end = (index + num).min(model.len());
+                    assert_eq!(deleted, end.saturating_sub(index));
+                    if index < end {
+                        model.drain(index..end);
+                    }
+                }
+                _ => {}
+            }
+            assert_eq!(lp.len(), model.len());
+            assert_valid(&lp);
+        }
+        assert_eq!(strings(&lp), model);
+
+        // Walking backwards gives the same elements
+        let mut reversed = Vec::new();
+        let mut p = lp.last();
+        while let Some(pos) = p {
+            reversed.push(lp.get(pos).to_bytes().into_owned());
+            p = lp.prev(pos);
+        }
+        reversed.reverse();
+        assert_eq!(reversed, model);
+    }
+
+    #[test]
+    fn test_find() {
+        let lp: Listpack = ["a", "12", "b", "007"].iter().map(|s| ListpackEntry::from(*s)).collect();
+        assert_eq!(lp.find("a"), lp.first());
+        assert_eq!(lp.find(12).and_then(|p| lp.next(p)), lp.seek(2));
+        assert_eq!(lp.find("12"), lp.seek(1));
+        assert_eq!(lp.find("007"), lp.seek(3));
+        assert_eq!(lp.find(7), None);
+        assert_eq!(lp.find("c"), None);
+        assert_eq!(Listpack::new().find("a"), None);
+    }
+
+    // Add more tests here...
+}
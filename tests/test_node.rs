@@ -9,10 +9,11 @@ mod tests {
         let node = ZSkipNode::<i32>::head(level_bound);
 
         assert_eq!(node.val, None);
-        assert_eq!(node.level, level_bound - 1);
-        assert_eq!(node.next.len(), level_bound);
-        for link in node.next {
-            assert_eq!(link, None);
+        assert!(node.is_head());
+        assert_eq!(node.level(), level_bound);
+        for link in node.level {
+            assert_eq!(link.forward, None);
+            assert_eq!(link.span, 0);
         }
     }
 
@@ -20,24 +21,21 @@ mod tests {
     fn test_new() {
         let item = 42;
         let level = 3;
-        let node = ZSkipNode::new(item, level);
+        let node = ZSkipNode::new(item, level, 1.0);
 
         assert_eq!(node.val, Some(item));
-        assert_eq!(node.level, level);
-        assert_eq!(node.next.len(), level + 1);
-        for link in node.next {
-            assert_eq!(link, None);
+        assert_eq!(node.score, 1.0);
+        assert_eq!(node.level(), level);
+        assert!(node.is_tail());
+        for link in node.level {
+            assert_eq!(link.forward, None);
         }
     }
 
     #[test]
     fn test_get_val() {
         let item = "Hello, world!";
-        let node = ZSkipNode {
-            val: Some(item),
-            level: 2,
-            next: vec![None, None, None],
-        };
+        let node = ZSkipNode::new(item, 2, 0.0);
 
         assert_eq!(node.val, Some(item));
     }
@@ -45,11 +43,7 @@ mod tests {
     #[test]
     fn test_into_val() {
         let item = "Hello, world!";
-        let node = ZSkipNode {
-            val: Some(item.to_string()),
-            level: 2,
-            next: vec![None, None, None],
-        };
+        let node = ZSkipNode::new(item.to_string(), 2, 0.0);
         let val: Option<String> = node.into_val();
         assert_eq!(val, Some(item.to_string()));
     }
@@ -0,0 +1,224 @@
+use std::rc::Rc;
+
+use curly_giggle::object::*;
+use curly_giggle::protocol::resp::Reply;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LFU: AccessTracking = AccessTracking::Lfu(LfuConfig {
+        lfu_log_factor: 10,
+        lfu_decay_time: 1,
+    });
+
+    #[test]
+    fn test_string_encodings() {
+        let short = create_string_object(b"hello");
+        assert_eq!(short.obj_type(), ObjectType::String);
+        assert_eq!(short.encoding(), ObjectEncoding::Embstr);
+
+        let exact = create_string_object(&[b'x'; OBJ_ENCODING_EMBSTR_SIZE_LIMIT]);
+        assert_eq!(exact.encoding(), ObjectEncoding::Embstr);
+        let long = create_string_object(&[b'x'; OBJ_ENCODING_EMBSTR_SIZE_LIMIT + 1]);
+        assert_eq!(long.encoding(), ObjectEncoding::Raw);
+        assert_eq!(long.string_value().unwrap().len(), OBJ_ENCODING_EMBSTR_SIZE_LIMIT + 1);
+
+        let number = create_string_object_from_long_long(-12);
+        assert_eq!(number.encoding(), ObjectEncoding::Int);
+        assert_eq!(number.string_value().unwrap().as_ref(), b"-12");
+    }
+
+    #[test]
+    fn test_try_object_encoding() {
+        // Small integers become the shared object
+        let obj = try_object_encoding(create_raw_string_object(b"42"), true);
+        assert!(obj.is_shared());
+        assert!(Rc::ptr_eq(&obj, &shared_integer(42).unwrap()));
+
+        let obj = try_object_encoding(create_raw_string_object(b"42"), false);
+        assert!(!obj.is_shared());
+        assert_eq!(obj.encoding(), ObjectEncoding::Int);
+
+        let obj = try_object_encoding(create_raw_string_object(b"-9223372036854775808"), true);
+        assert_eq!(obj.encoding(), ObjectEncoding::Int);
+        assert_eq!(obj.string_value().unwrap().as_ref(), b"-9223372036854775808");
+
+        // Not the canonical form of an integer
+        let obj = try_object_encoding(create_raw_string_object(b"0042"), true);
+        assert_eq!(obj.encoding(), ObjectEncoding::Embstr);
+
+        let long = vec![b'a'; 50];
+        let obj = try_object_encoding(create_raw_string_object(&long), true);
+        assert_eq!(obj.encoding(), ObjectEncoding::Raw);
+
+        // Objects with other handles are left alone
+        let obj = create_raw_string_object(b"7");
+        let other = obj.clone();
+        let obj = try_object_encoding(obj, true);
+        assert!(Rc::ptr_eq(&obj, &other));
+        assert_eq!(obj.encoding(), ObjectEncoding::Raw);
+
+        let list = create_list_object();
+        assert_eq!(try_object_encoding(list, true).obj_type(), ObjectType::List);
+    }
+
+    #[test]
+    fn test_shared_integers() {
+        assert!(shared_integer(-1).is_none());
+        assert!(shared_integer(OBJ_SHARED_INTEGERS).is_none());
+        let zero = create_string_object_from_long_long(0);
+        let max = create_string_object_from_long_long(OBJ_SHARED_INTEGERS - 1);
+        assert!(zero.is_shared() && max.is_shared());
+        assert_eq!(get_refcount(&zero), OBJ_SHARED_REFCOUNT);
+        assert!(!create_string_object_from_long_long(OBJ_SHARED_INTEGERS).is_shared());
+        assert!(!create_string_object_from_long_long_with_options(5, false).is_shared());
+    }
+
+    #[test]
+    fn test_refcount() {
+        let obj = create_string_object(b"value");
+        assert_eq!(get_refcount(&obj), 1);
+        let handle = obj.clone();
+        assert_eq!(get_refcount(&obj), 2);
+        drop(handle);
+        assert_eq!(get_refcount(&obj), 1);
+    }
+
+    #[test]
+    fn test_container_encodings() {
+        let mut set = create_set_object();
+        assert_eq!(set.obj_type(), ObjectType::Set);
+        assert_eq!(set.encoding(), ObjectEncoding::Intset);
+        if let ObjectValue::Set(set) = Rc::get_mut(&mut set).unwrap().value_mut() {
+            set.sadd(&["a"]);
+        }
+        assert_eq!(set.encoding(), ObjectEncoding::Listpack);
+
+        let zset = create_zset_object();
+        assert_eq!((zset.obj_type(), zset.encoding()), (ObjectType::ZSet, ObjectEncoding::Listpack));
+
+        let mut hash = create_hash_object();
+        assert_eq!((hash.obj_type(), hash.encoding()), (ObjectType::Hash, ObjectEncoding::Listpack));
+        if let ObjectValue::Hash(hash) = Rc::get_mut(&mut hash).unwrap().value_mut() {
+            hash.hset(&[("f", "v")]);
+            hash.hexpire(100, None, &["f"]).unwrap();
+        }
+        assert_eq!(hash.encoding(), ObjectEncoding::ListpackEx);
+
+        let list = create_list_object();
+        assert_eq!((list.obj_type(), list.encoding()), (ObjectType::List, ObjectEncoding::Quicklist));
+        assert!(list.string_value().is_none());
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(ObjectType::ZSet.as_str(), "zset");
+        assert_eq!(ObjectType::Stream.as_str(), "stream");
+        assert_eq!(ObjectEncoding::Embstr.as_str(), "embstr");
+        assert_eq!(ObjectEncoding::ListpackEx.as_str(), "listpackex");
+        assert_eq!(ObjectEncoding::Skiplist.as_str(), "skiplist");
+    }
+
+    #[test]
+    fn test_lru_clock_and_idle_time() {
+        assert_eq!(get_lru_clock(12_345), 12);
+        assert_eq!(get_lru_clock((LRU_CLOCK_MAX as u64 + 1) * 1000), 0);
+
+        let obj = create_string_object(b"v");
+        obj.init_access(AccessTracking::Lru, 10_000);
+        assert_eq!(obj.lru(), 10);
+        assert_eq!(obj.estimate_idle_time(15_500), 5000);
+        obj.touch(AccessTracking::Lru, 20_000);
+        assert_eq!(obj.estimate_idle_time(20_000), 0);
+
+        // The clock wrapped around since the last access
+        obj.set_lru(LRU_CLOCK_MAX - 1);
+        assert_eq!(obj.estimate_idle_time(3000), 4000);
+    }
+
+    #[test]
+    fn test_lfu_counter() {
+        let obj = create_string_object(b"v");
+        obj.init_access(LFU, 0);
+        assert_eq!(obj.lfu_decr_and_return(1, 0), LFU_INIT_VAL);
+
+        // Below LFU_INIT_VAL every access counts
+        obj.set_lru(0);
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..5 {
+            obj.touch_with_rng(LFU, 0, &mut rng);
+        }
+        assert_eq!(obj.lru() & 255, 5);
+
+        // Above it, increments get rarer
+        for _ in 0..1000 {
+            obj.touch_with_rng(LFU, 0, &mut rng);
+        }
+        let counter = obj.lru() & 255;
+        assert!(counter > 10 && counter < 40, "counter {}", counter);
+        assert_eq!(lfu_log_incr(255, 10, &mut rng), 255);
+    }
+
+    #[test]
+    fn test_lfu_decay() {
+        let obj = create_string_object(b"v");
+        obj.set_lru((lfu_get_time_in_minutes(0) << 8) | 20);
+        assert_eq!(obj.lfu_decr_and_return(1, 59_999), 20);
+        assert_eq!(obj.lfu_decr_and_return(1, 3 * 60_000), 17);
+        assert_eq!(obj.lfu_decr_and_return(2, 3 * 60_000), 19);
+        assert_eq!(obj.lfu_decr_and_return(0, 3 * 60_000), 20);
+        assert_eq!(obj.lfu_decr_and_return(1, 100 * 60_000), 0);
+
+        // The minutes wrap around after 16 bits
+        assert_eq!(lfu_time_elapsed(65530, 60_000), 6);
+
+        // An access applies the decay before incrementing
+        let mut rng = StdRng::seed_from_u64(2);
+        obj.update_lfu(LfuConfig { lfu_log_factor: 10, lfu_decay_time: 1 }, 10 * 60_000, &mut rng);
+        assert!(matches!(obj.lru() & 255, 10 | 11));
+        assert_eq!(obj.lru() >> 8, 10);
+    }
+
+    #[test]
+    fn test_object_command() {
+        let obj = create_string_object(b"hello");
+        obj.init_access(AccessTracking::Lru, 0);
+        assert_eq!(object_command("encoding", Some(&obj), AccessTracking::Lru, 0), Ok(Reply::bulk("embstr")));
+        assert_eq!(object_command("REFCOUNT", Some(&obj), AccessTracking::Lru, 0), Ok(Reply::Integer(1)));
+        assert_eq!(object_command("IdleTime", Some(&obj), AccessTracking::Lru, 7500), Ok(Reply::Integer(7)));
+        assert!(object_command("FREQ", Some(&obj), AccessTracking::Lru, 0).unwrap_err().starts_with("An LFU maxmemory policy is not selected"));
+        assert_eq!(object_command("ENCODING", None, AccessTracking::Lru, 0), Ok(Reply::Null));
+        assert_eq!(
+            object_command("size", Some(&obj), AccessTracking::Lru, 0),
+            Err("unknown subcommand 'size'. Try OBJECT HELP.".to_string())
+        );
+
+        let obj = create_string_object(b"hello");
+        obj.init_access(LFU, 0);
+        assert_eq!(object_command("FREQ", Some(&obj), LFU, 0), Ok(Reply::Integer(LFU_INIT_VAL as i64)));
+        assert!(object_command("IDLETIME", Some(&obj), LFU, 0).unwrap_err().starts_with("An LRU maxmemory policy is not selected"));
+
+        let shared = create_string_object_from_long_long(1);
+        assert_eq!(object_command("refcount", Some(&shared), LFU, 0), Ok(Reply::Integer(OBJ_SHARED_REFCOUNT as i64)));
+        assert_eq!(object_command("encoding", Some(&shared), LFU, 0), Ok(Reply::bulk("int")));
+    }
+
+    #[test]
+    fn test_compute_size_samples_big_containers() {
+        let mut list = create_list_object();
+        if let ObjectValue::List(list) = Rc::get_mut(&mut list).unwrap().value_mut() {
+            for i in 0..100 {
+                // Only the first few elements are small
+                let len = if i < OBJ_COMPUTE_SIZE_DEF_SAMPLES { 10 } else { 1000 };
+                list.push_back(vec![b'e'; len]);
+            }
+        }
+        let size = object_compute_size(&list);
+        assert!(size > 100 * 10 && size < 100 * 1000, "size {}", size);
+    }
+
+    // Add more tests here...
+}
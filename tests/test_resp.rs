@@ -0,0 +1,270 @@
+use curly_giggle::protocol::resp::*;
+use curly_giggle::sds::SDS;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(argv: Vec<SDS>) -> Vec<Vec<u8>> {
+        argv.iter().map(|arg| arg.as_bytes().to_vec()).collect()
+    }
+
+    fn parse_all(parser: &mut RequestParser) -> Result<Vec<Vec<Vec<u8>>>, String> {
+        let mut commands = Vec::new();
+        while let Some(argv) = parser.next_command()? {
+            commands.push(args(argv));
+        }
+        Ok(commands)
+    }
+
+    fn parse_error(input: &[u8]) -> String {
+        let mut parser = RequestParser::new();
+        parser.feed(input);
+        parse_all(&mut parser).unwrap_err()
+    }
+
+    fn bytes(args: &[&str]) -> Vec<Vec<u8>> {
+        args.iter().map(|arg| arg.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn test_multibulk() {
+        let mut parser = RequestParser::new();
+        parser.feed(b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$0\r\n\r\n*1\r\n$4\r\nPING\r\n");
+        assert_eq!(parse_all(&mut parser), Ok(vec![bytes(&["SET", "key", ""]), bytes(&["PING"])]));
+        assert_eq!(parser.pending(), 0);
+    }
+
+    #[test]
+    fn test_binary_arguments() {
+        let mut parser = RequestParser::new();
+        parser.feed(b"*2\r\n$3\r\nGET\r\n$4\r\n\r\n\x00\xff\r\n");
+        let argv = parser.next_command().unwrap().unwrap();
+        assert_eq!(argv[1].as_bytes(), b"\r\n\x00\xff");
+    }
+
+    #[test]
+    fn test_one_byte_at_a_time() {
+        let input = b"*2\r\n$4\r\nECHO\r\n$11\r\nhello world\r\nPING\r\n*1\r\n$4\r\nQUIT\r\n";
+        let mut parser = RequestParser::new();
+        let mut commands = Vec::new();
+        for &byte in input.iter() {
+            parser.feed(&[byte]);
+            commands.extend(parse_all(&mut parser).unwrap());
+        }
+        assert_eq!(commands, vec![bytes(&["ECHO", "hello world"]), bytes(&["PING"]), bytes(&["QUIT"])]);
+        assert_eq!(parser.pending(), 0);
+    }
+
+    #[test]
+    fn test_long_line_in_pieces() {
+        // Each piece only searches the bytes it adds for the end of the line
+        let mut parser = RequestParser::new();
+        parser.feed(b"ECHO ");
+        for _ in 0..60 {
+            parser.feed(&[b'x'; 1000]);
+            assert!(parser.next_command().unwrap().is_none());
+        }
+        parser.feed(b"\r\n*1\r");
+        let argv = args(parser.next_command().unwrap().unwrap());
+        assert_eq!(argv, vec![b"ECHO".to_vec(), vec![b'x'; 60_000]]);
+        assert!(parser.next_command().unwrap().is_none());
+        parser.feed(b"\n$4\r\nPING\r\n");
+        assert_eq!(parse_all(&mut parser), Ok(vec![bytes(&["PING"])]));
+    }
+
+    #[test]
+    fn test_partial_bulk_is_kept() {
+        let mut parser = RequestParser::new();
+        parser.feed(b"*2\r\n$3\r\nGET\r\n$10\r\n01234");
+        assert!(parser.next_command().unwrap().is_none());
+        assert_eq!(parser.pending(), 5);
+        parser.feed(b"56789\r");
+        assert!(parser.next_command().unwrap().is_none());
+        parser.feed(b"\n");
+        assert_eq!(args(parser.next_command().unwrap().unwrap()), bytes(&["GET", "0123456789"]));
+    }
+
+    #[test]
+    fn test_empty_requests_are_skipped() {
+        let mut parser = RequestParser::new();
+        parser.feed(b"*0\r\n*-1\r\n\r\n   \n*1\r\n$4\r\nPING\r\n");
+        assert_eq!(parse_all(&mut parser), Ok(vec![bytes(&["PING"])]));
+    }
+
+    #[test]
+    fn test_inline() {
+        let mut parser = RequestParser::new();
+        parser.feed(b"SET  key \"hello world\"\r\nGET key\nLPUSH l 'it\\'s' \"\\x41\\t\\\"\"\r\n");
+        assert_eq!(
+            parse_all(&mut parser),
+            Ok(vec![bytes(&["SET", "key", "hello world"]), bytes(&["GET", "key"]), bytes(&["LPUSH", "l", "it's", "A\t\""])])
+        );
+
+        // A NUL byte ends an argument
+        parser.feed(b"GET a\0b\r\nGET\0\r\n");
+        assert_eq!(parse_all(&mut parser), Ok(vec![bytes(&["GET", "a", "b"]), bytes(&["GET"])]));
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(split_args(b""), Some(vec![]));
+        assert_eq!(split_args(b"  a  b  "), Some(bytes(&["a", "b"])));
+        assert_eq!(split_args(b"\"\""), Some(vec![vec![]]));
+        assert_eq!(split_args(b"a\"b c\""), Some(bytes(&["ab c"])));
+        assert_eq!(split_args(b"\"\\xzz\\n\""), Some(bytes(&["xzz\n"])));
+        assert_eq!(split_args(b"\"a\"b"), None);
+        assert_eq!(split_args(b"'a'b"), None);
+        assert_eq!(split_args(b"'a"), None);
+        assert_eq!(split_args(b"\"a\\\""), None);
+        assert_eq!(split_args(b"a\0\0b\0"), Some(bytes(&["a", "b"])));
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        assert_eq!(parse_error(b"*x\r\n"), "Protocol error: invalid multibulk length");
+        assert_eq!(parse_error(b"*2147483648\r\n"), "Protocol error: invalid multibulk length");
+        assert_eq!(parse_error(b"*1\r\n+OK\r\n"), "Protocol error: expected '$', got '+'");
+        assert_eq!(parse_error(b"*1\r\n$-1\r\n"), "Protocol error: invalid bulk length");
+        assert_eq!(parse_error(b"*1\r\n$abc\r\n"), "Protocol error: invalid bulk length");
+        assert_eq!(parse_error(b"*1\r\n$536870913\r\n"), "Protocol error: invalid bulk length");
+        assert_eq!(parse_error(b"SET k \"v\r\n"), "Protocol error: unbalanced quotes in request");
+
+        let long = vec![b'1'; PROTO_INLINE_MAX_SIZE + 1];
+        assert_eq!(parse_error(&long), "Protocol error: too big inline request");
+        let mut mbulk = b"*".to_vec();
+        mbulk.extend_from_slice(&long);
+        assert_eq!(parse_error(&mbulk), "Protocol error: too big mbulk count string");
+        let mut bulk = b"*1\r\n$".to_vec();
+        bulk.extend_from_slice(&long);
+        assert_eq!(parse_error(&bulk), "Protocol error: too big bulk count string");
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut parser = RequestParser::with_config(RespConfig {
+            proto_max_bulk_len: 4,
+            max_multibulk_len: 2,
+        });
+        parser.feed(b"*2\r\n$4\r\nabcd\r\n$0\r\n\r\n");
+        assert_eq!(parse_all(&mut parser), Ok(vec![bytes(&["abcd", ""])]));
+        parser.feed(b"*1\r\n$5\r\nabcde\r\n");
+        assert_eq!(parse_all(&mut parser), Err("Protocol error: invalid bulk length".to_string()));
+
+        let mut parser = RequestParser::with_config(RespConfig {
+            proto_max_bulk_len: 4,
+            max_multibulk_len: 2,
+        });
+        parser.feed(b"*3\r\n");
+        assert_eq!(parse_all(&mut parser), Err("Protocol error: invalid multibulk length".to_string()));
+    }
+
+    fn both(reply: &Reply) -> (String, String) {
+        (
+            String::from_utf8(reply.to_bytes(RespVersion::Resp2)).unwrap(),
+            String::from_utf8(reply.to_bytes(RespVersion::Resp3)).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_resp2_types() {
+        let reply = Reply::Array(vec![
+            Reply::Simple("OK".to_string()),
+            Reply::error("unknown command"),
+            Reply::Integer(-42),
+            Reply::bulk("hello"),
+            Reply::bulk(""),
+            Reply::Null,
+            Reply::NullArray,
+        ]);
+        let expected = "*7\r\n+OK\r\n-ERR unknown command\r\n:-42\r\n$5\r\nhello\r\n$0\r\n\r\n$-1\r\n*-1\r\n";
+        assert_eq!(both(&reply).0, expected);
+        assert_eq!(both(&Reply::Array(vec![])), ("*0\r\n".to_string(), "*0\r\n".to_string()));
+    }
+
+    #[test]
+    fn test_resp3_types() {
+        assert_eq!(both(&Reply::Null), ("$-1\r\n".to_string(), "_\r\n".to_string()));
+        assert_eq!(both(&Reply::NullArray), ("*-1\r\n".to_string(), "_\r\n".to_string()));
+        assert_eq!(both(&Reply::Boolean(true)), (":1\r\n".to_string(), "#t\r\n".to_string()));
+        assert_eq!(both(&Reply::Boolean(false)), (":0\r\n".to_string(), "#f\r\n".to_string()));
+        assert_eq!(both(&Reply::Double(3.25)), ("$4\r\n3.25\r\n".to_string(), ",3.25\r\n".to_string()));
+        assert_eq!(both(&Reply::Double(f64::INFINITY)), ("$3\r\ninf\r\n".to_string(), ",inf\r\n".to_string()));
+        assert_eq!(both(&Reply::Double(f64::NAN)).1, ",nan\r\n");
+        assert_eq!(
+            both(&Reply::BigNumber("3492890328409238509324850943850943825024385".to_string())),
+            (
+                "$43\r\n3492890328409238509324850943850943825024385\r\n".to_string(),
+                "(3492890328409238509324850943850943825024385\r\n".to_string()
+            )
+        );
+        assert_eq!(
+            both(&Reply::Verbatim { format: *b"txt", text: b"Some string".to_vec() }),
+            ("$11\r\nSome string\r\n".to_string(), "=15\r\ntxt:Some string\r\n".to_string())
+        );
+        assert_eq!(
+            both(&Reply::Set(vec![Reply::Integer(1), Reply::Integer(2)])),
+            ("*2\r\n:1\r\n:2\r\n".to_string(), "~2\r\n:1\r\n:2\r\n".to_string())
+        );
+        assert_eq!(
+            both(&Reply::Push(vec![Reply::bulk("message"), Reply::bulk("ch")])),
+            ("*2\r\n$7\r\nmessage\r\n$2\r\nch\r\n".to_string(), ">2\r\n$7\r\nmessage\r\n$2\r\nch\r\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_maps_and_attributes() {
+        let map = Reply::Map(vec![
+            (Reply::bulk("first"), Reply::Integer(1)),
+            (Reply::bulk("second"), Reply::Map(vec![(Reply::Integer(2), Reply::Boolean(true))])),
+        ]);
+        assert_eq!(
+            both(&map),
+            (
+                "*4\r\n$5\r\nfirst\r\n:1\r\n$6\r\nsecond\r\n*2\r\n:2\r\n:1\r\n".to_string(),
+                "%2\r\n$5\r\nfirst\r\n:1\r\n$6\r\nsecond\r\n%1\r\n:2\r\n#t\r\n".to_string()
+            )
+        );
+
+        let attribute = Reply::Attribute {
+            attributes: vec![(Reply::Simple("ttl".to_string()), Reply::Integer(3600))],
+            reply: Box::new(Reply::Array(vec![Reply::Integer(2039123), Reply::Integer(9543892)])),
+        };
+        assert_eq!(
+            both(&attribute),
+            (
+                "*2\r\n:2039123\r\n:9543892\r\n".to_string(),
+                "|1\r\n+ttl\r\n:3600\r\n*2\r\n:2039123\r\n:9543892\r\n".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_errors_and_simple_strings() {
+        assert_eq!(both(&Reply::error("-WRONGTYPE Operation against a key")).0, "-WRONGTYPE Operation against a key\r\n");
+        assert_eq!(both(&Reply::error("bad\r\ninput")).0, "-ERR bad  input\r\n");
+        assert_eq!(both(&Reply::Simple("a\nb".to_string())).1, "+a b\r\n");
+    }
+
+    #[test]
+    fn test_format_double() {
+        assert_eq!(format_double(0.0), "0");
+        assert_eq!(format_double(-0.0), "-0");
+        assert_eq!(format_double(1.0 / 3.0), "0.3333333333333333");
+        assert_eq!(format_double(0.0001), "0.0001");
+        assert_eq!(format_double(0.00001), "1e-05");
+        assert_eq!(format_double(1e16), "10000000000000000");
+        assert_eq!(format_double(1.5e17), "1.5e+17");
+        assert_eq!(format_double(1.7976931348623157e308), "1.7976931348623157e+308");
+        assert_eq!(format_double(5e-324), "5e-324");
+    }
+
+    #[test]
+    fn test_encode_appends() {
+        let mut out = b"+PONG\r\n".to_vec();
+        Reply::Integer(1).encode(RespVersion::Resp3, &mut out);
+        assert_eq!(out, b"+PONG\r\n:1\r\n");
+    }
+
+    // Add more tests here...
+}
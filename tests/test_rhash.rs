@@ -0,0 +1,342 @@
+use std::collections::BTreeSet;
+
+use curly_giggle::clock::ManualClock;
+use curly_giggle::collection::rhash::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL: HashConfig = HashConfig {
+        max_listpack_entries: 4,
+        max_listpack_value: 8,
+    };
+
+    fn string(value: Vec<u8>) -> String {
+        String::from_utf8(value).unwrap()
+    }
+
+    fn sorted(pairs: Vec<(Vec<u8>, Vec<u8>)>) -> Vec<(String, String)> {
+        let mut pairs: Vec<(String, String)> = pairs.into_iter().map(|(f, v)| (string(f), string(v))).collect();
+        pairs.sort();
+        pairs
+    }
+
+    // Hashes holding the same fields in each encoding, sharing `clock`
+    fn encoded(pairs: &[(&str, &str)], clock: &ManualClock) -> Vec<RHash<ManualClock>> {
+        [HashEncoding::Listpack, HashEncoding::ListpackEx, HashEncoding::HashTable]
+            .into_iter()
+            .map(|encoding| {
+                let mut hash = RHash::with_clock(HashConfig::default(), clock.clone());
+                hash.hset(pairs);
+                hash.hash_type_convert(encoding).unwrap();
+                assert_eq!(hash.encoding(), encoding);
+                hash
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hset_hget_hdel_in_every_encoding() {
+        let clock = ManualClock::new(0);
+        for mut hash in encoded(&[("a", "1"), ("b", "2")], &clock) {
+            assert_eq!(hash.hlen(), 2);
+            assert_eq!(hash.hset(&[("b", "20"), ("c", "three")]), 1);
+            assert_eq!(hash.hget("b"), Some(b"20".to_vec()));
+            assert_eq!(hash.hget("z"), None);
+            assert_eq!(hash.hmget(&["a", "z", "c"]), vec![Some(b"1".to_vec()), None, Some(b"three".to_vec())]);
+            assert!(hash.hexists("c"));
+            assert_eq!(hash.hstrlen("c"), 5);
+            assert_eq!(hash.hstrlen("z"), 0);
+            assert!(!hash.hsetnx("a", "x"));
+            assert!(hash.hsetnx("d", "4"));
+            assert_eq!(
+                sorted(hash.hgetall()),
+                vec![
+                    ("a".to_string(), "1".to_string()),
+                    ("b".to_string(), "20".to_string()),
+                    ("c".to_string(), "three".to_string()),
+                    ("d".to_string(), "4".to_string()),
+                ]
+            );
+            assert_eq!(hash.hdel(&["a", "z", "d"]), 2);
+            assert_eq!(hash.hlen(), 2);
+            assert_eq!(hash.hdel(&["b", "c"]), 2);
+            assert!(hash.is_empty());
+            assert!(hash.hgetall().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_fields_and_values_are_not_mixed_up() {
+        // Values equal to other fields are never taken for fields
+        let mut hash = RHash::new();
+        hash.hset(&[("a", "b"), ("b", "a"), ("1", "2")]);
+        assert_eq!(hash.hget("b"), Some(b"a".to_vec()));
+        assert_eq!(hash.hget("2"), None);
+        assert_eq!(hash.hdel(&["2"]), 0);
+        assert_eq!(hash.hdel(&["b"]), 1);
+        assert_eq!(hash.hget("a"), Some(b"b".to_vec()));
+        assert_eq!(hash.hkeys(), vec![b"a".to_vec(), b"1".to_vec()]);
+        assert_eq!(hash.hvals(), vec![b"b".to_vec(), b"2".to_vec()]);
+    }
+
+    #[test]
+    fn test_listpack_to_hashtable() {
+        let mut hash = RHash::with_config(SMALL);
+        hash.hset(&[("a", "1"), ("b", "2"), ("c", "3"), ("d", "4")]);
+        assert_eq!(hash.encoding(), HashEncoding::Listpack);
+        hash.hset(&[("e", "5")]);
+        assert_eq!(hash.encoding(), HashEncoding::HashTable);
+        assert_eq!(hash.hlen(), 5);
+
+        let mut hash = RHash::with_config(SMALL);
+        hash.hset(&[("a", "1")]);
+        hash.hset(&[("a", "long value")]);
+        assert_eq!(hash.encoding(), HashEncoding::HashTable);
+        assert_eq!(hash.hget("a"), Some(b"long value".to_vec()));
+
+        let mut hash = RHash::with_config(SMALL);
+        hash.hset(&[("long field", "1")]);
+        assert_eq!(hash.encoding(), HashEncoding::HashTable);
+
+        // Deleting fields does not convert back
+        hash.hdel(&["long field"]);
+        assert_eq!(hash.encoding(), HashEncoding::HashTable);
+    }
+
+    #[test]
+    fn test_convert() {
+        let clock = ManualClock::new(0);
+        let mut hash = RHash::with_clock(HashConfig::default(), clock);
+        hash.hset(&[("a", "1"), ("b", "2")]);
+        hash.hexpire(100, None, &["a"]).unwrap();
+        assert_eq!(hash.encoding(), HashEncoding::ListpackEx);
+        assert!(hash.hash_type_convert(HashEncoding::Listpack).is_err());
+
+        hash.hash_type_convert(HashEncoding::HashTable).unwrap();
+        assert_eq!(hash.httl(&["a", "b"]), Ok(vec![100, HFE_GET_NO_TTL]));
+        hash.hpersist(&["a"]).unwrap();
+        assert_eq!(hash.hash_type_convert(HashEncoding::Listpack), Ok(()));
+        assert_eq!(hash.hget("a"), Some(b"1".to_vec()));
+    }
+
+    #[test]
+    fn test_hincrby() {
+        let mut hash = RHash::new();
+        assert_eq!(hash.hincrby("n", 10), Ok(10));
+        assert_eq!(hash.hincrby("n", -15), Ok(-5));
+        assert_eq!(hash.hget("n"), Some(b"-5".to_vec()));
+
+        hash.hset(&[("s", "abc"), ("padded", "007"), ("max", "9223372036854775807")]);
+        assert_eq!(hash.hincrby("s", 1), Err("hash value is not an integer".to_string()));
+        assert_eq!(hash.hincrby("padded", 1), Err("hash value is not an integer".to_string()));
+        assert_eq!(hash.hincrby("max", 1), Err("increment or decrement would overflow".to_string()));
+        assert_eq!(hash.hincrby("n", i64::MIN), Err("increment or decrement would overflow".to_string()));
+        assert_eq!(hash.hget("max"), Some(b"9223372036854775807".to_vec()));
+    }
+
+    #[test]
+    fn test_hincrbyfloat() {
+        let mut hash = RHash::new();
+        assert_eq!(hash.hincrbyfloat("f", 1.5), Ok(b"1.5".to_vec()));
+        assert_eq!(hash.hincrbyfloat("f", 1.5), Ok(b"3".to_vec()));
+        hash.hset(&[("e", "5.0e3"), ("s", "abc"), ("nan", "nan")]);
+        assert_eq!(hash.hincrbyfloat("e", 1.0), Ok(b"5001".to_vec()));
+        assert_eq!(hash.hincrbyfloat("s", 1.0), Err("hash value is not a float".to_string()));
+        assert_eq!(hash.hincrbyfloat("nan", 1.0), Err("hash value is not a float".to_string()));
+        assert_eq!(hash.hincrbyfloat("f", f64::INFINITY), Err("increment would produce NaN or Infinity".to_string()));
+        assert_eq!(hash.hincrbyfloat("f", f64::NAN), Err("increment would produce NaN or Infinity".to_string()));
+        assert_eq!(hash.hget("f"), Some(b"3".to_vec()));
+    }
+
+    #[test]
+    fn test_hrandfield() {
+        let clock = ManualClock::new(0);
+        for hash in encoded(&[("a", "1"), ("b", "2"), ("c", "3")], &clock) {
+            let mut rng = StdRng::seed_from_u64(3);
+            let distinct = hash.hrandfield_with_rng(2, true, &mut rng).unwrap();
+            assert_eq!(distinct.len(), 2);
+            assert_ne!(distinct[0].0, distinct[1].0);
+            for (field, value) in &distinct {
+                assert_eq!(hash.hget(field), value.clone());
+            }
+            assert_eq!(hash.hrandfield(5, false).unwrap().len(), 3);
+            assert!(hash.hrandfield(5, false).unwrap().iter().all(|(_, value)| value.is_none()));
+
+            let repeated = hash.hrandfield_with_rng(-100, false, &mut rng).unwrap();
+            assert_eq!(repeated.len(), 100);
+            assert_eq!(repeated.iter().map(|(field, _)| field).collect::<BTreeSet<_>>().len(), 3);
+
+            assert!(hash.hrandfield(0, false).unwrap().is_empty());
+            assert_eq!(hash.hrandfield(i64::MIN, false), Err("value is out of range".to_string()));
+            assert_eq!(hash.hrandfield(-(i64::MAX / 2) - 1, true), Err("value is out of range".to_string()));
+            assert_eq!(hash.hrandfield(-i64::MAX, false), Err("value is out of range".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_hrandfield_skips_expired_fields() {
+        let clock = ManualClock::new(0);
+        let mut hash = RHash::with_clock(HashConfig::default(), clock.clone());
+        let fields: Vec<String> = (0..1000).map(|i| format!("f{:03}", i)).collect();
+        let pairs: Vec<(&str, &str)> = fields.iter().map(|field| (field.as_str(), "v")).collect();
+        hash.hset(&pairs);
+        assert_eq!(hash.encoding(), HashEncoding::HashTable);
+        assert_eq!(hash.hpexpire(1000, None, &fields[..900]), Ok(vec![HSETEX_OK; 900]));
+        clock.advance(2000);
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let is_live = |field: &Vec<u8>| field.as_slice() >= b"f900".as_slice();
+        for (count, expected) in [(10, 10), (60, 60), (500, 100)] {
+            let picked = hash.hrandfield_with_rng(count, true, &mut rng).unwrap();
+            assert_eq!(picked.iter().map(|(field, _)| field).collect::<BTreeSet<_>>().len(), expected);
+            assert!(picked.iter().all(|(field, value)| is_live(field) && value.as_deref() == Some(b"v".as_slice())));
+        }
+        let repeated = hash.hrandfield_with_rng(-50, false, &mut rng).unwrap();
+        assert_eq!(repeated.len(), 50);
+        assert!(repeated.iter().all(|(field, _)| is_live(field)));
+    }
+
+    #[test]
+    fn test_hexpire_and_lazy_expiration() {
+        let clock = ManualClock::new(1_000_000);
+        for mut hash in encoded(&[("a", "1"), ("b", "2"), ("c", "3")], &clock) {
+            clock.set(1_000_000);
+            assert_eq!(hash.hexpire(10, None, &["a", "z"]), Ok(vec![HSETEX_OK, HFE_GET_NO_FIELD]));
+            assert_eq!(hash.hpexpire(1500, None, &["b"]), Ok(vec![HSETEX_OK]));
+            if hash.encoding() != HashEncoding::HashTable {
+                assert_eq!(hash.encoding(), HashEncoding::ListpackEx);
+            }
+            assert_eq!(hash.httl(&["a", "b", "c", "z"]), Ok(vec![10, 2, HFE_GET_NO_TTL, HFE_GET_NO_FIELD]));
+            assert_eq!(hash.hpttl(&["a", "b"]), Ok(vec![10000, 1500]));
+            assert_eq!(hash.hexpiretime(&["a", "b"]), Ok(vec![1010, 1002]));
+            assert_eq!(hash.hpexpiretime(&["a", "b"]), Ok(vec![1_010_000, 1_001_500]));
+
+            // Alive up to its expire time
+            clock.advance(1500);
+            assert_eq!(hash.hget("b"), Some(b"2".to_vec()));
+            clock.advance(1);
+            assert_eq!(hash.hget("b"), None);
+            assert!(!hash.hexists("b"));
+            assert_eq!(hash.hlen(), 2);
+            assert_eq!(hash.httl(&["b"]), Ok(vec![HFE_GET_NO_FIELD]));
+            assert_eq!(sorted(hash.hgetall()).len(), 2);
+            assert_eq!(hash.hrandfield(-5, false).unwrap().iter().filter(|(f, _)| f == b"b").count(), 0);
+
+            // Setting an expired field adds it back without an expire time
+            assert_eq!(hash.hset(&[("b", "new")]), 1);
+            assert_eq!(hash.httl(&["b"]), Ok(vec![HFE_GET_NO_TTL]));
+
+            clock.advance(10000);
+            assert_eq!(hash.delete_expired_fields(), 1);
+            assert_eq!(hash.hkeys().len(), 2);
+            assert_eq!(hash.delete_expired_fields(), 0);
+        }
+    }
+
+    #[test]
+    fn test_hexpire_conditions() {
+        let clock = ManualClock::new(0);
+        for mut hash in encoded(&[("a", "1"), ("b", "2")], &clock) {
+            assert_eq!(hash.hexpire(100, Some(ExpireCondition::Xx), &["a"]), Ok(vec![HSETEX_NO_CONDITION_MET]));
+            assert_eq!(hash.hexpire(100, Some(ExpireCondition::Gt), &["a"]), Ok(vec![HSETEX_NO_CONDITION_MET]));
+            assert_eq!(hash.hexpire(100, Some(ExpireCondition::Lt), &["a"]), Ok(vec![HSETEX_OK]));
+            assert_eq!(hash.hexpire(200, Some(ExpireCondition::Nx), &["a", "b"]), Ok(vec![HSETEX_NO_CONDITION_MET, HSETEX_OK]));
+            assert_eq!(hash.hexpire(50, Some(ExpireCondition::Gt), &["a"]), Ok(vec![HSETEX_NO_CONDITION_MET]));
+            assert_eq!(hash.hexpire(150, Some(ExpireCondition::Gt), &["a"]), Ok(vec![HSETEX_OK]));
+            assert_eq!(hash.hexpire(300, Some(ExpireCondition::Lt), &["a"]), Ok(vec![HSETEX_NO_CONDITION_MET]));
+            assert_eq!(hash.hexpire(120, Some(ExpireCondition::Xx), &["a"]), Ok(vec![HSETEX_OK]));
+            assert_eq!(hash.httl(&["a", "b"]), Ok(vec![120, 200]));
+        }
+    }
+
+    #[test]
+    fn test_hexpire_in_the_past_deletes() {
+        let clock = ManualClock::new(5000);
+        for mut hash in encoded(&[("a", "1"), ("b", "2")], &clock) {
+            assert_eq!(hash.hexpire(0, None, &["a"]), Ok(vec![HSETEX_DELETED]));
+            assert_eq!(hash.hexpireat(1, None, &["b"]), Ok(vec![HSETEX_DELETED]));
+            assert!(hash.is_empty());
+            assert_eq!(hash.hexpire(10, None, &["a"]), Ok(vec![HFE_GET_NO_FIELD]));
+        }
+    }
+
+    #[test]
+    fn test_hexpireat() {
+        let clock = ManualClock::new(5000);
+        let mut hash = RHash::with_clock(HashConfig::default(), clock.clone());
+        hash.hset(&[("a", "1"), ("b", "2")]);
+        assert_eq!(hash.hexpireat(20, None, &["a"]), Ok(vec![HSETEX_OK]));
+        assert_eq!(hash.hpexpireat(7500, None, &["b"]), Ok(vec![HSETEX_OK]));
+        assert_eq!(hash.httl(&["a", "b"]), Ok(vec![15, 3]));
+        assert_eq!(hash.hpexpiretime(&["a", "b"]), Ok(vec![20000, 7500]));
+    }
+
+    #[test]
+    fn test_fields_keep_or_lose_expire_time() {
+        let clock = ManualClock::new(0);
+        for mut hash in encoded(&[("n", "1"), ("f", "1.5"), ("s", "x")], &clock) {
+            hash.hexpire(100, None, &["n", "f", "s"]).unwrap();
+            hash.hincrby("n", 1).unwrap();
+            hash.hincrbyfloat("f", 1.0).unwrap();
+            assert!(!hash.hsetnx("s", "y"));
+            assert_eq!(hash.httl(&["n", "f", "s"]), Ok(vec![100, 100, 100]));
+
+            hash.hset(&[("s", "y")]);
+            assert_eq!(hash.httl(&["n", "f", "s"]), Ok(vec![100, 100, HFE_GET_NO_TTL]));
+            assert_eq!(hash.hget("n"), Some(b"2".to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_hpersist() {
+        let clock = ManualClock::new(0);
+        for mut hash in encoded(&[("a", "1"), ("b", "2")], &clock) {
+            hash.hexpire(10, None, &["a"]).unwrap();
+            assert_eq!(hash.hpersist(&["a", "b", "z"]), Ok(vec![HFE_PERSIST_OK, HFE_GET_NO_TTL, HFE_GET_NO_FIELD]));
+            clock.advance(20000);
+            assert_eq!(hash.hget("a"), Some(b"1".to_vec()));
+            clock.set(0);
+        }
+    }
+
+    #[test]
+    fn test_ttl_conversion_to_hashtable() {
+        let clock = ManualClock::new(0);
+        let mut hash = RHash::with_clock(SMALL, clock.clone());
+        hash.hset(&[("a", "1"), ("b", "2")]);
+        hash.hexpire(10, None, &["a"]).unwrap();
+        hash.hset(&[("c", "3"), ("d", "4"), ("e", "5")]);
+        assert_eq!(hash.encoding(), HashEncoding::HashTable);
+        assert_eq!(hash.httl(&["a", "b"]), Ok(vec![10, HFE_GET_NO_TTL]));
+        clock.advance(10001);
+        assert_eq!(hash.hlen(), 4);
+    }
+
+    #[test]
+    fn test_expire_errors() {
+        let mut hash = RHash::new();
+        hash.hset(&[("a", "1")]);
+        let none: [&str; 0] = [];
+        assert_eq!(hash.hexpire(-1, None, &["a"]), Err("invalid expire time in 'hexpire' command".to_string()));
+        assert_eq!(hash.hexpire(i64::MAX, None, &["a"]), Err("invalid expire time in 'hexpire' command".to_string()));
+        assert_eq!(
+            hash.hpexpireat((HFE_MAX_ABS_TIME_MSEC + 1) as i64, None, &["a"]),
+            Err("invalid expire time in 'hpexpireat' command".to_string())
+        );
+        // Relative to now, past the largest expire time
+        assert_eq!(
+            hash.hpexpire(HFE_MAX_ABS_TIME_MSEC as i64, None, &["a"]),
+            Err("invalid expire time in 'hpexpire' command".to_string())
+        );
+        assert_eq!(hash.hexpire(10, None, &none), Err("Parameter `numFields` should be greater than 0".to_string()));
+        assert!(hash.httl(&none).is_err());
+        assert!(hash.hpersist(&none).is_err());
+        assert_eq!(hash.httl(&["a"]), Ok(vec![HFE_GET_NO_TTL]));
+    }
+
+    // Add more tests here...
+}
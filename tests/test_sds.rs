@@ -33,6 +33,15 @@ mod tests {
         assert_eq!(sds.sds_to_string(), "Hello, World!\0\0\0\0\0\0\0\0\0\0\0\0\0");
     }
     
+    #[test]
+    fn test_sds_new_len() {
+        let sds = SDS::sdsnewlen(b"\xff\x00abc");
+        assert_eq!(sds.sdslen(), 5);
+        assert_eq!(sds.sdsavail(), 5);
+        assert_eq!(sds.as_bytes(), b"\xff\x00abc");
+        assert_eq!(SDS::sdsnew("Hello").as_bytes(), b"Hello");
+    }
+
     #[test]
     fn test_sds_len() {
         let sds = SDS::sdsnew("Hello");
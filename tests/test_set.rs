@@ -0,0 +1,282 @@
+use std::collections::BTreeSet;
+
+use curly_giggle::collection::set::{RSet, SetConfig, SetEncoding};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL: SetConfig = SetConfig {
+        max_intset_entries: 4,
+        max_listpack_entries: 4,
+        max_listpack_value: 8,
+    };
+
+    fn sorted(members: Vec<Vec<u8>>) -> Vec<String> {
+        let mut members: Vec<String> = members.into_iter().map(|m| String::from_utf8(m).unwrap()).collect();
+        members.sort();
+        members
+    }
+
+    // Sets holding the same members in each encoding
+    fn encoded(members: &[&str]) -> Vec<RSet> {
+        [SetEncoding::IntSet, SetEncoding::Listpack, SetEncoding::HashTable]
+            .into_iter()
+            .filter_map(|encoding| {
+                let mut set = RSet::new();
+                set.sadd(members);
+                set.set_type_convert(encoding).ok()?;
+                assert_eq!(set.encoding(), encoding);
+                Some(set)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sadd_srem_in_every_encoding() {
+        for mut set in encoded(&["1", "2", "3"]) {
+            assert_eq!(set.scard(), 3);
+            assert_eq!(set.sadd(&["3", "4"]), 1);
+            assert!(set.sismember("4"));
+            assert!(!set.sismember("5"));
+            assert!(!set.sismember("x"));
+            assert_eq!(set.smismember(&["1", "5", "4"]), vec![true, false, true]);
+            assert_eq!(set.srem(&["1", "5", "x"]), 1);
+            assert_eq!(sorted(set.smembers()), vec!["2", "3", "4"]);
+            assert_eq!(set.srem(&["2", "3", "4"]), 3);
+            assert_eq!(set.scard(), 0);
+            assert!(set.smembers().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_intset_to_listpack() {
+        let mut set = RSet::with_config(SMALL);
+        set.sadd(&["3", "-1", "2"]);
+        assert_eq!(set.encoding(), SetEncoding::IntSet);
+        // Intset members come sorted
+        assert_eq!(set.smembers(), vec![b"-1".to_vec(), b"2".to_vec(), b"3".to_vec()]);
+
+        // Not the canonical form of an integer
+        assert_eq!(set.sadd(&["007"]), 1);
+        assert_eq!(set.encoding(), SetEncoding::Listpack);
+        assert!(set.sismember("007"));
+        assert!(!set.sismember("7"));
+        assert!(set.sismember("-1"));
+        assert_eq!(set.sadd(&["2"]), 0);
+    }
+
+    #[test]
+    fn test_intset_to_hashtable() {
+        let mut set = RSet::with_config(SMALL);
+        set.sadd(&["1", "2", "3", "4"]);
+        assert_eq!(set.encoding(), SetEncoding::IntSet);
+        set.sadd(&["5"]);
+        assert_eq!(set.encoding(), SetEncoding::HashTable);
+        assert_eq!(sorted(set.smembers()), vec!["1", "2", "3", "4", "5"]);
+
+        // Too many members for a listpack
+        let mut set = RSet::with_config(SMALL);
+        set.sadd(&["1", "2", "3", "4"]);
+        set.sadd(&["a"]);
+        assert_eq!(set.encoding(), SetEncoding::HashTable);
+
+        // An integer too long for a listpack entry
+        let mut set = RSet::with_config(SMALL);
+        set.sadd(&["123456789"]);
+        set.sadd(&["a"]);
+        assert_eq!(set.encoding(), SetEncoding::HashTable);
+        assert!(set.sismember("123456789"));
+
+        // A member too long for a listpack entry
+        let mut set = RSet::with_config(SMALL);
+        set.sadd(&["1"]);
+        set.sadd(&["abcdefghi"]);
+        assert_eq!(set.encoding(), SetEncoding::HashTable);
+    }
+
+    #[test]
+    fn test_listpack_to_hashtable() {
+        let mut set = RSet::with_config(SMALL);
+        set.sadd(&["a", "b", "c", "d"]);
+        assert_eq!(set.encoding(), SetEncoding::Listpack);
+        // Insertion order
+        assert_eq!(set.smembers(), vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]);
+        set.sadd(&["e"]);
+        assert_eq!(set.encoding(), SetEncoding::HashTable);
+        assert_eq!(sorted(set.smembers()), vec!["a", "b", "c", "d", "e"]);
+
+        let mut set = RSet::with_config(SMALL);
+        set.sadd(&["a"]);
+        set.sadd(&["long member"]);
+        assert_eq!(set.encoding(), SetEncoding::HashTable);
+
+        // Removing members does not convert back
+        set.srem(&["long member"]);
+        assert_eq!(set.encoding(), SetEncoding::HashTable);
+    }
+
+    #[test]
+    fn test_convert() {
+        let mut set = RSet::new();
+        set.sadd(&["1", "a"]);
+        assert!(set.set_type_convert(SetEncoding::IntSet).is_err());
+        assert_eq!(set.encoding(), SetEncoding::Listpack);
+        set.srem(&["a"]);
+        assert_eq!(set.set_type_convert(SetEncoding::IntSet), Ok(()));
+        assert!(set.sismember("1"));
+    }
+
+    #[test]
+    fn test_spop() {
+        for mut set in encoded(&["1", "2", "3", "4", "5"]) {
+            let mut rng = StdRng::seed_from_u64(5);
+            let popped = set.spop_with_rng(2, &mut rng);
+            assert_eq!(popped.len(), 2);
+            assert_ne!(popped[0], popped[1]);
+            assert_eq!(set.scard(), 3);
+            assert!(popped.iter().all(|member| !set.sismember(member)));
+
+            assert_eq!(set.spop(0), Vec::<Vec<u8>>::new());
+            let rest = set.spop(10);
+            assert_eq!(rest.len(), 3);
+            let mut all: Vec<Vec<u8>> = popped.into_iter().chain(rest).collect();
+            all.sort();
+            assert_eq!(sorted(all), vec!["1", "2", "3", "4", "5"]);
+            assert_eq!(set.scard(), 0);
+            assert!(set.spop(1).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_srandmember() {
+        for set in encoded(&["1", "2", "3"]) {
+            let mut rng = StdRng::seed_from_u64(9);
+            let distinct = set.srandmember_with_rng(2, &mut rng).unwrap();
+            assert_eq!(distinct.len(), 2);
+            assert_ne!(distinct[0], distinct[1]);
+            assert_eq!(sorted(set.srandmember(10).unwrap()), vec!["1", "2", "3"]);
+
+            let repeated = set.srandmember_with_rng(-20, &mut rng).unwrap();
+            assert_eq!(repeated.len(), 20);
+            assert!(repeated.iter().all(|member| set.sismember(member)));
+            assert_eq!(repeated.iter().collect::<BTreeSet<_>>().len(), 3);
+
+            assert!(set.srandmember(0).unwrap().is_empty());
+            assert_eq!(set.srandmember(i64::MIN), Err("value is out of range".to_string()));
+            assert_eq!(set.srandmember(-i64::MAX), Err("value is out of range".to_string()));
+            assert_eq!(set.scard(), 3);
+        }
+        assert!(RSet::new().srandmember(-3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_random_members_of_a_large_set() {
+        let members: Vec<String> = (0..1000).map(|i| format!("m{}", i)).collect();
+        let mut set = RSet::new();
+        set.sadd(&members);
+        assert_eq!(set.encoding(), SetEncoding::HashTable);
+        let mut rng = StdRng::seed_from_u64(3);
+
+        for count in [10, 500] {
+            let distinct = set.srandmember_with_rng(count, &mut rng).unwrap();
+            assert_eq!(distinct.iter().collect::<BTreeSet<_>>().len(), count as usize);
+            assert!(distinct.iter().all(|member| set.sismember(member)));
+        }
+        let repeated = set.srandmember_with_rng(-50, &mut rng).unwrap();
+        assert_eq!(repeated.len(), 50);
+        assert!(repeated.iter().all(|member| set.sismember(member)));
+
+        let popped = set.spop_with_rng(3, &mut rng);
+        assert_eq!(popped.iter().collect::<BTreeSet<_>>().len(), 3);
+        assert!(popped.iter().all(|member| !set.sismember(member)));
+        assert_eq!(set.scard(), 997);
+    }
+
+    #[test]
+    fn test_smove() {
+        let mut src = RSet::new();
+        let mut dst = RSet::new();
+        src.sadd(&["1", "a"]);
+        dst.sadd(&["2"]);
+        assert!(RSet::smove(&mut src, &mut dst, "a"));
+        assert!(!RSet::smove(&mut src, &mut dst, "a"));
+        assert!(!src.sismember("a"));
+        assert!(dst.sismember("a"));
+        assert_eq!(dst.encoding(), SetEncoding::Listpack);
+
+        // Already in the destination
+        dst.sadd(&["1"]);
+        assert!(RSet::smove(&mut src, &mut dst, "1"));
+        assert_eq!(src.scard(), 0);
+        assert_eq!(dst.scard(), 3);
+    }
+
+    #[test]
+    fn test_sinter_sunion_sdiff() {
+        let sets_a = encoded(&["1", "2", "3", "4"]);
+        let mut b = RSet::new();
+        b.sadd(&["3", "4", "5", "x"]);
+        let mut c = RSet::new();
+        c.sadd(&["4", "x", "y"]);
+
+        for a in &sets_a {
+            assert_eq!(sorted(RSet::sinter(&[a, &b]).unwrap()), vec!["3", "4"]);
+            assert_eq!(sorted(RSet::sinter(&[a, &b, &c]).unwrap()), vec!["4"]);
+            assert_eq!(sorted(RSet::sunion(&[a, &c]).unwrap()), vec!["1", "2", "3", "4", "x", "y"]);
+            assert_eq!(sorted(RSet::sdiff(&[a, &b]).unwrap()), vec!["1", "2"]);
+            assert_eq!(sorted(RSet::sdiff(&[a, &b, &c]).unwrap()), vec!["1", "2"]);
+            assert_eq!(sorted(RSet::sdiff(&[a]).unwrap()), vec!["1", "2", "3", "4"]);
+            assert!(RSet::sdiff(&[a, &b, a]).unwrap().is_empty());
+
+            assert_eq!(RSet::sintercard(&[a, &b], 0), Ok(2));
+            assert_eq!(RSet::sintercard(&[a, &b], 1), Ok(1));
+            assert_eq!(RSet::sintercard(&[a, &b], 5), Ok(2));
+        }
+
+        let empty = RSet::new();
+        assert!(RSet::sinter(&[&b, &empty]).unwrap().is_empty());
+        assert_eq!(sorted(RSet::sunion(&[&empty, &c]).unwrap()), vec!["4", "x", "y"]);
+        assert!(RSet::sdiff(&[&empty, &c]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_store_commands() {
+        let mut a = RSet::new();
+        a.sadd(&["1", "2", "3"]);
+        let mut b = RSet::new();
+        b.sadd(&["2", "3", "z"]);
+
+        let inter = RSet::sinterstore(&[&a, &b]).unwrap();
+        assert_eq!(inter.encoding(), SetEncoding::IntSet);
+        assert_eq!(inter.smembers(), vec![b"2".to_vec(), b"3".to_vec()]);
+
+        let union = RSet::sunionstore(&[&a, &b]).unwrap();
+        assert_eq!(union.encoding(), SetEncoding::Listpack);
+        assert_eq!(union.scard(), 4);
+
+        let diff = RSet::sdiffstore(&[&b, &a]).unwrap();
+        assert_eq!(diff.smembers(), vec![b"z".to_vec()]);
+
+        // Thresholds come from the first set
+        let mut small = RSet::with_config(SMALL);
+        small.sadd(&["9"]);
+        let union = RSet::sunionstore(&[&small, &a, &b]).unwrap();
+        assert_eq!(union.encoding(), SetEncoding::HashTable);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(RSet::sinter(&[]), Err("wrong number of arguments for 'sinter' command".to_string()));
+        assert_eq!(RSet::sunion(&[]), Err("wrong number of arguments for 'sunion' command".to_string()));
+        assert_eq!(RSet::sdiff(&[]), Err("wrong number of arguments for 'sdiff' command".to_string()));
+        assert!(RSet::sinterstore(&[]).is_err());
+        assert!(RSet::sunionstore(&[]).is_err());
+        assert!(RSet::sdiffstore(&[]).is_err());
+        assert_eq!(RSet::sintercard(&[], 0), Err("numkeys should be greater than 0".to_string()));
+    }
+
+    // Add more tests here...
+}
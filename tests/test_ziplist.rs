@@ -0,0 +1,210 @@
+use curly_giggle::collection::listpack::{Listpack, ListpackEntry};
+use curly_giggle::collection::ziplist::Ziplist;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    enum Value<'a> {
+        Int(i64),
+        Str(&'a [u8]),
+    }
+
+    // Writes a ziplist the way Redis does. Entries listed in `big_prevlen` get a 5 byte
+    // prevlen whatever the size of the previous entry, as left by a cascade update.
+    fn ziplist(values: &[Value], big_prevlen: &[usize]) -> Vec<u8> {
+        let mut buf = vec![0; 10];
+        let mut prev_size = 0;
+        let mut tail = 10;
+        for (i, value) in values.iter().enumerate() {
+            let start = buf.len();
+            if prev_size < 254 && !big_prevlen.contains(&i) {
+                buf.push(prev_size as u8);
+            } else {
+                buf.push(0xFE);
+                buf.extend_from_slice(&(prev_size as u32).to_le_bytes());
+            }
+            match *value {
+                Value::Int(v @ 0..=12) => buf.push(0xF1 + v as u8),
+                Value::Int(v) if i8::try_from(v).is_ok() => buf.extend_from_slice(&[0xFE, v as u8]),
+                Value::Int(v) if i16::try_from(v).is_ok() => {
+                    buf.push(0xC0);
+                    buf.extend_from_slice(&(v as i16).to_le_bytes());
+                }
+                Value::Int(v) if (-8388608..=8388607).contains(&v) => {
+                    buf.push(0xF0);
+                    buf.extend_from_slice(&(v as i32).to_le_bytes()[..3]);
+                }
+                Value::Int(v) if i32::try_from(v).is_ok() => {
+                    buf.push(0xD0);
+                    buf.extend_from_slice(&(v as i32).to_le_bytes());
+                }
+                Value::Int(v) => {
+                    buf.push(0xE0);
+                    buf.extend_from_slice(&v.to_le_bytes());
+                }
+                Value::Str(s) if s.len() <= 63 => {
+                    buf.push(s.len() as u8);
+                    buf.extend_from_slice(s);
+                }
+                Value::Str(s) if s.len() <= 16383 => {
+                    buf.extend_from_slice(&[0x40 | (s.len() >> 8) as u8, s.len() as u8]);
+                    buf.extend_from_slice(s);
+                }
+                Value::Str(s) => {
+                    buf.push(0x80);
+                    buf.extend_from_slice(&(s.len() as u32).to_be_bytes());
+                    buf.extend_from_slice(s);
+                }
+            }
+            prev_size = buf.len() - start;
+            tail = start;
+        }
+        buf.push(0xFF);
+        let total = buf.len() as u32;
+        buf[0..4].copy_from_slice(&total.to_le_bytes());
+        buf[4..8].copy_from_slice(&(tail as u32).to_le_bytes());
+        buf[8..10].copy_from_slice(&(values.len().min(65535) as u16).to_le_bytes());
+        buf
+    }
+
+    fn strs<'a>(values: &[&'a str]) -> Vec<Value<'a>> {
+        values.iter().map(|s| Value::Str(s.as_bytes())).collect()
+    }
+
+    #[test]
+    fn test_empty() {
+        let bytes = ziplist(&[], &[]);
+        assert_eq!(bytes, vec![11, 0, 0, 0, 10, 0, 0, 0, 0, 0, 0xFF]);
+        let zl = Ziplist::from_bytes(&bytes).unwrap();
+        assert!(zl.is_empty());
+        assert_eq!(zl.iter().next(), None);
+        assert_eq!(zl.to_listpack(), Listpack::new());
+        assert!(zl.to_list().is_empty());
+    }
+
+    #[test]
+    fn test_every_encoding() {
+        let long = "x".repeat(300);
+        let huge = "y".repeat(20000);
+        let ints = [0, 12, 13, -1, 127, -128, 128, 32767, -32768, 32768, 8388607, -8388608, 8388608, i32::MIN as i64, 1 << 40, i64::MIN];
+        let mut values: Vec<Value> = ints.iter().map(|&v| Value::Int(v)).collect();
+        values.extend([Value::Str(b""), Value::Str(b"abc"), Value::Str(long.as_bytes()), Value::Str(huge.as_bytes())]);
+        let bytes = ziplist(&values, &[]);
+        let zl = Ziplist::from_bytes(&bytes).unwrap();
+        assert_eq!(zl.len(), values.len());
+
+        let mut expected: Vec<ListpackEntry> = ints.iter().map(|&v| ListpackEntry::Int(v)).collect();
+        expected.extend([
+            ListpackEntry::Str(b""),
+            ListpackEntry::Str(b"abc"),
+            ListpackEntry::Str(long.as_bytes()),
+            ListpackEntry::Str(huge.as_bytes()),
+        ]);
+        assert_eq!(zl.iter().collect::<Vec<_>>(), expected);
+        // Backwards through the prevlen fields, 5 byte ones included after the huge string
+        let mut reversed = expected.clone();
+        reversed.reverse();
+        assert_eq!(zl.iter().rev().collect::<Vec<_>>(), reversed);
+    }
+
+    #[test]
+    fn test_cascade_update_prevlen() {
+        // A 5 byte prevlen holding a small size, left after the large entry was removed
+        let bytes = ziplist(&strs(&["a", "b", "c"]), &[1, 2]);
+        let zl = Ziplist::from_bytes(&bytes).unwrap();
+        assert_eq!(zl.iter().map(|e| e.to_bytes().into_owned()).collect::<Vec<_>>(), vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+        assert_eq!(zl.iter().next_back(), Some(ListpackEntry::Str(b"c")));
+        assert_eq!(zl.iter().rev().nth(2), Some(ListpackEntry::Str(b"a")));
+    }
+
+    #[test]
+    fn test_unknown_length() {
+        let values: Vec<Value> = (0..70000).map(|i| Value::Int(i % 10)).collect();
+        let bytes = ziplist(&values, &[]);
+        assert_eq!(&bytes[8..10], &[0xFF, 0xFF]);
+        let zl = Ziplist::from_bytes(&bytes).unwrap();
+        assert_eq!(zl.len(), 70000);
+        assert_eq!(zl.to_listpack().len(), 70000);
+    }
+
+    #[test]
+    fn test_validate_integrity() {
+        let bytes = ziplist(&[Value::Str(b"hello"), Value::Int(1000)], &[]);
+        assert!(Ziplist::from_bytes(&bytes).is_ok());
+
+        let corrupt = |f: &dyn Fn(&mut Vec<u8>)| {
+            let mut bytes = bytes.clone();
+            f(&mut bytes);
+            Ziplist::from_bytes(&bytes).map(|_| ())
+        };
+        assert!(Ziplist::from_bytes(&bytes[..10]).is_err());
+        assert!(corrupt(&|b| b[0] += 1).is_err()); // total bytes
+        assert!(corrupt(&|b| *b.last_mut().unwrap() = 0).is_err()); // no end marker
+        assert!(corrupt(&|b| b[4] = 200).is_err()); // tail out of range
+        assert!(corrupt(&|b| b[4] = 10).is_err()); // tail not on the last entry
+        assert!(corrupt(&|b| b[8] = 3).is_err()); // number of entries
+        assert!(corrupt(&|b| b[10] = 1).is_err()); // first prevlen not zero
+        assert!(corrupt(&|b| b[17] = 6).is_err()); // prevlen mismatch
+        assert!(corrupt(&|b| b[11] = 0x3F).is_err()); // string past the end
+        assert!(corrupt(&|b| b[18] = 0xC8).is_err()); // invalid encoding
+        assert!(corrupt(&|b| b[18] = 0x81).is_err()); // invalid 32 bit string encoding
+        assert!(corrupt(&|b| b[11] = 0xFF).is_err()); // end marker as encoding
+        assert!(corrupt(&|b| b[17] = 0xFF).is_err()); // end marker in the middle
+        assert!(corrupt(&|b| b[17] = 0xFE).is_err()); // 5 byte prevlen running past the end
+    }
+
+    #[test]
+    fn test_to_listpack_and_list() {
+        let bytes = ziplist(&[Value::Str(b"a"), Value::Int(-70000), Value::Str(b"123")], &[1]);
+        let zl = Ziplist::from_bytes(&bytes).unwrap();
+
+        let lp = zl.to_listpack();
+        assert_eq!(Listpack::validate_integrity(lp.as_bytes(), true), Ok(()));
+        assert_eq!(
+            lp.iter().collect::<Vec<_>>(),
+            vec![ListpackEntry::Str(b"a"), ListpackEntry::Int(-70000), ListpackEntry::Int(123)]
+        );
+
+        let mut list = zl.to_list();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.pop_front(), Some(b"a".to_vec()));
+        assert_eq!(list.pop_front(), Some(b"-70000".to_vec()));
+        assert_eq!(list.pop_front(), Some(b"123".to_vec()));
+    }
+
+    #[test]
+    fn test_to_dict() {
+        let bytes = ziplist(&[Value::Str(b"name"), Value::Str(b"redis"), Value::Str(b"version"), Value::Int(6)], &[]);
+        let dict = Ziplist::from_bytes(&bytes).unwrap().to_dict().unwrap();
+        assert_eq!(dict.size(), 2);
+        assert_eq!(dict.fetch_value(&b"name".to_vec()), Some(&b"redis".to_vec()));
+        assert_eq!(dict.fetch_value(&b"version".to_vec()), Some(&b"6".to_vec()));
+
+        let odd = ziplist(&strs(&["a", "1", "b"]), &[]);
+        assert!(Ziplist::from_bytes(&odd).unwrap().to_dict().is_err());
+        let duplicate = ziplist(&strs(&["a", "1", "a", "2"]), &[]);
+        assert!(Ziplist::from_bytes(&duplicate).unwrap().to_dict().is_err());
+    }
+
+    #[test]
+    fn test_to_zskiplist() {
+        let bytes = ziplist(
+            &[Value::Str(b"b"), Value::Str(b"2.5"), Value::Str(b"a"), Value::Int(1), Value::Int(7), Value::Str(b"-inf")],
+            &[],
+        );
+        let zsl = Ziplist::from_bytes(&bytes).unwrap().to_zskiplist().unwrap();
+        assert_eq!(zsl.get_len(), 3);
+        assert_eq!(zsl.zsl_get_rank(f64::NEG_INFINITY, b"7".to_vec()), Some(0));
+        assert_eq!(zsl.zsl_get_rank(1.0, b"a".to_vec()), Some(1));
+        assert_eq!(zsl.zsl_get_rank(2.5, b"b".to_vec()), Some(2));
+        assert_eq!(zsl.validate(), Ok(()));
+
+        for bad in [&strs(&["a", "x"]), &strs(&["a", "nan"]), &strs(&["a", "1", "a", "2"])] {
+            let bytes = ziplist(bad, &[]);
+            assert!(Ziplist::from_bytes(&bytes).unwrap().to_zskiplist().is_err());
+        }
+    }
+
+    // Add more tests here...
+}
@@ -0,0 +1,487 @@
+use curly_giggle::collection::skiplist::zrangespec::{ZLexRangeSpec, ZRangeSpec};
+use curly_giggle::collection::zset::{
+    ZAddFlags, ZAddReply, ZAggregate, ZDiffAlgorithm, ZSet, ZSetConfig, ZSetEncoding, ZStoreOptions,
+};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::BTreeSet;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The behavior tests run once per encoding and must give the same results
+    const SORTED_ARRAY: ZSetConfig = ZSetConfig {
+        max_sorted_array_entries: 128,
+        max_sorted_array_value: 64,
+    };
+    const SKIPLIST: ZSetConfig = ZSetConfig {
+        max_sorted_array_entries: 0,
+        max_sorted_array_value: 0,
+    };
+    const ENCODINGS: [(ZSetConfig, ZSetEncoding); 2] =
+        [(SORTED_ARRAY, ZSetEncoding::SortedArray), (SKIPLIST, ZSetEncoding::SkipList)];
+
+    fn zset_with(config: ZSetConfig, elements: Vec<(f64, &'static str)>) -> ZSet<&'static str> {
+        let mut zset = ZSet::with_config(config);
+        zset.zadd(ZAddFlags::default(), elements).unwrap();
+        zset
+    }
+
+    #[test]
+    fn test_zadd_and_zscore() {
+        for (config, encoding) in ENCODINGS {
+            let mut zset = ZSet::with_config(config);
+            let reply = zset.zadd(ZAddFlags::default(), vec![(1.0, "a"), (2.0, "b"), (3.0, "c")]);
+            assert_eq!(reply, Ok(ZAddReply::Count(3)));
+            assert_eq!(zset.zcard(), 3);
+            assert_eq!(zset.zscore(&"b"), Some(2.0));
+            assert_eq!(zset.zscore(&"d"), None);
+
+            // Updating a score does not count as added
+            let reply = zset.zadd(ZAddFlags::default(), vec![(5.0, "a"), (4.0, "d")]);
+            assert_eq!(reply, Ok(ZAddReply::Count(1)));
+            assert_eq!(zset.zscore(&"a"), Some(5.0));
+            assert_eq!(zset.zcard(), 4);
+            let order: Vec<_> = zset.iter().map(|(_, member)| *member).collect();
+            assert_eq!(order, vec!["b", "c", "d", "a"]);
+            assert_eq!(zset.encoding(), encoding);
+        }
+    }
+
+    #[test]
+    fn test_zadd_flag_errors() {
+        for (config, _) in ENCODINGS {
+            let mut zset: ZSet<&str> = ZSet::with_config(config);
+            let both = ZAddFlags { nx: true, xx: true, ..Default::default() };
+            assert_eq!(
+                zset.zadd(both, vec![(1.0, "a")]),
+                Err("XX and NX options at the same time are not compatible".to_string())
+            );
+            let gt_lt = ZAddFlags { gt: true, lt: true, ..Default::default() };
+            assert_eq!(
+                zset.zadd(gt_lt, vec![(1.0, "a")]),
+                Err("GT, LT, and/or NX options at the same time are not compatible".to_string())
+            );
+            let gt_nx = ZAddFlags { gt: true, nx: true, ..Default::default() };
+            assert!(zset.zadd(gt_nx, vec![(1.0, "a")]).is_err());
+            let incr = ZAddFlags { incr: true, ..Default::default() };
+            assert_eq!(
+                zset.zadd(incr, vec![(1.0, "a"), (2.0, "b")]),
+                Err("INCR option supports a single increment-element pair".to_string())
+            );
+            assert_eq!(
+                zset.zadd(ZAddFlags::default(), vec![(1.0, "a"), (f64::NAN, "b")]),
+                Err("value is not a valid float".to_string())
+            );
+            // Nothing was added by the failing calls
+            assert_eq!(zset.zcard(), 0);
+        }
+    }
+
+    #[test]
+    fn test_zadd_nx_xx() {
+        for (config, _) in ENCODINGS {
+            let mut zset = zset_with(config, vec![(1.0, "a")]);
+            let nx = ZAddFlags { nx: true, ..Default::default() };
+            assert_eq!(zset.zadd(nx, vec![(10.0, "a"), (2.0, "b")]), Ok(ZAddReply::Count(1)));
+            assert_eq!(zset.zscore(&"a"), Some(1.0));
+            assert_eq!(zset.zscore(&"b"), Some(2.0));
+
+            let xx = ZAddFlags { xx: true, ..Default::default() };
+            assert_eq!(zset.zadd(xx, vec![(10.0, "a"), (3.0, "c")]), Ok(ZAddReply::Count(0)));
+            assert_eq!(zset.zscore(&"a"), Some(10.0));
+            assert_eq!(zset.zscore(&"c"), None);
+        }
+    }
+
+    #[test]
+    fn test_zadd_gt_lt_ch() {
+        for (config, _) in ENCODINGS {
+            let mut zset = zset_with(config, vec![(5.0, "a"), (5.0, "b")]);
+            let gt_ch = ZAddFlags { gt: true, ch: true, ..Default::default() };
+            assert_eq!(zset.zadd(gt_ch, vec![(6.0, "a"), (4.0, "b"), (1.0, "c")]), Ok(ZAddReply::Count(2)));
+            assert_eq!(zset.zscore(&"a"), Some(6.0));
+            assert_eq!(zset.zscore(&"b"), Some(5.0));
+            // GT and LT still add new elements
+            assert_eq!(zset.zscore(&"c"), Some(1.0));
+
+            let lt = ZAddFlags { lt: true, ..Default::default() };
+            assert_eq!(zset.zadd(lt, vec![(4.0, "b"), (7.0, "a")]), Ok(ZAddReply::Count(0)));
+            assert_eq!(zset.zscore(&"b"), Some(4.0));
+            assert_eq!(zset.zscore(&"a"), Some(6.0));
+
+            // CH does not count elements whose score did not change
+            let ch = ZAddFlags { ch: true, ..Default::default() };
+            assert_eq!(zset.zadd(ch, vec![(4.0, "b")]), Ok(ZAddReply::Count(0)));
+        }
+    }
+
+    #[test]
+    fn test_zadd_incr() {
+        for (config, _) in ENCODINGS {
+            let mut zset = ZSet::with_config(config);
+            let incr = ZAddFlags { incr: true, ..Default::default() };
+            assert_eq!(zset.zadd(incr, vec![(2.5, "a")]), Ok(ZAddReply::Score(Some(2.5))));
+            assert_eq!(zset.zadd(incr, vec![(1.5, "a")]), Ok(ZAddReply::Score(Some(4.0))));
+            assert_eq!(zset.zscore(&"a"), Some(4.0));
+
+            // Aborted by NX replies nil
+            let incr_nx = ZAddFlags { incr: true, nx: true, ..Default::default() };
+            assert_eq!(zset.zadd(incr_nx, vec![(1.0, "a")]), Ok(ZAddReply::Score(None)));
+            let incr_gt = ZAddFlags { incr: true, gt: true, ..Default::default() };
+            assert_eq!(zset.zadd(incr_gt, vec![(-1.0, "a")]), Ok(ZAddReply::Score(None)));
+
+            zset.zadd(ZAddFlags::default(), vec![(f64::INFINITY, "inf")]).unwrap();
+            assert_eq!(
+                zset.zadd(incr, vec![(f64::NEG_INFINITY, "inf")]),
+                Err("resulting score is not a number (NaN)".to_string())
+            );
+            assert_eq!(zset.zscore(&"inf"), Some(f64::INFINITY));
+        }
+    }
+
+    #[test]
+    fn test_zrem() {
+        for (config, _) in ENCODINGS {
+            let mut zset = zset_with(config, vec![(1.0, "a"), (2.0, "b"), (3.0, "c")]);
+            assert_eq!(zset.zrem(&["a", "c", "x"]), 2);
+            assert_eq!(zset.zcard(), 1);
+            assert_eq!(zset.zscore(&"a"), None);
+            assert_eq!(zset.zrank(&"b"), Some(0));
+            assert_eq!(zset.zrem(&["b"]), 1);
+            assert_eq!(zset.zcard(), 0);
+            assert_eq!(zset.iter().count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_zmscore() {
+        for (config, _) in ENCODINGS {
+            let zset = zset_with(config, vec![(1.0, "a"), (2.0, "b")]);
+            assert_eq!(zset.zmscore(&["a", "x", "b"]), vec![Some(1.0), None, Some(2.0)]);
+        }
+    }
+
+    #[test]
+    fn test_zrank_and_zrevrank() {
+        for (config, _) in ENCODINGS {
+            let zset = zset_with(config, vec![(1.0, "a"), (2.0, "b"), (2.0, "c"), (3.0, "d")]);
+            assert_eq!(zset.zrank(&"a"), Some(0));
+            assert_eq!(zset.zrank(&"c"), Some(2));
+            assert_eq!(zset.zrank(&"d"), Some(3));
+            assert_eq!(zset.zrevrank(&"d"), Some(0));
+            assert_eq!(zset.zrevrank(&"a"), Some(3));
+            assert_eq!(zset.zrank(&"x"), None);
+            assert_eq!(zset.zrevrank(&"x"), None);
+            assert_eq!(zset.zrank_withscore(&"b", false), Some((1, 2.0)));
+            assert_eq!(zset.zrank_withscore(&"b", true), Some((2, 2.0)));
+        }
+    }
+
+    #[test]
+    fn test_zcount_and_zlexcount() {
+        for (config, _) in ENCODINGS {
+            let zset = zset_with(config, vec![(1.0, "a"), (2.0, "b"), (3.0, "c"), (4.0, "d")]);
+            assert_eq!(zset.zcount(&ZRangeSpec::new(2.0, 3.0)), 2);
+            assert_eq!(zset.zcount(&ZRangeSpec::parse("(1", "+inf").unwrap()), 3);
+            assert_eq!(zset.zcount(&ZRangeSpec::parse("(4", "+inf").unwrap()), 0);
+            assert_eq!(zset.zcount(&ZRangeSpec::new(3.0, 2.0)), 0);
+
+            let mut lex = ZSet::with_config(config);
+            let members = ["a", "b", "c", "d"].iter().map(|m| (0.0, m.to_string())).collect();
+            lex.zadd(ZAddFlags::default(), members).unwrap();
+            assert_eq!(lex.zlexcount(&ZLexRangeSpec::parse("[b", "+").unwrap()), 3);
+            assert_eq!(lex.zlexcount(&ZLexRangeSpec::parse("(a", "(d").unwrap()), 2);
+            assert_eq!(lex.zlexcount(&ZLexRangeSpec::parse("-", "+").unwrap()), 4);
+            assert_eq!(lex.zlexcount(&ZLexRangeSpec::parse("[c", "[b").unwrap()), 0);
+        }
+    }
+
+    #[test]
+    fn test_update_keeps_order_in_sync() {
+        for (config, _) in ENCODINGS {
+            let mut zset = ZSet::with_config(config);
+            for i in 0..100 {
+                zset.zadd(ZAddFlags::default(), vec![(i as f64, i.to_string())]).unwrap();
+            }
+            for i in 0..100 {
+                zset.zadd(ZAddFlags::default(), vec![((100 - i) as f64, i.to_string())]).unwrap();
+            }
+            for i in 0..100 {
+                assert_eq!(zset.zscore(&i.to_string()), Some((100 - i) as f64));
+                assert_eq!(zset.zrevrank(&i.to_string()), Some(i as usize));
+            }
+            assert_eq!(zset.zcard(), 100);
+        }
+    }
+
+    #[test]
+    fn test_convert_on_max_entries() {
+        let config = ZSetConfig {
+            max_sorted_array_entries: 3,
+            max_sorted_array_value: 64,
+        };
+        let mut zset = zset_with(config, vec![(1.0, "a"), (2.0, "b"), (3.0, "c")]);
+        assert_eq!(zset.encoding(), ZSetEncoding::SortedArray);
+        // Updating a member does not grow the set
+        zset.zadd(ZAddFlags::default(), vec![(0.5, "c")]).unwrap();
+        assert_eq!(zset.encoding(), ZSetEncoding::SortedArray);
+
+        zset.zadd(ZAddFlags::default(), vec![(4.0, "d")]).unwrap();
+        assert_eq!(zset.encoding(), ZSetEncoding::SkipList);
+        let items: Vec<_> = zset.iter().collect();
+        assert_eq!(items, vec![(0.5, &"c"), (1.0, &"a"), (2.0, &"b"), (4.0, &"d")]);
+        assert_eq!(zset.zrank(&"d"), Some(3));
+    }
+
+    #[test]
+    fn test_convert_on_max_value() {
+        let config = ZSetConfig {
+            max_sorted_array_entries: 128,
+            max_sorted_array_value: 4,
+        };
+        let mut zset = zset_with(config, vec![(1.0, "abcd")]);
+        assert_eq!(zset.encoding(), ZSetEncoding::SortedArray);
+        zset.zadd(ZAddFlags::default(), vec![(2.0, "abcde")]).unwrap();
+        assert_eq!(zset.encoding(), ZSetEncoding::SkipList);
+        assert_eq!(zset.zscore(&"abcd"), Some(1.0));
+        assert_eq!(zset.zscore(&"abcde"), Some(2.0));
+    }
+
+    #[test]
+    fn test_convert_back_to_sorted_array() {
+        let config = ZSetConfig {
+            max_sorted_array_entries: 2,
+            max_sorted_array_value: 64,
+        };
+        let mut zset = zset_with(config, vec![(1.0, "a"), (2.0, "b"), (3.0, "c")]);
+        assert_eq!(zset.encoding(), ZSetEncoding::SkipList);
+        // Too many members to fit
+        assert!(!zset.zset_convert_to_sorted_array_if_needed());
+
+        zset.zrem(&["b"]);
+        // Removing members never converts on its own
+        assert_eq!(zset.encoding(), ZSetEncoding::SkipList);
+        assert!(zset.zset_convert_to_sorted_array_if_needed());
+        assert_eq!(zset.encoding(), ZSetEncoding::SortedArray);
+        let items: Vec<_> = zset.iter().collect();
+        assert_eq!(items, vec![(1.0, &"a"), (3.0, &"c")]);
+        assert_eq!(zset.zrevrank(&"a"), Some(1));
+    }
+
+    #[test]
+    fn test_forced_convert_keeps_members() {
+        let mut zset = zset_with(SORTED_ARRAY, vec![(2.0, "b"), (1.0, "a"), (2.0, "a2")]);
+        zset.zset_convert(ZSetEncoding::SkipList);
+        assert_eq!(zset.encoding(), ZSetEncoding::SkipList);
+        let skiplist: Vec<_> = zset.iter().map(|(score, member)| (score, *member)).collect();
+        zset.zset_convert(ZSetEncoding::SortedArray);
+        assert_eq!(zset.encoding(), ZSetEncoding::SortedArray);
+        let sorted_array: Vec<_> = zset.iter().map(|(score, member)| (score, *member)).collect();
+        assert_eq!(skiplist, sorted_array);
+        assert_eq!(sorted_array, vec![(1.0, "a"), (2.0, "a2"), (2.0, "b")]);
+        assert_eq!(zset.iter().next_back(), Some((2.0, &"b")));
+    }
+
+    fn items(zset: &ZSet<&'static str>) -> Vec<(f64, &'static str)> {
+        zset.iter().map(|(score, member)| (score, *member)).collect()
+    }
+
+    #[test]
+    fn test_zunionstore_aggregate() {
+        for (config, _) in ENCODINGS {
+            let a = zset_with(config, vec![(1.0, "a"), (2.0, "b"), (3.0, "c")]);
+            let b = zset_with(config, vec![(10.0, "b"), (1.0, "c"), (5.0, "d")]);
+
+            let sum = ZSet::zunionstore(&[&a, &b], &ZStoreOptions::default()).unwrap();
+            assert_eq!(items(&sum), vec![(1.0, "a"), (4.0, "c"), (5.0, "d"), (12.0, "b")]);
+
+            let min = ZStoreOptions { aggregate: ZAggregate::Min, ..Default::default() };
+            let min = ZSet::zunionstore(&[&a, &b], &min).unwrap();
+            assert_eq!(items(&min), vec![(1.0, "a"), (1.0, "c"), (2.0, "b"), (5.0, "d")]);
+
+            let max = ZStoreOptions { aggregate: ZAggregate::Max, ..Default::default() };
+            let max = ZSet::zunionstore(&[&a, &b], &max).unwrap();
+            assert_eq!(items(&max), vec![(1.0, "a"), (3.0, "c"), (5.0, "d"), (10.0, "b")]);
+        }
+    }
+
+    #[test]
+    fn test_zunionstore_weights() {
+        for (config, _) in ENCODINGS {
+            let a = zset_with(config, vec![(1.0, "a"), (2.0, "b")]);
+            let b = zset_with(config, vec![(3.0, "b"), (4.0, "c")]);
+            let options = ZStoreOptions { weights: Some(vec![2.0, -1.0]), ..Default::default() };
+            let union = ZSet::zunionstore(&[&a, &b], &options).unwrap();
+            assert_eq!(items(&union), vec![(-4.0, "c"), (1.0, "b"), (2.0, "a")]);
+
+            // A set used twice counts twice
+            let twice = ZSet::zunionstore(&[&a, &a], &ZStoreOptions::default()).unwrap();
+            assert_eq!(items(&twice), vec![(2.0, "a"), (4.0, "b")]);
+        }
+    }
+
+    #[test]
+    fn test_zunion_nan_handling() {
+        for (config, _) in ENCODINGS {
+            let a = zset_with(config, vec![(f64::INFINITY, "inf"), (f64::NEG_INFINITY, "ninf")]);
+            let b = zset_with(config, vec![(f64::NEG_INFINITY, "inf"), (1.0, "one")]);
+
+            // inf * 0 gives 0 instead of NaN
+            let options = ZStoreOptions { weights: Some(vec![0.0, 1.0]), ..Default::default() };
+            let union = ZSet::zunionstore(&[&a, &b], &options).unwrap();
+            assert_eq!(union.zscore(&"ninf"), Some(0.0));
+            assert_eq!(union.zscore(&"inf"), Some(f64::NEG_INFINITY));
+
+            // inf + -inf gives 0 instead of NaN
+            let union = ZSet::zunionstore(&[&a, &b], &ZStoreOptions::default()).unwrap();
+            assert_eq!(union.zscore(&"inf"), Some(0.0));
+            let inter = ZSet::zinterstore(&[&a, &b], &ZStoreOptions::default()).unwrap();
+            assert_eq!(items(&inter), vec![(0.0, "inf")]);
+        }
+    }
+
+    #[test]
+    fn test_zinterstore() {
+        for (config, _) in ENCODINGS {
+            let a = zset_with(config, vec![(1.0, "a"), (2.0, "b"), (3.0, "c"), (4.0, "d")]);
+            let b = zset_with(config, vec![(10.0, "b"), (30.0, "d")]);
+            let c = zset_with(config, vec![(100.0, "d"), (200.0, "b"), (300.0, "e")]);
+
+            let inter = ZSet::zinterstore(&[&a, &b, &c], &ZStoreOptions::default()).unwrap();
+            assert_eq!(items(&inter), vec![(134.0, "d"), (212.0, "b")]);
+
+            let options = ZStoreOptions {
+                weights: Some(vec![1.0, 0.5, 0.0]),
+                aggregate: ZAggregate::Max,
+            };
+            let inter = ZSet::zinterstore(&[&a, &b, &c], &options).unwrap();
+            assert_eq!(items(&inter), vec![(5.0, "b"), (15.0, "d")]);
+
+            let empty = ZSet::with_config(config);
+            let inter = ZSet::zinterstore(&[&a, &empty], &ZStoreOptions::default()).unwrap();
+            assert_eq!(inter.zcard(), 0);
+
+            // The lazy iterator gives the same members
+            let mut lazy: Vec<_> = ZSet::zinter(&[&a, &b, &c], &ZStoreOptions::default()).unwrap().collect();
+            lazy.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap());
+            assert_eq!(lazy, vec![(134.0, "d"), (212.0, "b")]);
+        }
+    }
+
+    #[test]
+    fn test_zdiffstore() {
+        for (config, _) in ENCODINGS {
+            let a = zset_with(config, vec![(1.0, "a"), (2.0, "b"), (3.0, "c"), (4.0, "d")]);
+            let b = zset_with(config, vec![(10.0, "b")]);
+            let c = zset_with(config, vec![(30.0, "d"), (50.0, "e")]);
+
+            let diff = ZSet::zdiffstore(&[&a, &b, &c]).unwrap();
+            assert_eq!(items(&diff), vec![(1.0, "a"), (3.0, "c")]);
+            let diff = ZSet::zdiffstore(&[&a]).unwrap();
+            assert_eq!(diff.zcard(), 4);
+            let diff = ZSet::zdiffstore(&[&b, &a]).unwrap();
+            assert_eq!(diff.zcard(), 0);
+            // The first set repeated removes everything
+            let diff = ZSet::zdiffstore(&[&a, &b, &a]).unwrap();
+            assert_eq!(diff.zcard(), 0);
+        }
+    }
+
+    #[test]
+    fn test_zdiff_algorithms_agree() {
+        let mut first = ZSet::with_config(SKIPLIST);
+        for i in 0..100 {
+            first.zadd(ZAddFlags::default(), vec![(i as f64, i.to_string())]).unwrap();
+        }
+        let mut small = ZSet::with_config(SKIPLIST);
+        small.zadd(ZAddFlags::default(), vec![(0.0, "7".to_string())]).unwrap();
+        let mut other = ZSet::with_config(SKIPLIST);
+        other.zadd(ZAddFlags::default(), vec![(0.0, "42".to_string())]).unwrap();
+
+        // 100 / 2 <= 100 + 1
+        assert_eq!(ZSet::zset_choose_diff_algorithm(&[&first, &small]), Some(ZDiffAlgorithm::One));
+        // 300 / 2 > 100 + 1 + 1
+        assert_eq!(ZSet::zset_choose_diff_algorithm(&[&first, &small, &other]), Some(ZDiffAlgorithm::Two));
+        assert_eq!(ZSet::zset_choose_diff_algorithm(&[&first, &first]), None);
+
+        let one: ZSet<_> = ZSet::zdiff(&[&first, &small]).unwrap().collect();
+        assert_eq!(one.zcard(), 99);
+        assert_eq!(one.zscore(&"7".to_string()), None);
+        let two: ZSet<_> = ZSet::zdiff(&[&first, &small, &other]).unwrap().collect();
+        assert_eq!(two.zcard(), 98);
+        assert_eq!(two.zscore(&"42".to_string()), None);
+        assert!(one.iter().filter(|(_, member)| *member != "42").eq(two.iter()));
+    }
+
+    #[test]
+    fn test_zset_op_errors() {
+        let a = zset_with(SORTED_ARRAY, vec![(1.0, "a")]);
+        let empty: [&ZSet<&str>; 0] = [];
+        assert_eq!(
+            ZSet::zunionstore(&empty, &ZStoreOptions::default()).err(),
+            Some("at least 1 input key is needed for 'zunionstore' command".to_string())
+        );
+        assert_eq!(
+            ZSet::zinterstore(&empty, &ZStoreOptions::default()).err(),
+            Some("at least 1 input key is needed for 'zinterstore' command".to_string())
+        );
+        assert_eq!(
+            ZSet::zdiffstore(&empty).err(),
+            Some("at least 1 input key is needed for 'zdiffstore' command".to_string())
+        );
+        let options = ZStoreOptions { weights: Some(vec![1.0]), ..Default::default() };
+        assert_eq!(ZSet::zunionstore(&[&a, &a], &options).err(), Some("syntax error".to_string()));
+        let options = ZStoreOptions { weights: Some(vec![f64::NAN]), ..Default::default() };
+        assert_eq!(
+            ZSet::zinterstore(&[&a], &options).err(),
+            Some("weight value is not a float".to_string())
+        );
+    }
+
+    #[test]
+    fn test_zpopmin_zpopmax() {
+        for (config, encoding) in ENCODINGS {
+            let mut zset = zset_with(config, vec![(3.0, "c"), (1.0, "a"), (2.0, "b"), (4.0, "d")]);
+            assert_eq!(zset.zpopmin(2), vec![(1.0, "a"), (2.0, "b")]);
+            assert_eq!(zset.zpopmax(1), vec![(4.0, "d")]);
+            assert_eq!(zset.zscore(&"a"), None);
+            assert_eq!(zset.zscore(&"d"), None);
+            assert_eq!(zset.zrank(&"c"), Some(0));
+            assert!(zset.zpopmin(0).is_empty());
+            assert_eq!(zset.zpopmax(10), vec![(3.0, "c")]);
+            assert_eq!(zset.zcard(), 0);
+            assert!(zset.zpopmin(1).is_empty());
+            assert_eq!(zset.encoding(), encoding);
+        }
+    }
+
+    #[test]
+    fn test_zrandmember() {
+        for (config, _) in ENCODINGS {
+            let mut rng = StdRng::seed_from_u64(1);
+            let mut zset = ZSet::with_config(config);
+            for i in 0..20 {
+                zset.zadd(ZAddFlags::default(), vec![(i as f64, i.to_string())]).unwrap();
+            }
+            let all = zset.zrandmember_with_rng(20, false, &mut rng).unwrap();
+            assert_eq!(all.len(), 20);
+            for count in [15, 3] {
+                let picked = zset.zrandmember_with_rng(count, true, &mut rng).unwrap();
+                let unique: BTreeSet<_> = picked.iter().map(|(member, _)| member.clone()).collect();
+                assert_eq!(unique.len(), count as usize);
+                for (member, score) in picked {
+                    assert_eq!(zset.zscore(&member), score);
+                }
+            }
+            let repeated = zset.zrandmember_with_rng(-50, false, &mut rng).unwrap();
+            assert_eq!(repeated.len(), 50);
+            assert!(repeated.iter().all(|(member, score)| score.is_none() && zset.zscore(member).is_some()));
+            assert!(zset.zrandmember(i64::MIN, false).is_err());
+            assert_eq!(zset.zrandmember(-i64::MAX, false), Err("value is out of range".to_string()));
+        }
+    }
+
+    // Add more tests here...
+}
@@ -1,4 +1,12 @@
-use curly_giggle::collection::skiplist::zskiplist::ZSkipList;
+use curly_giggle::collection::skiplist::zrangespec::{LexBound, ZLexRangeSpec, ZRangeSpec};
+use curly_giggle::collection::skiplist::level_generator::LevelGenerator;
+use curly_giggle::collection::skiplist::zskiplist::{InvariantViolation, ZSkipList, ZRANDMEMBER_MAX_COUNT};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
 
 #[cfg(test)]
 mod tests {
@@ -19,10 +27,10 @@ mod tests {
         list.zsl_insert(2.0, 2);
         list.zsl_insert(3.0, 3);
 
-        assert_eq!(list.contains(&1), true);
-        assert_eq!(list.contains(&2), true);
-        assert_eq!(list.contains(&3), true);
-        assert_eq!(list.contains(&4), false);
+        assert_eq!(list.contains(1.0, &1), true);
+        assert_eq!(list.contains(2.0, &2), true);
+        assert_eq!(list.contains(3.0, &3), true);
+        assert_eq!(list.contains(4.0, &4), false);
         drop(list)
     }
 
@@ -34,9 +42,9 @@ mod tests {
         list.zsl_insert(2.0, 2);
         list.zsl_insert(3.0, 3);
 
-        assert_eq!(list.zsl_delete(&2), Some(2));
-        assert_eq!(list.zsl_delete(&2), None);
-        assert_eq!(list.contains(&2), false);
+        assert_eq!(list.zsl_delete(2.0, &2), Some(2));
+        assert_eq!(list.zsl_delete(2.0, &2), None);
+        assert_eq!(list.contains(2.0, &2), false);
         drop(list)
     }
 
@@ -73,8 +81,8 @@ mod tests {
     #[test]
     fn test_empty_list() {
         let mut list = ZSkipList::zsl_create();
-        assert_eq!(list.contains(&1), false);
-        assert_eq!(list.zsl_delete(&1), None);
+        assert_eq!(list.contains(1.0, &1), false);
+        assert_eq!(list.zsl_delete(1.0, &1), None);
         let mut iter = list.iter();
         assert_eq!(iter.next(), None);
         drop(list)
@@ -87,19 +95,19 @@ mod tests {
         list.zsl_insert(2.0, 2);
         list.zsl_insert(3.0, 3);
 
-        assert_eq!(list.contains(&1), true);
-        assert_eq!(list.zsl_delete(&1), Some(1));
+        assert_eq!(list.contains(1.0, &1), true);
+        assert_eq!(list.zsl_delete(1.0, &1), Some(1));
         assert_eq!(list.get_len(), 2);
-        assert_eq!(list.contains(&1), false);
-        assert_eq!(list.contains(&2), true);
-        assert_eq!(list.contains(&3), true);
+        assert_eq!(list.contains(1.0, &1), false);
+        assert_eq!(list.contains(2.0, &2), true);
+        assert_eq!(list.contains(3.0, &3), true);
         
-        assert_eq!(list.zsl_delete(&1), None);
-        assert_eq!(list.zsl_delete(&2), Some(2));
-        assert_eq!(list.contains(&2), false);
+        assert_eq!(list.zsl_delete(1.0, &1), None);
+        assert_eq!(list.zsl_delete(2.0, &2), Some(2));
+        assert_eq!(list.contains(2.0, &2), false);
 
-        assert_eq!(list.zsl_delete(&3), Some(3));
-        assert_eq!(list.contains(&3), false);
+        assert_eq!(list.zsl_delete(3.0, &3), Some(3));
+        assert_eq!(list.contains(3.0, &3), false);
         drop(list)
     }
 
@@ -114,6 +122,9 @@ mod tests {
         assert_eq!(list.zsl_get_rank(2.0, 2), Some(1));
         assert_eq!(list.zsl_get_rank(3.0, 3), Some(2));
         assert_eq!(list.zsl_get_rank(4.0, 4), None);
+        // The element is there, but under another score
+        assert_eq!(list.zsl_get_rank(2.5, 3), None);
+        assert_eq!(list.zsl_get_rank(1.0, 2), None);
         drop(list)
     }
 
@@ -139,10 +150,10 @@ mod tests {
         list.zsl_insert(2.0, 2);
         list.zsl_insert(3.0, 3);
         
-        assert_eq!(list.zsl_is_in_range(1.0, 3.0), true);
-        assert_eq!(list.zsl_is_in_range(2.0, 4.0), true);
-        assert_eq!(list.zsl_is_in_range(0.0, 0.9), false);
-        assert_eq!(list.zsl_is_in_range(4.0, 5.0), false);
+        assert_eq!(list.zsl_is_in_range(&ZRangeSpec::new(1.0, 3.0)), true);
+        assert_eq!(list.zsl_is_in_range(&ZRangeSpec::new(2.0, 4.0)), true);
+        assert_eq!(list.zsl_is_in_range(&ZRangeSpec::new(0.0, 0.9)), false);
+        assert_eq!(list.zsl_is_in_range(&ZRangeSpec::new(4.0, 5.0)), false);
         drop(list)
     }
 
@@ -153,10 +164,10 @@ mod tests {
         list.zsl_insert(2.0, 2);
         list.zsl_insert(3.0, 3);
         
-        assert_eq!(list.zsl_first_in_range(1.0, 3.0), Some(1));
-        assert_eq!(list.zsl_first_in_range(2.0, 4.0), Some(2));
-        assert_eq!(list.zsl_first_in_range(0.0, 0.9), None);
-        assert_eq!(list.zsl_first_in_range(4.0, 5.0), None);
+        assert_eq!(list.zsl_first_in_range(&ZRangeSpec::new(1.0, 3.0)), Some(1));
+        assert_eq!(list.zsl_first_in_range(&ZRangeSpec::new(2.0, 4.0)), Some(2));
+        assert_eq!(list.zsl_first_in_range(&ZRangeSpec::new(0.0, 0.9)), None);
+        assert_eq!(list.zsl_first_in_range(&ZRangeSpec::new(4.0, 5.0)), None);
     }
 
     #[test]
@@ -166,10 +177,10 @@ mod tests {
         list.zsl_insert(2.0, 2);
         list.zsl_insert(3.0, 3);
         
-        assert_eq!(list.zsl_last_in_range(1.0, 3.0), Some(3));
-        assert_eq!(list.zsl_last_in_range(2.0, 4.0), Some(3));
-        assert_eq!(list.zsl_last_in_range(0.0, 0.9), None);
-        assert_eq!(list.zsl_last_in_range(4.0, 5.0), None);
+        assert_eq!(list.zsl_last_in_range(&ZRangeSpec::new(1.0, 3.0)), Some(3));
+        assert_eq!(list.zsl_last_in_range(&ZRangeSpec::new(2.0, 4.0)), Some(3));
+        assert_eq!(list.zsl_last_in_range(&ZRangeSpec::new(0.0, 0.9)), None);
+        assert_eq!(list.zsl_last_in_range(&ZRangeSpec::new(4.0, 5.0)), None);
     }
 
     #[test]
@@ -179,9 +190,9 @@ mod tests {
             list.zsl_insert(i as f64, i);
         }
         
-        assert_eq!(list.zsl_delete_range_by_score(1.0, 3.0), 3);
-        assert_eq!(list.zsl_delete_range_by_score(2.0, 10.0), 7);
-        assert_eq!(list.zsl_delete_range_by_score(21.0, 23.0), 0);
+        assert_eq!(list.zsl_delete_range_by_score(&ZRangeSpec::new(1.0, 3.0)), 3);
+        assert_eq!(list.zsl_delete_range_by_score(&ZRangeSpec::new(2.0, 10.0)), 7);
+        assert_eq!(list.zsl_delete_range_by_score(&ZRangeSpec::new(21.0, 23.0)), 0);
     }
 
     #[test]
@@ -196,5 +207,847 @@ mod tests {
         assert_eq!(list.zsl_delete_range_by_rank(21, 23), 0);
     }
 
+    #[test]
+    fn test_zsl_delete_same_score() {
+        let mut list = ZSkipList::zsl_create();
+        for i in 0..10 {
+            list.zsl_insert(1.0, i);
+        }
+
+        assert_eq!(list.zsl_delete(2.0, &5), None);
+        assert_eq!(list.zsl_delete(1.0, &5), Some(5));
+        assert_eq!(list.get_len(), 9);
+        assert_eq!(list.zsl_get_rank(1.0, 6), Some(5));
+        assert_eq!(list.zsl_get_element_by_rank(6), Some(6));
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_zsl_delete_detach() {
+        let mut list = ZSkipList::zsl_create();
+        list.zsl_insert(1.0, 1);
+        list.zsl_insert(2.0, 2);
+
+        let node = list.zsl_delete_detach(2.0, &2).unwrap();
+        assert_eq!(node.score, 2.0);
+        assert!(node.backward.is_none());
+        assert!(node.level.iter().all(|l| l.forward.is_none()));
+        assert_eq!(node.into_val(), Some(2));
+        assert!(list.zsl_delete_detach(2.0, &2).is_none());
+        assert_eq!(list.get_len(), 1);
+    }
+
+    #[test]
+    fn test_pop_front() {
+        let mut list = ZSkipList::zsl_create();
+        for i in (0..50).rev() {
+            list.zsl_insert(i as f64, i);
+        }
+        for i in 0..50 {
+            assert_eq!(list.pop_front(), Some(i));
+            assert_eq!(list.get_len(), 49 - i as usize);
+            if i < 49 {
+                assert_eq!(list.zsl_get_element_by_rank(1), Some(i + 1));
+            }
+        }
+        assert_eq!(list.pop_front(), None);
+    }
+
+    /// Apply random inserts and deletes to a skiplist and a `BTreeSet` model,
+    /// checking order and ranks against the model after every step.
+    #[test]
+    fn test_zsl_delete_against_model() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let mut list = ZSkipList::zsl_create();
+        let mut model: BTreeSet<(i64, i32)> = BTreeSet::new();
+        let mut scores: HashMap<i32, i64> = HashMap::new();
+
+        for _ in 0..3000 {
+            let element = rng.gen_range(0..200);
+            match rng.gen_range(0..4) {
+                0 | 1 => {
+                    if let Entry::Vacant(entry) = scores.entry(element) {
+                        // Narrow score range so ties are common
+                        let score = rng.gen_range(-10..10);
+                        list.zsl_insert(score as f64, element);
+                        model.insert((score, element));
+                        entry.insert(score);
+                    }
+                }
+                2 => match scores.remove(&element) {
+                    Some(score) => {
+                        // A wrong score must not match
+                        assert_eq!(list.zsl_delete(score as f64 + 0.5, &element), None);
+                        assert_eq!(list.zsl_delete(score as f64, &element), Some(element));
+                        model.remove(&(score, element));
+                    }
+                    None => assert_eq!(list.zsl_delete(0.0, &element), None),
+                },
+                _ => {
+                    let expected = model.iter().next().copied();
+                    if let Some((score, element)) = expected {
+                        model.remove(&(score, element));
+                        scores.remove(&element);
+                    }
+                    assert_eq!(list.pop_front(), expected.map(|(_, e)| e));
+                }
+            }
+
+            assert_eq!(list.validate(), Ok(()));
+            assert_eq!(list.get_len(), model.len());
+            let expected: Vec<i32> = model.iter().map(|&(_, e)| e).collect();
+            assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+            for (rank, &(score, element)) in model.iter().enumerate() {
+                assert_eq!(list.zsl_get_rank(score as f64, element), Some(rank as i128));
+                assert_eq!(list.zsl_get_element_by_rank(rank + 1), Some(element));
+            }
+            assert_eq!(list.zsl_get_element_by_rank(model.len() + 1), None);
+        }
+    }
+
+    #[test]
+    fn test_zsl_delete_range_by_score_exclusive() {
+        let mut list = ZSkipList::zsl_create();
+        for i in 0..20 {
+            list.zsl_insert(i as f64, i);
+        }
+
+        let range = ZRangeSpec { min: 1.0, max: 5.0, minex: true, maxex: true };
+        assert_eq!(list.zsl_delete_range_by_score(&range), 3);
+        let range = ZRangeSpec { min: 5.0, max: 5.0, minex: false, maxex: true };
+        assert_eq!(list.zsl_delete_range_by_score(&range), 0);
+        let range = ZRangeSpec { min: 18.0, max: f64::INFINITY, minex: false, maxex: false };
+        assert_eq!(list.zsl_delete_range_by_score(&range), 2);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![0, 1, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17]);
+        assert_eq!(list.zsl_get_rank(17.0, 17), Some(14));
+    }
+
+    #[test]
+    fn test_zsl_delete_range_with_callback() {
+        let mut list = ZSkipList::zsl_create();
+        for i in 0..10 {
+            list.zsl_insert(i as f64, i);
+        }
+
+        let mut removed = Vec::new();
+        assert_eq!(list.zsl_delete_range_by_score_with(&ZRangeSpec::new(2.0, 4.0), |score, e| removed.push((score, e))), 3);
+        assert_eq!(removed, vec![(2.0, 2), (3.0, 3), (4.0, 4)]);
+
+        removed.clear();
+        assert_eq!(list.zsl_delete_range_by_rank_with(1, 2, |score, e| removed.push((score, e))), 2);
+        assert_eq!(removed, vec![(0.0, 0), (1.0, 1)]);
+        assert_eq!(list.zsl_get_element_by_rank(1), Some(5));
+    }
+
+    #[test]
+    fn test_zsl_delete_range_frees_nodes() {
+        let item = Rc::new(0);
+        let mut list = ZSkipList::zsl_create();
+        for i in 0..10 {
+            // Same element everywhere keeps the order stable by score
+            list.zsl_insert(i as f64, item.clone());
+        }
+        assert_eq!(Rc::strong_count(&item), 11);
+
+        assert_eq!(list.zsl_delete_range_by_score(&ZRangeSpec::new(0.0, 3.0)), 4);
+        assert_eq!(Rc::strong_count(&item), 7);
+        assert_eq!(list.zsl_delete_range_by_rank(2, 3), 2);
+        assert_eq!(Rc::strong_count(&item), 5);
+
+        list.zsl_free();
+        assert_eq!(Rc::strong_count(&item), 1);
+        assert_eq!(list.get_len(), 0);
+
+        list.zsl_insert(1.0, item.clone());
+        drop(list);
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+
+    /// Random range deletions checked against a `BTreeSet` model.
+    #[test]
+    fn test_zsl_delete_range_against_model() {
+        let mut rng = StdRng::seed_from_u64(0xdead);
+        let mut list = ZSkipList::zsl_create();
+        let mut model: BTreeSet<(i64, i32)> = BTreeSet::new();
+        let mut next_element = 0;
+
+        for _ in 0..500 {
+            for _ in 0..rng.gen_range(0..20) {
+                let score = rng.gen_range(0..50);
+                list.zsl_insert(score as f64, next_element);
+                model.insert((score, next_element));
+                next_element += 1;
+            }
+
+            if rng.gen_bool(0.5) {
+                let min = rng.gen_range(0..50);
+                let range = ZRangeSpec {
+                    min: min as f64,
+                    max: (min + rng.gen_range(0..10)) as f64,
+                    minex: rng.gen_bool(0.5),
+                    maxex: rng.gen_bool(0.5),
+                };
+                let expected: Vec<(i64, i32)> = model
+                    .iter()
+                    .filter(|&&(score, _)| range.value_gte_min(score as f64) && range.value_lte_max(score as f64))
+                    .copied()
+                    .collect();
+                let mut removed = Vec::new();
+                list.zsl_delete_range_by_score_with(&range, |score, e| removed.push((score as i64, e)));
+                assert_eq!(removed, expected);
+                for item in expected {
+                    model.remove(&item);
+                }
+            } else {
+                let start = rng.gen_range(1..model.len() + 2);
+                let end = start + rng.gen_range(0..10);
+                let expected: Vec<(i64, i32)> = model.iter().skip(start - 1).take(end - start + 1).copied().collect();
+                assert_eq!(list.zsl_delete_range_by_rank(start, end), expected.len());
+                for item in expected {
+                    model.remove(&item);
+                }
+            }
+
+            assert_eq!(list.validate(), Ok(()));
+            assert_eq!(list.get_len(), model.len());
+            let expected: Vec<i32> = model.iter().map(|&(_, e)| e).collect();
+            assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+            for (rank, &(score, element)) in model.iter().enumerate() {
+                assert_eq!(list.zsl_get_rank(score as f64, element), Some(rank as i128));
+                assert_eq!(list.zsl_get_element_by_rank(rank + 1), Some(element));
+            }
+        }
+    }
+
+    #[test]
+    fn test_zrangespec_parse() {
+        assert_eq!(ZRangeSpec::parse("1", "2"), Ok(ZRangeSpec::new(1.0, 2.0)));
+        assert_eq!(
+            ZRangeSpec::parse("-inf", "(2.5"),
+            Ok(ZRangeSpec { min: f64::NEG_INFINITY, max: 2.5, minex: false, maxex: true })
+        );
+        assert_eq!(ZRangeSpec::parse("(inf", "+inf").map(|r| (r.min, r.minex)), Ok((f64::INFINITY, true)));
+        assert_eq!(ZRangeSpec::parse("nan", "1"), Err("min or max is not a float".to_string()));
+        assert_eq!(ZRangeSpec::parse("1", "("), Err("min or max is not a float".to_string()));
+        assert_eq!(ZRangeSpec::parse("[1", "2"), Err("min or max is not a float".to_string()));
+    }
+
+    #[test]
+    fn test_zlexrangespec_parse() {
+        let range: ZLexRangeSpec<String> = ZLexRangeSpec::parse("-", "(b").unwrap();
+        assert_eq!(range.min, LexBound::NegInf);
+        assert_eq!(range.max, LexBound::Exclusive("b".to_string()));
+        let range: ZLexRangeSpec<Vec<u8>> = ZLexRangeSpec::parse("[", "+").unwrap();
+        assert_eq!(range.min, LexBound::Inclusive(Vec::new()));
+        assert_eq!(
+            ZLexRangeSpec::<String>::parse("a", "+"),
+            Err("min or max not valid string range item".to_string())
+        );
+        assert!(ZLexRangeSpec::<String>::parse("", "+").is_err());
+    }
+
+    #[test]
+    fn test_zsl_range_exclusive_and_infinite() {
+        let mut list = ZSkipList::zsl_create();
+        for i in 1..=5 {
+            list.zsl_insert(i as f64, i);
+        }
+
+        let range = ZRangeSpec::parse("(1", "(5").unwrap();
+        assert!(list.zsl_is_in_range(&range));
+        assert_eq!(list.zsl_first_in_range(&range), Some(2));
+        assert_eq!(list.zsl_last_in_range(&range), Some(4));
+        assert_eq!(list.zsl_count_in_range(&range), 3);
+
+        let range = ZRangeSpec::parse("-inf", "+inf").unwrap();
+        assert_eq!(list.zsl_count_in_range(&range), 5);
+        assert_eq!(list.zsl_last_in_range(&range), Some(5));
+
+        let range = ZRangeSpec::parse("(3", "3").unwrap();
+        assert!(!list.zsl_is_in_range(&range));
+        assert_eq!(list.zsl_count_in_range(&range), 0);
+        let range = ZRangeSpec::parse("(5", "+inf").unwrap();
+        assert!(!list.zsl_is_in_range(&range));
+        assert_eq!(list.zsl_first_in_range(&range), None);
+        let range = ZRangeSpec::parse("(4", "(5").unwrap();
+        assert!(list.zsl_is_in_range(&range));
+        assert_eq!(list.zsl_first_in_range(&range), None);
+        assert_eq!(list.zsl_count_in_range(&range), 0);
+    }
+
+    #[test]
+    fn test_zsl_lex_range() {
+        let mut list = ZSkipList::zsl_create();
+        for member in ["a", "b", "c", "d", "e", "f", "g"] {
+            list.zsl_insert(0.0, member.to_string());
+        }
+
+        let range = ZLexRangeSpec::parse("[b", "(e").unwrap();
+        assert!(list.zsl_is_in_lex_range(&range));
+        assert_eq!(list.zsl_first_in_lex_range(&range), Some("b".to_string()));
+        assert_eq!(list.zsl_last_in_lex_range(&range), Some("d".to_string()));
+        assert_eq!(list.zsl_count_in_lex_range(&range), 3);
+
+        let range = ZLexRangeSpec::parse("-", "+").unwrap();
+        assert_eq!(list.zsl_count_in_lex_range(&range), 7);
+        let range = ZLexRangeSpec::parse("(g", "+").unwrap();
+        assert!(!list.zsl_is_in_lex_range(&range));
+        let range = ZLexRangeSpec::parse("+", "-").unwrap();
+        assert!(!list.zsl_is_in_lex_range(&range));
+        let range = ZLexRangeSpec::parse("(c", "[c").unwrap();
+        assert_eq!(list.zsl_count_in_lex_range(&range), 0);
+        let range = ZLexRangeSpec::parse("(bb", "(c").unwrap();
+        assert_eq!(list.zsl_first_in_lex_range(&range), None);
+
+        let range = ZLexRangeSpec::parse("(a", "[c").unwrap();
+        let mut removed = Vec::new();
+        assert_eq!(list.zsl_delete_range_by_lex_with(&range, |_, e| removed.push(e)), 2);
+        assert_eq!(removed, vec!["b".to_string(), "c".to_string()]);
+        assert_eq!(list.zsl_delete_range_by_lex(&ZLexRangeSpec::parse("[f", "+").unwrap()), 2);
+        assert_eq!(list.validate(), Ok(()));
+        assert_eq!(list.iter().cloned().collect::<Vec<_>>(), vec!["a", "d", "e"]);
+        assert_eq!(list.zsl_get_rank(0.0, "e".to_string()), Some(2));
+    }
+
+    #[test]
+    fn test_contains_uses_score() {
+        let mut list = ZSkipList::zsl_create();
+        // Element order disagrees with score order
+        list.zsl_insert(1.0, 5);
+        list.zsl_insert(2.0, 1);
+        list.zsl_insert(3.0, 3);
+
+        assert!(list.contains(2.0, &1));
+        assert!(list.contains(1.0, &5));
+        assert!(!list.contains(1.0, &1));
+        assert!(!list.contains(3.0, &5));
+    }
+
+    #[test]
+    fn test_zsl_range_lookups_against_model() {
+        let mut rng = StdRng::seed_from_u64(0xbeef);
+        let mut list = ZSkipList::zsl_create();
+        let mut model: Vec<(i64, i32)> = Vec::new();
+        for element in 0..2000 {
+            let score = rng.gen_range(0..500);
+            list.zsl_insert(score as f64, element);
+            model.push((score, element));
+        }
+        model.sort();
+
+        for _ in 0..500 {
+            let min = rng.gen_range(-10..510);
+            let range = ZRangeSpec {
+                min: min as f64,
+                max: (min + rng.gen_range(0..30)) as f64,
+                minex: rng.gen_bool(0.5),
+                maxex: rng.gen_bool(0.5),
+            };
+            let expected: Vec<i32> = model
+                .iter()
+                .filter(|&&(score, _)| range.value_gte_min(score as f64) && range.value_lte_max(score as f64))
+                .map(|&(_, e)| e)
+                .collect();
+
+            // is_in_range only checks the range overlaps the list, it can be true for a gap
+            if !expected.is_empty() {
+                assert!(list.zsl_is_in_range(&range));
+            }
+            assert_eq!(list.zsl_first_in_range(&range), expected.first().copied());
+            assert_eq!(list.zsl_last_in_range(&range), expected.last().copied());
+            assert_eq!(list.zsl_count_in_range(&range), expected.len());
+
+            let n = rng.gen_range(0..expected.len() as i64 + 2);
+            assert_eq!(list.zsl_nth_in_range(&range, n), expected.get(n as usize).copied());
+            let expected_rev = expected.len().checked_sub(n as usize + 1).and_then(|i| expected.get(i).copied());
+            assert_eq!(list.zsl_nth_in_range(&range, -n - 1), expected_rev);
+        }
+    }
+
+    #[test]
+    fn test_zsl_update_score_in_place() {
+        let mut list = ZSkipList::zsl_create();
+        list.zsl_insert(1.0, 1);
+        let node = list.zsl_insert(2.0, 2);
+        list.zsl_insert(3.0, 3);
+
+        let updated = list.zsl_update_score(2.0, &2, 2.5).unwrap();
+        assert_eq!(updated, node);
+        assert!(list.contains(2.5, &2));
+        assert!(!list.contains(2.0, &2));
+        assert_eq!(list.zsl_update_score(2.0, &2, 5.0), None);
+        assert_eq!(list.zsl_update_score(2.5, &4, 5.0), None);
+    }
+
+    #[test]
+    fn test_zsl_update_score_relinks_same_node() {
+        let mut list = ZSkipList::zsl_create();
+        let node = list.zsl_insert(1.0, 1);
+        list.zsl_insert(2.0, 2);
+        list.zsl_insert(3.0, 3);
+
+        let updated = list.zsl_update_score(1.0, &1, 10.0).unwrap();
+        assert_eq!(updated, node);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![2, 3, 1]);
+        assert_eq!(list.zsl_get_rank(10.0, 1), Some(2));
+        assert_eq!(list.get_len(), 3);
+
+        list.zsl_update_score(10.0, &1, -1.0);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(list.pop_front(), Some(1));
+    }
+
+    #[test]
+    fn test_zsl_update_score_ties() {
+        let mut list = ZSkipList::zsl_create();
+        list.zsl_insert(1.0, "a");
+        list.zsl_insert(1.0, "c");
+        list.zsl_insert(2.0, "b");
+
+        // Same score as its neighbors, placed by element order
+        list.zsl_update_score(2.0, &"b", 1.0);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        list.zsl_update_score(1.0, &"a", 1.0);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        list.zsl_update_score(1.0, &"c", 0.0);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec!["c", "a", "b"]);
+        // Tie with the previous node but ordered before it
+        list.zsl_update_score(1.0, &"b", 0.0);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec!["b", "c", "a"]);
+        assert_eq!(list.zsl_get_rank(1.0, "a"), Some(2));
+    }
+
+    #[test]
+    fn test_zsl_update_score_against_model() {
+        let mut rng = StdRng::seed_from_u64(0xcafe);
+        let mut list = ZSkipList::zsl_create();
+        let mut model: BTreeSet<(i64, i32)> = BTreeSet::new();
+        let mut scores: Vec<i64> = Vec::new();
+        for element in 0..300 {
+            let score = rng.gen_range(0..30);
+            list.zsl_insert(score as f64, element);
+            model.insert((score, element));
+            scores.push(score);
+        }
+
+        for _ in 0..2000 {
+            let element = rng.gen_range(0..300);
+            let cur = scores[element as usize];
+            let new = (cur + rng.gen_range(-3..4)).max(0);
+            assert!(list.zsl_update_score(cur as f64, &element, new as f64).is_some());
+            model.remove(&(cur, element));
+            model.insert((new, element));
+            scores[element as usize] = new;
+            assert_eq!(list.validate(), Ok(()));
+
+            let expected: Vec<i32> = model.iter().map(|&(_, e)| e).collect();
+            assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+        }
+        for (rank, &(score, element)) in model.iter().enumerate() {
+            assert_eq!(list.zsl_get_rank(score as f64, element), Some(rank as i128));
+            assert_eq!(list.zsl_get_element_by_rank(rank + 1), Some(element));
+        }
+    }
+
+    #[test]
+    fn test_iter_rev() {
+        let mut list = ZSkipList::zsl_create();
+        assert_eq!(list.iter().next_back(), None);
+        for i in 0..10 {
+            list.zsl_insert(i as f64, i);
+        }
+
+        assert_eq!(list.iter().rev().copied().collect::<Vec<_>>(), (0..10).rev().collect::<Vec<_>>());
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(&0));
+        assert_eq!(iter.next_back(), Some(&9));
+        assert_eq!(iter.len(), 8);
+        assert_eq!(iter.rev().copied().collect::<Vec<_>>(), vec![8, 7, 6, 5, 4, 3, 2, 1]);
+
+        for x in list.iter_mut().rev().take(2) {
+            *x *= 10;
+        }
+        assert_eq!(list.iter().rev().take(3).copied().collect::<Vec<_>>(), vec![90, 80, 7]);
+    }
+
+    #[test]
+    fn test_range_by_score() {
+        let mut list = ZSkipList::zsl_create();
+        for i in 0..10 {
+            list.zsl_insert(i as f64, i);
+        }
+
+        let range = ZRangeSpec::parse("(2", "(6").unwrap();
+        assert_eq!(list.range_by_score(&range).collect::<Vec<_>>(), vec![(3.0, &3), (4.0, &4), (5.0, &5)]);
+        assert_eq!(list.range_by_score(&range).rev().map(|(_, e)| *e).collect::<Vec<_>>(), vec![5, 4, 3]);
+        assert_eq!(list.range_by_score(&range).len(), 3);
+
+        // LIMIT offset count
+        let range = ZRangeSpec::parse("-inf", "+inf").unwrap();
+        assert_eq!(list.range_by_score(&range).skip(2).take(3).map(|(_, e)| *e).collect::<Vec<_>>(), vec![2, 3, 4]);
+
+        let range = ZRangeSpec::parse("(2", "(3").unwrap();
+        assert_eq!(list.range_by_score(&range).next(), None);
+        let range = ZRangeSpec::parse("10", "+inf").unwrap();
+        assert_eq!(list.range_by_score(&range).next_back(), None);
+    }
+
+    #[test]
+    fn test_range_by_rank() {
+        let mut list = ZSkipList::zsl_create();
+        for i in 0..10 {
+            list.zsl_insert(i as f64, i);
+        }
+
+        let ranks = |start, stop| list.range_by_rank(start, stop).map(|(_, e)| *e).collect::<Vec<_>>();
+        assert_eq!(ranks(0, 2), vec![0, 1, 2]);
+        assert_eq!(ranks(8, 20), vec![8, 9]);
+        assert_eq!(ranks(-2, -1), vec![8, 9]);
+        assert_eq!(ranks(-100, 1), vec![0, 1]);
+        assert_eq!(ranks(0, -1).len(), 10);
+        assert!(ranks(5, 4).is_empty());
+        assert!(ranks(10, 12).is_empty());
+        assert!(ranks(0, -11).is_empty());
+        assert_eq!(list.range_by_rank(2, 4).rev().map(|(_, e)| *e).collect::<Vec<_>>(), vec![4, 3, 2]);
+
+        let empty: ZSkipList<i32> = ZSkipList::zsl_create();
+        assert_eq!(empty.range_by_rank(0, -1).next(), None);
+    }
+
+    #[test]
+    fn test_range_by_lex() {
+        let mut list = ZSkipList::zsl_create();
+        for member in ["a", "b", "c", "d", "e"] {
+            list.zsl_insert(0.0, member);
+        }
+
+        let range = ZLexRangeSpec { min: LexBound::Exclusive("a"), max: LexBound::Inclusive("c") };
+        assert_eq!(list.range_by_lex(&range).map(|(_, e)| *e).collect::<Vec<_>>(), vec!["b", "c"]);
+        let range = ZLexRangeSpec { min: LexBound::NegInf, max: LexBound::PosInf };
+        assert_eq!(list.range_by_lex(&range).rev().map(|(_, e)| *e).collect::<Vec<_>>(), vec!["e", "d", "c", "b", "a"]);
+        let range = ZLexRangeSpec { min: LexBound::Exclusive("e"), max: LexBound::PosInf };
+        assert_eq!(list.range_by_lex(&range).len(), 0);
+    }
+
+    /// Backward links are only visible through reverse iteration, check them
+    /// against the model after random mutations.
+    #[test]
+    fn test_iter_rev_against_model() {
+        let mut rng = StdRng::seed_from_u64(0xface);
+        let mut list = ZSkipList::zsl_create();
+        let mut model: BTreeSet<(i64, i32)> = BTreeSet::new();
+        let mut next_element = 0;
+
+        for _ in 0..1000 {
+            match rng.gen_range(0..5) {
+                0 | 1 => {
+                    let score = rng.gen_range(0..100);
+                    list.zsl_insert(score as f64, next_element);
+                    model.insert((score, next_element));
+                    next_element += 1;
+                }
+                2 => {
+                    if let Some(&(score, element)) = model.iter().nth(rng.gen_range(0..model.len().max(1))) {
+                        let new = rng.gen_range(0..100);
+                        list.zsl_update_score(score as f64, &element, new as f64);
+                        model.remove(&(score, element));
+                        model.insert((new, element));
+                    }
+                }
+                3 => {
+                    if let Some(&(score, element)) = model.iter().last() {
+                        assert_eq!(list.zsl_delete(score as f64, &element), Some(element));
+                        model.remove(&(score, element));
+                    }
+                }
+                _ => {
+                    let min = rng.gen_range(0..100);
+                    let range = ZRangeSpec::new(min as f64, (min + 3) as f64);
+                    list.zsl_delete_range_by_score(&range);
+                    model.retain(|&(score, _)| score < min || score > min + 3);
+                }
+            }
+
+            assert_eq!(list.validate(), Ok(()));
+            let expected: Vec<i32> = model.iter().rev().map(|&(_, e)| e).collect();
+            assert_eq!(list.iter().rev().copied().collect::<Vec<_>>(), expected);
+            let expected: Vec<i32> = model.iter().rev().take(5).map(|&(_, e)| e).collect();
+            assert_eq!(list.range_by_rank(-5, -1).rev().map(|(_, e)| *e).collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn test_pop_min_max() {
+        let mut list = ZSkipList::zsl_create();
+        for i in 0..10 {
+            list.zsl_insert(i as f64, i);
+        }
+        assert!(list.pop_min(0).is_empty());
+        assert_eq!(list.pop_min(3), vec![(0.0, 0), (1.0, 1), (2.0, 2)]);
+        assert_eq!(list.pop_max(2), vec![(9.0, 9), (8.0, 8)]);
+        assert_eq!(list.get_len(), 5);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![3, 4, 5, 6, 7]);
+        assert_eq!(list.zsl_get_rank(7.0, 7), Some(4));
+
+        // Asking for more than the list holds pops everything
+        assert_eq!(list.pop_max(100), vec![(7.0, 7), (6.0, 6), (5.0, 5), (4.0, 4), (3.0, 3)]);
+        assert_eq!(list.validate(), Ok(()));
+        assert_eq!(list.get_len(), 0);
+        assert!(list.pop_min(1).is_empty());
+        assert!(list.pop_max(1).is_empty());
+        list.zsl_insert(1.0, 1);
+        assert_eq!(list.pop_min(1), vec![(1.0, 1)]);
+    }
+
+    #[test]
+    fn test_random_members_strategies() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut list = ZSkipList::zsl_create();
+        for i in 0..100 {
+            list.zsl_insert(i as f64, i);
+        }
+
+        assert!(list.random_members_with_rng(0, true, &mut rng).unwrap().is_empty());
+
+        // Whole set when count >= size, in order
+        let all = list.random_members_with_rng(1000, false, &mut rng).unwrap();
+        assert_eq!(all.iter().map(|(e, _)| *e).collect::<Vec<_>>(), (0..100).collect::<Vec<_>>());
+        assert!(all.iter().all(|(_, score)| score.is_none()));
+
+        // Count close to size (trim a copy) and small count (pick one by one)
+        for count in [90, 5] {
+            let picked = list.random_members_with_rng(count, true, &mut rng).unwrap();
+            assert_eq!(picked.len(), count as usize);
+            let unique: BTreeSet<_> = picked.iter().map(|(e, _)| *e).collect();
+            assert_eq!(unique.len(), count as usize);
+            assert!(picked.iter().all(|(e, score)| *score == Some(*e as f64)));
+        }
+
+        // Negative count allows repetitions and returns exactly -count elements
+        let picked = list.random_members_with_rng(-500, false, &mut rng).unwrap();
+        assert_eq!(picked.len(), 500);
+        let unique: BTreeSet<_> = picked.iter().map(|(e, _)| *e).collect();
+        assert!(unique.len() < 100);
+
+        let mut single = ZSkipList::zsl_create();
+        single.zsl_insert(1.0, 1);
+        assert_eq!(single.random_members(-3, true).unwrap(), vec![(1, Some(1.0)); 3]);
+        let empty: ZSkipList<i32> = ZSkipList::zsl_create();
+        assert!(empty.random_members(-3, true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_random_members_out_of_range() {
+        let mut list = ZSkipList::zsl_create();
+        list.zsl_insert(1.0, 1);
+        assert_eq!(list.random_members(i64::MIN, false), Err("value is out of range".to_string()));
+        assert_eq!(
+            list.random_members(-(i64::MAX / 2) - 1, true),
+            Err("value is out of range".to_string())
+        );
+        // Capped before allocating anything
+        let max = ZRANDMEMBER_MAX_COUNT as i64;
+        assert_eq!(list.random_members(-max - 1, false), Err("value is out of range".to_string()));
+        assert_eq!(list.random_members(-(1 << 31), true), Err("value is out of range".to_string()));
+        assert_eq!(list.random_members(-max, false).unwrap().len(), max as usize);
+    }
+
+    // Replays a fixed sequence of levels and records how often it was called
+    struct FixedLevels {
+        bound: usize,
+        levels: Vec<usize>,
+        calls: Rc<RefCell<usize>>,
+    }
+
+    impl LevelGenerator for FixedLevels {
+        fn level_bound(&self) -> usize {
+            self.bound
+        }
+
+        fn random(&mut self) -> usize {
+            let mut calls = self.calls.borrow_mut();
+            let level = self.levels[*calls % self.levels.len()];
+            *calls += 1;
+            level
+        }
+    }
+
+    #[test]
+    fn test_with_comparator_not_ord() {
+        #[derive(Clone, Debug, PartialEq)]
+        struct Weight(f32);
+
+        // f32 is not Ord, order by total_cmp and reversed
+        let mut list = ZSkipList::with_comparator(|a: &Weight, b: &Weight| b.0.total_cmp(&a.0));
+        for w in [1.5, -2.0, 3.25, 0.0] {
+            list.zsl_insert(0.0, Weight(w));
+        }
+        list.zsl_insert(-1.0, Weight(100.0));
+        let items: Vec<_> = list.iter().map(|w| w.0).collect();
+        assert_eq!(items, vec![100.0, 3.25, 1.5, 0.0, -2.0]);
+        assert!(list.contains(0.0, &Weight(1.5)));
+        assert!(!list.contains(0.0, &Weight(1.25)));
+        assert_eq!(list.zsl_get_rank(0.0, Weight(0.0)), Some(3));
+        assert_eq!(list.zsl_delete(0.0, &Weight(3.25)), Some(Weight(3.25)));
+        assert_eq!(list.validate(), Ok(()));
+        assert_eq!(list.get_len(), 4);
+    }
+
+    #[test]
+    fn test_with_comparator_case_insensitive() {
+        let mut list = ZSkipList::with_comparator(|a: &Vec<u8>, b: &Vec<u8>| {
+            a.to_ascii_lowercase().cmp(&b.to_ascii_lowercase())
+        });
+        for name in ["banana", "Apple", "cherry", "APRICOT"] {
+            list.zsl_insert(0.0, name.as_bytes().to_vec());
+        }
+        let items: Vec<_> = list.iter().map(|e| String::from_utf8(e.clone()).unwrap()).collect();
+        assert_eq!(items, vec!["Apple", "APRICOT", "banana", "cherry"]);
+
+        let range = ZLexRangeSpec {
+            min: LexBound::Inclusive(b"APPLE".to_vec()),
+            max: LexBound::Exclusive(b"BANANA".to_vec()),
+        };
+        assert_eq!(list.zsl_count_in_lex_range(&range), 2);
+        assert_eq!(list.zsl_delete(0.0, &b"CHERRY".to_vec()), Some(b"cherry".to_vec()));
+    }
+
+    #[test]
+    fn test_with_key() {
+        #[derive(Debug, PartialEq)]
+        struct Job {
+            id: u32,
+            name: &'static str,
+        }
+
+        let mut list = ZSkipList::with_key(|job: &Job| job.id);
+        list.zsl_insert(1.0, Job { id: 9, name: "late" });
+        list.zsl_insert(1.0, Job { id: 2, name: "early" });
+        list.zsl_insert(0.5, Job { id: 5, name: "first" });
+        let names: Vec<_> = list.iter().map(|job| job.name).collect();
+        assert_eq!(names, vec!["first", "early", "late"]);
+        // Only the key matters when looking an element up
+        assert!(list.contains(1.0, &Job { id: 9, name: "" }));
+    }
+
+    #[test]
+    fn test_with_level_generator_is_used() {
+        let calls = Rc::new(RefCell::new(0));
+        let g = FixedLevels {
+            bound: 4,
+            levels: vec![1, 4, 2, 1, 3],
+            calls: calls.clone(),
+        };
+        let mut list = ZSkipList::with_level_generator(g);
+        for i in 0..50 {
+            list.zsl_insert(i as f64, i);
+        }
+        assert_eq!(*calls.borrow(), 50);
+        for i in 0..50 {
+            assert_eq!(list.zsl_get_rank(i as f64, i), Some(i as i128));
+        }
+        assert_eq!(list.range_by_rank(10, 12).map(|(_, e)| *e).collect::<Vec<_>>(), vec![10, 11, 12]);
+    }
+
+    #[test]
+    fn test_level_generator_out_of_bounds_is_clamped() {
+        // Levels of 0 or above the bound, and a bound above ZSKIPLIST_MAXLEVEL
+        let g = FixedLevels {
+            bound: 64,
+            levels: vec![0, 100, 64, 33, 1],
+            calls: Rc::new(RefCell::new(0)),
+        };
+        let mut list = ZSkipList::with_comparator_and_level_generator(|a: &i32, b: &i32| a.cmp(b), g);
+        for i in 0..20 {
+            list.zsl_insert(i as f64, i);
+        }
+        for i in (0..20).step_by(2) {
+            assert_eq!(list.zsl_delete(i as f64, &i), Some(i));
+        }
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), (1..20).step_by(2).collect::<Vec<_>>());
+        assert_eq!(list.validate(), Ok(()));
+        assert_eq!(list.zsl_get_rank(19.0, 19), Some(9));
+    }
+
+    #[test]
+    fn test_validate_after_every_mutation() {
+        let mut rng = StdRng::seed_from_u64(0x7e57);
+        let mut list = ZSkipList::zsl_create();
+        assert_eq!(list.validate(), Ok(()));
+        for i in 0..500 {
+            list.zsl_insert(rng.gen_range(0..20) as f64, i);
+            assert_eq!(list.validate(), Ok(()));
+        }
+        for _ in 0..300 {
+            match rng.gen_range(0..6) {
+                0 => {
+                    list.pop_front();
+                }
+                1 => {
+                    list.pop_max(rng.gen_range(0..3));
+                }
+                2 => {
+                    let start = rng.gen_range(1..list.get_len() + 2);
+                    list.zsl_delete_range_by_rank(start, start + rng.gen_range(0..5));
+                }
+                3 => {
+                    let min = rng.gen_range(0..20) as f64;
+                    list.zsl_delete_range_by_score(&ZRangeSpec::new(min, min + 0.5));
+                }
+                4 => {
+                    if let Some(&element) = list.iter().nth(rng.gen_range(0..list.get_len().max(1))) {
+                        let rank = list.range_by_rank(0, -1).position(|(_, e)| *e == element).unwrap() as i64;
+                        let score = list.range_by_rank(rank, rank).next().unwrap().0;
+                        list.zsl_update_score(score, &element, rng.gen_range(0..20) as f64);
+                    }
+                }
+                _ => {
+                    list.zsl_insert(rng.gen_range(0..20) as f64, 1000 + rng.gen_range(0..1000));
+                }
+            }
+            assert_eq!(list.validate(), Ok(()));
+        }
+        list.zsl_free();
+        assert_eq!(list.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_detects_unordered_elements() {
+        let mut list = ZSkipList::zsl_create();
+        for i in 0..10 {
+            list.zsl_insert(1.0, i);
+        }
+        // Changing elements in place can break the ordering
+        for element in list.iter_mut() {
+            *element = -*element;
+        }
+        assert_eq!(list.validate(), Err(InvariantViolation::Unordered { rank: 2 }));
+        assert_eq!(
+            InvariantViolation::Unordered { rank: 2 }.to_string(),
+            "element at rank 2 is out of order"
+        );
+    }
+
+    #[test]
+    fn test_debug_writes_to_formatter() {
+        let mut list = ZSkipList::with_level_generator(FixedLevels {
+            bound: 2,
+            levels: vec![2, 1],
+            calls: Rc::new(RefCell::new(0)),
+        });
+        list.zsl_insert(1.0, 10);
+        list.zsl_insert(2.0, 20);
+        let output = format!("{:?}", list);
+        assert_eq!(
+            output,
+            "=====================\n\
+             Level 0:\n\
+             None,(1),None -> Some(10),(1),(1.0) -> Some(20),(0),(2.0) -> \n\
+             Level 1:\n\
+             None,(1),None -> Some(10),(1),(1.0) -> \n\
+             ====================="
+        );
+    }
+
     // Add more tests here...
 }
\ No newline at end of file